@@ -1,4 +1,6 @@
-use tauri::command;
+use tauri::{command, State};
+
+use crate::db::{Database, SearchHit};
 
 #[command]
 pub fn get_version() -> String {
@@ -78,6 +80,18 @@ pub async fn decrypt_message(data: String) -> Result<String, String> {
     Ok(data)
 }
 
+/// Поиск по локальному индексу (в том числе по E2EE-чатам)
+#[command]
+pub fn search_messages(
+    db: State<'_, Database>,
+    query: String,
+    chat_id: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<SearchHit>, String> {
+    db.search_messages(&query, chat_id.as_deref(), limit.unwrap_or(50).min(200))
+        .map_err(|e| e.to_string())
+}
+
 #[derive(serde::Serialize)]
 pub struct MessageResponse {
     pub success: bool,
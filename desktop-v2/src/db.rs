@@ -1,6 +1,8 @@
-use rusqlite::{Connection, Result};
+use rusqlite::{params, Connection, Result};
 use std::path::PathBuf;
 
+use crate::search;
+
 pub struct Database {
     conn: Connection,
 }
@@ -51,6 +53,36 @@ impl Database {
             [],
         )?;
         
+        // Локальный полнотекстовый индекс: для E2EE-чатов сервер искать не может
+        let fts_missing: bool = conn.query_row(
+            "SELECT COUNT(*) = 0 FROM sqlite_master WHERE type = 'table' AND name = 'messages_fts'",
+            [],
+            |row| row.get(0),
+        )?;
+
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(
+                content,
+                content = 'messages',
+                content_rowid = 'rowid',
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            CREATE TRIGGER IF NOT EXISTS messages_fts_ai AFTER INSERT ON messages BEGIN
+                INSERT INTO messages_fts(rowid, content) VALUES (new.rowid, new.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS messages_fts_ad AFTER DELETE ON messages BEGIN
+                INSERT INTO messages_fts(messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS messages_fts_au AFTER UPDATE OF content ON messages BEGIN
+                INSERT INTO messages_fts(messages_fts, rowid, content) VALUES ('delete', old.rowid, old.content);
+                INSERT INTO messages_fts(rowid, content) VALUES (new.rowid, new.content);
+            END;",
+        )?;
+
+        if fts_missing {
+            conn.execute("INSERT INTO messages_fts(messages_fts) VALUES ('rebuild')", [])?;
+        }
+        
         conn.execute(
             "CREATE TABLE IF NOT EXISTS p2p_peers (
                 peer_id TEXT PRIMARY KEY,
@@ -73,8 +105,16 @@ impl Database {
     
     pub fn save_message(&self, message: &Message) -> Result<()> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO messages (id, chat_id, sender_id, content, encrypted, timestamp)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            // Не INSERT OR REPLACE: при замене не срабатывают триггеры удаления,
+            // и в полнотекстовом индексе остался бы старый текст
+            "INSERT INTO messages (id, chat_id, sender_id, content, encrypted, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(id) DO UPDATE SET
+                 chat_id = excluded.chat_id,
+                 sender_id = excluded.sender_id,
+                 content = excluded.content,
+                 encrypted = excluded.encrypted,
+                 timestamp = excluded.timestamp",
             [
                 &message.id,
                 &message.chat_id,
@@ -109,6 +149,53 @@ impl Database {
         Ok(messages)
     }
     
+    /// Поиск по локальным сообщениям, синтаксис как у серверного `/search`.
+    /// Пустой запрос или запрос из одних исключений даёт пустой результат.
+    pub fn search_messages(
+        &self,
+        query: &str,
+        chat_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<SearchHit>> {
+        let Some(expression) = search::to_fts5(query) else {
+            return Ok(vec![]);
+        };
+
+        let mut stmt = self.conn.prepare(
+            "SELECT m.id, m.chat_id, m.sender_id, m.timestamp,
+                    snippet(messages_fts, 0, ?1, ?2, '…', 16), bm25(messages_fts)
+             FROM messages_fts
+             JOIN messages m ON m.rowid = messages_fts.rowid
+             WHERE messages_fts MATCH ?3 AND (?4 IS NULL OR m.chat_id = ?4)
+             ORDER BY bm25(messages_fts), m.timestamp DESC
+             LIMIT ?5"
+        )?;
+
+        let hits = stmt.query_map(
+            params![
+                search::MARK_OPEN.to_string(),
+                search::MARK_CLOSE.to_string(),
+                expression,
+                chat_id,
+                limit,
+            ],
+            |row| {
+                let snippet: String = row.get(4)?;
+                Ok(SearchHit {
+                    message_id: row.get(0)?,
+                    chat_id: row.get(1)?,
+                    sender_id: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    snippet: search::highlight(&snippet),
+                    rank: row.get(5)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>>>()?;
+
+        Ok(hits)
+    }
+    
     pub fn save_user(&self, user: &User) -> Result<()> {
         self.conn.execute(
             "INSERT OR REPLACE INTO users (id, username, public_key, created_at)
@@ -134,6 +221,18 @@ pub struct Message {
     pub timestamp: u64,
 }
 
+/// Результат локального поиска
+#[derive(Debug, serde::Serialize)]
+pub struct SearchHit {
+    pub message_id: String,
+    pub chat_id: String,
+    pub sender_id: String,
+    pub timestamp: u64,
+    /// HTML-экранированный фрагмент, совпадения в `<mark>`
+    pub snippet: String,
+    pub rank: f64,
+}
+
 #[derive(Debug)]
 pub struct User {
     pub id: String,
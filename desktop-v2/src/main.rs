@@ -9,6 +9,7 @@ mod commands;
 mod p2p;
 mod crypto;
 mod db;
+mod search;
 
 fn main() {
     // Создаём меню системного трея
@@ -76,6 +77,7 @@ fn main() {
             commands::join_p2p_network,
             commands::encrypt_message,
             commands::decrypt_message,
+            commands::search_messages,
         ])
         .setup(|app| {
             // Инициализация P2P сети
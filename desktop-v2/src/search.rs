@@ -0,0 +1,134 @@
+//! Синтаксис поискового запроса для локального индекса сообщений.
+//!
+//! Повторяет синтаксис серверного `/search` (server/src/api/search.rs),
+//! чтобы поиск по E2EE-чатам вёл себя так же, как по серверным:
+//! `слово`, `"точная фраза"`, `прив*`, `-слово`, `OR`.
+
+/// Маркеры подсветки для `snippet()`, заменяются на `<mark>` в `highlight`
+pub const MARK_OPEN: char = '\u{E000}';
+pub const MARK_CLOSE: char = '\u{E001}';
+
+/// Экранирует HTML и превращает маркеры `snippet()` в `<mark>`
+pub fn highlight(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len() + 16);
+    for c in raw.chars() {
+        match c {
+            MARK_OPEN => out.push_str("<mark>"),
+            MARK_CLOSE => out.push_str("</mark>"),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+enum Token {
+    Term { text: String, prefix: bool, negated: bool },
+    Or,
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut negated = false;
+        if c == '-' {
+            chars.next();
+            match chars.peek() {
+                Some(next) if !next.is_whitespace() => negated = true,
+                _ => continue,
+            }
+        }
+
+        let mut text = String::new();
+        let mut prefix = false;
+        let quoted = chars.peek() == Some(&'"');
+        if quoted {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                text.push(c);
+            }
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                prefix = true;
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+            if text == "OR" && !negated {
+                tokens.push(Token::Or);
+                continue;
+            }
+            let trimmed = text.trim_end_matches('*');
+            prefix = trimmed.len() != text.len();
+            text.truncate(trimmed.len());
+        }
+
+        if !text.trim().is_empty() {
+            tokens.push(Token::Term { text, prefix, negated });
+        }
+    }
+
+    tokens
+}
+
+/// Переводит пользовательский запрос в выражение FTS5.
+/// Каждый терм берётся в кавычки, поэтому служебные символы FTS5
+/// из ввода не могут сломать запрос. `None` — искать нечего
+/// (пустой запрос или только исключения).
+pub fn to_fts5(input: &str) -> Option<String> {
+    let mut positive: Vec<String> = Vec::new();
+    let mut negative: Vec<String> = Vec::new();
+    let mut pending_or = false;
+
+    for token in tokenize(input) {
+        match token {
+            Token::Or => pending_or = !positive.is_empty(),
+            Token::Term { text, prefix, negated } => {
+                let term = format!(
+                    "\"{}\"{}",
+                    text.replace('"', "\"\""),
+                    if prefix { "*" } else { "" }
+                );
+                if negated {
+                    negative.push(term);
+                    continue;
+                }
+                if pending_or {
+                    positive.push("OR".to_string());
+                    pending_or = false;
+                }
+                positive.push(term);
+            }
+        }
+    }
+
+    if positive.is_empty() {
+        return None;
+    }
+
+    let mut expression = format!("({})", positive.join(" "));
+    for term in negative {
+        expression.push_str(" NOT ");
+        expression.push_str(&term);
+    }
+    Some(expression)
+}
@@ -14,6 +14,10 @@ pub struct ChannelMessage {
     pub media: Option<Vec<String>>,
     pub timestamp: DateTime<Utc>,
     pub translated_text: Option<String>,
+    /// Подпись администратора, если канал подписывает посты
+    #[serde(default)]
+    pub author_signature: Option<String>,
+    /// Счётчик с сервера: каждый подписчик засчитывается один раз
     pub views: u64,
 }
 
@@ -28,6 +32,7 @@ pub struct Channel {
     pub messages: Vec<ChannelMessage>,
     pub created_at: DateTime<Utc>,
     pub is_public: bool,
+    /// Ссылка-приглашение, выданная сервером (`POST /chats/:chat_id/invites`)
     pub invite_link: Option<String>,
 }
 
@@ -36,12 +41,6 @@ impl Channel {
         let mut admins = HashSet::new();
         admins.insert(owner.clone());
         
-        let invite_link = if is_public {
-            Some(format!("https://libertyreach.io/channel/{}", Uuid::new_v4()))
-        } else {
-            None
-        };
-        
         Self {
             id: Uuid::new_v4().to_string(),
             name,
@@ -52,7 +51,7 @@ impl Channel {
             messages: Vec::new(),
             created_at: Utc::now(),
             is_public,
-            invite_link,
+            invite_link: None,
         }
     }
     
@@ -72,6 +71,7 @@ impl Channel {
             media,
             timestamp: Utc::now(),
             translated_text: None,
+            author_signature: None,
             views: 0,
         };
         
@@ -1,3 +1,4 @@
 // messenger/src/crypto/mod.rs
 pub mod pqcrypto;
+pub mod steganalysis;
 pub mod steganography;
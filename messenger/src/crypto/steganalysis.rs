@@ -0,0 +1,350 @@
+// messenger/src/crypto/steganalysis.rs
+//! Стегоанализ входящих изображений: хи-квадрат и RS-анализ LSB-плоскостей
+//!
+//! Оценки вероятностные: чистое изображение может получить ненулевой балл,
+//! а слабое вложение (несколько процентов пикселей) — остаться незамеченным.
+
+use image::{DynamicImage, RgbImage};
+
+/// Порог RS-оценки доли изменённых пикселей, ниже которого изображение считается чистым
+const RS_CLEAN_RATE: f64 = 0.03;
+/// RS-оценка, при которой вложение считается практически достоверным
+const RS_CERTAIN_RATE: f64 = 0.15;
+/// Минимальное ожидаемое число наблюдений в ячейке хи-квадрат
+const CHI_MIN_EXPECTED: f64 = 5.0;
+
+/// Результат анализа изображения
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SteganalysisReport {
+    /// Вероятность выравнивания пар значений (2k, 2k+1) по хи-квадрат, 0–1
+    pub chi_square: f64,
+    /// RS-оценка доли пикселей со встроенными битами, 0–1
+    pub rs_rate: f64,
+    /// Итоговая вероятность наличия скрытых данных, 0–1
+    pub probability: f64,
+}
+
+impl SteganalysisReport {
+    /// Изображение стоит показать модератору
+    pub fn is_suspicious(&self) -> bool {
+        self.probability >= 0.5
+    }
+}
+
+/// Анализирует LSB-плоскости RGB-каналов
+pub fn analyze(image: &DynamicImage) -> SteganalysisReport {
+    let rgb = image.to_rgb8();
+    let chi_square = chi_square_probability(&rgb);
+    let rs_rate = rs_embedding_rate(&rgb);
+
+    let rs_score = ((rs_rate - RS_CLEAN_RATE) / (RS_CERTAIN_RATE - RS_CLEAN_RATE)).clamp(0.0, 1.0);
+
+    SteganalysisReport {
+        chi_square,
+        rs_rate,
+        probability: chi_square.max(rs_score),
+    }
+}
+
+/// Атака хи-квадрат (Вестфельд–Пфицманн).
+///
+/// Запись случайных бит выравнивает частоты пар значений (2k, 2k+1).
+/// Возвращает вероятность того, что наблюдаемые частоты получены из выровненных.
+pub fn chi_square_probability(rgb: &RgbImage) -> f64 {
+    let mut histograms = [[0u64; 256]; 3];
+    for pixel in rgb.pixels() {
+        for (channel, histogram) in histograms.iter_mut().enumerate() {
+            histogram[pixel[channel] as usize] += 1;
+        }
+    }
+
+    let mut statistic = 0.0;
+    let mut categories = 0usize;
+    for histogram in &histograms {
+        for pair in histogram.chunks_exact(2) {
+            let expected = (pair[0] + pair[1]) as f64 / 2.0;
+            if expected < CHI_MIN_EXPECTED {
+                continue;
+            }
+            statistic += (pair[0] as f64 - expected).powi(2) / expected;
+            categories += 1;
+        }
+    }
+
+    if categories < 2 {
+        return 0.0;
+    }
+
+    1.0 - regularized_gamma_p((categories - 1) as f64 / 2.0, statistic / 2.0)
+}
+
+/// RS-анализ (Фридрих–Голян–Ду): оценка доли пикселей с изменёнными LSB.
+///
+/// Возвращает среднее по RGB-каналам, ограниченное диапазоном 0–1.
+pub fn rs_embedding_rate(rgb: &RgbImage) -> f64 {
+    let rates: Vec<f64> = (0..3)
+        .filter_map(|channel| {
+            let plane: Vec<u8> = rgb.pixels().map(|p| p[channel]).collect();
+            rs_channel_rate(&plane, rgb.width() as usize)
+        })
+        .collect();
+
+    if rates.is_empty() {
+        return 0.0;
+    }
+    (rates.iter().sum::<f64>() / rates.len() as f64).clamp(0.0, 1.0)
+}
+
+/// Доли регулярных и сингулярных групп для маски M и −M
+#[derive(Debug, Default, Clone, Copy)]
+struct RsCounts {
+    regular: f64,
+    singular: f64,
+    regular_neg: f64,
+    singular_neg: f64,
+}
+
+/// Маска инверсии для групп из четырёх соседних пикселей
+const RS_MASK: [bool; 4] = [false, true, true, false];
+
+fn rs_channel_rate(plane: &[u8], width: usize) -> Option<f64> {
+    let flipped: Vec<u8> = plane.iter().map(|v| v ^ 1).collect();
+    let base = rs_counts(plane, width)?;
+    let inverted = rs_counts(&flipped, width)?;
+
+    // Квадратное уравнение из работы Фридрих и др. (2001)
+    let d0 = base.regular - base.singular;
+    let d1 = inverted.regular - inverted.singular;
+    let n0 = base.regular_neg - base.singular_neg;
+    let n1 = inverted.regular_neg - inverted.singular_neg;
+
+    let a = 2.0 * (d1 + d0);
+    let b = n0 - n1 - d1 - 3.0 * d0;
+    let c = d0 - n0;
+
+    let root = if a.abs() < f64::EPSILON {
+        if b.abs() < f64::EPSILON {
+            return Some(0.0);
+        }
+        -c / b
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            return Some(0.0);
+        }
+        let sqrt = discriminant.sqrt();
+        let (r1, r2) = ((-b + sqrt) / (2.0 * a), (-b - sqrt) / (2.0 * a));
+        if r1.abs() < r2.abs() { r1 } else { r2 }
+    };
+
+    let denominator = root - 0.5;
+    if denominator.abs() < f64::EPSILON {
+        return Some(1.0);
+    }
+    Some(root / denominator)
+}
+
+fn rs_counts(plane: &[u8], width: usize) -> Option<RsCounts> {
+    let groups_per_row = width / RS_MASK.len();
+    if groups_per_row == 0 {
+        return None;
+    }
+
+    let mut counts = RsCounts::default();
+    let mut total = 0usize;
+    for row in plane.chunks_exact(width) {
+        for group in row.chunks_exact(RS_MASK.len()) {
+            let original = smoothness(group);
+            let positive = smoothness(&apply_mask(group, flip_positive));
+            let negative = smoothness(&apply_mask(group, flip_negative));
+
+            counts.regular += (positive > original) as u8 as f64;
+            counts.singular += (positive < original) as u8 as f64;
+            counts.regular_neg += (negative > original) as u8 as f64;
+            counts.singular_neg += (negative < original) as u8 as f64;
+            total += 1;
+        }
+    }
+
+    if total == 0 {
+        return None;
+    }
+    let total = total as f64;
+    Some(RsCounts {
+        regular: counts.regular / total,
+        singular: counts.singular / total,
+        regular_neg: counts.regular_neg / total,
+        singular_neg: counts.singular_neg / total,
+    })
+}
+
+/// Дискриминирующая функция: сумма модулей разностей соседей
+fn smoothness(group: &[u8]) -> i32 {
+    group
+        .windows(2)
+        .map(|pair| (pair[1] as i32 - pair[0] as i32).abs())
+        .sum()
+}
+
+fn apply_mask(group: &[u8], flip: fn(i32) -> i32) -> [u8; 4] {
+    let mut out = [0u8; 4];
+    for (i, (&value, &masked)) in group.iter().zip(RS_MASK.iter()).enumerate() {
+        out[i] = if masked { flip(value as i32).clamp(0, 255) as u8 } else { value };
+    }
+    out
+}
+
+/// F1: 0↔1, 2↔3, …
+fn flip_positive(value: i32) -> i32 {
+    value ^ 1
+}
+
+/// F−1: −1↔0, 1↔2, …
+fn flip_negative(value: i32) -> i32 {
+    ((value + 1) ^ 1) - 1
+}
+
+/// Регуляризованная нижняя неполная гамма-функция P(a, x)
+fn regularized_gamma_p(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+
+    if x < a + 1.0 {
+        // Ряд
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..500 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * 1e-12 {
+                break;
+            }
+        }
+        (sum.ln() - x + a * x.ln() - ln_gamma(a)).exp()
+    } else {
+        // Цепная дробь для Q(a, x) (метод Лентца)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..500 {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < 1e-12 {
+                break;
+            }
+        }
+        1.0 - (-x + a * x.ln() - ln_gamma(a)).exp() * h
+    }
+}
+
+/// ln Γ(x), аппроксимация Ланцоша
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::steganography::{LSBSteganography, Steganography};
+    use image::Rgb;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha20Rng;
+
+    /// Области разной яркости со слабым шумом сенсора: соседние пиксели
+    /// коррелированы, гистограмма неровная, как у фотографии
+    fn photo_like_image(width: u32, height: u32) -> DynamicImage {
+        let mut rng = ChaCha20Rng::seed_from_u64(3);
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let base = 50.0 + 37.0 * (x / 32) as f32 + 11.0 * (y / 32) as f32 + 0.1 * x as f32;
+            let noise: f32 = rng.gen_range(-1.0..1.0) + rng.gen_range(-1.0..1.0);
+            let v = (base + noise).round() as u8;
+            Rgb([v, v.saturating_add(25), v.saturating_sub(20)])
+        }))
+    }
+
+    #[test]
+    fn test_clean_image_scores_low() {
+        let report = analyze(&photo_like_image(256, 256));
+
+        assert!(report.rs_rate < RS_CLEAN_RATE, "{report:?}");
+        assert!(!report.is_suspicious(), "{report:?}");
+    }
+
+    #[test]
+    fn test_partial_lsb_embedding_detected() {
+        let mut image = photo_like_image(256, 256);
+        let message = "m".repeat(LSBSteganography.capacity(&image) * 6 / 10);
+        LSBSteganography.hide_message(&mut image, &message, "key").unwrap();
+
+        let report = analyze(&image);
+        assert!(report.rs_rate > 0.3, "{report:?}");
+        assert!(report.is_suspicious(), "{report:?}");
+    }
+
+    #[test]
+    fn test_full_lsb_embedding_detected() {
+        let mut image = photo_like_image(256, 256);
+        let message = "m".repeat(LSBSteganography.capacity(&image));
+        LSBSteganography.hide_message(&mut image, &message, "key").unwrap();
+
+        let report = analyze(&image);
+        assert!(report.chi_square > 0.9, "{report:?}");
+        assert!(report.is_suspicious(), "{report:?}");
+    }
+
+    #[test]
+    fn test_flip_functions() {
+        assert_eq!(flip_positive(4), 5);
+        assert_eq!(flip_positive(5), 4);
+        assert_eq!(flip_negative(4), 3);
+        assert_eq!(flip_negative(3), 4);
+        assert_eq!(flip_negative(0), -1);
+    }
+
+    #[test]
+    fn test_gamma_p_known_values() {
+        // P(1, x) = 1 - e^{-x}
+        assert!((regularized_gamma_p(1.0, 2.0) - (1.0 - (-2.0f64).exp())).abs() < 1e-9);
+        // Медиана хи-квадрат с 2 степенями свободы: 2 ln 2
+        assert!((regularized_gamma_p(1.0, 2f64.ln()) - 0.5).abs() < 1e-9);
+        assert!((regularized_gamma_p(10.0, 30.0) - 1.0).abs() < 1e-4);
+        assert!(regularized_gamma_p(10.0, 1.0) < 1e-6);
+    }
+
+    #[test]
+    fn test_tiny_image() {
+        let report = analyze(&DynamicImage::ImageRgb8(RgbImage::new(2, 2)));
+        assert_eq!(report.rs_rate, 0.0);
+        assert!(!report.is_suspicious());
+    }
+}
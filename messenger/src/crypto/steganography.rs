@@ -1,101 +1,720 @@
 // messenger/src/crypto/steganography.rs
-//! Стеганография LSB (Least Significant Bit)
+//! Стеганография: LSB (без потерь) и DCT (переживает перекодирование в JPEG)
+//!
+//! Сообщение шифруется AES-256-GCM ключом, выведенным из пароля (PBKDF2-HMAC-SHA256),
+//! и записывается в порядке, заданном ключевым ГПСЧ. Без пароля нельзя
+//! ни расшифровать данные, ни узнать, какие пиксели или блоки их содержат.
 
-use image::{DynamicImage, GenericImageView, GenericImage, Rgba};
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use image::{DynamicImage, ImageFormat, ImageOutputFormat, Rgb, RgbImage};
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::Path;
 
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+/// Сигнатура и версия формата в начале кадра
+const MAGIC: [u8; 4] = *b"LRS\x01";
+const LEN_PREFIX: usize = 4;
+/// Служебные байты кадра: сигнатура, длина шифротекста, соль и nonce
+const HEADER_LEN: usize = MAGIC.len() + LEN_PREFIX + SALT_LEN + NONCE_LEN;
+const PBKDF2_ROUNDS: u32 = 100_000;
+/// Используются только RGB: альфа-канал часто теряется при конвертации
+const CHANNELS: usize = 3;
+const LSB_ORDER_DOMAIN: &[u8] = b"liberty-reach/stego/lsb-order";
+const DCT_ORDER_DOMAIN: &[u8] = b"liberty-reach/stego/dct-order";
+
+/// Ошибки стеганографии
+#[derive(Debug, thiserror::Error)]
+pub enum StegoError {
+    #[error("сообщение не помещается: нужно {needed} байт, доступно {available}")]
+    CapacityExceeded { needed: usize, available: usize },
+
+    #[error("изображение не содержит скрытого сообщения")]
+    NoPayload,
+
+    #[error("неверный пароль или повреждённые данные")]
+    Decryption,
+
+    #[error("ошибка шифрования")]
+    Encryption,
+
+    #[error("сообщение не является корректным UTF-8: {0}")]
+    InvalidUtf8(#[from] std::string::FromUtf8Error),
+
+    #[error("ошибка изображения: {0}")]
+    Image(#[from] image::ImageError),
+}
+
+/// Общий интерфейс режимов стеганографии.
+///
+/// Режим отвечает только за запись и чтение бит; шифрование, кадр
+/// и проверка ёмкости общие для всех режимов.
+pub trait Steganography {
+    /// Количество бит полезной нагрузки, которое помещается в изображение
+    fn bit_capacity(&self, image: &DynamicImage) -> usize;
+
+    /// Записывает биты в изображение в порядке, заданном паролем
+    fn embed_bits(&self, image: &mut DynamicImage, bits: &[u8], passphrase: &str);
+
+    /// Читает биты в том же порядке, лениво
+    fn extract_bits(&self, image: &DynamicImage, passphrase: &str) -> Box<dyn Iterator<Item = u8>>;
+
+    /// Максимальная длина сообщения (в байтах), которую можно спрятать в изображение
+    fn capacity(&self, image: &DynamicImage) -> usize {
+        payload_capacity(self.bit_capacity(image))
+    }
+
+    /// Шифрует сообщение и прячет его в изображение
+    fn hide_message(
+        &self,
+        image: &mut DynamicImage,
+        message: &str,
+        passphrase: &str,
+    ) -> Result<(), StegoError> {
+        let available = self.capacity(image);
+        if message.len() > available {
+            return Err(StegoError::CapacityExceeded {
+                needed: message.len(),
+                available,
+            });
+        }
+
+        let frame = seal(message.as_bytes(), passphrase)?;
+        let bits: Vec<u8> = frame_bits(&frame).collect();
+        self.embed_bits(image, &bits, passphrase);
+        Ok(())
+    }
+
+    /// Извлекает и расшифровывает сообщение
+    fn extract_message(&self, image: &DynamicImage, passphrase: &str) -> Result<String, StegoError> {
+        let capacity = self.capacity(image);
+        let mut bits = self.extract_bits(image, passphrase);
+        let body = read_frame(&mut bits, capacity)?;
+
+        Ok(String::from_utf8(open(&body, passphrase)?)?)
+    }
+
+    /// Как `extract_message`, но `Ok(None)`, если корректного заголовка нет.
+    ///
+    /// Для произвольных входящих изображений: сигнатура и длина проверяются
+    /// до вывода ключа и выделения памяти, а ошибка означает, что заголовок
+    /// найден, но данные повреждены.
+    fn try_extract_message(
+        &self,
+        image: &DynamicImage,
+        passphrase: &str,
+    ) -> Result<Option<String>, StegoError> {
+        match self.extract_message(image, passphrase) {
+            Ok(message) => Ok(Some(message)),
+            Err(StegoError::NoPayload) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// LSB в RGB-каналах. Выдерживает только форматы без потерь.
 pub struct LSBSteganography;
 
 impl LSBSteganography {
-    pub fn hide_message(image: &mut DynamicImage, message: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let (width, height) = image.dimensions();
-        let mut data = image.as_mut_rgba8().unwrap();
-        
-        let message_bytes = message.as_bytes();
-        let mut bit_index = 0;
-        
-        // Скрываем длину сообщения (первые 32 бита)
-        let len = message_bytes.len() as u32;
-        for i in 0..32 {
-            let bit = ((len >> i) & 1) as u8;
-            let pixel_idx = bit_index / 8;
-            let channel = bit_index % 8;
-            
-            let x = (pixel_idx % width) as u32;
-            let y = (pixel_idx / width) as u32;
-            
-            let mut pixel = data.get_pixel(x, y);
-            let channel_idx = channel % 4;
-            pixel[channel_idx] = (pixel[channel_idx] & 0xFE) | bit;
-            data.put_pixel(x, y, pixel);
-            
-            bit_index += 1;
-        }
-        
-        // Скрываем само сообщение
-        for byte in message_bytes {
-            for i in 0..8 {
-                let bit = ((byte >> i) & 1) as u8;
-                let pixel_idx = bit_index / 8;
-                let channel = bit_index % 8;
-                
-                let x = (pixel_idx % width) as u32;
-                let y = (pixel_idx / width) as u32;
-                
-                let mut pixel = data.get_pixel(x, y);
-                let channel_idx = channel % 4;
-                pixel[channel_idx] = (pixel[channel_idx] & 0xFE) | bit;
-                data.put_pixel(x, y, pixel);
-                
-                bit_index += 1;
-            }
-        }
-        
+    /// Кодирует изображение в PNG (без потерь, младшие биты сохраняются)
+    pub fn encode_png(image: &DynamicImage) -> Result<Vec<u8>, StegoError> {
+        let mut buf = Vec::new();
+        image.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Png)?;
+        Ok(buf)
+    }
+
+    /// Сохраняет изображение в PNG независимо от расширения файла
+    pub fn save_png(image: &DynamicImage, path: impl AsRef<Path>) -> Result<(), StegoError> {
+        image.save_with_format(path, ImageFormat::Png)?;
         Ok(())
     }
-    
-    pub fn extract_message(image: &DynamicImage) -> Result<String, Box<dyn std::error::Error>> {
-        let (width, height) = image.dimensions();
-        let data = image.as_rgba8().unwrap();
-        
-        // Читаем длину сообщения (первые 32 бита)
-        let mut len: u32 = 0;
-        for i in 0..32 {
-            let pixel_idx = i / 8;
-            let channel = i % 8;
-            
-            let x = (pixel_idx % width) as u32;
-            let y = (pixel_idx / width) as u32;
-            
-            let pixel = data.get_pixel(x, y);
-            let channel_idx = channel % 4;
-            let bit = (pixel[channel_idx] & 1) as u32;
-            
-            len |= bit << i;
-        }
-        
-        // Читаем сообщение
-        let mut message_bytes = Vec::with_capacity(len as usize);
-        let mut bit_index = 32;
-        
-        for _ in 0..len {
-            let mut byte: u8 = 0;
-            for i in 0..8 {
-                let pixel_idx = bit_index / 8;
-                let channel = bit_index % 8;
-                
-                let x = (pixel_idx % width) as u32;
-                let y = (pixel_idx / width) as u32;
-                
-                let pixel = data.get_pixel(x, y);
-                let channel_idx = channel % 4;
-                let bit = (pixel[channel_idx] & 1) as u8;
-                
-                byte |= bit << i;
-                bit_index += 1;
+
+    /// Индекс байта в буфере RGBA для номера бита
+    fn byte_index(slot: usize) -> usize {
+        slot / CHANNELS * 4 + slot % CHANNELS
+    }
+}
+
+impl Steganography for LSBSteganography {
+    /// По одному биту на RGB-канал пикселя
+    fn bit_capacity(&self, image: &DynamicImage) -> usize {
+        image.width() as usize * image.height() as usize * CHANNELS
+    }
+
+    /// Изображение любого формата приводится к RGBA8
+    fn embed_bits(&self, image: &mut DynamicImage, bits: &[u8], passphrase: &str) {
+        let order = KeyedOrder::new(LSB_ORDER_DOMAIN, passphrase, self.bit_capacity(image));
+        let mut rgba = image.to_rgba8();
+        let data: &mut [u8] = &mut rgba;
+
+        for (&bit, slot) in bits.iter().zip(order) {
+            let idx = Self::byte_index(slot);
+            data[idx] = (data[idx] & 0xFE) | bit;
+        }
+
+        *image = DynamicImage::ImageRgba8(rgba);
+    }
+
+    fn extract_bits(&self, image: &DynamicImage, passphrase: &str) -> Box<dyn Iterator<Item = u8>> {
+        let order = KeyedOrder::new(LSB_ORDER_DOMAIN, passphrase, self.bit_capacity(image));
+        let rgba = image.to_rgba8();
+
+        Box::new(order.map(move |slot| rgba.as_raw()[Self::byte_index(slot)] & 1))
+    }
+}
+
+/// Встраивание в квантованные DCT-коэффициенты яркости (в духе JSteg/F5).
+///
+/// `image` не даёт доступа к коэффициентам JPEG, поэтому блоки 8×8 канала Y
+/// преобразуются здесь же, а бит записывается чётностью коэффициента,
+/// квантованного шагом `step` (QIM). Шаг заметно крупнее шага квантования
+/// JPEG среднего качества, поэтому чётность переживает перекодирование.
+/// Защита от искажений — простой код повторения, а не полноценный
+/// корректирующий код: каждый бит пишется `redundancy` раз в разных блоках
+/// и восстанавливается голосованием большинства, то есть переживает
+/// искажение меньше половины своих копий.
+pub struct DctSteganography {
+    /// Шаг квантования коэффициентов
+    pub step: f32,
+    /// Кратность повторения бит (нечётная); ёмкость делится на неё
+    pub redundancy: usize,
+}
+
+impl Default for DctSteganography {
+    fn default() -> Self {
+        Self {
+            step: 24.0,
+            redundancy: 7,
+        }
+    }
+}
+
+impl DctSteganography {
+    /// Кодирует изображение в JPEG с заданным качеством (1–100)
+    pub fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, StegoError> {
+        let mut buf = Vec::new();
+        image.write_to(&mut Cursor::new(&mut buf), ImageOutputFormat::Jpeg(quality))?;
+        Ok(buf)
+    }
+
+    /// Количество коэффициентов, пригодных для записи
+    fn slots(image: &DynamicImage) -> usize {
+        let (blocks_x, blocks_y) = Self::blocks(image);
+        blocks_x * blocks_y * DCT_POSITIONS.len()
+    }
+
+    /// Количество полных блоков 8×8 по горизонтали и вертикали
+    fn blocks(image: &DynamicImage) -> (usize, usize) {
+        (image.width() as usize / 8, image.height() as usize / 8)
+    }
+
+    fn redundancy(&self) -> usize {
+        self.redundancy.max(1) | 1
+    }
+
+    /// Бит, закодированный в коэффициенте
+    fn coefficient_bit(&self, coefficient: f32) -> u8 {
+        ((coefficient / self.step).round() as i64).rem_euclid(2) as u8
+    }
+
+    /// Ближайший к коэффициенту уровень квантования с нужной чётностью
+    fn quantize_to_bit(&self, coefficient: f32, bit: u8) -> f32 {
+        let scaled = coefficient / self.step;
+        let mut level = scaled.round();
+        if (level as i64).rem_euclid(2) as u8 != bit {
+            level += if scaled > level { 1.0 } else { -1.0 };
+        }
+        level * self.step
+    }
+}
+
+impl Steganography for DctSteganography {
+    fn bit_capacity(&self, image: &DynamicImage) -> usize {
+        Self::slots(image) / self.redundancy()
+    }
+
+    /// Результат — RGB8 (альфа-канал в JPEG всё равно теряется)
+    fn embed_bits(&self, image: &mut DynamicImage, bits: &[u8], passphrase: &str) {
+        let redundancy = self.redundancy();
+        let order = KeyedOrder::new(DCT_ORDER_DOMAIN, passphrase, Self::slots(image));
+        let (blocks_x, _) = Self::blocks(image);
+        let mut rgb = image.to_rgb8();
+        let mut touched: HashMap<usize, [f32; 64]> = HashMap::new();
+
+        let repeated = bits.iter().flat_map(|&bit| std::iter::repeat_n(bit, redundancy));
+        for (bit, slot) in repeated.zip(order) {
+            let block = slot / DCT_POSITIONS.len();
+            let (bx, by) = (block % blocks_x, block / blocks_x);
+            let coefficients = touched
+                .entry(block)
+                .or_insert_with(|| fdct(&luma_block(&rgb, bx, by)));
+
+            let pos = DCT_POSITIONS[slot % DCT_POSITIONS.len()];
+            coefficients[pos] = self.quantize_to_bit(coefficients[pos], bit);
+        }
+
+        for (block, coefficients) in &touched {
+            write_luma_block(&mut rgb, block % blocks_x, block / blocks_x, &idct(coefficients));
+        }
+
+        *image = DynamicImage::ImageRgb8(rgb);
+    }
+
+    fn extract_bits(&self, image: &DynamicImage, passphrase: &str) -> Box<dyn Iterator<Item = u8>> {
+        let redundancy = self.redundancy();
+        let mut order = KeyedOrder::new(DCT_ORDER_DOMAIN, passphrase, Self::slots(image));
+        let (blocks_x, blocks_y) = Self::blocks(image);
+        let rgb = image.to_rgb8();
+
+        let mut bits = Vec::with_capacity(Self::slots(image));
+        for by in 0..blocks_y {
+            for bx in 0..blocks_x {
+                let coefficients = fdct(&luma_block(&rgb, bx, by));
+                bits.extend(DCT_POSITIONS.iter().map(|&pos| self.coefficient_bit(coefficients[pos])));
             }
-            message_bytes.push(byte);
         }
-        
-        Ok(String::from_utf8(message_bytes)?)
+
+        Box::new(std::iter::from_fn(move || {
+            let mut ones = 0;
+            for _ in 0..redundancy {
+                ones += bits[order.next()?] as usize;
+            }
+            Some((ones * 2 > redundancy) as u8)
+        }))
+    }
+}
+
+/// Среднечастотные коэффициенты блока 8×8 (индекс v * 8 + u), зигзаг 3–8:
+/// низкие частоты заметны глазу, высокие JPEG обнуляет
+const DCT_POSITIONS: [usize; 6] = [16, 9, 2, 3, 10, 17];
+
+/// Яркость и цветность пикселя (YCbCr, как в JPEG)
+fn rgb_to_ycc(pixel: &Rgb<u8>) -> [f32; 3] {
+    let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+    [
+        0.299 * r + 0.587 * g + 0.114 * b,
+        128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b,
+        128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b,
+    ]
+}
+
+fn ycc_to_rgb([luma, cb, cr]: [f32; 3]) -> Rgb<u8> {
+    let (cb, cr) = (cb - 128.0, cr - 128.0);
+    let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    Rgb([
+        clamp(luma + 1.402 * cr),
+        clamp(luma - 0.344_136 * cb - 0.714_136 * cr),
+        clamp(luma + 1.772 * cb),
+    ])
+}
+
+/// Яркость блока 8×8, сдвинутая к нулю (как перед DCT в JPEG)
+fn luma_block(rgb: &RgbImage, bx: usize, by: usize) -> [f32; 64] {
+    let mut block = [0.0; 64];
+    for (i, value) in block.iter_mut().enumerate() {
+        let (x, y) = ((bx * 8 + i % 8) as u32, (by * 8 + i / 8) as u32);
+        *value = rgb_to_ycc(rgb.get_pixel(x, y))[0] - 128.0;
+    }
+    block
+}
+
+/// Заменяет яркость блока, сохраняя цветность каждого пикселя
+fn write_luma_block(rgb: &mut RgbImage, bx: usize, by: usize, block: &[f32; 64]) {
+    for (i, value) in block.iter().enumerate() {
+        let (x, y) = ((bx * 8 + i % 8) as u32, (by * 8 + i / 8) as u32);
+        let [_, cb, cr] = rgb_to_ycc(rgb.get_pixel(x, y));
+        rgb.put_pixel(x, y, ycc_to_rgb([value + 128.0, cb, cr]));
+    }
+}
+
+/// Базис DCT-II 8×8: `DCT_BASIS[u][x] = c(u) * cos((2x + 1)uπ / 16)`
+fn dct_basis() -> [[f32; 8]; 8] {
+    let mut basis = [[0.0; 8]; 8];
+    for (u, row) in basis.iter_mut().enumerate() {
+        let scale = if u == 0 { (1.0f32 / 8.0).sqrt() } else { (2.0f32 / 8.0).sqrt() };
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = scale * ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0).cos();
+        }
+    }
+    basis
+}
+
+/// Ортонормированное DCT-II блока 8×8 (масштаб совпадает с JPEG)
+fn fdct(block: &[f32; 64]) -> [f32; 64] {
+    let basis = dct_basis();
+    let mut rows = [0.0; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| basis[u][x] * block[y * 8 + x]).sum();
+        }
+    }
+
+    let mut out = [0.0; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            out[v * 8 + u] = (0..8).map(|y| basis[v][y] * rows[y * 8 + u]).sum();
+        }
+    }
+    out
+}
+
+/// Обратное преобразование к `fdct`
+fn idct(coefficients: &[f32; 64]) -> [f32; 64] {
+    let basis = dct_basis();
+    let mut cols = [0.0; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            cols[y * 8 + u] = (0..8).map(|v| basis[v][y] * coefficients[v * 8 + u]).sum();
+        }
+    }
+
+    let mut out = [0.0; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            out[y * 8 + x] = (0..8).map(|u| basis[u][x] * cols[y * 8 + u]).sum();
+        }
+    }
+    out
+}
+
+/// Полезная ёмкость в байтах для заданного числа бит-слотов
+fn payload_capacity(slots: usize) -> usize {
+    (slots / 8).saturating_sub(HEADER_LEN + TAG_LEN)
+}
+
+/// Ключ AES-256 из пароля и соли
+fn derive_key(passphrase: &str, salt: &[u8]) -> Key<Aes256Gcm> {
+    let mut key = Key::<Aes256Gcm>::default();
+    pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+/// Шифрует сообщение в кадр: сигнатура | длина шифротекста (LE u32) | соль | nonce | шифротекст
+fn seal(message: &[u8], passphrase: &str) -> Result<Vec<u8>, StegoError> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+
+    let cipher = Aes256Gcm::new(&derive_key(passphrase, &salt));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, message)
+        .map_err(|_| StegoError::Encryption)?;
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+    frame.extend_from_slice(&MAGIC);
+    frame.extend_from_slice(&(ciphertext.len() as u32).to_le_bytes());
+    frame.extend_from_slice(&salt);
+    frame.extend_from_slice(&nonce);
+    frame.extend_from_slice(&ciphertext);
+    Ok(frame)
+}
+
+/// Расшифровывает тело кадра (соль | nonce | шифротекст)
+fn open(body: &[u8], passphrase: &str) -> Result<Vec<u8>, StegoError> {
+    let (salt, rest) = body.split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+    Aes256Gcm::new(&derive_key(passphrase, salt))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| StegoError::Decryption)
+}
+
+/// Биты кадра, начиная с младшего бита каждого байта
+fn frame_bits(frame: &[u8]) -> impl Iterator<Item = u8> + '_ {
+    frame
+        .iter()
+        .flat_map(|byte| (0..8).map(move |i| (byte >> i) & 1))
+}
+
+fn read_bytes(bits: &mut impl Iterator<Item = u8>, len: usize) -> Result<Vec<u8>, StegoError> {
+    let mut bytes = Vec::with_capacity(len);
+    for _ in 0..len {
+        let mut byte = 0u8;
+        for i in 0..8 {
+            byte |= bits.next().ok_or(StegoError::NoPayload)? << i;
+        }
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+/// Читает кадр и возвращает его тело без сигнатуры и префикса длины.
+/// Сигнатура и длина проверяются до выделения памяти под тело.
+fn read_frame(bits: &mut impl Iterator<Item = u8>, capacity: usize) -> Result<Vec<u8>, StegoError> {
+    if read_bytes(bits, MAGIC.len())? != MAGIC {
+        return Err(StegoError::NoPayload);
+    }
+
+    let len_bytes = read_bytes(bits, LEN_PREFIX)?;
+    let len = u32::from_le_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]) as usize;
+
+    if len < TAG_LEN || len > capacity + TAG_LEN {
+        return Err(StegoError::NoPayload);
+    }
+
+    read_bytes(bits, SALT_LEN + NONCE_LEN + len)
+}
+
+/// Ключевая перестановка позиций `0..len`.
+///
+/// Разреженный Фишер–Йетс: хранятся только переставленные элементы,
+/// поэтому память пропорциональна длине сообщения, а не размеру изображения.
+struct KeyedOrder {
+    rng: ChaCha20Rng,
+    swapped: HashMap<usize, usize>,
+    next: usize,
+    len: usize,
+}
+
+impl KeyedOrder {
+    fn new(domain: &[u8], passphrase: &str, len: usize) -> Self {
+        let seed: [u8; 32] = Sha256::new()
+            .chain_update(domain)
+            .chain_update(passphrase.as_bytes())
+            .finalize()
+            .into();
+
+        Self {
+            rng: ChaCha20Rng::from_seed(seed),
+            swapped: HashMap::new(),
+            next: 0,
+            len,
+        }
+    }
+}
+
+impl Iterator for KeyedOrder {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.next >= self.len {
+            return None;
+        }
+
+        let i = self.next;
+        let j = self.rng.gen_range(i..self.len);
+        let picked = self.swapped.remove(&j).unwrap_or(j);
+        if j != i {
+            let current = self.swapped.remove(&i).unwrap_or(i);
+            self.swapped.insert(j, current);
+        }
+
+        self.next += 1;
+        Some(picked)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{GrayImage, RgbImage, RgbaImage};
+    use std::collections::HashSet;
+
+    fn noise_image(width: u32, height: u32) -> DynamicImage {
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |_, _| {
+            image::Rgb([rng.gen(), rng.gen(), rng.gen()])
+        }))
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut image = noise_image(64, 64);
+        LSBSteganography.hide_message(&mut image, "Привет из Liberty Reach", "пароль").unwrap();
+
+        let message = LSBSteganography.extract_message(&image, "пароль").unwrap();
+        assert_eq!(message, "Привет из Liberty Reach");
+    }
+
+    #[test]
+    fn test_wrong_passphrase() {
+        let mut image = noise_image(64, 64);
+        LSBSteganography.hide_message(&mut image, "secret", "correct").unwrap();
+
+        assert!(LSBSteganography.extract_message(&image, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_capacity_exceeded() {
+        let mut image = noise_image(16, 16);
+        let capacity = LSBSteganography.capacity(&image);
+        let message = "x".repeat(capacity + 1);
+
+        let result = LSBSteganography.hide_message(&mut image, &message, "key");
+        assert!(matches!(
+            result,
+            Err(StegoError::CapacityExceeded { needed, available }) if needed == capacity + 1 && available == capacity
+        ));
+    }
+
+    #[test]
+    fn test_fills_full_capacity() {
+        let mut image = noise_image(16, 16);
+        let message = "y".repeat(LSBSteganography.capacity(&image));
+
+        LSBSteganography.hide_message(&mut image, &message, "key").unwrap();
+        assert_eq!(LSBSteganography.extract_message(&image, "key").unwrap(), message);
+    }
+
+    #[test]
+    fn test_tiny_image() {
+        let mut image = noise_image(2, 2);
+        assert_eq!(LSBSteganography.capacity(&image), 0);
+        assert!(LSBSteganography.hide_message(&mut image, "a", "key").is_err());
+        assert!(matches!(
+            LSBSteganography.extract_message(&image, "key"),
+            Err(StegoError::NoPayload)
+        ));
+    }
+
+    #[test]
+    fn test_try_extract_without_payload() {
+        let image = noise_image(64, 64);
+        assert!(LSBSteganography.try_extract_message(&image, "key").unwrap().is_none());
+        assert!(DctSteganography::default().try_extract_message(&image, "key").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_try_extract_with_payload() {
+        let mut image = noise_image(64, 64);
+        LSBSteganography.hide_message(&mut image, "есть", "key").unwrap();
+
+        assert_eq!(
+            LSBSteganography.try_extract_message(&image, "key").unwrap().as_deref(),
+            Some("есть")
+        );
+        assert!(LSBSteganography.try_extract_message(&image, "other").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_non_rgba_input() {
+        let mut gray = DynamicImage::ImageLuma8(GrayImage::from_pixel(40, 40, image::Luma([128])));
+        LSBSteganography.hide_message(&mut gray, "gray", "key").unwrap();
+        assert_eq!(LSBSteganography.extract_message(&gray, "key").unwrap(), "gray");
+    }
+
+    #[test]
+    fn test_png_round_trip() {
+        let mut image = noise_image(48, 48);
+        LSBSteganography.hide_message(&mut image, "через PNG", "key").unwrap();
+
+        let png = LSBSteganography::encode_png(&image).unwrap();
+        let decoded = image::load_from_memory(&png).unwrap();
+        assert_eq!(LSBSteganography.extract_message(&decoded, "key").unwrap(), "через PNG");
+    }
+
+    #[test]
+    fn test_payload_is_scattered() {
+        let original = DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 64, image::Rgba([0, 0, 0, 255])));
+        let mut image = original.clone();
+        LSBSteganography.hide_message(&mut image, "scatter", "key").unwrap();
+
+        let frame_bits = (HEADER_LEN + "scatter".len() + TAG_LEN) * 8;
+        let changed: Vec<usize> = image
+            .to_rgba8()
+            .iter()
+            .zip(original.to_rgba8().iter())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .map(|(i, _)| i)
+            .collect();
+
+        assert!(!changed.is_empty());
+        // Последовательная запись уместилась бы в первые frame_bits * 4 / 3 байт
+        assert!(changed.iter().any(|&i| i > frame_bits * 2));
+    }
+
+    /// Плавный градиент с небольшим шумом, похожий на фотографию
+    fn photo_like_image(width: u32, height: u32) -> DynamicImage {
+        let mut rng = ChaCha20Rng::seed_from_u64(11);
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let base = 60.0 + 120.0 * (x + y) as f32 / (width + height) as f32;
+            let jitter: f32 = rng.gen_range(-6.0..6.0);
+            let v = (base + jitter) as u8;
+            image::Rgb([v, v.saturating_add(20), v.saturating_sub(15)])
+        }))
+    }
+
+    #[test]
+    fn test_dct_round_trip() {
+        let mut image = photo_like_image(256, 256);
+        DctSteganography::default().hide_message(&mut image, "DCT режим", "key").unwrap();
+
+        let message = DctSteganography::default().extract_message(&image, "key").unwrap();
+        assert_eq!(message, "DCT режим");
+    }
+
+    #[test]
+    fn test_dct_survives_jpeg() {
+        let stego = DctSteganography::default();
+        for quality in [90, 75] {
+            let mut image = photo_like_image(256, 256);
+            stego.hide_message(&mut image, "переживёт JPEG", "key").unwrap();
+
+            let jpeg = DctSteganography::encode_jpeg(&image, quality).unwrap();
+            let decoded = image::load_from_memory(&jpeg).unwrap();
+            assert_eq!(stego.extract_message(&decoded, "key").unwrap(), "переживёт JPEG");
+        }
+    }
+
+    #[test]
+    fn test_dct_capacity_exceeded() {
+        let stego = DctSteganography::default();
+        let mut image = photo_like_image(128, 128);
+        let message = "z".repeat(stego.capacity(&image) + 1);
+
+        assert!(matches!(
+            stego.hide_message(&mut image, &message, "key"),
+            Err(StegoError::CapacityExceeded { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dct_wrong_passphrase() {
+        let stego = DctSteganography::default();
+        let mut image = photo_like_image(256, 256);
+        stego.hide_message(&mut image, "secret", "correct").unwrap();
+
+        assert!(stego.extract_message(&image, "wrong").is_err());
+    }
+
+    #[test]
+    fn test_dct_transform_is_invertible() {
+        let block: [f32; 64] = std::array::from_fn(|i| (i as f32 * 7.0) % 255.0 - 128.0);
+        let restored = idct(&fdct(&block));
+
+        for (a, b) in block.iter().zip(restored.iter()) {
+            assert!((a - b).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_modes_behind_trait_object() {
+        let modes: Vec<Box<dyn Steganography>> =
+            vec![Box::new(LSBSteganography), Box::new(DctSteganography::default())];
+
+        for mode in modes {
+            let mut image = photo_like_image(256, 256);
+            mode.hide_message(&mut image, "trait", "key").unwrap();
+            assert_eq!(mode.extract_message(&image, "key").unwrap(), "trait");
+        }
+    }
+
+    #[test]
+    fn test_keyed_order_is_permutation() {
+        let order: Vec<usize> = KeyedOrder::new(b"test", "key", 1000).collect();
+        let unique: HashSet<usize> = order.iter().copied().collect();
+
+        assert_eq!(order.len(), 1000);
+        assert_eq!(unique.len(), 1000);
+        assert!(unique.iter().all(|&i| i < 1000));
     }
 }
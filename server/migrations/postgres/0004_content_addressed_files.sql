@@ -0,0 +1,17 @@
+-- Содержимое файлов по SHA-256 (см. storage): одинаковые файлы хранятся
+-- один раз, ref_count — число строк files, ссылающихся на содержимое.
+CREATE TABLE file_blobs (
+    hash TEXT PRIMARY KEY,
+    size BIGINT NOT NULL,
+    mime_type TEXT NOT NULL,
+    ref_count BIGINT NOT NULL DEFAULT 0,
+    created_at TEXT DEFAULT utc_now_text(),
+    updated_at TEXT DEFAULT utc_now_text()
+);
+
+-- NULL у файлов, загруженных до хранилища по хешу (переносятся при старте)
+ALTER TABLE files ADD COLUMN content_hash TEXT REFERENCES file_blobs(hash);
+
+CREATE INDEX idx_files_content_hash ON files(content_hash);
+CREATE INDEX idx_file_blobs_unreferenced ON file_blobs(updated_at) WHERE ref_count = 0;
+CREATE INDEX idx_messages_file_url ON messages(file_url) WHERE file_url IS NOT NULL;
@@ -0,0 +1,10 @@
+-- Шифрование содержимого на сервере (см. encryption): ключ данных объекта,
+-- обёрнутый мастер-ключом key_id. NULL — объект хранится открытым.
+ALTER TABLE file_blobs ADD COLUMN key_id TEXT;
+ALTER TABLE file_blobs ADD COLUMN wrapped_key TEXT;
+
+-- Файлы, зашифрованные клиентом: сервер не разбирает их содержимое
+ALTER TABLE files ADD COLUMN client_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE uploads ADD COLUMN client_encrypted BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX idx_file_blobs_key_id ON file_blobs(key_id);
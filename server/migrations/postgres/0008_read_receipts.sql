@@ -0,0 +1,27 @@
+-- Указатели «доставлено до» и «прочитано до»: позиция (created_at, id)
+-- последнего сообщения чата. Непрочитанные — чужие сообщения правее
+-- указателя; message_reads хранит, кто и когда прочитал каждое сообщение,
+-- если отчёты о прочтении у читателя включены.
+CREATE TABLE chat_reads (
+    chat_id TEXT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    read_created_at TEXT,
+    read_id TEXT,
+    delivered_created_at TEXT,
+    delivered_id TEXT,
+    updated_at TEXT DEFAULT utc_now_text(),
+    PRIMARY KEY (chat_id, user_id)
+);
+
+-- Упоминания и ответы: счётчик непрочитанных упоминаний в списке чатов
+CREATE TABLE message_mentions (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    PRIMARY KEY (message_id, user_id)
+);
+
+CREATE INDEX idx_chat_reads_user ON chat_reads(user_id);
+CREATE INDEX idx_message_mentions_user ON message_mentions(user_id);
+
+-- Отправлять ли другим отчёты о прочтении
+ALTER TABLE users ADD COLUMN read_receipts BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,21 @@
+-- Роли участников: owner | admin | member | restricted (см. permissions).
+-- rights — набор прав администратора, restricted_until — срок ограничения,
+-- promoted_by — кто назначил администратора.
+ALTER TABLE chat_members ADD COLUMN rights BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE chat_members ADD COLUMN restricted_until TEXT;
+ALTER TABLE chat_members ADD COLUMN promoted_by TEXT REFERENCES users(id) ON DELETE SET NULL;
+
+UPDATE chat_members SET role = 'member' WHERE role IS NULL OR role NOT IN ('owner', 'admin', 'member', 'restricted');
+-- Прежние администраторы могли всё
+UPDATE chat_members SET rights = 63 WHERE role = 'admin';
+
+-- Блокировки: заблокированный не состоит в чате и не может вернуться
+-- до until (NULL — бессрочно)
+CREATE TABLE chat_bans (
+    chat_id TEXT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    banned_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    until TEXT,
+    created_at TEXT DEFAULT utc_now_text(),
+    PRIMARY KEY (chat_id, user_id)
+);
@@ -0,0 +1,26 @@
+-- Пригласительные ссылки: code — секрет из ссылки. Ссылка действует, пока
+-- не отозвана, не истекла (expires_at) и не исчерпана (uses < max_uses).
+CREATE TABLE chat_invites (
+    code TEXT PRIMARY KEY,
+    chat_id TEXT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+    name TEXT,
+    expires_at TEXT,
+    max_uses BIGINT,
+    uses BIGINT NOT NULL DEFAULT 0,
+    -- Вступление только после одобрения администратором
+    requires_approval BOOLEAN NOT NULL DEFAULT FALSE,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TEXT DEFAULT utc_now_text()
+);
+
+CREATE INDEX idx_chat_invites_chat ON chat_invites(chat_id);
+
+-- Заявки на вступление по ссылке с одобрением
+CREATE TABLE chat_join_requests (
+    chat_id TEXT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invite_code TEXT REFERENCES chat_invites(code) ON DELETE SET NULL,
+    created_at TEXT DEFAULT utc_now_text(),
+    PRIMARY KEY (chat_id, user_id)
+);
@@ -0,0 +1,20 @@
+-- Треды: thread_id — корневое сообщение. Комментарии к постам канала
+-- (is_comment) живут только в треде и не попадают в ленту канала.
+ALTER TABLE messages ADD COLUMN thread_id TEXT REFERENCES messages(id) ON DELETE CASCADE;
+ALTER TABLE messages ADD COLUMN is_comment BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX idx_messages_thread ON messages(thread_id, created_at, id);
+
+-- Участие в треде: подписка на ответы и указатель «прочитано до»
+-- (позиция (created_at, id), как в chat_reads)
+CREATE TABLE thread_members (
+    thread_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    subscribed BOOLEAN NOT NULL DEFAULT FALSE,
+    read_created_at TEXT,
+    read_id TEXT,
+    updated_at TEXT DEFAULT utc_now_text(),
+    PRIMARY KEY (thread_id, user_id)
+);
+
+CREATE INDEX idx_thread_members_user ON thread_members(user_id);
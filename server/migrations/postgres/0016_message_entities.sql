@@ -0,0 +1,3 @@
+-- Разметка текста: JSON-массив сущностей в формате Telegram
+-- (смещения в UTF-16), NULL — без разметки
+ALTER TABLE messages ADD COLUMN entities TEXT;
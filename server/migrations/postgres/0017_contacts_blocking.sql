@@ -0,0 +1,16 @@
+-- Контакты и чёрный список. Заблокированный не может создать личный чат
+-- с заблокировавшим, писать ему в личный чат и видеть его присутствие.
+-- Импорт контактов ищет по имени или по email_hash — SHA-256 от почты
+-- в нижнем регистре; у старых пользователей хеш досчитывается при запуске.
+ALTER TABLE users ADD COLUMN email_hash TEXT;
+CREATE INDEX IF NOT EXISTS idx_users_email_hash ON users(email_hash);
+CREATE INDEX IF NOT EXISTS idx_contacts_contact ON contacts(contact_id);
+
+CREATE TABLE blocked_users (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    blocked_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    blocked_at TEXT DEFAULT utc_now_text(),
+    PRIMARY KEY (user_id, blocked_id)
+);
+
+CREATE INDEX idx_blocked_users_blocked ON blocked_users(blocked_id);
@@ -0,0 +1,16 @@
+-- Доставка отложенных сообщений. Планировщик захватывает наступившую
+-- запись (status = 'sending', claim — метка захвата) и в одной транзакции
+-- со вставкой сообщения переводит её в 'sent' или, у повторяющихся,
+-- на следующее время. Ошибка — повтор позже (attempts, last_error),
+-- после нескольких — 'failed'. when_online — отправить, когда
+-- собеседник в личном чате появится в сети.
+ALTER TABLE scheduled_messages ADD COLUMN recurrence TEXT NOT NULL DEFAULT 'once';
+ALTER TABLE scheduled_messages ADD COLUMN when_online BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE scheduled_messages ADD COLUMN attempts BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE scheduled_messages ADD COLUMN last_error TEXT;
+ALTER TABLE scheduled_messages ADD COLUMN claim TEXT;
+ALTER TABLE scheduled_messages ADD COLUMN claimed_at TEXT;
+-- Последнее отправленное по записи сообщение
+ALTER TABLE scheduled_messages ADD COLUMN message_id TEXT;
+
+CREATE INDEX idx_scheduled_messages_status ON scheduled_messages(status, send_at);
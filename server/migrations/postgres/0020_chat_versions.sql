@@ -0,0 +1,6 @@
+-- Версия чата для синхронизации списка: изменение, видное всем
+-- участникам (сообщение, название, состав), меняет одну строку чата,
+-- а не строку chat_list_changes на каждого участника. В chat_list_changes
+-- остаются личные изменения: настройки, прочтение, выход из чата.
+-- Изменения до этой миграции уже записаны в chat_list_changes.
+ALTER TABLE chats ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- Единое именование колонок: тип сущности — `<сущность>_type`, как у
+-- messages.message_type, files.mime_type и chat_wallpapers.wallpaper_type.
+ALTER TABLE chats RENAME COLUMN type TO chat_type;
+
+-- Автоудаление настраивается на чат; сообщения получают delete_at при отправке
+ALTER TABLE chats ADD COLUMN auto_delete_hours INTEGER DEFAULT NULL;
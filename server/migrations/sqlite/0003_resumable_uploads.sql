@@ -0,0 +1,14 @@
+-- Незавершённые загрузки (докачка кусками, см. api::files)
+CREATE TABLE uploads (
+    id TEXT PRIMARY KEY,
+    owner_id TEXT REFERENCES users(id) ON DELETE CASCADE,
+    filename TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    received INTEGER NOT NULL DEFAULT 0,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_uploads_owner ON uploads(owner_id);
+CREATE INDEX idx_uploads_updated_at ON uploads(updated_at);
+CREATE INDEX idx_files_owner ON files(owner_id);
@@ -0,0 +1,22 @@
+-- Результаты обработки медиа (см. media): размеры картинок, blurhash,
+-- длительность и волна аудио. media_status: pending → ready | failed;
+-- NULL — файл не обрабатывается (документы, видео).
+ALTER TABLE files ADD COLUMN width INTEGER;
+ALTER TABLE files ADD COLUMN height INTEGER;
+ALTER TABLE files ADD COLUMN duration_ms INTEGER;
+ALTER TABLE files ADD COLUMN waveform TEXT;
+ALTER TABLE files ADD COLUMN blurhash TEXT;
+ALTER TABLE files ADD COLUMN media_status TEXT;
+
+-- Превью лежат в хранилище наравне с файлами и держат ссылку на file_blobs
+CREATE TABLE file_thumbnails (
+    file_id TEXT NOT NULL REFERENCES files(id),
+    size TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    mime_type TEXT NOT NULL,
+    content_hash TEXT NOT NULL REFERENCES file_blobs(hash),
+    PRIMARY KEY (file_id, size)
+);
+
+CREATE INDEX idx_files_media_pending ON files(created_at) WHERE media_status = 'pending';
@@ -0,0 +1,6 @@
+-- Присутствие: status ведёт сервер по WebSocket-соединениям, last_seen_at
+-- пишется при закрытии последнего соединения. Кому видно время захода
+-- и статус online: everyone | contacts | nobody.
+ALTER TABLE users ADD COLUMN last_seen_at TEXT;
+ALTER TABLE users ADD COLUMN last_seen_privacy TEXT NOT NULL DEFAULT 'everyone';
+ALTER TABLE users ADD COLUMN online_privacy TEXT NOT NULL DEFAULT 'everyone';
@@ -0,0 +1,29 @@
+-- Личные настройки чата в списке: без звука, закреплён (pinned_at задаёт
+-- порядок закреплённых), в архиве, черновик
+CREATE TABLE chat_user_settings (
+    chat_id TEXT NOT NULL REFERENCES chats(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    muted BOOLEAN NOT NULL DEFAULT FALSE,
+    pinned_at TEXT,
+    archived BOOLEAN NOT NULL DEFAULT FALSE,
+    draft TEXT,
+    PRIMARY KEY (chat_id, user_id)
+);
+
+-- Синхронизация списка чатов дельтами: общий счётчик версий и версия
+-- последнего изменения чата у каждого пользователя. Без внешнего ключа
+-- на chats: удалённый чат должен остаться в изменениях.
+CREATE TABLE sync_state (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    version BIGINT NOT NULL
+);
+INSERT INTO sync_state (id, version) VALUES (1, 0);
+
+CREATE TABLE chat_list_changes (
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    chat_id TEXT NOT NULL,
+    version BIGINT NOT NULL,
+    PRIMARY KEY (user_id, chat_id)
+);
+
+CREATE INDEX idx_chat_list_changes_version ON chat_list_changes(user_id, version);
@@ -0,0 +1,15 @@
+-- Каналы: посты публикуются от имени канала, подпись администратора —
+-- по настройке sign_messages
+ALTER TABLE chats ADD COLUMN sign_messages BOOLEAN NOT NULL DEFAULT FALSE;
+
+ALTER TABLE messages ADD COLUMN channel_post BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE messages ADD COLUMN author_signature TEXT;
+ALTER TABLE messages ADD COLUMN views BIGINT NOT NULL DEFAULT 0;
+
+-- Кто уже видел пост: просмотр засчитывается один раз на пользователя
+CREATE TABLE message_views (
+    message_id TEXT NOT NULL REFERENCES messages(id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    viewed_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, user_id)
+);
@@ -0,0 +1,28 @@
+-- Опросы: сообщение с message_type = 'poll', вопрос — его content.
+-- Викторина (quiz) — опрос с одним правильным вариантом correct_option.
+-- Опрос закрыт, если closed или наступил closes_at.
+CREATE TABLE polls (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    multiple BOOLEAN NOT NULL DEFAULT FALSE,
+    anonymous BOOLEAN NOT NULL DEFAULT TRUE,
+    correct_option BIGINT,
+    explanation TEXT,
+    closes_at TEXT,
+    closed BOOLEAN NOT NULL DEFAULT FALSE
+);
+
+-- Варианты ответа по порядку, position с нуля
+CREATE TABLE poll_options (
+    message_id TEXT NOT NULL REFERENCES polls(message_id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    text TEXT NOT NULL,
+    PRIMARY KEY (message_id, position)
+);
+
+CREATE TABLE poll_votes (
+    message_id TEXT NOT NULL REFERENCES polls(message_id) ON DELETE CASCADE,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    position BIGINT NOT NULL,
+    voted_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (message_id, user_id, position)
+);
@@ -0,0 +1,18 @@
+-- Пересылка: копия сообщения хранит, откуда оно. Автор виден ссылкой
+-- (sender_id), только если его forwards_privacy это разрешает пересылающему;
+-- иначе остаётся лишь имя. У поста канала ссылка — на канал и пост.
+-- Пересылка пересланного сохраняет исходную подпись. Из чатов
+-- с restrict_forwarding пересылать нельзя.
+ALTER TABLE users ADD COLUMN forwards_privacy TEXT NOT NULL DEFAULT 'everyone';
+ALTER TABLE chats ADD COLUMN restrict_forwarding BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- Исходное сообщение может быть удалено: ссылки на него без внешнего ключа
+CREATE TABLE message_forwards (
+    message_id TEXT PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+    sender_id TEXT,
+    sender_name TEXT NOT NULL,
+    chat_id TEXT,
+    chat_name TEXT,
+    from_message_id TEXT,
+    original_date TEXT NOT NULL
+);
@@ -0,0 +1,21 @@
+-- Устройства для push-уведомлений. endpoint — URL подписки (Web Push,
+-- UnifiedPush) или токен FCM; p256dh и auth — ключи подписки для
+-- шифрования по RFC 8291. hint_key — ключ устройства (AES-256, base64):
+-- им шифруется подсказка «что загрузить», текста сообщений в push нет.
+CREATE TABLE push_devices (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider TEXT NOT NULL,
+    endpoint TEXT NOT NULL UNIQUE,
+    p256dh TEXT,
+    auth TEXT,
+    hint_key TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_push_devices_user ON push_devices(user_id);
+
+-- Уведомления чата: без звука до muted_until (muted — бессрочно);
+-- mute_mentions — молчать и об упоминаниях в чате без звука
+ALTER TABLE chat_user_settings ADD COLUMN muted_until TEXT;
+ALTER TABLE chat_user_settings ADD COLUMN mute_mentions BOOLEAN NOT NULL DEFAULT FALSE;
@@ -52,7 +52,7 @@ pub struct ChatResponse {
 
 /// Перевод текста через Qwen API
 pub async fn translate(
-    State(state): State<AppState>,
+    State(_state): State<AppState>,
     Json(req): Json<TranslateRequest>,
 ) -> Result<Json<TranslateResponse>, StatusCode> {
     let api_key = std::env::var("QWEN_API_KEY").unwrap_or_default();
@@ -92,7 +92,7 @@ pub async fn translate(
 
 /// Саммаризация текста
 pub async fn summarize(
-    State(state): State<AppState>,
+    State(_state): State<AppState>,
     Json(req): Json<SummarizeRequest>,
 ) -> Result<Json<SummarizeResponse>, StatusCode> {
     let api_key = std::env::var("QWEN_API_KEY").unwrap_or_default();
@@ -130,7 +130,7 @@ pub async fn summarize(
 
 /// AI чат с ассистентом
 pub async fn chat(
-    State(state): State<AppState>,
+    State(_state): State<AppState>,
     Json(req): Json<ChatRequest>,
 ) -> Result<Json<ChatResponse>, StatusCode> {
     let api_key = std::env::var("QWEN_API_KEY").unwrap_or_default();
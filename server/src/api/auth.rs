@@ -4,11 +4,11 @@
 use axum::{
     extract::State,
     Json,
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::{api::AppState, auth};
+use crate::{api::{contacts, AppState}, auth, db::users::NewUser};
 
 /// Запрос регистрации
 #[derive(Debug, Deserialize)]
@@ -44,24 +44,28 @@ pub async fn register(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Генерация ключей Ed25519
-    let (signing_key, verifying_key) = auth::generate_keypair();
+    let (_signing_key, verifying_key) = auth::generate_keypair();
     let user_id = Uuid::new_v4().to_string();
 
     // Сохранение в базу данных
-    let result = sqlx::query(
-        "INSERT INTO users (id, username, email, password_hash, public_key) VALUES (?, ?, ?, ?, ?)"
-    )
-    .bind(&user_id)
-    .bind(&req.username)
-    .bind(&req.email)
-    .bind(&password_hash)
-    .bind(hex::encode(verifying_key.to_bytes()))
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка регистрации: {}", e);
-        StatusCode::BAD_REQUEST
-    })?;
+    let public_key = hex::encode(verifying_key.to_bytes());
+    let email_hash = req.email.as_deref().map(contacts::email_hash);
+    state
+        .db
+        .users()
+        .create(&NewUser {
+            id: &user_id,
+            username: &req.username,
+            email: req.email.as_deref(),
+            email_hash: email_hash.as_deref(),
+            password_hash: &password_hash,
+            public_key: &public_key,
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка регистрации: {}", e);
+            StatusCode::BAD_REQUEST
+        })?;
 
     // Создание JWT токена
     let token = auth::create_token(&user_id, &req.username)
@@ -71,7 +75,7 @@ pub async fn register(
         user_id,
         username: req.username,
         token,
-        public_key: hex::encode(verifying_key.to_bytes()),
+        public_key,
     }))
 }
 
@@ -81,35 +85,37 @@ pub async fn login(
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>, StatusCode> {
     // Поиск пользователя
-    let user: (String, String, String, String) = sqlx::query_as(
-        "SELECT id, username, password_hash, public_key FROM users WHERE username = ?"
-    )
-    .bind(&req.username)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let user = state
+        .db
+        .users()
+        .credentials(&req.username)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка входа: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
 
     // Верификация пароля
-    if !auth::verify_password(&req.password, &user.2) {
+    if !auth::verify_password(&req.password, &user.password_hash) {
         return Err(StatusCode::UNAUTHORIZED);
     }
 
     // Создание JWT токена
-    let token = auth::create_token(&user.0, &user.1)
+    let token = auth::create_token(&user.id, &user.username)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(AuthResponse {
-        user_id: user.0,
-        username: user.1,
+        user_id: user.id,
+        username: user.username,
         token,
-        public_key: user.3,
+        public_key: user.public_key,
     }))
 }
 
 /// Верификация токена
 pub async fn verify_token(
-    State(state): State<AppState>,
-    axum::extract::Headers(headers): axum::extract::Headers<axum::http::HeaderMap>,
+    headers: HeaderMap,
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     let auth_header = headers
         .get("Authorization")
@@ -2,13 +2,27 @@
 //! API чатов
 
 use axum::{
-    extract::{State, Path},
+    extract::{State, Path, Query},
     Json,
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
-use crate::api::AppState;
+use crate::{
+    api::{contacts, AppState},
+    auth::Claims,
+    db::{
+        chats::{ChatFilter, ChatSettings, ChatSummary, NewChat},
+        reads::UnreadCounts,
+        timestamp,
+        Database,
+    },
+    permissions::{Membership, Permission},
+};
+
+/// Тип личного чата двух пользователей
+pub const PRIVATE_CHAT_TYPE: &str = "private";
 
 #[derive(Serialize)]
 pub struct ChatResponse {
@@ -18,17 +32,18 @@ pub struct ChatResponse {
     pub name: Option<String>,
     pub description: Option<String>,
     pub owner_id: Option<String>,
+    pub sign_messages: bool,
+    pub restrict_forwarding: bool,
     pub members: Vec<ChatMember>,
     pub last_message: Option<MessagePreview>,
+    #[serde(flatten)]
+    pub counts: UnreadCounts,
+    #[serde(flatten)]
+    pub settings: ChatSettings,
     pub created_at: String,
 }
 
-#[derive(Serialize)]
-pub struct ChatMember {
-    pub user_id: String,
-    pub username: String,
-    pub role: String,
-}
+pub use crate::db::chats::ChatMember;
 
 #[derive(Serialize)]
 pub struct MessagePreview {
@@ -38,6 +53,46 @@ pub struct MessagePreview {
     pub created_at: String,
 }
 
+/// Изменения списка чатов после версии `since`
+#[derive(Serialize)]
+pub struct ChatListDelta {
+    /// Передать как `since` в следующий раз
+    pub version: i64,
+    /// Новые и изменённые чаты (без `since` — все), в порядке списка
+    pub chats: Vec<ChatResponse>,
+    /// Чаты, из которых пользователь вышел или которые удалены
+    pub removed: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SyncQuery {
+    pub since: Option<i64>,
+}
+
+/// Не указанные поля не меняются; пустой `draft` удаляет черновик,
+/// `muted: false` снимает и `muted_until`
+#[derive(Deserialize)]
+pub struct UpdateChatSettingsRequest {
+    pub muted: Option<bool>,
+    /// Без звука до этого времени
+    pub muted_until: Option<chrono::DateTime<chrono::Utc>>,
+    pub mute_mentions: Option<bool>,
+    pub pinned: Option<bool>,
+    pub archived: Option<bool>,
+    pub draft: Option<String>,
+}
+
+/// Не указанные поля не меняются
+#[derive(Deserialize)]
+pub struct UpdateChatRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    /// Только для каналов
+    pub sign_messages: Option<bool>,
+    /// Запретить пересылку сообщений чата
+    pub restrict_forwarding: Option<bool>,
+}
+
 #[derive(Deserialize)]
 pub struct CreateChatRequest {
     #[serde(rename = "type")]
@@ -47,106 +102,261 @@ pub struct CreateChatRequest {
     pub member_ids: Option<Vec<String>>,
 }
 
-/// Список чатов пользователя
+/// Положение пользователя в чате (`None`, если он не участник)
+pub(crate) async fn membership(
+    db: &Database,
+    chat_id: &str,
+    user_id: &str,
+) -> Result<Option<Membership>, StatusCode> {
+    db.chats().membership(chat_id, user_id).await.map_err(|e| {
+        tracing::error!("Ошибка проверки участника чата: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Единая проверка прав в чате: положение пользователя, если действие
+/// ему разрешено, иначе 403 (в том числе не участнику)
+pub(crate) async fn authorize(
+    db: &Database,
+    chat_id: &str,
+    user_id: &str,
+    permission: Permission,
+) -> Result<Membership, StatusCode> {
+    let now = timestamp(chrono::Utc::now());
+    match membership(db, chat_id, user_id).await? {
+        Some(membership) if membership.allows(permission, &now) => Ok(membership),
+        _ => Err(StatusCode::FORBIDDEN),
+    }
+}
+
+impl ChatResponse {
+    fn new(summary: ChatSummary, members: Vec<ChatMember>) -> Self {
+        let last_message = match (
+            summary.last_message_id,
+            summary.last_message_content,
+            summary.last_message_sender_id,
+            summary.last_message_at,
+        ) {
+            (Some(id), Some(content), Some(sender_id), Some(created_at)) => Some(MessagePreview {
+                id,
+                content,
+                sender_id,
+                created_at,
+            }),
+            _ => None,
+        };
+        let chat = summary.chat;
+
+        Self {
+            id: chat.id,
+            chat_type: chat.chat_type,
+            name: chat.name,
+            description: chat.description,
+            owner_id: chat.owner_id,
+            sign_messages: chat.sign_messages,
+            restrict_forwarding: chat.restrict_forwarding,
+            members,
+            last_message,
+            counts: summary.counts,
+            settings: summary.settings,
+            created_at: chat.created_at,
+        }
+    }
+}
+
+/// Чаты пользователя по фильтру, с участниками
+async fn load_chats(state: &AppState, user_id: &str, filter: ChatFilter<'_>) -> Result<Vec<ChatResponse>, StatusCode> {
+    let chats = state.db.chats();
+    let summaries = chats.summaries(user_id, filter).await.map_err(|e| {
+        tracing::error!("Ошибка получения чатов: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let rows = chats.summary_members(user_id, filter).await.map_err(|e| {
+        tracing::error!("Ошибка получения участников: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut members: HashMap<String, Vec<ChatMember>> = HashMap::new();
+    for row in rows {
+        members.entry(row.chat_id).or_default().push(row.member);
+    }
+
+    Ok(summaries
+        .into_iter()
+        .map(|summary| {
+            let chat_members = members.remove(&summary.chat.id).unwrap_or_default();
+            ChatResponse::new(summary, chat_members)
+        })
+        .collect())
+}
+
+/// Список чатов пользователя: закреплённые, затем по последней активности
 pub async fn list_chats(
     State(state): State<AppState>,
-    // claims: Claims,
+    claims: Claims,
 ) -> Result<Json<Vec<ChatResponse>>, StatusCode> {
-    // В реальности здесь будет фильтрация по user_id из токена
-    let chats = sqlx::query_as(
-        "SELECT id, type, name, description, owner_id, created_at FROM chats ORDER BY updated_at DESC"
-    )
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка получения чатов: {}", e);
+    load_chats(&state, &claims.sub, ChatFilter::All).await.map(Json)
+}
+
+/// Изменения списка чатов после версии `since` (без неё — весь список)
+pub async fn sync_chats(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<SyncQuery>,
+) -> Result<Json<ChatListDelta>, StatusCode> {
+    // Версия берётся до выборки: изменение между ними придёт ещё раз
+    let version = state.db.chats().version().await.map_err(|e| {
+        tracing::error!("Ошибка получения версии чатов: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    Ok(Json(chats))
+    let (chats, removed) = match query.since {
+        Some(since) => {
+            let chats = load_chats(&state, &claims.sub, ChatFilter::ChangedSince(since)).await?;
+            let removed = state.db.chats().removed_since(&claims.sub, since).await.map_err(|e| {
+                tracing::error!("Ошибка получения удалённых чатов: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            (chats, removed)
+        }
+        None => (load_chats(&state, &claims.sub, ChatFilter::All).await?, vec![]),
+    };
+
+    Ok(Json(ChatListDelta { version, chats, removed }))
 }
 
-/// Создать чат
-pub async fn create_chat(
+/// Личные настройки чата: звук, закрепление, архив, черновик
+pub async fn update_chat_settings(
     State(state): State<AppState>,
-    Json(req): Json<CreateChatRequest>,
-    // claims: Claims,
-) -> Result<Json<ChatResponse>, StatusCode> {
-    let chat_id = Uuid::new_v4().to_string();
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<UpdateChatSettingsRequest>,
+) -> Result<Json<ChatSettings>, StatusCode> {
+    authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let chats = state.db.chats();
+    let current = chats.settings(&chat_id, &claims.sub).await.map_err(|e| {
+        tracing::error!("Ошибка получения настроек чата: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let settings = ChatSettings {
+        muted: req.muted.unwrap_or(current.muted),
+        muted_until: match (req.muted, req.muted_until) {
+            (Some(false), _) => None,
+            (_, Some(until)) => Some(timestamp(until)),
+            _ => current.muted_until,
+        },
+        mute_mentions: req.mute_mentions.unwrap_or(current.mute_mentions),
+        pinned: req.pinned.unwrap_or(current.pinned),
+        archived: req.archived.unwrap_or(current.archived),
+        draft: match req.draft {
+            Some(draft) if draft.is_empty() => None,
+            Some(draft) => Some(draft),
+            None => current.draft,
+        },
+    };
 
-    // Создание чата
-    sqlx::query(
-        "INSERT INTO chats (id, type, name, description, owner_id) VALUES (?, ?, ?, ?, ?)"
-    )
-    .bind(&chat_id)
-    .bind(&req.chat_type)
-    .bind(&req.name)
-    .bind(&req.description)
-    .bind("owner-id") // claims.sub
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка создания чата: {}", e);
+    chats.set_settings(&chat_id, &claims.sub, &settings).await.map_err(|e| {
+        tracing::error!("Ошибка сохранения настроек чата: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    Ok(Json(settings))
+}
 
-    // Добавление участников
-    if let Some(member_ids) = req.member_ids {
-        for member_id in member_ids {
-            sqlx::query("INSERT INTO chat_members (chat_id, user_id) VALUES (?, ?)")
-                .bind(&chat_id)
-                .bind(&member_id)
-                .execute(&state.db)
-                .await
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+/// Создать чат
+pub async fn create_chat(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<CreateChatRequest>,
+) -> Result<Json<ChatResponse>, StatusCode> {
+    // Личный чат не создать с тем, кто заблокировал пользователя
+    if req.chat_type == PRIVATE_CHAT_TYPE {
+        for member_id in req.member_ids.iter().flatten().filter(|id| **id != claims.sub) {
+            if contacts::is_blocked(&state, member_id, &claims.sub).await? {
+                return Err(StatusCode::FORBIDDEN);
+            }
         }
     }
 
+    let chat_id = Uuid::new_v4().to_string();
+
+    // Создание чата вместе с участниками
+    state
+        .db
+        .chats()
+        .create(
+            &NewChat {
+                id: &chat_id,
+                chat_type: &req.chat_type,
+                name: req.name.as_deref(),
+                description: req.description.as_deref(),
+                owner_id: &claims.sub,
+            },
+            req.member_ids.as_deref().unwrap_or_default(),
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка создания чата: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
     Ok(Json(ChatResponse {
         id: chat_id,
         chat_type: req.chat_type,
         name: req.name,
         description: req.description,
-        owner_id: Some("owner-id".to_string()),
+        owner_id: Some(claims.sub),
+        sign_messages: false,
+        restrict_forwarding: false,
         members: vec![],
         last_message: None,
+        counts: UnreadCounts::default(),
+        settings: ChatSettings::default(),
         created_at: chrono::Utc::now().to_rfc3339(),
     }))
 }
 
-/// Получить чат по ID
+/// Получить чат по ID (только участнику)
 pub async fn get_chat(
     State(state): State<AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
 ) -> Result<Json<ChatResponse>, StatusCode> {
-    let chat: (String, String, Option<String>, Option<String>, Option<String>, String) = sqlx::query_as(
-        "SELECT id, type, name, description, owner_id, created_at FROM chats WHERE id = ?"
-    )
-    .bind(&chat_id)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    // Получение участников
-    let members = sqlx::query_as(
-        "SELECT cm.user_id, u.username, cm.role 
-         FROM chat_members cm 
-         JOIN users u ON cm.user_id = u.id 
-         WHERE cm.chat_id = ?"
-    )
-    .bind(&chat_id)
-    .fetch_all(&state.db)
-    .await
-    .unwrap_or_default();
+    load_chats(&state, &claims.sub, ChatFilter::One(&chat_id))
+        .await?
+        .pop()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
 
-    Ok(Json(ChatResponse {
-        id: chat.0,
-        chat_type: chat.1,
-        name: chat.2,
-        description: chat.3,
-        owner_id: chat.4,
-        members,
-        last_message: None,
-        created_at: chat.5,
-    }))
+/// Изменить название, описание и настройки (право менять информацию о чате)
+pub async fn update_chat(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<UpdateChatRequest>,
+) -> Result<Json<ChatResponse>, StatusCode> {
+    authorize(&state.db, &chat_id, &claims.sub, Permission::ChangeInfo).await?;
+
+    state
+        .db
+        .chats()
+        .update_info(
+            &chat_id,
+            req.name.as_deref(),
+            req.description.as_deref(),
+            req.sign_messages,
+            req.restrict_forwarding,
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка изменения чата: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    load_chats(&state, &claims.sub, ChatFilter::One(&chat_id))
+        .await?
+        .pop()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
 }
@@ -0,0 +1,238 @@
+// server/src/api/contacts.rs
+//! API контактов и чёрного списка
+//!
+//! Контакты можно добавить по одному или импортировать из адресной книжки
+//! телефона: по именам пользователей и SHA-256 от почты (`email_hash`),
+//! чтобы сами адреса не уходили на сервер. Заблокированный не создаёт
+//! личных чатов с заблокировавшим, не пишет ему и не видит его присутствия.
+
+use axum::{
+    extract::{State, Path},
+    Json,
+    http::StatusCode,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use crate::{
+    api::{db_error, AppState},
+    auth::Claims,
+    db::contacts::{BlockedUser, Contact},
+};
+
+/// Сколько имён и хешей можно импортировать за раз
+const MAX_IMPORT: usize = 1000;
+
+#[derive(Deserialize)]
+pub struct UserIdRequest {
+    pub user_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct ImportContactsRequest {
+    #[serde(default)]
+    pub usernames: Vec<String>,
+    /// SHA-256 от почты в нижнем регистре, hex (см. `email_hash`)
+    #[serde(default)]
+    pub email_hashes: Vec<String>,
+}
+
+/// Хеш почты для поиска при импорте: SHA-256 от адреса без пробелов
+/// по краям и в нижнем регистре, hex
+pub fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(email.trim().to_lowercase().as_bytes()))
+}
+
+/// Другой существующий пользователь: себя — 400, неизвестного — 404
+async fn other_user(state: &AppState, claims: &Claims, user_id: &str) -> Result<(), StatusCode> {
+    if user_id == claims.sub {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state
+        .db
+        .users()
+        .find(user_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения пользователя", e))?
+        .map(|_| ())
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Заблокировал ли `user_id` пользователя `blocked_id`
+pub(crate) async fn is_blocked(state: &AppState, user_id: &str, blocked_id: &str) -> Result<bool, StatusCode> {
+    state
+        .db
+        .contacts()
+        .is_blocked(user_id, blocked_id)
+        .await
+        .map_err(|e| db_error("Ошибка проверки блокировки", e))
+}
+
+pub async fn list_contacts(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<Contact>>, StatusCode> {
+    state
+        .db
+        .contacts()
+        .list(&claims.sub)
+        .await
+        .map(Json)
+        .map_err(|e| db_error("Ошибка получения контактов", e))
+}
+
+/// Добавить в контакты; повторное добавление ничего не меняет
+pub async fn add_contact(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<UserIdRequest>,
+) -> Result<Json<Contact>, StatusCode> {
+    other_user(&state, &claims, &req.user_id).await?;
+
+    let contacts = state.db.contacts();
+    contacts
+        .add(&claims.sub, &req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка добавления контакта", e))?;
+    contacts
+        .find(&claims.sub, &req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения контакта", e))?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn remove_contact(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    let removed = state
+        .db
+        .contacts()
+        .remove(&claims.sub, &user_id)
+        .await
+        .map_err(|e| db_error("Ошибка удаления контакта", e))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Импорт адресной книжки: добавляет найденных и возвращает их. Кого
+/// не нашли, в ответе нет; заблокировавшие пользователя не находятся.
+pub async fn import_contacts(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<ImportContactsRequest>,
+) -> Result<Json<Vec<Contact>>, StatusCode> {
+    if req.usernames.len() + req.email_hashes.len() > MAX_IMPORT {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let usernames: Vec<String> = req
+        .usernames
+        .iter()
+        .map(|name| name.trim().trim_start_matches('@').to_string())
+        .filter(|name| !name.is_empty())
+        .collect();
+    let email_hashes: Vec<String> = req.email_hashes.iter().map(|hash| hash.trim().to_lowercase()).collect();
+
+    let contacts = state.db.contacts();
+    let found = contacts
+        .import(&claims.sub, &usernames, &email_hashes)
+        .await
+        .map_err(|e| db_error("Ошибка импорта контактов", e))?;
+    let imported = contacts
+        .list(&claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка получения контактов", e))?
+        .into_iter()
+        .filter(|contact| found.contains(&contact.user_id))
+        .collect();
+    Ok(Json(imported))
+}
+
+pub async fn list_blocked(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<BlockedUser>>, StatusCode> {
+    state
+        .db
+        .contacts()
+        .blocked(&claims.sub)
+        .await
+        .map(Json)
+        .map_err(|e| db_error("Ошибка получения чёрного списка", e))
+}
+
+/// Заблокировать; действует сразу, в том числе на уже созданные личные чаты
+pub async fn block_user(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<UserIdRequest>,
+) -> Result<StatusCode, StatusCode> {
+    other_user(&state, &claims, &req.user_id).await?;
+
+    state
+        .db
+        .contacts()
+        .block(&claims.sub, &req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка блокировки", e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn unblock_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    let unblocked = state
+        .db
+        .contacts()
+        .unblock(&claims.sub, &user_id)
+        .await
+        .map_err(|e| db_error("Ошибка разблокировки", e))?;
+    if unblocked {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Досчитать `email_hash` пользователям, зарегистрированным до появления
+/// импорта контактов. Возвращает число обновлённых.
+pub async fn backfill_email_hashes(state: &AppState) -> Result<u64, sqlx::Error> {
+    const BATCH: i64 = 500;
+    let users = state.db.users();
+    let mut updated = 0;
+    let mut after = String::new();
+    loop {
+        let batch = users.missing_email_hashes(&after, BATCH).await?;
+        let Some((last, _)) = batch.last() else {
+            break;
+        };
+        after = last.clone();
+
+        for (user_id, email) in &batch {
+            users.set_email_hash(user_id, &email_hash(email)).await?;
+            updated += 1;
+        }
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_email_hash_is_normalized() {
+        assert_eq!(email_hash(" Alice@Example.COM "), email_hash("alice@example.com"));
+        assert_eq!(
+            email_hash("alice@example.com"),
+            "ff8d9819fc0e12bf0d24892e45987e249a28dce836a85cad60e28eaaa8c6d976"
+        );
+    }
+}
@@ -1,5 +1,5 @@
 // server/src/api/extra.rs
-//! Дополнительные функции: стикеры, GIF, эмодзи, закреплённые, избранные, отложенные сообщения, био, темы, демонстрация экрана
+//! Дополнительные функции: стикеры, GIF, эмодзи, закреплённые, избранные сообщения, био, темы, демонстрация экрана
 
 use axum::{
     extract::{State, Path, Query},
@@ -7,8 +7,17 @@ use axum::{
     http::StatusCode,
 };
 use serde::{Deserialize, Serialize};
-use chrono::{Utc, Duration};
+use chrono::Utc;
 use uuid::Uuid;
+use crate::{
+    api::chats::authorize,
+    auth::Claims,
+    db::{
+        extra::{NewSavedMessage, NewSelfDestructMessage},
+        Database,
+    },
+    permissions::Permission,
+};
 
 // ==================== Стикеры ====================
 
@@ -81,22 +90,36 @@ pub struct AddReactionRequest {
     pub emoji: String,
 }
 
+/// Проверка прав в чате сообщения
+async fn authorize_message(
+    db: &Database,
+    message_id: &str,
+    user_id: &str,
+    permission: Permission,
+) -> Result<(), StatusCode> {
+    let message = db
+        .messages()
+        .find(message_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    authorize(db, &message.chat_id, user_id, permission).await.map(|_| ())
+}
+
 /// Добавить реакцию на сообщение
 pub async fn add_reaction(
     State(db): State<crate::api::AppState>,
-    Path((message_id, user_id)): Path<(String, String)>,
+    Path(message_id): Path<String>,
+    claims: Claims,
     Json(req): Json<AddReactionRequest>,
 ) -> Result<StatusCode, StatusCode> {
-    sqlx::query(
-        "INSERT OR REPLACE INTO message_reactions (message_id, user_id, emoji, created_at)
-         VALUES (?, ?, ?, CURRENT_TIMESTAMP)"
-    )
-    .bind(&message_id)
-    .bind(&user_id)
-    .bind(&req.emoji)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    authorize_message(&db.db, &message_id, &claims.sub, Permission::React).await?;
+
+    db.db
+        .extra()
+        .add_reaction(&message_id, &claims.sub, &req.emoji)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(StatusCode::OK)
 }
@@ -105,22 +128,22 @@ pub async fn add_reaction(
 pub async fn get_reactions(
     State(db): State<crate::api::AppState>,
     Path(message_id): Path<String>,
+    claims: Claims,
 ) -> Result<Json<Vec<Reaction>>, StatusCode> {
-    let rows = sqlx::query_as(
-        "SELECT emoji, COUNT(*) as count, GROUP_CONCAT(user_id) as users
-         FROM message_reactions WHERE message_id = ?
-         GROUP BY emoji"
-    )
-    .bind(&message_id)
-    .fetch_all(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let reactions = rows.into_iter().map(|(emoji, count, users): (String, i64, String)| {
+    authorize_message(&db.db, &message_id, &claims.sub, Permission::View).await?;
+
+    let rows = db
+        .db
+        .extra()
+        .reactions(&message_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let reactions = rows.into_iter().map(|row| {
         Reaction {
-            emoji,
-            count,
-            users: users.split(',').map(String::from).collect(),
+            emoji: row.emoji,
+            count: row.count,
+            users: row.users.split(',').map(String::from).collect(),
         }
     }).collect();
 
@@ -129,15 +152,7 @@ pub async fn get_reactions(
 
 // ==================== Закреплённые сообщения ====================
 
-#[derive(Serialize)]
-pub struct PinnedMessage {
-    pub message_id: String,
-    pub chat_id: String,
-    pub content: String,
-    pub sender_id: String,
-    pub pinned_at: String,
-    pub pinned_by: String,
-}
+pub use crate::db::extra::{PinnedMessage, SavedMessage, ScreenShareSession};
 
 #[derive(Deserialize)]
 pub struct PinMessageRequest {
@@ -147,49 +162,35 @@ pub struct PinMessageRequest {
 /// Закрепить сообщение
 pub async fn pin_message(
     State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<PinMessageRequest>,
 ) -> Result<Json<PinnedMessage>, StatusCode> {
+    authorize(&db.db, &chat_id, &claims.sub, Permission::PinMessages).await?;
+
     // Получение информации о сообщении
-    let message = sqlx::query_as(
-        "SELECT id, chat_id, content, sender_id FROM messages WHERE id = ? AND chat_id = ?"
-    )
-    .bind(&req.message_id)
-    .bind(&chat_id)
-    .fetch_optional(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .ok_or(StatusCode::NOT_FOUND)?;
+    let message = db
+        .db
+        .extra()
+        .message_in_chat(&chat_id, &req.message_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
     // Закрепление
-    sqlx::query(
-        "INSERT OR REPLACE INTO pinned_messages (chat_id, message_id, pinned_by, pinned_at)
-         VALUES (?, ?, ?, CURRENT_TIMESTAMP)"
-    )
-    .bind(&chat_id)
-    .bind(&req.message_id)
-    .bind(&user_id)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Обновление сообщения
-    sqlx::query("UPDATE messages SET is_pinned = 1, pinned_at = CURRENT_TIMESTAMP, pinned_by = ? WHERE id = ?")
-        .bind(&user_id)
-        .bind(&req.message_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .pin(&chat_id, &req.message_id, &claims.sub)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let (id, chat, content, sender): (String, String, String, String) = message;
-
     Ok(Json(PinnedMessage {
-        message_id: id,
-        chat_id: chat,
-        content,
-        sender_id: sender,
+        message_id: message.id,
+        chat_id: message.chat_id,
+        content: message.content,
+        sender_id: message.sender_id,
         pinned_at: Utc::now().to_rfc3339(),
-        pinned_by: user_id,
+        pinned_by: claims.sub,
     }))
 }
 
@@ -197,17 +198,13 @@ pub async fn pin_message(
 pub async fn unpin_message(
     State(db): State<crate::api::AppState>,
     Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
 ) -> Result<StatusCode, StatusCode> {
-    sqlx::query("DELETE FROM pinned_messages WHERE chat_id = ? AND message_id = ?")
-        .bind(&chat_id)
-        .bind(&message_id)
-        .execute(&db.db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    authorize(&db.db, &chat_id, &claims.sub, Permission::PinMessages).await?;
 
-    sqlx::query("UPDATE messages SET is_pinned = 0 WHERE id = ?")
-        .bind(&message_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .unpin(&chat_id, &message_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -218,35 +215,22 @@ pub async fn unpin_message(
 pub async fn get_pinned_messages(
     State(db): State<crate::api::AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
 ) -> Result<Json<Vec<PinnedMessage>>, StatusCode> {
-    let messages = sqlx::query_as(
-        "SELECT m.id, m.chat_id, m.content, m.sender_id, pm.pinned_at, pm.pinned_by
-         FROM pinned_messages pm
-         JOIN messages m ON pm.message_id = m.id
-         WHERE pm.chat_id = ?
-         ORDER BY pm.pinned_at DESC"
-    )
-    .bind(&chat_id)
-    .fetch_all(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    authorize(&db.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let messages = db
+        .db
+        .extra()
+        .pinned(&chat_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(messages))
 }
 
 // ==================== Избранные сообщения (Заметки) ====================
 
-#[derive(Serialize, Deserialize)]
-pub struct SavedMessage {
-    pub id: String,
-    pub user_id: String,
-    pub content: String,
-    pub message_type: String,
-    pub file_url: Option<String>,
-    pub tags: Option<String>,
-    pub created_at: String,
-}
-
 #[derive(Deserialize)]
 pub struct SaveMessageRequest {
     pub content: String,
@@ -255,27 +239,32 @@ pub struct SaveMessageRequest {
     pub tags: Option<String>,
 }
 
+#[derive(Deserialize)]
+pub struct SavedMessagesQuery {
+    /// Подстрока в тегах
+    pub tag: Option<String>,
+}
+
 /// Сохранить сообщение в избранное
 pub async fn save_message(
     State(db): State<crate::api::AppState>,
-    user_id: String,
+    Path(user_id): Path<String>,
     Json(req): Json<SaveMessageRequest>,
 ) -> Result<Json<SavedMessage>, StatusCode> {
     let id = Uuid::new_v4().to_string();
 
-    sqlx::query(
-        "INSERT INTO saved_messages (id, user_id, content, message_type, file_url, tags, created_at)
-         VALUES (?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP)"
-    )
-    .bind(&id)
-    .bind(&user_id)
-    .bind(&req.content)
-    .bind(req.message_type.as_deref().unwrap_or("text"))
-    .bind(&req.file_url)
-    .bind(&req.tags)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    db.db
+        .extra()
+        .save(&NewSavedMessage {
+            id: &id,
+            user_id: &user_id,
+            content: &req.content,
+            message_type: req.message_type.as_deref().unwrap_or("text"),
+            file_url: req.file_url.as_deref(),
+            tags: req.tags.as_deref(),
+        })
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(SavedMessage {
         id,
@@ -291,26 +280,15 @@ pub async fn save_message(
 /// Получить избранные сообщения
 pub async fn get_saved_messages(
     State(db): State<crate::api::AppState>,
-    user_id: String,
-    Query(tags): Query<Option<String>>,
+    Path(user_id): Path<String>,
+    Query(query): Query<SavedMessagesQuery>,
 ) -> Result<Json<Vec<SavedMessage>>, StatusCode> {
-    let messages = if let Some(tag) = tags {
-        sqlx::query_as(
-            "SELECT * FROM saved_messages WHERE user_id = ? AND tags LIKE ? ORDER BY created_at DESC"
-        )
-        .bind(&user_id)
-        .bind(&format!("%{}%", tag))
-        .fetch_all(&db.db)
-        .await
-    } else {
-        sqlx::query_as(
-            "SELECT * FROM saved_messages WHERE user_id = ? ORDER BY created_at DESC"
-        )
-        .bind(&user_id)
-        .fetch_all(&db.db)
+    let messages = db
+        .db
+        .extra()
+        .saved(&user_id, query.tag.as_deref())
         .await
-    }
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(messages))
 }
@@ -320,93 +298,9 @@ pub async fn delete_saved_message(
     State(db): State<crate::api::AppState>,
     Path((user_id, message_id)): Path<(String, String)>,
 ) -> Result<StatusCode, StatusCode> {
-    sqlx::query("DELETE FROM saved_messages WHERE user_id = ? AND id = ?")
-        .bind(&user_id)
-        .bind(&message_id)
-        .execute(&db.db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(StatusCode::OK)
-}
-
-// ==================== Отложенные сообщения ====================
-
-#[derive(Serialize)]
-pub struct ScheduledMessage {
-    pub id: String,
-    pub chat_id: String,
-    pub content: String,
-    pub send_at: String,
-    pub status: String,
-}
-
-#[derive(Deserialize)]
-pub struct ScheduleMessageRequest {
-    pub content: String,
-    pub send_at: String,
-    pub message_type: Option<String>,
-    pub file_url: Option<String>,
-}
-
-/// Запланировать сообщение
-pub async fn schedule_message(
-    State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
-    Json(req): Json<ScheduleMessageRequest>,
-) -> Result<Json<ScheduledMessage>, StatusCode> {
-    let id = Uuid::new_v4().to_string();
-
-    sqlx::query(
-        "INSERT INTO scheduled_messages (id, chat_id, sender_id, content, message_type, file_url, send_at, status)
-         VALUES (?, ?, ?, ?, ?, ?, ?, 'pending')"
-    )
-    .bind(&id)
-    .bind(&chat_id)
-    .bind(&user_id)
-    .bind(&req.content)
-    .bind(req.message_type.as_deref().unwrap_or("text"))
-    .bind(&req.file_url)
-    .bind(&req.send_at)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(ScheduledMessage {
-        id,
-        chat_id,
-        content: req.content,
-        send_at: req.send_at,
-        status: "pending".to_string(),
-    }))
-}
-
-/// Получить отложенные сообщения
-pub async fn get_scheduled_messages(
-    State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
-) -> Result<Json<Vec<ScheduledMessage>>, StatusCode> {
-    let messages = sqlx::query_as(
-        "SELECT * FROM scheduled_messages WHERE chat_id = ? AND sender_id = ? AND status = 'pending' ORDER BY send_at ASC"
-    )
-    .bind(&chat_id)
-    .bind(&user_id)
-    .fetch_all(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    Ok(Json(messages))
-}
-
-/// Отменить отложенное сообщение
-pub async fn cancel_scheduled_message(
-    State(db): State<crate::api::AppState>,
-    Path((chat_id, message_id)): Path<(String, String)>,
-) -> Result<StatusCode, StatusCode> {
-    sqlx::query("UPDATE scheduled_messages SET status = 'cancelled' WHERE chat_id = ? AND id = ?")
-        .bind(&chat_id)
-        .bind(&message_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .delete_saved(&user_id, &message_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -432,13 +326,12 @@ pub async fn get_bio(
     State(db): State<crate::api::AppState>,
     Path(user_id): Path<String>,
 ) -> Result<Json<Bio>, StatusCode> {
-    let bio = sqlx::query_scalar(
-        "SELECT bio FROM users WHERE id = ?"
-    )
-    .bind(&user_id)
-    .fetch_optional(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let bio = db
+        .db
+        .extra()
+        .bio(&user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     match bio {
         Some(text) => Ok(Json(Bio {
@@ -456,10 +349,9 @@ pub async fn set_bio(
     Path(user_id): Path<String>,
     Json(req): Json<SetBioRequest>,
 ) -> Result<Json<Bio>, StatusCode> {
-    sqlx::query("UPDATE users SET bio = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
-        .bind(&req.bio)
-        .bind(&user_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .set_bio(&user_id, &req.bio)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -528,10 +420,9 @@ pub async fn set_user_theme(
     State(db): State<crate::api::AppState>,
     Path((user_id, theme)): Path<(String, String)>,
 ) -> Result<StatusCode, StatusCode> {
-    sqlx::query("UPDATE users SET theme = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
-        .bind(&theme)
-        .bind(&user_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .set_theme(&user_id, &theme)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -543,10 +434,9 @@ pub async fn set_night_mode(
     State(db): State<crate::api::AppState>,
     Path((user_id, enabled)): Path<(String, bool)>,
 ) -> Result<StatusCode, StatusCode> {
-    sqlx::query("UPDATE users SET night_mode = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
-        .bind(&enabled)
-        .bind(&user_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .set_night_mode(&user_id, enabled)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -555,45 +445,31 @@ pub async fn set_night_mode(
 
 // ==================== Демонстрация экрана ====================
 
-#[derive(Serialize)]
-pub struct ScreenShareSession {
-    pub id: String,
-    pub chat_id: String,
-    pub user_id: String,
-    pub stream_url: String,
-    pub started_at: String,
-}
-
 #[derive(Deserialize)]
 pub struct StartScreenShareRequest {
     pub chat_id: String,
-    pub user_id: String,
     pub stream_url: String,
 }
 
 /// Начать демонстрацию экрана
 pub async fn start_screen_share(
     State(db): State<crate::api::AppState>,
+    claims: Claims,
     Json(req): Json<StartScreenShareRequest>,
 ) -> Result<Json<ScreenShareSession>, StatusCode> {
+    authorize(&db.db, &req.chat_id, &claims.sub, Permission::SendMessages).await?;
     let id = Uuid::new_v4().to_string();
 
-    sqlx::query(
-        "INSERT INTO screen_share_sessions (id, chat_id, user_id, stream_url, started_at)
-         VALUES (?, ?, ?, ?, CURRENT_TIMESTAMP)"
-    )
-    .bind(&id)
-    .bind(&req.chat_id)
-    .bind(&req.user_id)
-    .bind(&req.stream_url)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    db.db
+        .extra()
+        .start_screen_share(&id, &req.chat_id, &claims.sub, &req.stream_url)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(ScreenShareSession {
         id,
         chat_id: req.chat_id,
-        user_id: req.user_id,
+        user_id: claims.sub,
         stream_url: req.stream_url,
         started_at: Utc::now().to_rfc3339(),
     }))
@@ -604,9 +480,9 @@ pub async fn stop_screen_share(
     State(db): State<crate::api::AppState>,
     Path(session_id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
-    sqlx::query("UPDATE screen_share_sessions SET ended_at = CURRENT_TIMESTAMP WHERE id = ?")
-        .bind(&session_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .stop_screen_share(&session_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -617,14 +493,16 @@ pub async fn stop_screen_share(
 pub async fn get_active_screen_shares(
     State(db): State<crate::api::AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
 ) -> Result<Json<Vec<ScreenShareSession>>, StatusCode> {
-    let sessions = sqlx::query_as(
-        "SELECT * FROM screen_share_sessions WHERE chat_id = ? AND ended_at IS NULL"
-    )
-    .bind(&chat_id)
-    .fetch_all(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    authorize(&db.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let sessions = db
+        .db
+        .extra()
+        .active_screen_shares(&chat_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(sessions))
 }
@@ -646,32 +524,18 @@ pub struct SetSelfDestructRequest {
 /// Установить таймер самоуничтожения для чата
 pub async fn set_chat_self_destruct(
     State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<SetSelfDestructRequest>,
 ) -> Result<Json<SelfDestructConfig>, StatusCode> {
-    // Проверка прав пользователя в чате
-    let is_member = sqlx::query_scalar(
-        "SELECT COUNT(*) > 0 FROM chat_members WHERE chat_id = ? AND user_id = ?"
-    )
-    .bind(&chat_id)
-    .bind(&user_id)
-    .fetch_one(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if !is_member {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    authorize(&db.db, &chat_id, &claims.sub, Permission::ChangeInfo).await?;
 
     // Обновление настроек чата
-    sqlx::query(
-        "UPDATE chats SET self_destruct_timer = ? WHERE id = ?"
-    )
-    .bind(&req.timer_seconds)
-    .bind(&chat_id)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    db.db
+        .extra()
+        .set_self_destruct_timer(&chat_id, Some(req.timer_seconds))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(Json(SelfDestructConfig {
         chat_id,
@@ -684,35 +548,36 @@ pub async fn set_chat_self_destruct(
 pub async fn send_self_destruct_message(
     State(db): State<crate::api::AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<SendSelfDestructRequest>,
 ) -> Result<Json<crate::api::messages::MessageResponse>, StatusCode> {
+    authorize(&db.db, &chat_id, &claims.sub, Permission::SendMessages).await?;
     let message_id = uuid::Uuid::new_v4().to_string();
-    let sender_id = "sender-id"; // TODO: Получить из токена
+    let sender_id = claims.sub.as_str();
     let message_type = req.message_type.clone().unwrap_or_else(|| "text".to_string());
     
     // Вычисление времени удаления на основе таймера
     let delete_at = chrono::Utc::now() + chrono::Duration::seconds(req.timer_seconds);
 
     // Сохранение сообщения с таймером самоуничтожения
-    sqlx::query(
-        "INSERT INTO messages (id, chat_id, sender_id, content, type, file_url, reply_to_id, self_destruct_timer, delete_at)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
-    )
-    .bind(&message_id)
-    .bind(&chat_id)
-    .bind(sender_id)
-    .bind(&req.content)
-    .bind(&message_type)
-    .bind(&req.file_url)
-    .bind(&req.reply_to_id)
-    .bind(&req.timer_seconds)
-    .bind(delete_at)
-    .execute(&db.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка отправки сообщения с таймером: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    db.db
+        .extra()
+        .insert_self_destruct_message(&NewSelfDestructMessage {
+            id: &message_id,
+            chat_id: &chat_id,
+            sender_id,
+            content: &req.content,
+            message_type: &message_type,
+            file_url: req.file_url.as_deref(),
+            reply_to_id: req.reply_to_id.as_deref(),
+            timer_seconds: req.timer_seconds,
+            delete_at: &crate::db::timestamp(delete_at),
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка отправки сообщения с таймером: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
     // TODO: Отправка через WebSocket
 
@@ -721,12 +586,25 @@ pub async fn send_self_destruct_message(
         chat_id,
         sender_id: sender_id.to_string(),
         content: req.content.clone(),
+        entities: Default::default(),
         translated_content: None,
         message_type,
         file_url: req.file_url.clone(),
         reply_to_id: req.reply_to_id.clone(),
         is_edited: false,
+        is_deleted: false,
         created_at: chrono::Utc::now().to_rfc3339(),
+        updated_at: chrono::Utc::now().to_rfc3339(),
+        author_signature: None,
+        views: None,
+        thread_id: None,
+        reply_count: 0,
+        last_reply_id: None,
+        last_reply_at: None,
+        status: None,
+        poll: None,
+        forward_from: None,
+        forwarded: false,
     }))
 }
 
@@ -742,25 +620,14 @@ pub struct SendSelfDestructRequest {
 /// Отключить таймер самоуничтожения для чата
 pub async fn disable_self_destruct(
     State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
 ) -> Result<StatusCode, StatusCode> {
-    // Проверка прав
-    let is_admin = sqlx::query_scalar(
-        "SELECT COUNT(*) > 0 FROM chat_members WHERE chat_id = ? AND user_id = ? AND role = 'admin'"
-    )
-    .bind(&chat_id)
-    .bind(&user_id)
-    .fetch_one(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if !is_admin {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    authorize(&db.db, &chat_id, &claims.sub, Permission::ChangeInfo).await?;
 
-    sqlx::query("UPDATE chats SET self_destruct_timer = NULL WHERE id = ?")
-        .bind(&chat_id)
-        .execute(&db.db)
+    db.db
+        .extra()
+        .set_self_destruct_timer(&chat_id, None)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -771,14 +638,17 @@ pub async fn disable_self_destruct(
 pub async fn get_self_destruct_settings(
     State(db): State<crate::api::AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
 ) -> Result<Json<SelfDestructConfig>, StatusCode> {
-    let timer = sqlx::query_scalar(
-        "SELECT COALESCE(self_destruct_timer, 0) FROM chats WHERE id = ?"
-    )
-    .bind(&chat_id)
-    .fetch_one(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    authorize(&db.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let timer = db
+        .db
+        .extra()
+        .self_destruct_timer(&chat_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
 
     Ok(Json(SelfDestructConfig {
         chat_id,
@@ -786,29 +656,3 @@ pub async fn get_self_destruct_settings(
         enabled: timer > 0,
     }))
 }
-
-// ==================== Очистка отложенных сообщений ====================
-
-/// Отправить просроченные отложенные сообщения
-pub async fn send_scheduled_messages(db: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
-    // Получаем сообщения, которые нужно отправить
-    let messages = sqlx::query_as::<_, (String, String, String, String, Option<String>, Option<String>)>(
-        "SELECT id, chat_id, sender_id, content, message_type, file_url
-         FROM scheduled_messages
-         WHERE send_at <= CURRENT_TIMESTAMP AND status = 'pending'"
-    )
-    .fetch_all(db)
-    .await?;
-
-    let count = messages.len() as u64;
-
-    // Помечаем как отправленные
-    sqlx::query("UPDATE scheduled_messages SET status = 'sent' WHERE send_at <= CURRENT_TIMESTAMP AND status = 'pending'")
-        .execute(db)
-        .await?;
-
-    // TODO: Здесь должна быть логика отправки сообщений через WebSocket
-    // Для каждого сообщения создать событие и отправить через WebSocket
-
-    Ok(count)
-}
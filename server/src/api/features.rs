@@ -8,6 +8,12 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use chrono::{Utc, Duration};
+use crate::{
+    api::chats::authorize,
+    auth::Claims,
+    db::{features::NewAutoDeleteMessage, Database},
+    permissions::Permission,
+};
 
 #[derive(Serialize)]
 pub struct FamilyStatus {
@@ -37,7 +43,7 @@ pub struct SetWallpaperRequest {
     pub sync_to_chat: Option<bool>,
 }
 
-#[derive(Serialize)]
+#[derive(Deserialize)]
 pub struct AutoDeleteMessageRequest {
     pub hours: u32,
 }
@@ -45,7 +51,7 @@ pub struct AutoDeleteMessageRequest {
 /// Получить семейный статус пользователя
 pub async fn get_family_status(
     State(_db): State<crate::api::AppState>,
-    user_id: String,
+    Path(_user_id): Path<String>,
 ) -> Result<Json<FamilyStatus>, StatusCode> {
     // TODO: Получить из БД
     Ok(Json(FamilyStatus {
@@ -58,38 +64,23 @@ pub async fn get_family_status(
 /// Установить семейный статус
 pub async fn set_family_status(
     State(db): State<crate::api::AppState>,
-    user_id: String,
+    Path(user_id): Path<String>,
     Json(req): Json<SetFamilyStatusRequest>,
 ) -> Result<Json<FamilyStatus>, StatusCode> {
     // Обновление статуса пользователя
-    sqlx::query("UPDATE users SET family_status = ? WHERE id = ?")
-        .bind(&req.status)
-        .bind(&user_id)
-        .execute(&db.db)
+    db.db
+        .features()
+        .set_family_status(&user_id, &req.status)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Если статус "married" или "in_relationship" и указан партнёр
-    if let Some(partner_id) = req.partner_id {
+    if let Some(partner_id) = &req.partner_id {
         if req.status == "married" || req.status == "in_relationship" {
-            // Создание семейной связи
-            let relation_id = uuid::Uuid::new_v4().to_string();
-            sqlx::query(
-                "INSERT OR REPLACE INTO family_relations (id, user_id, relative_id, relation_type)
-                 VALUES (?, ?, ?, 'partner')"
-            )
-            .bind(&relation_id)
-            .bind(&user_id)
-            .bind(&partner_id)
-            .execute(&db.db)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-            // Обновление статуса партнёра
-            sqlx::query("UPDATE users SET family_status = ? WHERE id = ?")
-                .bind(&req.status)
-                .bind(&partner_id)
-                .execute(&db.db)
+            // Семейная связь и статус партнёра
+            db.db
+                .features()
+                .set_partner(&user_id, partner_id, &req.status)
                 .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
         }
@@ -105,24 +96,24 @@ pub async fn set_family_status(
 /// Получить обои чата
 pub async fn get_chat_wallpaper(
     State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
 ) -> Result<Json<WallpaperResponse>, StatusCode> {
-    let wallpaper = sqlx::query_as(
-        "SELECT wallpaper_url, wallpaper_type, synced FROM chat_wallpapers
-         WHERE chat_id = ? AND user_id = ?"
-    )
-    .bind(&chat_id)
-    .bind(&user_id)
-    .fetch_optional(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    authorize(&db.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let wallpaper = db
+        .db
+        .features()
+        .wallpaper(&chat_id, &claims.sub)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     match wallpaper {
-        Some((url, wtype, synced)) => Ok(Json(WallpaperResponse {
+        Some(wallpaper) => Ok(Json(WallpaperResponse {
             chat_id,
-            wallpaper_url: url,
-            wallpaper_type: wtype,
-            synced,
+            wallpaper_url: wallpaper.wallpaper_url,
+            wallpaper_type: wallpaper.wallpaper_type,
+            synced: wallpaper.synced,
         })),
         None => {
             // Обои по умолчанию
@@ -136,49 +127,32 @@ pub async fn get_chat_wallpaper(
     }
 }
 
-/// Установить обои чата
+/// Установить обои чата; для всех участников — с правом менять информацию о чате
 pub async fn set_chat_wallpaper(
     State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<SetWallpaperRequest>,
 ) -> Result<Json<WallpaperResponse>, StatusCode> {
     let sync = req.sync_to_chat.unwrap_or(false);
+    let permission = if sync { Permission::ChangeInfo } else { Permission::View };
+    authorize(&db.db, &chat_id, &claims.sub, permission).await?;
     let wallpaper_type = req.wallpaper_type.unwrap_or_else(|| "custom".to_string());
 
     // Сохранение обоев для пользователя
-    sqlx::query(
-        "INSERT OR REPLACE INTO chat_wallpapers (chat_id, user_id, wallpaper_url, wallpaper_type, synced, updated_at)
-         VALUES (?, ?, ?, ?, ?, CURRENT_TIMESTAMP)"
-    )
-    .bind(&chat_id)
-    .bind(&user_id)
-    .bind(&req.wallpaper_url)
-    .bind(&wallpaper_type)
-    .bind(&sync)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    db.db
+        .features()
+        .set_wallpaper(&chat_id, &claims.sub, &req.wallpaper_url, &wallpaper_type, sync)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Если синхронизация включена, обновить обои чата для всех участников
     if sync {
-        // Обновление общих обоев чата
-        sqlx::query("UPDATE chats SET wallpaper_url = ?, wallpaper_sync = TRUE WHERE id = ?")
-            .bind(&req.wallpaper_url)
-            .bind(&chat_id)
-            .execute(&db.db)
+        db.db
+            .features()
+            .sync_wallpaper(&chat_id, &req.wallpaper_url)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-        // Обновление обоев для всех участников
-        sqlx::query(
-            "UPDATE chat_wallpapers SET wallpaper_url = ?, synced = TRUE, updated_at = CURRENT_TIMESTAMP
-             WHERE chat_id = ?"
-        )
-        .bind(&req.wallpaper_url)
-        .bind(&chat_id)
-        .execute(&db.db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
     Ok(Json(WallpaperResponse {
@@ -192,37 +166,18 @@ pub async fn set_chat_wallpaper(
 /// Установить автоудаление сообщений (24 часа)
 pub async fn set_auto_delete(
     State(db): State<crate::api::AppState>,
-    Path((chat_id, user_id)): Path<(String, String)>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<AutoDeleteMessageRequest>,
 ) -> Result<StatusCode, StatusCode> {
-    // Проверка прав пользователя в чате
-    let is_member = sqlx::query_scalar(
-        "SELECT COUNT(*) > 0 FROM chat_members WHERE chat_id = ? AND user_id = ?"
-    )
-    .bind(&chat_id)
-    .bind(&user_id)
-    .fetch_one(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    if !is_member {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    authorize(&db.db, &chat_id, &claims.sub, Permission::ChangeInfo).await?;
 
     // Обновление настроек чата
-    sqlx::query(
-        "UPDATE chats SET auto_delete_hours = ? WHERE id = ? AND (owner_id = ? OR EXISTS (
-            SELECT 1 FROM chat_members WHERE chat_id = ? AND user_id = ? AND role = 'admin'
-        ))"
-    )
-    .bind(&(req.hours as i64))
-    .bind(&chat_id)
-    .bind(&user_id)
-    .bind(&chat_id)
-    .bind(&user_id)
-    .execute(&db.db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    db.db
+        .features()
+        .set_auto_delete(&chat_id, req.hours as i64)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     Ok(StatusCode::OK)
 }
@@ -231,61 +186,70 @@ pub async fn set_auto_delete(
 pub async fn send_auto_delete_message(
     State(db): State<crate::api::AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<crate::api::messages::SendMessageRequest>,
 ) -> Result<Json<crate::api::messages::MessageResponse>, StatusCode> {
+    authorize(&db.db, &chat_id, &claims.sub, Permission::SendMessages).await?;
     let message_id = uuid::Uuid::new_v4().to_string();
-    let sender_id = "sender-id"; // TODO: Получить из токена
+    let sender_id = claims.sub.as_str();
     let message_type = req.message_type.clone().unwrap_or_else(|| "text".to_string());
     
     // Вычисление времени удаления
     let delete_at = Utc::now() + Duration::hours(24);
 
     // Сохранение сообщения с автоудалением
-    sqlx::query(
-        "INSERT INTO messages (id, chat_id, sender_id, content, type, file_url, reply_to_id, auto_delete_hours, delete_at)
-         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
-    )
-    .bind(&message_id)
-    .bind(&chat_id)
-    .bind(sender_id)
-    .bind(&req.content)
-    .bind(&message_type)
-    .bind(&req.file_url)
-    .bind(&req.reply_to_id)
-    .bind(24i64)
-    .bind(delete_at)
-    .execute(&db.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка отправки сообщения: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    db.db
+        .features()
+        .insert_auto_delete_message(&NewAutoDeleteMessage {
+            id: &message_id,
+            chat_id: &chat_id,
+            sender_id,
+            content: &req.content,
+            message_type: &message_type,
+            file_url: req.file_url.as_deref(),
+            reply_to_id: req.reply_to_id.as_deref(),
+            auto_delete_hours: 24,
+            delete_at: &crate::db::timestamp(delete_at),
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка отправки сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
     // TODO: Отправка через WebSocket
 
+    let now = Utc::now().to_rfc3339();
     Ok(Json(crate::api::messages::MessageResponse {
         id: message_id,
         chat_id,
         sender_id: sender_id.to_string(),
         content: req.content.clone(),
+        entities: Default::default(),
         translated_content: None,
         message_type,
         file_url: req.file_url.clone(),
         reply_to_id: req.reply_to_id.clone(),
         is_edited: false,
-        created_at: Utc::now().to_rfc3339(),
+        is_deleted: false,
+        created_at: now.clone(),
+        updated_at: now,
+        author_signature: None,
+        views: None,
+        thread_id: None,
+        reply_count: 0,
+        last_reply_id: None,
+        last_reply_at: None,
+        status: None,
+        poll: None,
+        forward_from: None,
+        forwarded: false,
     }))
 }
 
 /// Удалить просроченные сообщения (задача по расписанию)
-pub async fn cleanup_expired_messages(db: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
-    let result = sqlx::query(
-        "DELETE FROM messages WHERE delete_at IS NOT NULL AND delete_at < CURRENT_TIMESTAMP"
-    )
-    .execute(db)
-    .await?;
-
-    Ok(result.rows_affected())
+pub async fn cleanup_expired_messages(db: &Database) -> Result<u64, sqlx::Error> {
+    db.messages().delete_expired().await
 }
 
 /// Предустановленные обои
@@ -1,15 +1,56 @@
 // server/src/api/files.rs
 //! API загрузки файлов
+//!
+//! Большие файлы грузятся с докачкой (по мотивам tus):
+//! `POST /files/uploads` резервирует место, `PATCH /files/uploads/:id`
+//! с заголовком `Upload-Offset` дописывает кусок, `GET` возвращает
+//! текущее смещение, `POST /files/uploads/:id/finalize` превращает
+//! загрузку в файл. Тип файла определяется по сигнатуре содержимого,
+//! `Content-Type` клиента не используется.
+//!
+//! Содержимое лежит в `storage` по SHA-256; скачивание — только по
+//! подписанной ссылке из `GET /files/:id/url`. Метаданные картинок
+//! (EXIF, GPS) убираются до сохранения, превью и волна аудио строятся
+//! в фоне (`process_media`).
+//!
+//! При заданных мастер-ключах содержимое шифруется в хранилище
+//! (`encryption`) и расшифровывается при скачивании. Файлы с
+//! `client_encrypted` клиент шифрует сам: сервер хранит их как есть,
+//! без определения типа, очистки метаданных и обработки медиа.
 
 use axum::{
-    extract::{State, Multipart, Path},
+    body::Body,
+    extract::{Multipart, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Response,
     Json,
-    http::StatusCode,
 };
-use serde::Serialize;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use std::path::PathBuf;
-use crate::api::AppState;
+use std::{
+    collections::HashSet,
+    path::{Path as FsPath, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use crate::{
+    api::AppState,
+    auth::Claims,
+    db::{
+        files::{FileRecord, MediaMetadata, NewFile, NewThumbnail},
+        uploads::NewUpload,
+    },
+    encryption::{self, DataKey, WrappedKey},
+    media,
+    storage::{self, Object},
+    websocket::WsMessage,
+};
+
+/// Сколько байт с начала файла смотрит `sniff_mime`
+const SNIFF_LEN: usize = 512;
 
 #[derive(Serialize)]
 pub struct FileResponse {
@@ -20,110 +61,1464 @@ pub struct FileResponse {
     pub size: u64,
     pub url: String,
     pub created_at: String,
+    /// `pending`, `ready`, `failed`; `null` — файл не обрабатывается
+    pub media_status: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub duration_ms: Option<i64>,
+    /// Пиковые уровни 0–255 (`media::WAVEFORM_LEN` столбцов)
+    pub waveform: Option<Vec<u8>>,
+    pub blurhash: Option<String>,
+    /// Скачиваются через `GET /files/:id/url?thumbnail=<size>`
+    pub thumbnails: Vec<ThumbnailResponse>,
+    /// Зашифрован клиентом: `mime_type` всегда `application/octet-stream`
+    pub client_encrypted: bool,
+}
+
+#[derive(Serialize)]
+pub struct ThumbnailResponse {
+    pub size: String,
+    pub width: i64,
+    pub height: i64,
+    pub mime_type: String,
+}
+
+#[derive(Deserialize)]
+pub struct CreateUploadRequest {
+    pub filename: String,
+    /// Полный размер файла в байтах
+    pub size: u64,
+    /// Содержимое зашифровано клиентом, сервер его не разбирает
+    #[serde(default)]
+    pub client_encrypted: bool,
+}
+
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    /// См. `CreateUploadRequest::client_encrypted`
+    #[serde(default)]
+    pub client_encrypted: bool,
+}
+
+#[derive(Serialize)]
+pub struct UploadStatus {
+    pub upload_id: String,
+    pub filename: String,
+    pub size: u64,
+    /// Сколько байт уже принято; следующий кусок начинается отсюда
+    pub offset: u64,
+}
+
+/// Лимиты загрузок в байтах (`UPLOAD_*` в окружении)
+#[derive(Debug, Clone)]
+pub struct UploadLimits {
+    pub max_image_bytes: u64,
+    pub max_video_bytes: u64,
+    pub max_audio_bytes: u64,
+    pub max_file_bytes: u64,
+    /// Сумма файлов и незавершённых загрузок пользователя
+    pub user_quota_bytes: u64,
+    /// Загрузка без новых кусков дольше этого считается брошенной
+    pub abandon_after_secs: u64,
+    /// Сколько содержимое без ссылок живёт в хранилище: загрузка того же
+    /// файла, начатая до удаления последней ссылки, успевает её восстановить
+    pub orphan_grace_secs: u64,
+}
+
+impl Default for UploadLimits {
+    fn default() -> Self {
+        const MIB: u64 = 1024 * 1024;
+        Self {
+            max_image_bytes: 20 * MIB,
+            max_video_bytes: 2048 * MIB,
+            max_audio_bytes: 200 * MIB,
+            max_file_bytes: 2048 * MIB,
+            user_quota_bytes: 10 * 1024 * MIB,
+            abandon_after_secs: 24 * 3600,
+            orphan_grace_secs: 3600,
+        }
+    }
+}
+
+impl UploadLimits {
+    /// `UPLOAD_MAX_IMAGE_BYTES`, `UPLOAD_MAX_VIDEO_BYTES`, `UPLOAD_MAX_AUDIO_BYTES`,
+    /// `UPLOAD_MAX_FILE_BYTES`, `UPLOAD_USER_QUOTA_BYTES`, `UPLOAD_ABANDON_AFTER_SECS`,
+    /// `UPLOAD_ORPHAN_GRACE_SECS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |name: &str, default: u64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            max_image_bytes: read("UPLOAD_MAX_IMAGE_BYTES", defaults.max_image_bytes),
+            max_video_bytes: read("UPLOAD_MAX_VIDEO_BYTES", defaults.max_video_bytes),
+            max_audio_bytes: read("UPLOAD_MAX_AUDIO_BYTES", defaults.max_audio_bytes),
+            max_file_bytes: read("UPLOAD_MAX_FILE_BYTES", defaults.max_file_bytes),
+            user_quota_bytes: read("UPLOAD_USER_QUOTA_BYTES", defaults.user_quota_bytes),
+            abandon_after_secs: read("UPLOAD_ABANDON_AFTER_SECS", defaults.abandon_after_secs),
+            orphan_grace_secs: read("UPLOAD_ORPHAN_GRACE_SECS", defaults.orphan_grace_secs),
+        }
+    }
+
+    pub fn max_for(&self, kind: FileKind) -> u64 {
+        match kind {
+            FileKind::Image => self.max_image_bytes,
+            FileKind::Video => self.max_video_bytes,
+            FileKind::Audio => self.max_audio_bytes,
+            FileKind::File => self.max_file_bytes,
+        }
+    }
+
+    /// Предел для файла, тип которого ещё не известен
+    pub fn max_any(&self) -> u64 {
+        self.max_image_bytes
+            .max(self.max_video_bytes)
+            .max(self.max_audio_bytes)
+            .max(self.max_file_bytes)
+    }
+}
+
+/// Загрузки, в которые сейчас пишет какой-то запрос
+#[derive(Clone, Default)]
+pub struct ActiveUploads(Arc<Mutex<HashSet<String>>>);
+
+impl ActiveUploads {
+    /// Занять загрузку; `None`, если в неё уже пишут
+    fn claim(&self, upload_id: &str) -> Option<ActiveUpload> {
+        let mut active = self.0.lock().unwrap();
+        active.insert(upload_id.to_string()).then(|| ActiveUpload {
+            uploads: self.clone(),
+            upload_id: upload_id.to_string(),
+        })
+    }
+}
+
+struct ActiveUpload {
+    uploads: ActiveUploads,
+    upload_id: String,
+}
+
+impl Drop for ActiveUpload {
+    fn drop(&mut self) {
+        self.uploads.0.lock().unwrap().remove(&self.upload_id);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    Image,
+    Video,
+    Audio,
+    File,
+}
+
+impl FileKind {
+    pub fn from_mime(mime_type: &str) -> Self {
+        if mime_type.starts_with("image/") {
+            FileKind::Image
+        } else if mime_type.starts_with("video/") {
+            FileKind::Video
+        } else if mime_type.starts_with("audio/") {
+            FileKind::Audio
+        } else {
+            FileKind::File
+        }
+    }
 }
 
-/// Загрузка файла
+/// MIME-тип и расширение по первым байтам файла
+pub fn sniff_mime(head: &[u8]) -> (&'static str, &'static str) {
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+
+    if at(0, b"\x89PNG\r\n\x1a\n") {
+        return ("image/png", "png");
+    }
+    if at(0, b"\xFF\xD8\xFF") {
+        return ("image/jpeg", "jpg");
+    }
+    if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        return ("image/gif", "gif");
+    }
+    if at(0, b"BM") && head.len() >= 14 {
+        return ("image/bmp", "bmp");
+    }
+    if at(0, b"II*\0") || at(0, b"MM\0*") {
+        return ("image/tiff", "tiff");
+    }
+    if at(0, b"RIFF") {
+        if at(8, b"WEBP") {
+            return ("image/webp", "webp");
+        }
+        if at(8, b"WAVE") {
+            return ("audio/wav", "wav");
+        }
+        if at(8, b"AVI ") {
+            return ("video/x-msvideo", "avi");
+        }
+    }
+    if at(4, b"ftyp") {
+        // ISO BMFF: тип по основному бренду
+        return match head.get(8..12) {
+            Some(b"heic") | Some(b"heix") | Some(b"mif1") => ("image/heic", "heic"),
+            Some(b"avif") => ("image/avif", "avif"),
+            Some(b"qt  ") => ("video/quicktime", "mov"),
+            Some(b"M4A ") | Some(b"M4B ") => ("audio/mp4", "m4a"),
+            _ => ("video/mp4", "mp4"),
+        };
+    }
+    if at(0, b"\x1A\x45\xDF\xA3") {
+        // Matroska и WebM различаются DocType в заголовке EBML
+        return if head.windows(4).any(|w| w == b"webm") {
+            ("video/webm", "webm")
+        } else {
+            ("video/x-matroska", "mkv")
+        };
+    }
+    if at(0, b"OggS") {
+        return ("audio/ogg", "ogg");
+    }
+    if at(0, b"fLaC") {
+        return ("audio/flac", "flac");
+    }
+    if at(0, b"ID3") || (head.len() >= 2 && head[0] == 0xFF && head[1] & 0xE0 == 0xE0) {
+        return ("audio/mpeg", "mp3");
+    }
+    if at(0, b"%PDF-") {
+        return ("application/pdf", "pdf");
+    }
+    if at(0, b"PK\x03\x04") {
+        return ("application/zip", "zip");
+    }
+    if at(0, b"\x1F\x8B") {
+        return ("application/gzip", "gz");
+    }
+    if at(0, b"7z\xBC\xAF\x27\x1C") {
+        return ("application/x-7z-compressed", "7z");
+    }
+    if at(0, b"Rar!\x1A\x07") {
+        return ("application/vnd.rar", "rar");
+    }
+
+    // Текст: корректный UTF-8 (последний символ может быть обрезан) без NUL
+    let utf8 = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+    if !head.is_empty() && utf8 && !head.contains(&0) {
+        return ("text/plain", "txt");
+    }
+
+    ("application/octet-stream", "bin")
+}
+
+/// Каталог незавершённых загрузок: рядом с `uploads_dir`, на той же
+/// файловой системе, но вне хранилища
+fn partial_dir(state: &AppState) -> PathBuf {
+    PathBuf::from(format!("{}.partial", state.uploads_dir.trim_end_matches('/')))
+}
+
+fn partial_path(state: &AppState, id: &str) -> PathBuf {
+    partial_dir(state).join(id)
+}
+
+/// Дописать поток в файл, не больше `max` байт. Возвращает число
+/// записанных байт и итог: записанное остаётся на диске и при ошибке.
+async fn append_stream<S, E>(file: &mut tokio::fs::File, mut stream: S, max: u64) -> (u64, Result<(), StatusCode>)
+where
+    S: Stream<Item = Result<axum::body::Bytes, E>> + Unpin,
+    E: std::fmt::Display,
+{
+    let mut written = 0u64;
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::warn!("Обрыв потока загрузки: {}", e);
+                return (written, Err(StatusCode::BAD_REQUEST));
+            }
+        };
+        if written + chunk.len() as u64 > max {
+            return (written, Err(StatusCode::PAYLOAD_TOO_LARGE));
+        }
+        if let Err(e) = file.write_all(&chunk).await {
+            tracing::error!("Ошибка записи загрузки: {}", e);
+            return (written, Err(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+        written += chunk.len() as u64;
+    }
+    (written, Ok(()))
+}
+
+/// Свободное место в квоте пользователя
+async fn remaining_quota(state: &AppState, user_id: &str) -> Result<u64, StatusCode> {
+    let used = state.db.files().used_bytes(user_id).await;
+    let reserved = state.db.uploads().reserved_bytes(user_id).await;
+    let (used, reserved) = used.and_then(|u| reserved.map(|r| (u, r))).map_err(|e| {
+        tracing::error!("Ошибка подсчёта квоты: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(state.upload_limits.user_quota_bytes.saturating_sub((used + reserved) as u64))
+}
+
+/// Файл, принятый к сохранению: тип определён, метаданные убраны,
+/// хеш посчитан
+struct PreparedFile {
+    client_encrypted: bool,
+    filename: String,
+    mime_type: String,
+    /// Размер после удаления метаданных
+    size: u64,
+    content_hash: String,
+    media_status: Option<&'static str>,
+}
+
+/// Определить тип по содержимому, проверить лимит, убрать метаданные
+/// картинки и посчитать SHA-256. При отказе по размеру временный файл удаляется.
+/// Зашифрованное клиентом содержимое не разбирается.
+async fn prepare_file(
+    state: &AppState,
+    file_id: &str,
+    source: &FsPath,
+    size: u64,
+    client_encrypted: bool,
+) -> Result<PreparedFile, StatusCode> {
+    if client_encrypted {
+        if size > state.upload_limits.max_file_bytes {
+            let _ = tokio::fs::remove_file(source).await;
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        let content_hash = storage::sha256_file(source).await.map_err(|e| {
+            tracing::error!("Ошибка подготовки загрузки: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        return Ok(PreparedFile {
+            client_encrypted,
+            filename: format!("{}.bin", file_id),
+            mime_type: "application/octet-stream".to_string(),
+            size,
+            content_hash,
+            media_status: None,
+        });
+    }
+
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    let read = async {
+        let file = tokio::fs::File::open(source).await?;
+        file.take(SNIFF_LEN as u64).read_to_end(&mut head).await
+    };
+    read.await.map_err(|e| {
+        tracing::error!("Ошибка чтения загрузки: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let (mime_type, extension) = sniff_mime(&head);
+    if size > state.upload_limits.max_for(FileKind::from_mime(mime_type)) {
+        let _ = tokio::fs::remove_file(source).await;
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    let prepared = async {
+        let size = if media::has_strippable_metadata(mime_type) {
+            strip_file_metadata(source, mime_type, size).await?
+        } else {
+            size
+        };
+        Ok::<_, std::io::Error>((size, storage::sha256_file(source).await?))
+    };
+    let (size, content_hash) = prepared.await.map_err(|e| {
+        tracing::error!("Ошибка подготовки загрузки: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(PreparedFile {
+        client_encrypted,
+        filename: format!("{}.{}", file_id, extension),
+        mime_type: mime_type.to_string(),
+        size,
+        content_hash,
+        media_status: media::is_supported(mime_type).then_some("pending"),
+    })
+}
+
+/// Убрать метаданные картинки на месте; возвращает новый размер
+async fn strip_file_metadata(source: &FsPath, mime_type: &'static str, size: u64) -> std::io::Result<u64> {
+    let data = tokio::fs::read(source).await?;
+    let stripped = tokio::task::spawn_blocking(move || media::strip_metadata(&data, mime_type))
+        .await
+        .map_err(std::io::Error::other)?;
+
+    match stripped {
+        Ok(Some(stripped)) => {
+            tokio::fs::write(source, &stripped).await?;
+            Ok(stripped.len() as u64)
+        }
+        Ok(None) => Ok(size),
+        // Битую структуру не разберут и читатели метаданных; обработка
+        // медиа пометит такой файл failed
+        Err(e) => {
+            tracing::debug!("Метаданные {} не разобраны: {}", source.display(), e);
+            Ok(size)
+        }
+    }
+}
+
+/// Ключ объекта: зашифрованный лежит отдельно от открытого, чтобы
+/// `rotate_file_keys` мог зашифровать старое содержимое без подмены на месте
+fn content_object_key(hash: &str, encrypted: bool) -> String {
+    let key = storage::object_key(hash);
+    if encrypted {
+        format!("{}.enc", key)
+    } else {
+        key
+    }
+}
+
+/// Новый ключ данных для содержимого `hash`; `None` — шифрование выключено
+async fn new_data_key(state: &AppState, hash: &str) -> std::io::Result<Option<WrappedKey>> {
+    match &state.key_provider {
+        Some(keys) => keys.wrap(&DataKey::generate(), hash).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+async fn unwrap_data_key(state: &AppState, hash: &str, key: &WrappedKey) -> std::io::Result<DataKey> {
+    let keys = state
+        .key_provider
+        .as_ref()
+        .ok_or_else(|| std::io::Error::other("объект зашифрован, а мастер-ключи не заданы"))?;
+    keys.unwrap(key, hash).await
+}
+
+/// Положить содержимое в хранилище, если его там ещё нет; с ключом
+/// данных — зашифрованным. Файл-источник забирается.
+async fn store_content(state: &AppState, hash: &str, key: Option<&WrappedKey>, source: &FsPath) -> std::io::Result<()> {
+    let object_key = content_object_key(hash, key.is_some());
+    if state.storage.exists(&object_key).await? {
+        return tokio::fs::remove_file(source).await;
+    }
+    let Some(key) = key else {
+        return state.storage.put_file(&object_key, source).await;
+    };
+
+    let mut sealed = source.as_os_str().to_owned();
+    sealed.push(".enc");
+    let sealed = PathBuf::from(sealed);
+    let stored = async {
+        let data_key = unwrap_data_key(state, hash, key).await?;
+        encryption::encrypt_file(&data_key, source, &sealed).await?;
+        state.storage.put_file(&object_key, &sealed).await?;
+        tokio::fs::remove_file(source).await
+    }
+    .await;
+    if stored.is_err() {
+        let _ = tokio::fs::remove_file(&sealed).await;
+    }
+    stored
+}
+
+/// Открыть содержимое для чтения; зашифрованное расшифровывается потоком
+async fn open_content(state: &AppState, hash: &str) -> std::io::Result<Option<Object>> {
+    let key = state.db.files().blob_key(hash).await.map_err(std::io::Error::other)?;
+    let Some(key) = key else {
+        return state.storage.get(&content_object_key(hash, false)).await;
+    };
+
+    let Some(object) = state.storage.get(&content_object_key(hash, true)).await? else {
+        return Ok(None);
+    };
+    let size = encryption::plaintext_len(object.size)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "размер объекта не сходится"))?;
+    let data_key = unwrap_data_key(state, hash, &key).await?;
+    Ok(Some(Object {
+        size,
+        body: encryption::decrypt_stream(data_key, object.body),
+    }))
+}
+
+/// Положить содержимое в хранилище (если такого ещё нет). Вызывается
+/// после записи ссылки в базу: пока ссылка есть, сборщик содержимое не тронет.
+/// При ошибке ссылка снимается, файл пользователя удаляется.
+async fn commit_content(
+    state: &AppState,
+    file_id: &str,
+    owner_id: &str,
+    content_hash: &str,
+    key: Option<&WrappedKey>,
+    source: &FsPath,
+) -> Result<(), StatusCode> {
+    if let Err(e) = store_content(state, content_hash, key, source).await {
+        tracing::error!("Ошибка записи в хранилище: {}", e);
+        let _ = state.db.files().delete(file_id, owner_id).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    Ok(())
+}
+
+fn file_url(file_id: &str) -> String {
+    format!("/files/{}", file_id)
+}
+
+/// Прикрепить к сообщению можно только доступный отправителю файл,
+/// иначе ссылка на чужой файл открывала бы к нему доступ
+pub(crate) async fn authorize_attachment(state: &AppState, file_url: Option<&str>, user_id: &str) -> Result<(), StatusCode> {
+    let Some(file_url) = file_url else {
+        return Ok(());
+    };
+    let allowed = state.db.files().can_attach(file_url, user_id).await.map_err(|e| {
+        tracing::error!("Ошибка проверки доступа к файлу: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !allowed {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(())
+}
+
+/// Ответ с метаданными и превью файла
+async fn file_response(state: &AppState, file: FileRecord) -> Result<FileResponse, StatusCode> {
+    let thumbnails = state.db.files().thumbnails(&file.id).await.map_err(|e| {
+        tracing::error!("Ошибка получения превью: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(FileResponse {
+        id: file.id,
+        filename: file.filename,
+        original_name: file.original_name,
+        mime_type: file.mime_type,
+        size: file.size as u64,
+        url: file.url,
+        created_at: file.created_at,
+        media_status: file.media_status,
+        width: file.width,
+        height: file.height,
+        duration_ms: file.duration_ms,
+        waveform: file.waveform.and_then(|waveform| BASE64.decode(waveform).ok()),
+        blurhash: file.blurhash,
+        thumbnails: thumbnails
+            .into_iter()
+            .map(|thumbnail| ThumbnailResponse {
+                size: thumbnail.size,
+                width: thumbnail.width,
+                height: thumbnail.height,
+                mime_type: thumbnail.mime_type,
+            })
+            .collect(),
+        client_encrypted: file.client_encrypted,
+    })
+}
+
+/// Ответ по только что сохранённому файлу; обработка медиа уходит в фон
+async fn saved_file_response(state: &AppState, file_id: &str) -> Result<Json<FileResponse>, StatusCode> {
+    let file = state
+        .db
+        .files()
+        .find(file_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения файла: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if file.media_status.as_deref() == Some("pending") {
+        let state = state.clone();
+        let file_id = file_id.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = process_media(&state, &file_id).await {
+                tracing::error!("Ошибка обработки медиа {}: {}", file_id, e);
+            }
+        });
+    }
+
+    file_response(state, file).await.map(Json)
+}
+
+/// Загрузка файла одним multipart-запросом
 pub async fn upload_file(
     State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<UploadQuery>,
     mut multipart: Multipart,
-    // claims: Claims,
 ) -> Result<Json<FileResponse>, StatusCode> {
     let field = multipart
         .next_field()
         .await
         .map_err(|_| StatusCode::BAD_REQUEST)?
         .ok_or(StatusCode::BAD_REQUEST)?;
+    let original_name = field.file_name().unwrap_or("unknown").to_string();
 
-    let filename = field.file_name().unwrap_or("unknown").to_string();
-    let mime_type = field.content_type().map(|m| m.to_string()).unwrap_or_default();
-    let data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
-    let size = data.len() as u64;
-
-    // Генерация уникального имени
+    let max = state.upload_limits.max_any().min(remaining_quota(&state, &claims.sub).await?);
     let file_id = Uuid::new_v4().to_string();
-    let extension = std::path::Path::new(&filename)
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("bin");
-    let new_filename = format!("{}.{}", file_id, extension);
-
-    // Определение типа файла и пути
-    let (file_type, subdir) = if mime_type.starts_with("image/") {
-        ("image", "images")
-    } else if mime_type.starts_with("video/") {
-        ("video", "videos")
-    } else if mime_type.starts_with("audio/") {
-        ("audio", "audio")
-    } else {
-        ("file", "files")
+    let temp_path = partial_path(&state, &file_id);
+
+    let mut temp = async {
+        tokio::fs::create_dir_all(partial_dir(&state)).await?;
+        tokio::fs::File::create(&temp_path).await
+    }
+    .await
+    .map_err(|e| {
+        tracing::error!("Ошибка создания временного файла: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let (size, streamed) = append_stream(&mut temp, field, max).await;
+    let flushed = temp.flush().await;
+    drop(temp);
+    if let Err(status) = streamed {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(status);
+    }
+    if flushed.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let prepared = prepare_file(&state, &file_id, &temp_path, size, query.client_encrypted).await?;
+    let url = file_url(&file_id);
+
+    let saved = async {
+        let data_key = new_data_key(&state, &prepared.content_hash).await?;
+        let file = NewFile {
+            id: &file_id,
+            owner_id: &claims.sub,
+            filename: &prepared.filename,
+            original_name: &original_name,
+            mime_type: &prepared.mime_type,
+            size: prepared.size as i64,
+            url: &url,
+            content_hash: &prepared.content_hash,
+            media_status: prepared.media_status,
+            client_encrypted: prepared.client_encrypted,
+            data_key: data_key.as_ref(),
+        };
+        state.db.files().insert(&file).await.map_err(anyhow::Error::from)
+    }
+    .await;
+    let key = match saved {
+        Ok(key) => key,
+        Err(e) => {
+            tracing::error!("Ошибка сохранения файла: {}", e);
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
     };
 
-    // Сохранение файла
-    let file_path = PathBuf::from(&state.uploads_dir).join(subdir).join(&new_filename);
-    
-    // Создание директории если не существует
-    if let Some(parent) = file_path.parent() {
-        tokio::fs::create_dir_all(parent)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    commit_content(&state, &file_id, &claims.sub, &prepared.content_hash, key.as_ref(), &temp_path).await?;
+
+    saved_file_response(&state, &file_id).await
+}
+
+/// Начать загрузку с докачкой: место резервируется в квоте сразу
+pub async fn create_upload(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<CreateUploadRequest>,
+) -> Result<(StatusCode, Json<UploadStatus>), StatusCode> {
+    if req.size == 0 || req.filename.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if req.size > state.upload_limits.max_any() || req.size > remaining_quota(&state, &claims.sub).await? {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
     }
 
-    tokio::fs::write(&file_path, &data)
+    let upload_id = Uuid::new_v4().to_string();
+    async {
+        tokio::fs::create_dir_all(partial_dir(&state)).await?;
+        tokio::fs::File::create(partial_path(&state, &upload_id)).await
+    }
+    .await
+    .map_err(|e| {
+        tracing::error!("Ошибка создания загрузки: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    state
+        .db
+        .uploads()
+        .create(&NewUpload {
+            id: &upload_id,
+            owner_id: &claims.sub,
+            filename: &req.filename,
+            size: req.size as i64,
+            client_encrypted: req.client_encrypted,
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка создания загрузки: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(UploadStatus {
+            upload_id,
+            filename: req.filename,
+            size: req.size,
+            offset: 0,
+        }),
+    ))
+}
+
+async fn fetch_upload(
+    state: &AppState,
+    upload_id: &str,
+    user_id: &str,
+) -> Result<crate::db::uploads::Upload, StatusCode> {
+    state
+        .db
+        .uploads()
+        .find(upload_id, user_id)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Сохранение в базу данных
-    let url = format!("/uploads/{}/{}", subdir, new_filename);
-    
-    sqlx::query(
-        "INSERT INTO files (id, owner_id, filename, original_name, mime_type, size, url) 
-         VALUES (?, ?, ?, ?, ?, ?, ?)"
-    )
-    .bind(&file_id)
-    .bind("owner-id") // claims.sub
-    .bind(&new_filename)
-    .bind(&filename)
-    .bind(&mime_type)
-    .bind(size as i64)
-    .bind(&url)
-    .execute(&state.db)
+        .map_err(|e| {
+            tracing::error!("Ошибка получения загрузки: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Текущее смещение загрузки (после обрыва связи)
+pub async fn get_upload(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(upload_id): Path<String>,
+) -> Result<Json<UploadStatus>, StatusCode> {
+    let upload = fetch_upload(&state, &upload_id, &claims.sub).await?;
+    Ok(Json(UploadStatus {
+        upload_id: upload.id,
+        filename: upload.filename,
+        size: upload.size as u64,
+        offset: upload.received as u64,
+    }))
+}
+
+/// Дописать кусок. `Upload-Offset` должен совпадать с принятым
+/// смещением, иначе 409; при обрыве принятая часть сохраняется.
+pub async fn upload_chunk(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(upload_id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Json<UploadStatus>, StatusCode> {
+    let offset: u64 = headers
+        .get("upload-offset")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    // Смещение читается уже под захватом: иначе запрос со старым смещением
+    // дождался бы конца чужой записи и обрезал её
+    let _active = state.active_uploads.claim(&upload_id).ok_or(StatusCode::CONFLICT)?;
+    let upload = fetch_upload(&state, &upload_id, &claims.sub).await?;
+    let received = upload.received as u64;
+    if offset != received {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    // Хвост от оборванной записи, не учтённый в базе, отбрасывается
+    let path = partial_path(&state, &upload_id);
+    let opened = async {
+        let file = tokio::fs::OpenOptions::new().write(true).open(&path).await?;
+        file.set_len(received).await?;
+        let mut file = file;
+        tokio::io::AsyncSeekExt::seek(&mut file, std::io::SeekFrom::Start(received)).await?;
+        Ok::<_, std::io::Error>(file)
+    };
+    let mut file = opened.await.map_err(|e| {
+        tracing::error!("Ошибка открытия загрузки: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let (written, streamed) =
+        append_stream(&mut file, body.into_data_stream(), upload.size as u64 - received).await;
+    let synced = file.sync_data().await;
+    drop(file);
+    if let Err(e) = synced {
+        tracing::error!("Ошибка записи загрузки: {}", e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let new_offset = received + written;
+    if written > 0 {
+        let advanced = state
+            .db
+            .uploads()
+            .advance(&upload_id, received as i64, new_offset as i64)
+            .await
+            .map_err(|e| {
+                tracing::error!("Ошибка обновления загрузки: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+        if !advanced {
+            return Err(StatusCode::CONFLICT);
+        }
+    }
+    streamed?;
+
+    Ok(Json(UploadStatus {
+        upload_id: upload.id,
+        filename: upload.filename,
+        size: upload.size as u64,
+        offset: new_offset,
+    }))
+}
+
+/// Завершить загрузку: все байты приняты — файл появляется в `files`
+pub async fn finalize_upload(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(upload_id): Path<String>,
+) -> Result<Json<FileResponse>, StatusCode> {
+    let _active = state.active_uploads.claim(&upload_id).ok_or(StatusCode::CONFLICT)?;
+    let upload = fetch_upload(&state, &upload_id, &claims.sub).await?;
+    if upload.received != upload.size {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let file_id = Uuid::new_v4().to_string();
+    let source = partial_path(&state, &upload_id);
+    let prepared = match prepare_file(&state, &file_id, &source, upload.size as u64, upload.client_encrypted).await {
+        Ok(prepared) => prepared,
+        Err(status) => {
+            if status == StatusCode::PAYLOAD_TOO_LARGE {
+                // Файл уже удалён — освобождаем квоту
+                let _ = state.db.uploads().delete(&upload_id).await;
+            }
+            return Err(status);
+        }
+    };
+    let url = file_url(&file_id);
+
+    let key = async {
+        let data_key = new_data_key(&state, &prepared.content_hash).await?;
+        let file = NewFile {
+            id: &file_id,
+            owner_id: &claims.sub,
+            filename: &prepared.filename,
+            original_name: &upload.filename,
+            mime_type: &prepared.mime_type,
+            size: prepared.size as i64,
+            url: &url,
+            content_hash: &prepared.content_hash,
+            media_status: prepared.media_status,
+            client_encrypted: prepared.client_encrypted,
+            data_key: data_key.as_ref(),
+        };
+        state
+            .db
+            .files()
+            .insert_from_upload(&file, &upload_id)
+            .await
+            .map_err(anyhow::Error::from)
+    }
     .await
     .map_err(|e| {
         tracing::error!("Ошибка сохранения файла: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
-    Ok(Json(FileResponse {
-        id: file_id,
-        filename: new_filename,
-        original_name: filename,
-        mime_type,
-        size,
-        url,
-        created_at: chrono::Utc::now().to_rfc3339(),
-    }))
+    commit_content(&state, &file_id, &claims.sub, &prepared.content_hash, key.as_ref(), &source).await?;
+
+    saved_file_response(&state, &file_id).await
+}
+
+/// Отменить загрузку и освободить квоту
+pub async fn cancel_upload(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(upload_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let _active = state.active_uploads.claim(&upload_id).ok_or(StatusCode::CONFLICT)?;
+    fetch_upload(&state, &upload_id, &claims.sub).await?;
+
+    state.db.uploads().delete(&upload_id).await.map_err(|e| {
+        tracing::error!("Ошибка отмены загрузки: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let _ = tokio::fs::remove_file(partial_path(&state, &upload_id)).await;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Удалить брошенные загрузки и временные файлы (задача по расписанию)
+pub async fn cleanup_abandoned_uploads(state: &AppState) -> Result<u64, sqlx::Error> {
+    let max_age = Duration::from_secs(state.upload_limits.abandon_after_secs);
+    let before = chrono::Utc::now() - chrono::Duration::seconds(max_age.as_secs() as i64);
+    let stale = state.db.uploads().stale(&crate::db::timestamp(before)).await?;
+
+    for upload_id in &stale {
+        state.db.uploads().delete(upload_id).await?;
+        let _ = tokio::fs::remove_file(partial_path(state, upload_id)).await;
+    }
+
+    // Временные файлы без строки в базе (оборванные multipart-загрузки)
+    if let Ok(mut entries) = tokio::fs::read_dir(partial_dir(state)).await {
+        let cutoff = SystemTime::now() - max_age;
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let modified = entry.metadata().await.and_then(|m| m.modified());
+            if matches!(modified, Ok(at) if at < cutoff) {
+                let _ = tokio::fs::remove_file(entry.path()).await;
+            }
+        }
+    }
+
+    Ok(stale.len() as u64)
+}
+
+/// Удалить из хранилища содержимое, на которое не осталось ссылок
+pub async fn collect_unreferenced_content(state: &AppState) -> Result<u64, sqlx::Error> {
+    let grace = chrono::Duration::seconds(state.upload_limits.orphan_grace_secs as i64);
+    let before = crate::db::timestamp(chrono::Utc::now() - grace);
+    let mut removed = 0;
+    for hash in state.db.files().unreferenced_blobs(&before).await? {
+        if !state.db.files().forget_blob(&hash, &before).await? {
+            continue;
+        }
+        // Объект мог быть и открытым, и зашифрованным (см. `rotate_file_keys`)
+        let deleted = match state.storage.delete(&content_object_key(&hash, false)).await {
+            Ok(()) => state.storage.delete(&content_object_key(&hash, true)).await,
+            Err(e) => Err(e),
+        };
+        match deleted {
+            Ok(()) => removed += 1,
+            Err(e) => tracing::error!("Ошибка удаления {} из хранилища: {}", hash, e),
+        }
+    }
+    Ok(removed)
+}
+
+/// Перенести файлы, загруженные до хранилища по хешу
+/// (`uploads_dir/{images,videos,audio,files}/…`), в хранилище
+pub async fn import_legacy_files(state: &AppState) -> Result<u64, sqlx::Error> {
+    const BATCH: i64 = 100;
+    let mut imported = 0;
+    let mut after = String::new();
+    loop {
+        let batch = state.db.files().legacy(&after, BATCH).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = last.id.clone();
+
+        for legacy in &batch {
+            match import_legacy_file(state, &legacy.url).await {
+                Ok((hash, size, mime_type)) => {
+                    state
+                        .db
+                        .files()
+                        .attach_legacy(legacy, &hash, size as i64, mime_type, &file_url(&legacy.id))
+                        .await?;
+                    imported += 1;
+                }
+                // Файл остаётся без хеша и недоступен для скачивания
+                Err(e) => tracing::warn!("Файл {} ({}) не перенесён: {}", legacy.id, legacy.url, e),
+            }
+        }
+    }
+    Ok(imported)
+}
+
+/// Положить старый файл в хранилище; строка `file_blobs` заводится
+/// заранее, ссылку на неё ставит `attach_legacy`
+async fn import_legacy_file(state: &AppState, url: &str) -> anyhow::Result<(String, u64, &'static str)> {
+    let relative = url
+        .strip_prefix("/uploads/")
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "неизвестный адрес"))?;
+    let path = PathBuf::from(&state.uploads_dir).join(relative);
+
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    let file = tokio::fs::File::open(&path).await?;
+    let size = file.metadata().await?.len();
+    file.take(SNIFF_LEN as u64).read_to_end(&mut head).await?;
+    let hash = storage::sha256_file(&path).await?;
+    let mime_type = sniff_mime(&head).0;
+
+    let data_key = new_data_key(state, &hash).await?;
+    let key = state
+        .db
+        .files()
+        .reserve_blob(&hash, size as i64, mime_type, data_key.as_ref())
+        .await?;
+    store_content(state, &hash, key.as_ref(), &path).await?;
+    Ok((hash, size, mime_type))
+}
+
+/// Итог `rotate_file_keys`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct KeyRotation {
+    /// Ключей данных перешифровано текущим мастер-ключом
+    pub rewrapped: u64,
+    /// Открытых объектов зашифровано
+    pub encrypted: u64,
+}
+
+/// Перешифровать ключи данных текущим мастер-ключом и зашифровать
+/// содержимое, сохранённое до включения шифрования (`--rotate-keys`).
+/// Само содержимое при смене мастер-ключа не перезаписывается.
+/// Запускается одним экземпляром.
+pub async fn rotate_file_keys(state: &AppState) -> anyhow::Result<KeyRotation> {
+    const BATCH: i64 = 100;
+    let keys = state
+        .key_provider
+        .as_ref()
+        .ok_or_else(|| anyhow::anyhow!("Мастер-ключи не заданы (FILE_MASTER_KEYS)"))?;
+    let files = state.db.files();
+    let mut rotation = KeyRotation::default();
+
+    let mut after = String::new();
+    loop {
+        let batch = files.keys_to_rewrap(keys.current_key_id(), &after, BATCH).await?;
+        let Some((last, _)) = batch.last() else {
+            break;
+        };
+        after = last.clone();
+
+        for (hash, old) in &batch {
+            let data_key = keys.unwrap(old, hash).await?;
+            let new = keys.wrap(&data_key, hash).await?;
+            if files.rewrap_key(hash, old, &new).await? {
+                rotation.rewrapped += 1;
+            }
+        }
+    }
+
+    let mut after = String::new();
+    loop {
+        let batch = files.unencrypted_blobs(&after, BATCH).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = last.clone();
+
+        for hash in &batch {
+            if encrypt_stored_content(state, hash).await? {
+                rotation.encrypted += 1;
+            }
+        }
+    }
+    Ok(rotation)
+}
+
+/// Зашифровать открытый объект: зашифрованная копия кладётся рядом,
+/// строка `file_blobs` получает ключ, открытый объект удаляется
+async fn encrypt_stored_content(state: &AppState, hash: &str) -> anyhow::Result<bool> {
+    let Some(object) = state.storage.get(&content_object_key(hash, false)).await? else {
+        tracing::warn!("Нет содержимого {} — не зашифровано", hash);
+        return Ok(false);
+    };
+
+    let temp = partial_path(state, &format!("{}.rotate", hash));
+    let key = new_data_key(state, hash)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("Мастер-ключи не заданы"))?;
+    let stored = async {
+        tokio::fs::create_dir_all(partial_dir(state)).await?;
+        let mut output = tokio::fs::File::create(&temp).await?;
+        let mut body = object.body;
+        while let Some(chunk) = body.next().await {
+            output.write_all(&chunk?).await?;
+        }
+        output.flush().await?;
+        drop(output);
+        store_content(state, hash, Some(&key), &temp).await
+    }
+    .await;
+    if let Err(e) = stored {
+        let _ = tokio::fs::remove_file(&temp).await;
+        return Err(e.into());
+    }
+
+    if !state.db.files().set_blob_key(hash, &key).await? {
+        // Строку успел забрать сборщик
+        state.storage.delete(&content_object_key(hash, true)).await?;
+        return Ok(false);
+    }
+    state.storage.delete(&content_object_key(hash, false)).await?;
+    Ok(true)
+}
+
+/// Сколько файлов обрабатывается одновременно (`MEDIA_WORKERS`)
+#[derive(Clone)]
+pub struct MediaWorkers(Arc<tokio::sync::Semaphore>);
+
+impl MediaWorkers {
+    pub fn new(workers: usize) -> Self {
+        Self(Arc::new(tokio::sync::Semaphore::new(workers.max(1))))
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            std::env::var("MEDIA_WORKERS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(2),
+        )
+    }
+}
+
+enum MediaInfo {
+    Image(media::ImageInfo),
+    Audio(media::AudioInfo),
+}
+
+/// Обработать файл из очереди: размеры, превью и blurhash картинок,
+/// длительность и волна аудио. Неудача помечает файл `failed`, сам файл
+/// остаётся доступен. Владелец получает `file_processed` по WebSocket.
+pub async fn process_media(state: &AppState, file_id: &str) -> Result<(), sqlx::Error> {
+    let _worker = state.media_workers.0.acquire().await.expect("семафор не закрывается");
+
+    let Some(file) = state.db.files().find(file_id).await? else {
+        return Ok(());
+    };
+    let (Some("pending"), Some(hash)) = (file.media_status.as_deref(), file.content_hash.as_deref()) else {
+        return Ok(());
+    };
+
+    let media_status = match analyze_media(state, &file, hash).await {
+        Ok(info) => match save_media(state, file_id, info).await {
+            Ok(true) => "ready",
+            // Файл удалён или обработан параллельно
+            Ok(false) => return Ok(()),
+            Err(e) => {
+                tracing::warn!("Превью {} не сохранены: {}", file_id, e);
+                state.db.files().fail_media(file_id).await?;
+                "failed"
+            }
+        },
+        Err(e) => {
+            tracing::warn!("Файл {} не обработан: {}", file_id, e);
+            state.db.files().fail_media(file_id).await?;
+            "failed"
+        }
+    };
+
+    state.ws.read().await.send_to_user(
+        &file.owner_id,
+        WsMessage::FileProcessed {
+            file_id: file_id.to_string(),
+            media_status: media_status.to_string(),
+        },
+    );
+    Ok(())
+}
+
+/// Скачать содержимое во временный файл и разобрать в отдельном потоке
+async fn analyze_media(state: &AppState, file: &FileRecord, hash: &str) -> anyhow::Result<MediaInfo> {
+    let temp = partial_path(state, &format!("{}.media", file.id));
+    let analyzed = async {
+        let object = open_content(state, hash)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("нет содержимого {}", hash))?;
+        tokio::fs::create_dir_all(partial_dir(state)).await?;
+        let mut output = tokio::fs::File::create(&temp).await?;
+        let mut body = object.body;
+        while let Some(chunk) = body.next().await {
+            output.write_all(&chunk?).await?;
+        }
+        output.flush().await?;
+
+        let path = temp.clone();
+        let mime_type = file.mime_type.clone();
+        tokio::task::spawn_blocking(move || match FileKind::from_mime(&mime_type) {
+            FileKind::Image => media::analyze_image(&std::fs::read(&path)?).map(MediaInfo::Image),
+            _ => media::analyze_audio(std::fs::File::open(&path)?, &mime_type).map(MediaInfo::Audio),
+        })
+        .await?
+    }
+    .await;
+
+    let _ = tokio::fs::remove_file(&temp).await;
+    analyzed
+}
+
+/// Записать результат; превью кладутся в хранилище как обычное содержимое
+async fn save_media(state: &AppState, file_id: &str, info: MediaInfo) -> anyhow::Result<bool> {
+    let files = state.db.files();
+    match info {
+        MediaInfo::Audio(audio) => {
+            let waveform = BASE64.encode(&audio.waveform);
+            let metadata = MediaMetadata {
+                duration_ms: Some(audio.duration_ms as i64),
+                waveform: Some(&waveform),
+                ..Default::default()
+            };
+            Ok(files.save_media(file_id, &metadata, &[]).await?)
+        }
+        MediaInfo::Image(image) => {
+            let mut hashes = Vec::with_capacity(image.thumbnails.len());
+            for thumbnail in &image.thumbnails {
+                hashes.push(store_thumbnail(state, file_id, thumbnail).await?);
+            }
+            let thumbnails: Vec<NewThumbnail> = image
+                .thumbnails
+                .iter()
+                .zip(&hashes)
+                .map(|(thumbnail, hash)| NewThumbnail {
+                    size: thumbnail.size,
+                    width: thumbnail.width as i64,
+                    height: thumbnail.height as i64,
+                    mime_type: thumbnail.mime_type,
+                    content_hash: hash,
+                    byte_size: thumbnail.data.len() as i64,
+                })
+                .collect();
+            let metadata = MediaMetadata {
+                width: Some(image.width as i64),
+                height: Some(image.height as i64),
+                blurhash: Some(&image.blurhash),
+                ..Default::default()
+            };
+            Ok(files.save_media(file_id, &metadata, &thumbnails).await?)
+        }
+    }
 }
 
-/// Получить файл
+/// Положить превью в хранилище и вернуть его хеш. Строка `file_blobs`
+/// заводится заранее без ссылок: если `save_media` не состоится,
+/// превью уберёт `collect_unreferenced_content`.
+async fn store_thumbnail(state: &AppState, file_id: &str, thumbnail: &media::Thumbnail) -> anyhow::Result<String> {
+    let temp = partial_path(state, &format!("{}.{}", file_id, thumbnail.size));
+    let stored = async {
+        tokio::fs::write(&temp, &thumbnail.data).await?;
+        let hash = storage::sha256_file(&temp).await?;
+        let data_key = new_data_key(state, &hash).await?;
+        let key = state
+            .db
+            .files()
+            .reserve_blob(&hash, thumbnail.data.len() as i64, thumbnail.mime_type, data_key.as_ref())
+            .await?;
+
+        store_content(state, &hash, key.as_ref(), &temp).await?;
+        Ok(hash)
+    }
+    .await;
+
+    if stored.is_err() {
+        let _ = tokio::fs::remove_file(&temp).await;
+    }
+    stored
+}
+
+/// Обработать файлы, оставшиеся в очереди (после перезапуска сервера)
+pub async fn process_pending_media(state: &AppState) -> Result<u64, sqlx::Error> {
+    const BATCH: i64 = 100;
+    let pending = state.db.files().pending_media(BATCH).await?;
+    for file_id in &pending {
+        process_media(state, file_id).await?;
+    }
+    Ok(pending.len() as u64)
+}
+
+/// Метаданные файла (владельцу и участникам чатов, где он отправлен)
 pub async fn get_file(
     State(state): State<AppState>,
+    claims: Claims,
     Path(file_id): Path<String>,
 ) -> Result<Json<FileResponse>, StatusCode> {
-    let file: (String, String, String, String, i64, String, String) = sqlx::query_as(
-        "SELECT id, filename, original_name, mime_type, size, url, created_at FROM files WHERE id = ?"
-    )
-    .bind(&file_id)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    Ok(Json(FileResponse {
-        id: file.0,
-        filename: file.1,
-        original_name: file.2,
-        mime_type: file.3,
-        size: file.4 as u64,
-        url: file.5,
-        created_at: file.6,
+    let file = fetch_accessible_file(&state, &file_id, &claims.sub).await?;
+    file_response(&state, file).await.map(Json)
+}
+
+/// Файл, доступный пользователю; чужие неотличимы от несуществующих
+async fn fetch_accessible_file(state: &AppState, file_id: &str, user_id: &str) -> Result<FileRecord, StatusCode> {
+    let files = state.db.files();
+    let allowed = files.can_access(file_id, user_id).await.map_err(|e| {
+        tracing::error!("Ошибка проверки доступа к файлу: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !allowed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    files
+        .find(file_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения файла: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Serialize)]
+pub struct SignedUrl {
+    /// Путь скачивания относительно корня API
+    pub url: String,
+    /// Unix-время, до которого ссылка действительна
+    pub expires_at: i64,
+}
+
+#[derive(Deserialize)]
+pub struct FileUrlQuery {
+    /// Метка превью (`s`, `m`, `x`); без неё — сам файл
+    pub thumbnail: Option<String>,
+}
+
+/// Что подписывается: файл или его превью
+fn signed_resource(file_id: &str, thumbnail: Option<&str>) -> String {
+    match thumbnail {
+        Some(size) => format!("{}/{}", file_id, size),
+        None => file_id.to_string(),
+    }
+}
+
+/// Выдать ссылку на скачивание файла или превью, ограниченную по времени
+pub async fn get_file_url(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<String>,
+    Query(query): Query<FileUrlQuery>,
+) -> Result<Json<SignedUrl>, StatusCode> {
+    fetch_accessible_file(&state, &file_id, &claims.sub).await?;
+
+    let expires_at = chrono::Utc::now().timestamp() + state.url_signer.ttl_secs;
+    let signature = state
+        .url_signer
+        .sign(&signed_resource(&file_id, query.thumbnail.as_deref()), expires_at);
+    let thumbnail = query
+        .thumbnail
+        .map(|size| format!("thumbnail={}&", storage::uri_encode(&size)))
+        .unwrap_or_default();
+    Ok(Json(SignedUrl {
+        url: format!("/files/{}/download?{}expires={}&sig={}", file_id, thumbnail, expires_at, signature),
+        expires_at,
     }))
 }
+
+#[derive(Deserialize)]
+pub struct DownloadQuery {
+    pub thumbnail: Option<String>,
+    pub expires: i64,
+    pub sig: String,
+}
+
+/// Скачать файл по подписанной ссылке (без токена — для `<img src>` и плееров)
+pub async fn download_file(
+    State(state): State<AppState>,
+    Path(file_id): Path<String>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<Response, StatusCode> {
+    let now = chrono::Utc::now().timestamp();
+    let resource = signed_resource(&file_id, query.thumbnail.as_deref());
+    if !state.url_signer.verify(&resource, query.expires, &query.sig, now) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let file = state
+        .db
+        .files()
+        .find(&file_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения файла: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (hash, mime_type) = match &query.thumbnail {
+        Some(size) => {
+            let thumbnails = state.db.files().thumbnails(&file_id).await.map_err(|e| {
+                tracing::error!("Ошибка получения превью: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+            let thumbnail = thumbnails
+                .into_iter()
+                .find(|thumbnail| &thumbnail.size == size)
+                .ok_or(StatusCode::NOT_FOUND)?;
+            (thumbnail.content_hash, thumbnail.mime_type)
+        }
+        None => (file.content_hash.ok_or(StatusCode::NOT_FOUND)?, file.mime_type),
+    };
+
+    let object = open_content(&state, &hash)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка чтения из хранилища: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Response::builder()
+        .header(header::CONTENT_TYPE, mime_type)
+        .header(header::CONTENT_LENGTH, object.size)
+        .header(
+            header::CONTENT_DISPOSITION,
+            format!("inline; filename*=UTF-8''{}", storage::uri_encode(&file.original_name)),
+        )
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff")
+        .header(header::CACHE_CONTROL, format!("private, max-age={}", (query.expires - now).max(0)))
+        .body(Body::from_stream(object.body))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Удалить свой файл; содержимое уходит из хранилища, когда на него
+/// не останется ссылок (`collect_unreferenced_content`)
+pub async fn delete_file(
+    State(state): State<AppState>,
+    claims: Claims,
+    Path(file_id): Path<String>,
+) -> Result<StatusCode, StatusCode> {
+    let deleted = state.db.files().delete(&file_id, &claims.sub).await.map_err(|e| {
+        tracing::error!("Ошибка удаления файла: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_mime_by_signature() {
+        assert_eq!(sniff_mime(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), ("image/png", "png"));
+        assert_eq!(sniff_mime(b"\xFF\xD8\xFF\xE0\0\x10JFIF"), ("image/jpeg", "jpg"));
+        assert_eq!(sniff_mime(b"RIFF\0\0\0\0WEBPVP8 "), ("image/webp", "webp"));
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypisom\0\0\x02\0"), ("video/mp4", "mp4"));
+        assert_eq!(sniff_mime(b"\0\0\0\x18ftypM4A \0\0\0\0"), ("audio/mp4", "m4a"));
+        assert_eq!(sniff_mime(b"\x1A\x45\xDF\xA3\x9f\x42\x82\x84webm"), ("video/webm", "webm"));
+        assert_eq!(sniff_mime(b"OggS\0\x02"), ("audio/ogg", "ogg"));
+        assert_eq!(sniff_mime(b"%PDF-1.7\n"), ("application/pdf", "pdf"));
+    }
+
+    #[test]
+    fn test_sniff_mime_fallbacks() {
+        assert_eq!(sniff_mime("привет, мир".as_bytes()), ("text/plain", "txt"));
+        // Обрезанный посередине многобайтный символ — всё ещё текст
+        assert_eq!(sniff_mime(&"ж".as_bytes()[..1]), ("text/plain", "txt"));
+        assert_eq!(sniff_mime(b"\0\x01\x02\x03"), ("application/octet-stream", "bin"));
+        assert_eq!(sniff_mime(b""), ("application/octet-stream", "bin"));
+    }
+
+    #[test]
+    fn test_upload_limits_by_kind() {
+        let limits = UploadLimits {
+            max_image_bytes: 10,
+            max_video_bytes: 40,
+            max_audio_bytes: 20,
+            max_file_bytes: 30,
+            ..Default::default()
+        };
+        assert_eq!(limits.max_for(FileKind::from_mime("image/png")), 10);
+        assert_eq!(limits.max_for(FileKind::from_mime("application/pdf")), 30);
+        assert_eq!(limits.max_any(), 40);
+    }
+
+    #[test]
+    fn test_active_upload_released_on_drop() {
+        let active = ActiveUploads::default();
+        let claimed = active.claim("u1").unwrap();
+        assert!(active.claim("u1").is_none());
+        drop(claimed);
+        assert!(active.claim("u1").is_some());
+    }
+}
@@ -0,0 +1,258 @@
+// server/src/api/invites.rs
+//! API пригласительных ссылок и заявок на вступление
+//!
+//! Ссылку создаёт участник с правом приглашать. По ссылке можно посмотреть
+//! чат до вступления и вступить; ссылка «с одобрением» вместо вступления
+//! создаёт заявку, которую принимает или отклоняет администратор.
+
+use axum::{
+    extract::{State, Path},
+    Json,
+    http::StatusCode,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use crate::{
+    api::{chats, db_error, AppState},
+    auth::Claims,
+    db::{
+        invites::{Approval, ChatInvite, InvitePreview, JoinRequest, NewInvite},
+        timestamp,
+    },
+    permissions::Permission,
+};
+
+/// Все поля необязательны: без них ссылка бессрочная и без ограничений
+#[derive(Deserialize)]
+pub struct CreateInviteRequest {
+    /// Подпись для администраторов
+    pub name: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<i64>,
+    #[serde(default)]
+    pub requires_approval: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinStatus {
+    /// Пользователь участник чата
+    Joined,
+    /// Заявка ждёт одобрения
+    Requested,
+}
+
+#[derive(Serialize)]
+pub struct JoinResponse {
+    pub chat_id: String,
+    pub status: JoinStatus,
+}
+
+/// Код ссылки: 12 случайных байт в URL-safe base64
+fn generate_code() -> String {
+    let mut bytes = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Создать ссылку (право приглашать)
+pub async fn create_invite(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<CreateInviteRequest>,
+) -> Result<Json<ChatInvite>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+    if req.max_uses.is_some_and(|max_uses| max_uses < 1) || req.expires_at.is_some_and(|at| at <= Utc::now()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let code = generate_code();
+    let invites = state.db.invites();
+    invites
+        .create(&NewInvite {
+            code: &code,
+            chat_id: &chat_id,
+            created_by: &claims.sub,
+            name: req.name.as_deref(),
+            expires_at: req.expires_at.map(timestamp).as_deref(),
+            max_uses: req.max_uses,
+            requires_approval: req.requires_approval,
+        })
+        .await
+        .map_err(|e| db_error("Ошибка создания ссылки", e))?;
+
+    invites
+        .find(&code)
+        .await
+        .map_err(|e| db_error("Ошибка получения ссылки", e))?
+        .map(Json)
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Ссылки чата (право приглашать)
+pub async fn list_invites(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+) -> Result<Json<Vec<ChatInvite>>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+
+    let invites = state
+        .db
+        .invites()
+        .list(&chat_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения ссылок", e))?;
+    Ok(Json(invites))
+}
+
+/// Отозвать ссылку; ожидающие заявки по ней остаются
+pub async fn revoke_invite(
+    State(state): State<AppState>,
+    Path((chat_id, code)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+
+    let revoked = state
+        .db
+        .invites()
+        .revoke(&chat_id, &code)
+        .await
+        .map_err(|e| db_error("Ошибка отзыва ссылки", e))?;
+    if revoked {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Чат по ссылке до вступления; недействующая ссылка — 410
+pub async fn preview_invite(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    _claims: Claims,
+) -> Result<Json<InvitePreview>, StatusCode> {
+    let preview = state
+        .db
+        .invites()
+        .preview(&code)
+        .await
+        .map_err(|e| db_error("Ошибка получения ссылки", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !preview.active {
+        return Err(StatusCode::GONE);
+    }
+    Ok(Json(preview))
+}
+
+/// Вступить по ссылке или подать заявку. Участнику — сразу `joined`
+/// (даже по недействующей ссылке), заблокированному в чате — 403,
+/// недействующая ссылка — 410.
+pub async fn join_by_invite(
+    State(state): State<AppState>,
+    Path(code): Path<String>,
+    claims: Claims,
+) -> Result<Json<JoinResponse>, StatusCode> {
+    let invites = state.db.invites();
+    let invite = invites
+        .find(&code)
+        .await
+        .map_err(|e| db_error("Ошибка получения ссылки", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let chat_id = invite.chat_id;
+    if chats::membership(&state.db, &chat_id, &claims.sub).await?.is_some() {
+        return Ok(Json(JoinResponse { chat_id, status: JoinStatus::Joined }));
+    }
+    if !invite.active {
+        return Err(StatusCode::GONE);
+    }
+    let banned = state
+        .db
+        .chats()
+        .is_banned(&chat_id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка проверки блокировки", e))?;
+    if banned {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if invite.requires_approval {
+        invites
+            .request_join(&chat_id, &claims.sub, &code)
+            .await
+            .map_err(|e| db_error("Ошибка создания заявки", e))?;
+        return Ok(Json(JoinResponse { chat_id, status: JoinStatus::Requested }));
+    }
+
+    // Ссылка могла исчерпаться после проверки
+    invites
+        .join(&code, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка вступления по ссылке", e))?
+        .ok_or(StatusCode::GONE)?;
+    Ok(Json(JoinResponse { chat_id, status: JoinStatus::Joined }))
+}
+
+/// Заявки на вступление (право приглашать)
+pub async fn list_join_requests(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+) -> Result<Json<Vec<JoinRequest>>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+
+    let requests = state
+        .db
+        .invites()
+        .join_requests(&chat_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения заявок", e))?;
+    Ok(Json(requests))
+}
+
+/// Принять заявку; 410 — её ссылка уже не действует
+pub async fn approve_join_request(
+    State(state): State<AppState>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+
+    let approval = state
+        .db
+        .invites()
+        .approve(&chat_id, &user_id)
+        .await
+        .map_err(|e| db_error("Ошибка одобрения заявки", e))?;
+    match approval {
+        Approval::Approved => Ok(StatusCode::NO_CONTENT),
+        Approval::NotFound => Err(StatusCode::NOT_FOUND),
+        Approval::InviteInactive => Err(StatusCode::GONE),
+    }
+}
+
+/// Отклонить заявку
+pub async fn decline_join_request(
+    State(state): State<AppState>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+
+    let declined = state
+        .db
+        .invites()
+        .decline(&chat_id, &user_id)
+        .await
+        .map_err(|e| db_error("Ошибка отклонения заявки", e))?;
+    if declined {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
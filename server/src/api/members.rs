@@ -0,0 +1,315 @@
+// server/src/api/members.rs
+//! API участников групп и каналов: приглашение, роли, ограничения,
+//! блокировки и передача владения
+//!
+//! Все проверки идут через `chats::authorize`. Менять роль или удалять
+//! участника можно только тому, кого действующий «старше»
+//! (`Membership::outranks`).
+
+use axum::{
+    extract::{State, Path, Query},
+    Json,
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use crate::{
+    api::{chats, db_error, AppState},
+    auth::Claims,
+    db::{chats::{ChatBan, ChatMember}, timestamp},
+    permissions::{Membership, Permission, Rights, Role},
+};
+
+/// Размер страницы участников по умолчанию и наибольший
+const DEFAULT_MEMBERS_PAGE: u32 = 100;
+const MAX_MEMBERS_PAGE: u32 = 200;
+
+/// Страница участников: после `after` (id последнего на прошлой странице)
+#[derive(Deserialize)]
+pub struct ListMembersQuery {
+    pub after: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct AddMemberRequest {
+    pub user_id: String,
+}
+
+/// Новая роль участника. `rights` — только для `admin`,
+/// `until` — только для `restricted` (без него бессрочно).
+#[derive(Deserialize)]
+pub struct UpdateMemberRequest {
+    pub role: Role,
+    pub rights: Option<Rights>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct BanRequest {
+    pub user_id: String,
+    /// Без срока — бессрочно
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct TransferRequest {
+    pub user_id: String,
+}
+
+/// Положение участника, которым управляют; 404 — не участник
+async fn target_membership(state: &AppState, chat_id: &str, user_id: &str) -> Result<Membership, StatusCode> {
+    chats::membership(&state.db, chat_id, user_id)
+        .await?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Участники чата с ролями и правами, постранично по id. Подписчиков
+/// канала видят только администраторы, которые ими управляют.
+pub async fn list_members(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    Query(query): Query<ListMembersQuery>,
+    claims: Claims,
+) -> Result<Json<Vec<ChatMember>>, StatusCode> {
+    let membership = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    if membership.channel {
+        let now = timestamp(Utc::now());
+        if !membership.allows(Permission::InviteUsers, &now) && !membership.allows(Permission::BanUsers, &now) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_MEMBERS_PAGE).clamp(1, MAX_MEMBERS_PAGE);
+
+    let members = state
+        .db
+        .chats()
+        .members(&chat_id, query.after.as_deref().unwrap_or(""), limit as i64)
+        .await
+        .map_err(|e| db_error("Ошибка получения участников", e))?;
+    Ok(Json(members))
+}
+
+/// Пригласить пользователя (право приглашать); заблокированного — нельзя
+pub async fn add_member(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<AddMemberRequest>,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::InviteUsers).await?;
+
+    state
+        .db
+        .users()
+        .find(&req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения пользователя", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let chats = state.db.chats();
+    if chats
+        .is_banned(&chat_id, &req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка проверки блокировки", e))?
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    chats
+        .add_member(&chat_id, &req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка добавления участника", e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Назначить администратором, снять с должности, ограничить или снять
+/// ограничение. Администратор выдаёт только те права, что есть у него самого.
+pub async fn update_member(
+    State(state): State<AppState>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    claims: Claims,
+    Json(req): Json<UpdateMemberRequest>,
+) -> Result<Json<Membership>, StatusCode> {
+    let actor = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    let target = target_membership(&state, &chat_id, &user_id).await?;
+    if !actor.outranks(&claims.sub, &target) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let now = timestamp(Utc::now());
+    // Назначение и снятие администратора — право назначать,
+    // ограничения — право блокировать
+    let touches_admin = req.role == Role::Admin || target.role == Role::Admin;
+    let touches_restriction = req.role == Role::Restricted || target.role == Role::Restricted;
+    if (touches_admin && !actor.allows(Permission::PromoteMembers, &now))
+        || (touches_restriction && !actor.allows(Permission::BanUsers, &now))
+    {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let membership = match req.role {
+        Role::Owner => return Err(StatusCode::BAD_REQUEST),
+        Role::Admin => {
+            let rights = req.rights.unwrap_or_default();
+            if !actor.effective_rights().contains(rights) {
+                return Err(StatusCode::FORBIDDEN);
+            }
+            Membership {
+                role: Role::Admin,
+                rights,
+                restricted_until: None,
+                promoted_by: Some(claims.sub.clone()),
+                channel: target.channel,
+                blocked: target.blocked,
+            }
+        }
+        Role::Member => Membership {
+            role: Role::Member,
+            rights: Rights::NONE,
+            restricted_until: None,
+            promoted_by: None,
+            channel: target.channel,
+            blocked: target.blocked,
+        },
+        Role::Restricted => Membership {
+            role: Role::Restricted,
+            rights: Rights::NONE,
+            restricted_until: req.until.map(timestamp),
+            promoted_by: None,
+            channel: target.channel,
+            blocked: target.blocked,
+        },
+    };
+
+    state
+        .db
+        .chats()
+        .set_role(&chat_id, &user_id, &membership)
+        .await
+        .map_err(|e| db_error("Ошибка смены роли", e))?;
+    Ok(Json(membership))
+}
+
+/// Выйти из чата (свой `user_id`) или удалить участника (право блокировать).
+/// Владелец выйти не может — сначала передаёт владение.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    let actor = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    if user_id == claims.sub {
+        if actor.role == Role::Owner {
+            return Err(StatusCode::CONFLICT);
+        }
+    } else {
+        let target = target_membership(&state, &chat_id, &user_id).await?;
+        if !actor.allows(Permission::BanUsers, &timestamp(Utc::now())) || !actor.outranks(&claims.sub, &target) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    state
+        .db
+        .chats()
+        .remove_member(&chat_id, &user_id)
+        .await
+        .map_err(|e| db_error("Ошибка удаления участника", e))?;
+    state.ws.write().await.unsubscribe_chat(&chat_id, &user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Заблокировать: участник удаляется и не может быть приглашён до `until`
+pub async fn ban_member(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<BanRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let actor = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::BanUsers).await?;
+    if req.user_id == claims.sub {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    // Заблокировать можно и ещё не вступившего
+    if let Some(target) = chats::membership(&state.db, &chat_id, &req.user_id).await? {
+        if !actor.outranks(&claims.sub, &target) {
+            return Err(StatusCode::FORBIDDEN);
+        }
+    }
+
+    let until = req.until.map(timestamp);
+    state
+        .db
+        .chats()
+        .ban(&chat_id, &req.user_id, &claims.sub, until.as_deref())
+        .await
+        .map_err(|e| db_error("Ошибка блокировки", e))?;
+    state.ws.write().await.unsubscribe_chat(&chat_id, &req.user_id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Снять блокировку (в чат пользователь не возвращается)
+pub async fn unban_member(
+    State(state): State<AppState>,
+    Path((chat_id, user_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::BanUsers).await?;
+
+    let removed = state
+        .db
+        .chats()
+        .unban(&chat_id, &user_id)
+        .await
+        .map_err(|e| db_error("Ошибка снятия блокировки", e))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+/// Действующие блокировки чата
+pub async fn list_bans(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+) -> Result<Json<Vec<ChatBan>>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::BanUsers).await?;
+
+    let bans = state
+        .db
+        .chats()
+        .bans(&chat_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения блокировок", e))?;
+    Ok(Json(bans))
+}
+
+/// Передать владение участнику; прежний владелец становится
+/// администратором со всеми правами
+pub async fn transfer_ownership(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<TransferRequest>,
+) -> Result<StatusCode, StatusCode> {
+    let actor = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    if actor.role != Role::Owner {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if req.user_id == claims.sub {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    target_membership(&state, &chat_id, &req.user_id).await?;
+
+    state
+        .db
+        .chats()
+        .transfer_ownership(&chat_id, &claims.sub, &req.user_id)
+        .await
+        .map_err(|e| db_error("Ошибка передачи владения", e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
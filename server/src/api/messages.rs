@@ -8,22 +8,32 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::api::AppState;
+use crate::{
+    api::{
+        chats, files,
+        db_error,
+        polls::{self, CreatePollRequest},
+        reads, threads,
+        users::{self, Visibility},
+        AppState,
+    },
+    auth::Claims,
+    db::{
+        chats::Chat,
+        messages::{Direction, Feed, ForwardFrom, MessageMeta, MessageViews, NewMessage},
+        polls::Poll,
+        scheduled::ScheduledSend,
+        threads::ThreadRoot,
+        timestamp,
+    },
+    entities::{self, Entities, MessageEntity},
+    fanout::FanoutJob,
+    push::PushJob,
+    permissions::{Membership, Permission},
+    websocket::WsMessage,
+};
 
-#[derive(Serialize)]
-pub struct MessageResponse {
-    pub id: String,
-    pub chat_id: String,
-    pub sender_id: String,
-    pub content: String,
-    pub translated_content: Option<String>,
-    #[serde(rename = "type")]
-    pub message_type: String,
-    pub file_url: Option<String>,
-    pub reply_to_id: Option<String>,
-    pub is_edited: bool,
-    pub created_at: String,
-}
+pub use crate::db::messages::{Message as MessageResponse, MessageEdit};
 
 #[derive(Deserialize)]
 pub struct SendMessageRequest {
@@ -32,85 +42,859 @@ pub struct SendMessageRequest {
     pub message_type: Option<String>,
     pub file_url: Option<String>,
     pub reply_to_id: Option<String>,
+    /// Разметка `content` (см. `entities`)
+    #[serde(default)]
+    pub entities: Vec<MessageEntity>,
+    /// Обязателен для типа `poll`, у других типов его нет
+    pub poll: Option<CreatePollRequest>,
+}
+
+/// Максимальный размер страницы
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Сколько постов можно отметить просмотренными за раз
+const MAX_VIEWS_BATCH: usize = 100;
+
+/// Сколько сообщений и в сколько чатов можно переслать за раз
+const MAX_FORWARD_BATCH: usize = 100;
+const MAX_FORWARD_TARGETS: usize = 10;
+
+#[derive(Deserialize)]
+pub struct ForwardMessagesRequest {
+    pub from_chat_id: String,
+    pub message_ids: Vec<String>,
+    pub to_chat_ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RecordViewsRequest {
+    pub message_ids: Vec<String>,
 }
 
 #[derive(Deserialize)]
 pub struct ListMessagesQuery {
     limit: Option<u32>,
-    offset: Option<u32>,
+    /// Сообщения старше курсора (по умолчанию — с самого нового)
+    before: Option<String>,
+    /// Сообщения новее курсора
+    after: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MessageContextQuery {
+    /// Сколько сообщений взять с каждой стороны
+    limit: Option<u32>,
+}
+
+/// Страница ленты, от новых к старым
+#[derive(Serialize)]
+pub struct MessagePage {
+    pub messages: Vec<MessageResponse>,
+    /// Курсор для `before`: следующая страница более старых сообщений
+    pub next_cursor: Option<String>,
+    /// Курсор для `after`: более новые сообщения, в том числе пришедшие позже
+    pub prev_cursor: Option<String>,
+    /// Есть ли уже известные более новые сообщения
+    pub has_newer: bool,
+}
+
+/// Сообщение с окружением (переход к ответу или результату поиска)
+#[derive(Serialize)]
+pub struct MessageContext {
+    pub target_id: String,
+    /// От новых к старым, включая само сообщение
+    pub messages: Vec<MessageResponse>,
+    pub next_cursor: Option<String>,
+    pub prev_cursor: Option<String>,
+    pub has_newer: bool,
+}
+
+/// Позиция в ленте: ключ сортировки `(created_at, id)`.
+/// Наружу отдаётся непрозрачной hex-строкой.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub created_at: String,
+    pub id: String,
+}
+
+impl Cursor {
+    fn of(message: &MessageResponse) -> Self {
+        Self {
+            created_at: message.created_at.clone(),
+            id: message.id.clone(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}\n{}", self.created_at, self.id))
+    }
+
+    pub fn decode(raw: &str) -> Option<Self> {
+        let bytes = hex::decode(raw).ok()?;
+        let text = String::from_utf8(bytes).ok()?;
+        let (created_at, id) = text.split_once('\n')?;
+        if created_at.is_empty() || id.is_empty() {
+            return None;
+        }
+        Some(Self {
+            created_at: created_at.to_string(),
+            id: id.to_string(),
+        })
+    }
 }
 
-/// Список сообщений чата
+/// Выборка до `limit` сообщений по одну сторону от курсора (строго), от курсора наружу.
+/// Возвращает признак того, что за пределами выборки есть ещё сообщения.
+async fn fetch_page(
+    state: &AppState,
+    chat_id: &str,
+    user_id: &str,
+    feed: Feed<'_>,
+    cursor: Option<&Cursor>,
+    direction: Direction,
+    limit: u32,
+) -> Result<(Vec<MessageResponse>, bool), StatusCode> {
+    let from = cursor.map(|c| (c.created_at.as_str(), c.id.as_str()));
+    let (mut messages, has_more) = state
+        .db
+        .messages()
+        .page(chat_id, user_id, feed, from, direction, limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения сообщений: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    polls::attach(state, &mut messages, user_id).await?;
+    Ok((messages, has_more))
+}
+
+#[derive(Deserialize)]
+pub struct EditMessageRequest {
+    pub content: String,
+    /// Разметка нового текста
+    #[serde(default)]
+    pub entities: Vec<MessageEntity>,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteMessageQuery {
+    /// `true` — удалить у всех, иначе только у себя
+    #[serde(default)]
+    pub for_everyone: bool,
+}
+
+/// Окна, в течение которых автор может редактировать и удалять у всех
+/// своё сообщение. `0` — без ограничения. Администраторы чата удаляют
+/// чужие сообщения без ограничения по времени.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageLimits {
+    pub edit_window_secs: i64,
+    pub delete_window_secs: i64,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            edit_window_secs: 48 * 3600,
+            delete_window_secs: 48 * 3600,
+        }
+    }
+}
+
+impl MessageLimits {
+    /// `MESSAGE_EDIT_WINDOW_SECS` и `MESSAGE_DELETE_WINDOW_SECS`
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let read = |name: &str, default: i64| {
+            std::env::var(name)
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(default)
+        };
+
+        Self {
+            edit_window_secs: read("MESSAGE_EDIT_WINDOW_SECS", defaults.edit_window_secs),
+            delete_window_secs: read("MESSAGE_DELETE_WINDOW_SECS", defaults.delete_window_secs),
+        }
+    }
+
+    fn within(window_secs: i64, sent_at: i64) -> bool {
+        window_secs == 0 || chrono::Utc::now().timestamp() - sent_at <= window_secs
+    }
+
+    pub fn can_edit(&self, sent_at: i64) -> bool {
+        Self::within(self.edit_window_secs, sent_at)
+    }
+
+    pub fn can_delete(&self, sent_at: i64) -> bool {
+        Self::within(self.delete_window_secs, sent_at)
+    }
+}
+
+/// Отметить упомянутых и ответ автору и уведомить впервые упомянутых
+async fn save_mentions(
+    state: &AppState,
+    chat_id: &str,
+    message_id: &str,
+    sender_id: &str,
+    content: &str,
+    entities: &Entities,
+    reply_to_id: Option<&str>,
+) -> Result<(), StatusCode> {
+    let usernames = entities::mentioned_usernames(content, entities);
+    let user_ids = entities::mentioned_user_ids(entities);
+    if usernames.is_empty() && user_ids.is_empty() && reply_to_id.is_none() {
+        return Ok(());
+    }
+
+    let mentioned = state
+        .db
+        .reads()
+        .add_mentions(message_id, chat_id, sender_id, &usernames, &user_ids, reply_to_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка сохранения упоминаний: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let ws = state.ws.read().await;
+    for user_id in &mentioned {
+        ws.send_to_user(user_id, WsMessage::Mentioned {
+            chat_id: chat_id.to_string(),
+            message_id: message_id.to_string(),
+            sender_id: sender_id.to_string(),
+        });
+    }
+    Ok(())
+}
+
+async fn fetch_message_meta(
+    state: &AppState,
+    chat_id: &str,
+    message_id: &str,
+) -> Result<MessageMeta, StatusCode> {
+    state
+        .db
+        .messages()
+        .meta(chat_id, message_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Подпись поста канала: имя автора, если канал подписывает посты
+async fn channel_signature(state: &AppState, chat_id: &str, user_id: &str) -> Result<Option<String>, StatusCode> {
+    let chat = state
+        .db
+        .chats()
+        .find(chat_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения чата: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !chat.sign_messages {
+        return Ok(None);
+    }
+
+    let user = state
+        .db
+        .users()
+        .find(user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения пользователя: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    Ok(Some(user.username))
+}
+
+/// Сообщение глазами `viewer` (для опроса — его выбор)
+pub(crate) async fn fetch_message(
+    state: &AppState,
+    message_id: &str,
+    viewer: &str,
+) -> Result<MessageResponse, StatusCode> {
+    let message = state
+        .db
+        .messages()
+        .find(message_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let mut messages = [message];
+    polls::attach(state, &mut messages, viewer).await?;
+    let [message] = messages;
+    Ok(message)
+}
+
+/// Список сообщений чата (keyset-пагинация по `(created_at, id)`).
+/// Удалённые у всех возвращаются как «надгробия», удалённые у себя — скрываются.
 pub async fn list_messages(
     State(state): State<AppState>,
     Path(chat_id): Path<String>,
     Query(query): Query<ListMessagesQuery>,
-) -> Result<Json<Vec<MessageResponse>>, StatusCode> {
-    let limit = query.limit.unwrap_or(50);
-    let offset = query.offset.unwrap_or(0);
-
-    let messages = sqlx::query_as(
-        "SELECT id, chat_id, sender_id, content, translated_content, type, file_url, reply_to_id, is_edited, created_at 
-         FROM messages 
-         WHERE chat_id = ? 
-         ORDER BY created_at DESC 
-         LIMIT ? OFFSET ?"
-    )
-    .bind(&chat_id)
-    .bind(limit as i64)
-    .bind(offset as i64)
-    .fetch_all(&state.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка получения сообщений: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    Ok(Json(messages))
-}
-
-/// Отправить сообщение
+    claims: Claims,
+) -> Result<Json<MessagePage>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    Ok(Json(load_page(&state, &chat_id, &claims.sub, Feed::Chat, &query).await?))
+}
+
+/// Страница ленты `feed` по курсорам из запроса (права проверяет вызывающий)
+pub(crate) async fn load_page(
+    state: &AppState,
+    chat_id: &str,
+    user_id: &str,
+    feed: Feed<'_>,
+    query: &ListMessagesQuery,
+) -> Result<MessagePage, StatusCode> {
+    let limit = query.limit.unwrap_or(50).clamp(1, MAX_PAGE_SIZE);
+    let decode = |raw: &Option<String>| match raw {
+        Some(raw) => Cursor::decode(raw).map(Some).ok_or(StatusCode::BAD_REQUEST),
+        None => Ok(None),
+    };
+    let before = decode(&query.before)?;
+    let after = decode(&query.after)?;
+
+    let page = match (before, after) {
+        (Some(_), Some(_)) => return Err(StatusCode::BAD_REQUEST),
+        (None, Some(after)) => {
+            let (mut messages, has_newer) =
+                fetch_page(state, chat_id, user_id, feed, Some(&after), Direction::Newer, limit).await?;
+            messages.reverse();
+            MessagePage {
+                next_cursor: messages.last().map(|m| Cursor::of(m).encode()),
+                prev_cursor: messages.first().map(|m| Cursor::of(m).encode()),
+                has_newer,
+                messages,
+            }
+        }
+        (before, None) => {
+            let (messages, has_older) =
+                fetch_page(state, chat_id, user_id, feed, before.as_ref(), Direction::Older, limit).await?;
+            MessagePage {
+                next_cursor: if has_older { messages.last().map(|m| Cursor::of(m).encode()) } else { None },
+                prev_cursor: messages.first().map(|m| Cursor::of(m).encode()),
+                // Первая страница всегда самая свежая
+                has_newer: before.is_some(),
+                messages,
+            }
+        }
+    };
+
+    Ok(page)
+}
+
+/// Сообщение и по `limit` сообщений до и после него
+pub async fn get_message_context(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    Query(query): Query<MessageContextQuery>,
+    claims: Claims,
+) -> Result<Json<MessageContext>, StatusCode> {
+    let limit = query.limit.unwrap_or(20).clamp(1, MAX_PAGE_SIZE);
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let target = fetch_message(&state, &message_id, &claims.sub).await?;
+    if target.chat_id != chat_id {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    let cursor = Cursor::of(&target);
+
+    let (older, has_older) =
+        fetch_page(&state, &chat_id, &claims.sub, Feed::Chat, Some(&cursor), Direction::Older, limit).await?;
+    let (mut newer, has_newer) =
+        fetch_page(&state, &chat_id, &claims.sub, Feed::Chat, Some(&cursor), Direction::Newer, limit).await?;
+    newer.reverse();
+
+    let mut messages = newer;
+    messages.push(target);
+    messages.extend(older);
+
+    Ok(Json(MessageContext {
+        target_id: message_id,
+        next_cursor: if has_older { messages.last().map(|m| Cursor::of(m).encode()) } else { None },
+        prev_cursor: messages.first().map(|m| Cursor::of(m).encode()),
+        has_newer,
+        messages,
+    }))
+}
+
+/// Отправить сообщение. В канале пишут только администраторы: пост
+/// публикуется от имени канала и рассылается подписчикам через очередь.
+/// Ответ в группе попадает и в тред сообщения, на которое отвечает.
 pub async fn send_message(
     State(state): State<AppState>,
     Path(chat_id): Path<String>,
+    claims: Claims,
     Json(req): Json<SendMessageRequest>,
-    // claims: Claims,
 ) -> Result<Json<MessageResponse>, StatusCode> {
+    let membership = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::SendMessages).await?;
+    let thread = match &req.reply_to_id {
+        Some(reply_to_id) if !membership.channel => state
+            .db
+            .threads()
+            .root_of(&chat_id, reply_to_id)
+            .await
+            .map_err(|e| {
+                tracing::error!("Ошибка получения треда: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+        _ => None,
+    };
+
+    let message = post_message(&state, &chat_id, &claims.sub, &membership, req, thread.as_ref(), Origin::Direct).await?;
+    Ok(Json(message))
+}
+
+/// Откуда сообщение (см. `post_message`)
+#[derive(Clone, Copy)]
+pub(crate) enum Origin<'a> {
+    /// Пользователь пишет сейчас
+    Direct,
+    /// Копия пересылаемого сообщения: упоминания в ней не уведомляют
+    Forward(&'a ForwardFrom),
+    /// Наступило время отложенного: вставка завершает его захват
+    Scheduled(&'a ScheduledSend),
+}
+
+/// Сохранить и разослать сообщение от пользователя с положением `membership`
+/// (права проверяет вызывающий). С `thread` — ответ в треде; ответы на пост
+/// канала — комментарии: их нет в ленте канала.
+pub(crate) async fn post_message(
+    state: &AppState,
+    chat_id: &str,
+    user_id: &str,
+    membership: &Membership,
+    req: SendMessageRequest,
+    thread: Option<&ThreadRoot>,
+    origin: Origin<'_>,
+) -> Result<MessageResponse, StatusCode> {
+    let (forward_from, scheduled) = match origin {
+        Origin::Direct => (None, None),
+        Origin::Forward(forward_from) => (Some(forward_from), None),
+        Origin::Scheduled(scheduled) => (None, Some(scheduled)),
+    };
     let message_id = Uuid::new_v4().to_string();
-    let sender_id = "sender-id"; // claims.sub
     let message_type = req.message_type.unwrap_or_else(|| "text".to_string());
+    let entities = entities::normalize(&req.content, req.entities).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let poll = match (message_type == polls::POLL_MESSAGE_TYPE, req.poll) {
+        (true, Some(poll)) => Some(polls::validate(&req.content, poll)?),
+        (false, None) => None,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    let new_poll = poll.as_ref().map(|poll| poll.as_new());
+    files::authorize_attachment(state, req.file_url.as_deref(), user_id).await?;
+    let is_comment = thread.is_some_and(|root| root.channel_post);
+    let channel_post = membership.channel && !is_comment;
+    let signature = if channel_post {
+        channel_signature(state, chat_id, user_id).await?
+    } else {
+        None
+    };
+    // В треде ответ без явной цели — ответ на корень
+    let reply_to_id = req.reply_to_id.or_else(|| thread.map(|root| root.id.clone()));
 
     // Сохранение сообщения
-    sqlx::query(
-        "INSERT INTO messages (id, chat_id, sender_id, content, type, file_url, reply_to_id) 
-         VALUES (?, ?, ?, ?, ?, ?, ?)"
-    )
-    .bind(&message_id)
-    .bind(&chat_id)
-    .bind(sender_id)
-    .bind(&req.content)
-    .bind(&message_type)
-    .bind(&req.file_url)
-    .bind(&req.reply_to_id)
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка отправки сообщения: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-
-    // TODO: Отправка через WebSocket
-
-    Ok(Json(MessageResponse {
-        id: message_id,
-        chat_id,
-        sender_id: sender_id.to_string(),
-        content: req.content,
-        translated_content: None,
-        message_type,
-        file_url: req.file_url,
-        reply_to_id: req.reply_to_id,
-        is_edited: false,
-        created_at: chrono::Utc::now().to_rfc3339(),
-    }))
+    state
+        .db
+        .messages()
+        .insert(&NewMessage {
+            id: &message_id,
+            chat_id,
+            sender_id: user_id,
+            content: &req.content,
+            entities: &entities,
+            message_type: &message_type,
+            file_url: req.file_url.as_deref(),
+            reply_to_id: reply_to_id.as_deref(),
+            channel_post,
+            author_signature: signature.as_deref(),
+            thread_id: thread.map(|root| root.id.as_str()),
+            is_comment,
+            poll: new_poll.as_ref(),
+            forward_from,
+            scheduled,
+        })
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка отправки сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if forward_from.is_none() {
+        save_mentions(state, chat_id, &message_id, user_id, &req.content, &entities, reply_to_id.as_deref()).await?;
+    }
+
+    // Отправленное сообщение снимает «печатает» и отмечает чат прочитанным
+    state.ws.write().await.stop_typing(chat_id, user_id);
+    if !is_comment {
+        reads::advance_read(state, chat_id, user_id, &message_id).await?;
+    }
+
+    // Время создания выставляет база
+    let message = fetch_message(state, &message_id, user_id).await?;
+    // Остальным — без ответа викторины, который видит автор
+    let shared = MessageResponse {
+        poll: message.poll.clone().map(Poll::for_everyone),
+        ..message.clone()
+    };
+
+    if let Some(root) = thread {
+        threads::after_reply(state, root, user_id, &shared).await?;
+    }
+    // Постов канала может быть слишком много получателей — через очередь;
+    // комментарии получают подписчики треда
+    if channel_post {
+        state
+            .fanout
+            .enqueue(FanoutJob {
+                chat_id: chat_id.to_string(),
+                except_user_id: Some(user_id.to_string()),
+                event: WsMessage::NewMessage { message: Box::new(shared) },
+            })
+            .await;
+    } else if !is_comment {
+        state
+            .ws
+            .read()
+            .await
+            .broadcast_to_chat_except(chat_id, user_id, WsMessage::NewMessage { message: Box::new(shared) });
+    }
+    // Тем, кому не пришло по WebSocket; комментарии — только упомянутым
+    state
+        .push
+        .enqueue(PushJob {
+            chat_id: chat_id.to_string(),
+            message_id: message.id.clone(),
+            sender_id: user_id.to_string(),
+            mentions_only: is_comment,
+            subscribers_only: !channel_post && !is_comment,
+        })
+        .await;
+
+    Ok(message)
+}
+
+/// Переслать сообщения одного чата в другие чаты. Копии сохраняют текст,
+/// тип и файл, а в `forward_from` — автора (если он не скрыл ссылку на себя)
+/// или канал. Копии идут в порядке `message_ids`.
+pub async fn forward_messages(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<ForwardMessagesRequest>,
+) -> Result<Json<Vec<MessageResponse>>, StatusCode> {
+    if req.message_ids.is_empty()
+        || req.message_ids.len() > MAX_FORWARD_BATCH
+        || req.to_chat_ids.is_empty()
+        || req.to_chat_ids.len() > MAX_FORWARD_TARGETS
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    chats::authorize(&state.db, &req.from_chat_id, &claims.sub, Permission::View).await?;
+    let source = state
+        .db
+        .chats()
+        .find(&req.from_chat_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения чата: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if source.restrict_forwarding {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut originals = Vec::with_capacity(req.message_ids.len());
+    for message_id in &req.message_ids {
+        let message = fetch_message(&state, message_id, &claims.sub).await?;
+        if message.chat_id != source.id || message.is_deleted {
+            return Err(StatusCode::NOT_FOUND);
+        }
+        // Опрос живёт в своём чате: переслать можно только его текст
+        if message.message_type == polls::POLL_MESSAGE_TYPE {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if !originals.iter().any(|known: &MessageResponse| known.id == message.id) {
+            originals.push(message);
+        }
+    }
+
+    let mut attributions = Vec::with_capacity(originals.len());
+    for message in &originals {
+        attributions.push(forward_attribution(&state, &source, message, &claims.sub).await?);
+    }
+
+    // Права во всех чатах — до первой копии
+    let mut targets = Vec::with_capacity(req.to_chat_ids.len());
+    for chat_id in &req.to_chat_ids {
+        let membership = chats::authorize(&state.db, chat_id, &claims.sub, Permission::SendMessages).await?;
+        targets.push((chat_id, membership));
+    }
+
+    let mut forwarded = Vec::with_capacity(targets.len() * originals.len());
+    for (chat_id, membership) in &targets {
+        for (message, forward_from) in originals.iter().zip(&attributions) {
+            let copy = SendMessageRequest {
+                content: message.content.clone(),
+                entities: message.entities.0.clone(),
+                message_type: Some(message.message_type.clone()),
+                file_url: message.file_url.clone(),
+                reply_to_id: None,
+                poll: None,
+            };
+            forwarded.push(post_message(&state, chat_id, &claims.sub, membership, copy, None, Origin::Forward(forward_from)).await?);
+        }
+    }
+
+    Ok(Json(forwarded))
+}
+
+/// Подпись копии: у пересланного — исходная, у поста канала — канал,
+/// иначе автор; ссылку на себя автор может скрыть от пересылающего.
+/// Отправитель поста канала — сам канал (см. `MESSAGE_COLUMNS`).
+async fn forward_attribution(
+    state: &AppState,
+    source: &Chat,
+    message: &MessageResponse,
+    forwarder_id: &str,
+) -> Result<ForwardFrom, StatusCode> {
+    if let Some(forward_from) = &message.forward_from {
+        return Ok(forward_from.clone());
+    }
+
+    // Пост канала подписан каналом, автор — только если канал подписывает посты
+    if message.sender_id == source.id {
+        return Ok(ForwardFrom {
+            sender_id: None,
+            sender_name: message
+                .author_signature
+                .clone()
+                .unwrap_or_else(|| source.name.clone().unwrap_or_default()),
+            chat_id: Some(source.id.clone()),
+            chat_name: source.name.clone(),
+            message_id: Some(message.id.clone()),
+            date: message.created_at.clone(),
+        });
+    }
+
+    let sender_id = message.sender_id.clone();
+    let sender = state
+        .db
+        .users()
+        .find(&sender_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения пользователя: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let visibility = users::fetch_privacy(state, &sender_id).await?.forwards;
+    let linked = sender_id == forwarder_id
+        || visibility.allows(
+            visibility == Visibility::Contacts
+                && state.db.users().is_contact(&sender_id, forwarder_id).await.map_err(|e| {
+                    tracing::error!("Ошибка проверки контакта: {}", e);
+                    StatusCode::INTERNAL_SERVER_ERROR
+                })?,
+        );
+
+    Ok(ForwardFrom {
+        sender_id: linked.then_some(sender_id),
+        sender_name: sender.username,
+        chat_id: None,
+        chat_name: None,
+        message_id: None,
+        date: message.created_at.clone(),
+    })
+}
+
+/// Отметить посты канала просмотренными. Просмотр засчитывается один раз
+/// на пользователя; в ответе — текущие счётчики.
+pub async fn record_views(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<RecordViewsRequest>,
+) -> Result<Json<Vec<MessageViews>>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    if req.message_ids.len() > MAX_VIEWS_BATCH {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let views = state
+        .db
+        .messages()
+        .record_views(&chat_id, &claims.sub, &req.message_ids)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка учёта просмотров: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(views))
+}
+
+/// Редактировать сообщение (только автор, в пределах окна редактирования).
+/// Прежний текст сохраняется в `message_edits`; добавленные упоминания
+/// уведомляют так же, как при отправке.
+pub async fn edit_message(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
+    Json(req): Json<EditMessageRequest>,
+) -> Result<Json<MessageResponse>, StatusCode> {
+    if req.content.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let entities = entities::normalize(&req.content, req.entities).map_err(|_| StatusCode::BAD_REQUEST)?;
+    // Исключённый или ограниченный участник не правит и старые сообщения
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::SendMessages).await?;
+
+    let message = fetch_message_meta(&state, &chat_id, &message_id).await?;
+    if message.is_deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if message.sender_id != claims.sub || !state.message_limits.can_edit(message.sent_at) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    state
+        .db
+        .messages()
+        .edit(&message_id, &claims.sub, &message.content, &req.content, &entities)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка редактирования сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    save_mentions(&state, &chat_id, &message_id, &claims.sub, &req.content, &entities, None).await?;
+
+    let updated = fetch_message(&state, &message_id, &claims.sub).await?;
+
+    state.ws.read().await.broadcast_to_chat(&chat_id, WsMessage::MessageEdited {
+        chat_id: chat_id.clone(),
+        message_id: message_id.clone(),
+        content: updated.content.clone(),
+        entities: updated.entities.clone(),
+        edited_at: updated.updated_at.clone(),
+    });
+
+    Ok(Json(updated))
+}
+
+/// Удалить сообщение.
+///
+/// `?for_everyone=true` — у всех: автор в пределах окна удаления либо
+/// модератор с правом удалять сообщения. Содержимое и история правок
+/// стираются, в ленте остаётся «надгробие». Без флага сообщение скрывается только у вызывающего.
+pub async fn delete_message(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    Query(query): Query<DeleteMessageQuery>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    let membership = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    let message = fetch_message_meta(&state, &chat_id, &message_id).await?;
+
+    if !query.for_everyone {
+        state
+            .db
+            .messages()
+            .hide(&message_id, &claims.sub)
+            .await
+            .map_err(|e| db_error("Ошибка скрытия сообщения", e))?;
+
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    let now = timestamp(chrono::Utc::now());
+    let is_moderator = membership.allows(Permission::DeleteMessages, &now);
+    let is_author = message.sender_id == claims.sub && state.message_limits.can_delete(message.sent_at);
+    if !is_moderator && !is_author {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if message.is_deleted {
+        return Ok(StatusCode::NO_CONTENT);
+    }
+
+    state
+        .db
+        .messages()
+        .delete_for_everyone(&message_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка удаления сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    state.ws.read().await.broadcast_to_chat(&chat_id, WsMessage::MessageDeleted {
+        chat_id: chat_id.clone(),
+        message_id,
+    });
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// История правок сообщения (для участников чата)
+pub async fn list_message_edits(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<Json<Vec<MessageEdit>>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    fetch_message_meta(&state, &chat_id, &message_id).await?;
+
+    let edits = state
+        .db
+        .messages()
+        .edits(&message_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения истории правок", e))?;
+
+    Ok(Json(edits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cursor_round_trip() {
+        let cursor = Cursor {
+            created_at: "2024-03-01 12:00:00".to_string(),
+            id: "5f0c7a9e-1b2c-4d3e-8f9a-0b1c2d3e4f5a".to_string(),
+        };
+
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+    }
+
+    #[test]
+    fn test_cursor_rejects_garbage() {
+        assert_eq!(Cursor::decode("not-hex"), None);
+        assert_eq!(Cursor::decode(&hex::encode("no-separator")), None);
+        assert_eq!(Cursor::decode(&hex::encode("\nid")), None);
+    }
+
+    #[test]
+    fn test_message_limits() {
+        let limits = MessageLimits { edit_window_secs: 60, delete_window_secs: 0 };
+        let now = chrono::Utc::now().timestamp();
+
+        assert!(limits.can_edit(now - 30));
+        assert!(!limits.can_edit(now - 120));
+        // 0 — без ограничения
+        assert!(limits.can_delete(now - 10 * 365 * 24 * 3600));
+    }
 }
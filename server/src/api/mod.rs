@@ -3,26 +3,109 @@
 
 pub mod auth;
 pub mod users;
+pub mod contacts;
 pub mod chats;
+pub mod members;
+pub mod invites;
 pub mod messages;
+pub mod reads;
 pub mod files;
 pub mod web3;
 pub mod ai;
 pub mod nodes;
 pub mod features;
 pub mod extra;
+pub mod search;
+pub mod threads;
+pub mod polls;
+pub mod scheduled;
+pub mod push;
 
-use axum::{Router, routing::{get, post}};
-use sqlx::SqlitePool;
+use axum::{Router, extract::DefaultBodyLimit, routing::{delete, get, post, patch, put}};
 use std::sync::Arc;
+use tokio::sync::RwLock;
 use tower_http::{cors::{Any, CorsLayer}, trace::TraceLayer};
+use crate::{
+    db::DbPool,
+    encryption::KeyProvider,
+    fanout::FanoutQueue,
+    push::{PushGateway, PushQueue},
+    storage::{LocalStorage, StorageBackend, UrlSigner},
+    websocket::WebSocketManager,
+};
 
 /// Состояние приложения
 #[derive(Clone)]
 pub struct AppState {
-    pub db: Arc<SqlitePool>,
+    pub db: DbPool,
     pub jwt_secret: String,
     pub uploads_dir: String,
+    /// Рассылка событий подключённым WebSocket клиентам
+    pub ws: Arc<RwLock<WebSocketManager>>,
+    /// Доставка событий всем участникам больших чатов
+    pub fanout: FanoutQueue,
+    /// Push-уведомления на устройства участников не в сети
+    pub push: PushQueue,
+    /// Окна редактирования и удаления сообщений
+    pub message_limits: messages::MessageLimits,
+    /// Лимиты размеров и квоты загрузок
+    pub upload_limits: files::UploadLimits,
+    pub active_uploads: files::ActiveUploads,
+    /// Ограничение параллельной обработки медиа
+    pub media_workers: files::MediaWorkers,
+    /// Хранилище содержимого файлов (ключ — SHA-256)
+    pub storage: Arc<dyn StorageBackend>,
+    /// Подпись ссылок на скачивание
+    pub url_signer: UrlSigner,
+    /// Мастер-ключи шифрования файлов; `None` — файлы хранятся открытыми
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
+}
+
+impl AppState {
+    /// Запускает фоновые очереди рассылки, поэтому вызывается внутри tokio.
+    /// Push-провайдеров нет, пока не задан `with_push`.
+    pub fn new(db: DbPool, jwt_secret: String, uploads_dir: String) -> Self {
+        let ws = Arc::new(RwLock::new(WebSocketManager::new()));
+        Self {
+            fanout: FanoutQueue::start(db.clone(), ws.clone()),
+            push: PushQueue::start(db.clone(), ws.clone(), PushGateway::default()),
+            db,
+            jwt_secret,
+            storage: Arc::new(LocalStorage::new(&uploads_dir)),
+            uploads_dir,
+            ws,
+            message_limits: messages::MessageLimits::from_env(),
+            upload_limits: files::UploadLimits::from_env(),
+            active_uploads: files::ActiveUploads::default(),
+            media_workers: files::MediaWorkers::from_env(),
+            url_signer: UrlSigner::from_env(),
+            key_provider: None,
+        }
+    }
+
+    /// Заменить хранилище по умолчанию (локальный `uploads_dir`)
+    pub fn with_storage(mut self, storage: Arc<dyn StorageBackend>) -> Self {
+        self.storage = storage;
+        self
+    }
+
+    /// Включить шифрование содержимого файлов
+    pub fn with_key_provider(mut self, key_provider: Option<Arc<dyn KeyProvider>>) -> Self {
+        self.key_provider = key_provider;
+        self
+    }
+
+    /// Включить push-провайдеры (очередь перезапускается с ними)
+    pub fn with_push(mut self, gateway: PushGateway) -> Self {
+        self.push = PushQueue::start(self.db.clone(), self.ws.clone(), gateway);
+        self
+    }
+}
+
+/// Ошибка базы в обработчике: подробности — в лог, клиенту — 500
+pub(crate) fn db_error(context: &str, e: sqlx::Error) -> axum::http::StatusCode {
+    tracing::error!("{}: {}", context, e);
+    axum::http::StatusCode::INTERNAL_SERVER_ERROR
 }
 
 /// Проверка здоровья сервера
@@ -45,15 +128,70 @@ pub fn create_router(state: AppState) -> Router {
         .route("/auth/verify", post(auth::verify_token))
         // Users
         .route("/users/me", get(users::get_current_user))
+        .route("/users/me/privacy", get(users::get_privacy).patch(users::update_privacy))
         .route("/users/:id", get(users::get_user))
+        // Contacts & Blocking
+        .route("/contacts", get(contacts::list_contacts).post(contacts::add_contact))
+        .route("/contacts/import", post(contacts::import_contacts))
+        .route("/contacts/:user_id", delete(contacts::remove_contact))
+        .route("/blocked", get(contacts::list_blocked).post(contacts::block_user))
+        .route("/blocked/:user_id", delete(contacts::unblock_user))
+        // Push Notifications
+        .route("/push/config", get(push::get_config))
+        .route("/push/devices", get(push::list_devices).post(push::register_device))
+        .route("/push/devices/:device_id", delete(push::unregister_device))
         .route("/users/:user_id/bio", get(features::get_family_status))
         .route("/users/:user_id/bio", post(features::set_family_status))
         // Chats
         .route("/chats", get(chats::list_chats))
         .route("/chats", post(chats::create_chat))
-        .route("/chats/:chat_id", get(chats::get_chat))
+        .route("/chats/sync", get(chats::sync_chats))
+        .route("/chats/:chat_id", get(chats::get_chat).patch(chats::update_chat))
+        .route("/chats/:chat_id/settings", patch(chats::update_chat_settings))
+        // Members & Permissions
+        .route("/chats/:chat_id/members", get(members::list_members).post(members::add_member))
+        .route(
+            "/chats/:chat_id/members/:user_id",
+            patch(members::update_member).delete(members::remove_member),
+        )
+        .route("/chats/:chat_id/bans", get(members::list_bans).post(members::ban_member))
+        .route("/chats/:chat_id/bans/:user_id", delete(members::unban_member))
+        .route("/chats/:chat_id/transfer", post(members::transfer_ownership))
+        // Invites
+        .route("/chats/:chat_id/invites", get(invites::list_invites).post(invites::create_invite))
+        .route("/chats/:chat_id/invites/:code", delete(invites::revoke_invite))
+        .route("/chats/:chat_id/join-requests", get(invites::list_join_requests))
+        .route("/chats/:chat_id/join-requests/:user_id/approve", post(invites::approve_join_request))
+        .route("/chats/:chat_id/join-requests/:user_id/decline", post(invites::decline_join_request))
+        .route("/invites/:code", get(invites::preview_invite))
+        .route("/invites/:code/join", post(invites::join_by_invite))
         .route("/chats/:chat_id/messages", get(messages::list_messages))
         .route("/chats/:chat_id/messages", post(messages::send_message))
+        .route("/chats/:chat_id/messages/:message_id", patch(messages::edit_message).delete(messages::delete_message))
+        .route("/chats/:chat_id/messages/:message_id/edits", get(messages::list_message_edits))
+        .route("/chats/:chat_id/messages/:message_id/context", get(messages::get_message_context))
+        .route("/chats/:chat_id/messages/:message_id/reads", get(reads::list_message_reads))
+        .route("/chats/:chat_id/views", post(messages::record_views))
+        .route("/messages/forward", post(messages::forward_messages))
+        .route(
+            "/chats/:chat_id/messages/:message_id/vote",
+            post(polls::vote).delete(polls::retract_vote),
+        )
+        .route("/chats/:chat_id/messages/:message_id/close", post(polls::close_poll))
+        // Threads
+        .route("/threads", get(threads::list_threads))
+        .route("/chats/:chat_id/threads/:thread_id", get(threads::list_thread))
+        .route("/chats/:chat_id/threads/:thread_id/messages", post(threads::reply_in_thread))
+        .route("/chats/:chat_id/threads/:thread_id/read", post(threads::mark_thread_read))
+        .route(
+            "/chats/:chat_id/threads/:thread_id/subscription",
+            put(threads::subscribe_thread).delete(threads::unsubscribe_thread),
+        )
+        .route("/chats/:chat_id/read", post(reads::mark_read))
+        .route("/chats/:chat_id/delivered", post(reads::mark_delivered))
+        // Search
+        .route("/search", get(search::search_messages))
+        .route("/search/saved", get(search::search_saved_messages))
         // Pinned Messages
         .route("/chats/:chat_id/pinned", get(extra::get_pinned_messages))
         .route("/chats/:chat_id/pin", post(extra::pin_message))
@@ -63,9 +201,12 @@ pub fn create_router(state: AppState) -> Router {
         .route("/users/:user_id/saved", post(extra::save_message))
         .route("/users/:user_id/saved/:message_id", delete(extra::delete_saved_message))
         // Scheduled Messages
-        .route("/chats/:chat_id/scheduled", get(extra::get_scheduled_messages))
-        .route("/chats/:chat_id/schedule", post(extra::schedule_message))
-        .route("/chats/:chat_id/scheduled/:message_id", post(extra::cancel_scheduled_message))
+        .route("/chats/:chat_id/scheduled", get(scheduled::get_scheduled_messages))
+        .route("/chats/:chat_id/schedule", post(scheduled::schedule_message))
+        .route(
+            "/chats/:chat_id/scheduled/:message_id",
+            patch(scheduled::update_scheduled_message).delete(scheduled::cancel_scheduled_message),
+        )
         // Stickers & GIFs
         .route("/stickers", get(extra::list_stickers))
         .route("/sticker-packs", get(extra::list_sticker_packs))
@@ -95,8 +236,17 @@ pub fn create_router(state: AppState) -> Router {
         .route("/chats/:chat_id/self-destruct/disable", post(extra::disable_self_destruct))
         .route("/chats/:chat_id/messages/self-destruct", post(extra::send_self_destruct_message))
         // Files
-        .route("/files/upload", post(files::upload_file))
-        .route("/files/:file_id", get(files::get_file))
+        // Лимиты загрузок проверяет сам обработчик (UploadLimits)
+        .route("/files/upload", post(files::upload_file).layer(DefaultBodyLimit::disable()))
+        .route("/files/uploads", post(files::create_upload))
+        .route(
+            "/files/uploads/:upload_id",
+            get(files::get_upload).patch(files::upload_chunk).delete(files::cancel_upload),
+        )
+        .route("/files/uploads/:upload_id/finalize", post(files::finalize_upload))
+        .route("/files/:file_id", get(files::get_file).delete(files::delete_file))
+        .route("/files/:file_id/url", get(files::get_file_url))
+        .route("/files/:file_id/download", get(files::download_file))
         // Web3
         .route("/web3/balance", get(web3::get_balance))
         .route("/web3/swap", post(web3::swap_tokens))
@@ -112,5 +262,3 @@ pub fn create_router(state: AppState) -> Router {
         .layer(TraceLayer::new_for_http())
         .with_state(state)
 }
-
-use axum::routing::delete;
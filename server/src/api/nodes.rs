@@ -8,8 +8,9 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use chrono::{Utc, DateTime};
-use crate::api::AppState;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use chrono::{Utc, DateTime, NaiveDateTime};
+use crate::{api::AppState, db::nodes::{NewPeerNode, PeerNodeRecord}};
 
 /// Информация о P2P ноде
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,6 +36,38 @@ pub enum NodeStatus {
     Syncing,
 }
 
+impl From<PeerNodeRecord> for PeerNode {
+    fn from(record: PeerNodeRecord) -> Self {
+        // Списки хранятся JSON-строками, даты — в формате колонок
+        let list = |raw: Option<String>| {
+            raw.and_then(|raw| serde_json::from_str(&raw).ok()).unwrap_or_default()
+        };
+        let time = |raw: &str| {
+            NaiveDateTime::parse_from_str(raw, "%Y-%m-%d %H:%M:%S")
+                .map(|t| t.and_utc())
+                .unwrap_or_default()
+        };
+
+        Self {
+            status: match record.status.as_str() {
+                "online" => NodeStatus::Online,
+                "syncing" => NodeStatus::Syncing,
+                _ => NodeStatus::Offline,
+            },
+            joined_at: time(&record.joined_at),
+            last_seen: time(&record.last_seen),
+            multiaddr: list(record.multiaddr),
+            capabilities: list(record.capabilities),
+            id: record.id,
+            user_id: record.user_id,
+            username: record.username,
+            public_key: record.public_key,
+            peer_id: record.peer_id,
+            version: record.version,
+        }
+    }
+}
+
 /// Запрос на регистрацию ноды
 #[derive(Debug, Deserialize)]
 pub struct RegisterNodeRequest {
@@ -60,81 +93,56 @@ pub async fn register_node(
     State(state): State<AppState>,
     Json(req): Json<RegisterNodeRequest>,
 ) -> Result<Json<PeerNode>, StatusCode> {
-    use sqlx::Row;
-    
     let node_id = Uuid::new_v4().to_string();
     let now = Utc::now();
-    
+    let nodes = state.db.nodes();
+    let multiaddr = serde_json::to_string(&req.multiaddr).unwrap();
+    let capabilities = serde_json::to_string(&req.capabilities).unwrap();
+
     // Проверка существует ли уже нода
-    let existing = sqlx::query(
-        "SELECT id FROM peer_nodes WHERE user_id = ?"
-    )
-    .bind(&req.user_id)
-    .fetch_optional(&state.db)
-    .await
-    .map_err(|e| {
+    let existing = nodes.find_by_user(&req.user_id).await.map_err(|e| {
         tracing::error!("Ошибка проверки ноды: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
-    
+
     if existing.is_some() {
         // Обновление существующей ноды
-        sqlx::query(
-            "UPDATE peer_nodes SET 
-             status = 'online', 
-             last_seen = ?, 
-             multiaddr = ?,
-             version = ?,
-             capabilities = ?
-             WHERE user_id = ?"
-        )
-        .bind(now)
-        .bind(serde_json::to_string(&req.multiaddr).unwrap())
-        .bind(&req.version)
-        .bind(serde_json::to_string(&req.capabilities).unwrap())
-        .bind(&req.user_id)
-        .execute(&state.db)
+        nodes
+            .refresh(&req.user_id, &multiaddr, &req.version, &capabilities)
+            .await
+            .map_err(|e| {
+                tracing::error!("Ошибка обновления ноды: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        // Получить обновлённую ноду
+        let node = nodes
+            .find_by_user(&req.user_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        return Ok(Json(node.into()));
+    }
+
+    // Создание новой ноды
+    nodes
+        .insert(&NewPeerNode {
+            id: &node_id,
+            user_id: &req.user_id,
+            username: &req.username,
+            public_key: &req.public_key,
+            peer_id: &req.peer_id,
+            multiaddr: &multiaddr,
+            version: &req.version,
+            capabilities: &capabilities,
+        })
         .await
         .map_err(|e| {
-            tracing::error!("Ошибка обновления ноды: {}", e);
+            tracing::error!("Ошибка регистрации ноды: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
-        
-        // Получить обновлённую ноду
-        let node = sqlx::query_as(
-            "SELECT * FROM peer_nodes WHERE user_id = ?"
-        )
-        .bind(&req.user_id)
-        .fetch_one(&state.db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        
-        return Ok(Json(node));
-    }
-    
-    // Создание новой ноды
-    let node = sqlx::query_as(
-        "INSERT INTO peer_nodes 
-         (id, user_id, username, public_key, peer_id, multiaddr, status, joined_at, last_seen, version, capabilities)
-         VALUES (?, ?, ?, ?, ?, ?, 'online', ?, ?, ?, ?)"
-    )
-    .bind(&node_id)
-    .bind(&req.user_id)
-    .bind(&req.username)
-    .bind(&req.public_key)
-    .bind(&req.peer_id)
-    .bind(serde_json::to_string(&req.multiaddr).unwrap())
-    .bind(now)
-    .bind(now)
-    .bind(&req.version)
-    .bind(serde_json::to_string(&req.capabilities).unwrap())
-    .execute(&state.db)
-    .await
-    .map_err(|e| {
-        tracing::error!("Ошибка регистрации ноды: {}", e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
-    
+
     // Синхронизация с GitHub
     sync_to_github(&req.user_id, &req.username, &req.public_key).await?;
     
@@ -157,14 +165,19 @@ pub async fn register_node(
 pub async fn get_peer_list(
     State(state): State<AppState>,
 ) -> Result<Json<PeerListResponse>, StatusCode> {
-    let nodes: Vec<PeerNode> = sqlx::query_as("SELECT * FROM peer_nodes ORDER BY last_seen DESC")
-        .fetch_all(&state.db)
+    let nodes: Vec<PeerNode> = state
+        .db
+        .nodes()
+        .list()
         .await
         .map_err(|e| {
             tracing::error!("Ошибка получения нод: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-    
+        })?
+        .into_iter()
+        .map(PeerNode::from)
+        .collect();
+
     let total = nodes.len();
     let online = nodes.iter().filter(|n| n.status == NodeStatus::Online).count();
     
@@ -214,14 +227,14 @@ async fn sync_to_github(
         Ok(resp) => {
             let json: serde_json::Value = resp.json().await.map_err(|_| StatusCode::BAD_GATEWAY)?;
             let content = json["content"].as_str().unwrap_or("");
-            let decoded = base64::decode(content).unwrap_or_default();
+            let decoded = BASE64.decode(content.replace('\n', "")).unwrap_or_default();
             String::from_utf8_lossy(&decoded).to_string()
         }
         Err(_) => "# Liberty Reach Peer Network\n\nСписок участников P2P сети:\n".to_string(),
     };
     
     let new_content = format!("{}{}", content, peer_entry);
-    let encoded = base64::encode(&new_content);
+    let encoded = BASE64.encode(&new_content);
     
     // Обновить файл
     let _ = client
@@ -245,12 +258,12 @@ pub async fn node_heartbeat(
 ) -> Result<Json<serde_json::Value>, StatusCode> {
     let user_id = req["user_id"].as_str().ok_or(StatusCode::BAD_REQUEST)?;
     
-    sqlx::query("UPDATE peer_nodes SET last_seen = ?, status = 'online' WHERE user_id = ?")
-        .bind(Utc::now())
-        .bind(user_id)
-        .execute(&state.db)
+    state
+        .db
+        .nodes()
+        .heartbeat(user_id)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    
+
     Ok(Json(serde_json::json!({"status": "ok"})))
 }
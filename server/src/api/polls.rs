@@ -0,0 +1,279 @@
+// server/src/api/polls.rs
+//! API опросов и викторин
+//!
+//! Опрос — сообщение типа `poll`: вопрос в тексте, варианты и настройки
+//! в `poll`. Голосовать могут все, кто может ставить реакции. Результаты
+//! после каждого голоса рассылаются подписчикам чата (`poll_updated`),
+//! без выбора конкретного пользователя и без ответа открытой викторины.
+
+use axum::{
+    extract::{State, Path},
+    Json,
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use crate::{
+    api::{chats, db_error, messages::MessageResponse, AppState},
+    auth::Claims,
+    db::{
+        polls::{NewPoll, Poll, PollSettings},
+        timestamp,
+    },
+    permissions::Permission,
+    websocket::WsMessage,
+};
+
+/// Тип сообщения с опросом
+pub const POLL_MESSAGE_TYPE: &str = "poll";
+
+/// Сколько вариантов может быть в опросе
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+
+/// Опрос в запросе на отправку сообщения
+#[derive(Deserialize)]
+pub struct CreatePollRequest {
+    pub options: Vec<String>,
+    /// Можно выбрать несколько вариантов
+    #[serde(default)]
+    pub multiple: bool,
+    /// Анонимный по умолчанию: без списка проголосовавших
+    #[serde(default = "default_anonymous")]
+    pub anonymous: bool,
+    /// Викторина: номер правильного варианта (с нуля)
+    pub correct_option: Option<i64>,
+    /// Пояснение к ответу викторины
+    pub explanation: Option<String>,
+    pub closes_at: Option<DateTime<Utc>>,
+}
+
+fn default_anonymous() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+pub struct VoteRequest {
+    /// Номера выбранных вариантов (с нуля)
+    pub options: Vec<i64>,
+}
+
+/// Проверенный опрос для `MessageRepository::insert`
+pub(crate) struct ValidPoll {
+    options: Vec<String>,
+    multiple: bool,
+    anonymous: bool,
+    correct_option: Option<i64>,
+    explanation: Option<String>,
+    closes_at: Option<String>,
+}
+
+impl ValidPoll {
+    pub(crate) fn as_new(&self) -> NewPoll<'_> {
+        NewPoll {
+            options: &self.options,
+            multiple: self.multiple,
+            anonymous: self.anonymous,
+            correct_option: self.correct_option,
+            explanation: self.explanation.as_deref(),
+            closes_at: self.closes_at.as_deref(),
+        }
+    }
+}
+
+/// Проверить опрос при отправке: 2–10 непустых вариантов, у викторины
+/// один ответ из списка, закрытие — в будущем
+pub(crate) fn validate(question: &str, req: CreatePollRequest) -> Result<ValidPoll, StatusCode> {
+    let options: Vec<String> = req.options.iter().map(|option| option.trim().to_string()).collect();
+    let in_range = |position: i64| position >= 0 && (position as usize) < options.len();
+
+    if question.trim().is_empty()
+        || !(MIN_OPTIONS..=MAX_OPTIONS).contains(&options.len())
+        || options.iter().any(|option| option.is_empty())
+        || req.correct_option.is_some_and(|correct| req.multiple || !in_range(correct))
+        || (req.explanation.is_some() && req.correct_option.is_none())
+        || req.closes_at.is_some_and(|at| at <= Utc::now())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(ValidPoll {
+        options,
+        multiple: req.multiple,
+        anonymous: req.anonymous,
+        correct_option: req.correct_option,
+        explanation: req.explanation,
+        closes_at: req.closes_at.map(timestamp),
+    })
+}
+
+/// Подставить опросы в сообщения типа `poll` глазами `viewer`
+pub(crate) async fn attach(
+    state: &AppState,
+    messages: &mut [MessageResponse],
+    viewer: &str,
+) -> Result<(), StatusCode> {
+    let polls = state.db.polls();
+    for message in messages
+        .iter_mut()
+        .filter(|message| message.message_type == POLL_MESSAGE_TYPE && !message.is_deleted)
+    {
+        message.poll = polls
+            .load(&message.id, viewer)
+            .await
+            .map_err(|e| db_error("Ошибка получения опроса", e))?;
+    }
+    Ok(())
+}
+
+/// Опрос сообщения чата; 404 — нет опроса или сообщение удалено
+async fn poll_settings(state: &AppState, chat_id: &str, message_id: &str) -> Result<PollSettings, StatusCode> {
+    state
+        .db
+        .polls()
+        .settings(message_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения опроса", e))?
+        .filter(|poll| poll.chat_id == chat_id && !poll.is_deleted)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Опрос для пользователя, а чату — обновлённые результаты
+async fn publish(state: &AppState, chat_id: &str, message_id: &str, user_id: &str) -> Result<Poll, StatusCode> {
+    let poll = state
+        .db
+        .polls()
+        .load(message_id, user_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения опроса", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    state.ws.read().await.broadcast_to_chat(chat_id, WsMessage::PollUpdated {
+        chat_id: chat_id.to_string(),
+        message_id: message_id.to_string(),
+        poll: Box::new(poll.clone().for_everyone()),
+    });
+    Ok(poll)
+}
+
+/// Проголосовать. Переголосовать можно, только отозвав голос.
+pub async fn vote(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
+    Json(req): Json<VoteRequest>,
+) -> Result<Json<Poll>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::React).await?;
+    let settings = poll_settings(&state, &chat_id, &message_id).await?;
+    if settings.closed {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let mut positions = req.options;
+    positions.sort_unstable();
+    positions.dedup();
+    if positions.is_empty()
+        || (positions.len() > 1 && !settings.multiple)
+        || positions.iter().any(|&position| position < 0 || position >= settings.option_count)
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let voted = state
+        .db
+        .polls()
+        .vote(&message_id, &claims.sub, &positions)
+        .await
+        .map_err(|e| db_error("Ошибка голосования", e))?;
+    if !voted {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    Ok(Json(publish(&state, &chat_id, &message_id, &claims.sub).await?))
+}
+
+/// Отозвать голос (кроме викторины)
+pub async fn retract_vote(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<Json<Poll>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::React).await?;
+    let settings = poll_settings(&state, &chat_id, &message_id).await?;
+    if settings.closed || settings.quiz() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let retracted = state
+        .db
+        .polls()
+        .retract(&message_id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка отзыва голоса", e))?;
+    if !retracted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Json(publish(&state, &chat_id, &message_id, &claims.sub).await?))
+}
+
+/// Закрыть опрос досрочно (только автор). Закрытая викторина показывает
+/// ответ всем.
+pub async fn close_poll(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<Json<Poll>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    let settings = poll_settings(&state, &chat_id, &message_id).await?;
+    if settings.sender_id != claims.sub {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if !settings.closed {
+        state
+            .db
+            .polls()
+            .close(&message_id)
+            .await
+            .map_err(|e| db_error("Ошибка закрытия опроса", e))?;
+    }
+
+    Ok(Json(publish(&state, &chat_id, &message_id, &claims.sub).await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(options: &[&str]) -> CreatePollRequest {
+        CreatePollRequest {
+            options: options.iter().map(|option| option.to_string()).collect(),
+            multiple: false,
+            anonymous: true,
+            correct_option: None,
+            explanation: None,
+            closes_at: None,
+        }
+    }
+
+    #[test]
+    fn test_validate_poll() {
+        assert!(validate("Вопрос", request(&["да", "нет"])).is_ok());
+        assert!(validate(" ", request(&["да", "нет"])).is_err());
+        assert!(validate("Вопрос", request(&["да"])).is_err());
+        assert!(validate("Вопрос", request(&["да", "  "])).is_err());
+        assert!(validate("Вопрос", request(&["1"; 11])).is_err());
+
+        let quiz = CreatePollRequest { correct_option: Some(1), ..request(&["да", "нет"]) };
+        assert!(validate("Вопрос", quiz).is_ok());
+        let out_of_range = CreatePollRequest { correct_option: Some(2), ..request(&["да", "нет"]) };
+        assert!(validate("Вопрос", out_of_range).is_err());
+        let multiple_quiz = CreatePollRequest { correct_option: Some(0), multiple: true, ..request(&["да", "нет"]) };
+        assert!(validate("Вопрос", multiple_quiz).is_err());
+        let explanation = CreatePollRequest { explanation: Some("так".into()), ..request(&["да", "нет"]) };
+        assert!(validate("Вопрос", explanation).is_err());
+        let closed = CreatePollRequest { closes_at: Some(Utc::now()), ..request(&["да", "нет"]) };
+        assert!(validate("Вопрос", closed).is_err());
+    }
+}
@@ -0,0 +1,124 @@
+// server/src/api/push.rs
+//! API push-уведомлений
+//!
+//! Клиент узнаёт включённых провайдеров и ключ VAPID (`/push/config`),
+//! подписывается у push-сервиса и регистрирует устройство вместе
+//! с собственным ключом подсказки. Что уведомлять в каждом чате, задают
+//! его настройки (`muted`, `muted_until`, `mute_mentions`).
+
+use axum::{
+    extract::{State, Path},
+    Json,
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::{
+    api::{db_error, AppState},
+    auth::Claims,
+    db::push::{NewPushDevice, PushDevice},
+    push::{self, FCM, WEBPUSH},
+};
+
+/// Длина адреса подписки или токена FCM
+const MAX_ENDPOINT_LEN: usize = 2048;
+
+#[derive(Serialize)]
+pub struct PushConfigResponse {
+    pub providers: Vec<String>,
+    /// Для `PushManager.subscribe({ applicationServerKey })`
+    pub vapid_public_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RegisterDeviceRequest {
+    pub provider: String,
+    /// Адрес подписки; для FCM — токен регистрации
+    pub endpoint: String,
+    /// Ключи подписки Web Push (base64url); для `webpush` обязательны
+    pub p256dh: Option<String>,
+    pub auth: Option<String>,
+    /// Ключ шифрования подсказок, 32 байта в base64
+    pub hint_key: String,
+}
+
+pub async fn get_config(State(state): State<AppState>, _claims: Claims) -> Json<PushConfigResponse> {
+    let gateway = state.push.gateway();
+    Json(PushConfigResponse {
+        providers: gateway.names(),
+        vapid_public_key: gateway.vapid_public_key(),
+    })
+}
+
+/// Зарегистрировать устройство; повторная регистрация того же адреса
+/// обновляет ключи и владельца
+pub async fn register_device(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<RegisterDeviceRequest>,
+) -> Result<Json<PushDevice>, StatusCode> {
+    if state.push.gateway().provider(&req.provider).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let endpoint = req.endpoint.trim();
+    let endpoint_valid = match req.provider.as_str() {
+        FCM => !endpoint.is_empty(),
+        _ => reqwest::Url::parse(endpoint).is_ok_and(|url| matches!(url.scheme(), "https" | "http")),
+    };
+    if !endpoint_valid || endpoint.len() > MAX_ENDPOINT_LEN || push::parse_hint_key(&req.hint_key).is_none() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let keys = match (req.p256dh.as_deref(), req.auth.as_deref()) {
+        (Some(p256dh), Some(auth)) if push::valid_subscription_keys(p256dh, auth) => Some((p256dh, auth)),
+        (None, None) if req.provider != WEBPUSH => None,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let id = Uuid::new_v4().to_string();
+    state
+        .db
+        .push()
+        .register(&NewPushDevice {
+            id: &id,
+            user_id: &claims.sub,
+            provider: &req.provider,
+            endpoint,
+            p256dh: keys.map(|(p256dh, _)| p256dh),
+            auth: keys.map(|(_, auth)| auth),
+            hint_key: &req.hint_key,
+        })
+        .await
+        .map(Json)
+        .map_err(|e| db_error("Ошибка регистрации устройства", e))
+}
+
+pub async fn list_devices(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<PushDevice>>, StatusCode> {
+    state
+        .db
+        .push()
+        .devices(&claims.sub)
+        .await
+        .map(Json)
+        .map_err(|e| db_error("Ошибка получения устройств", e))
+}
+
+pub async fn unregister_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    let removed = state
+        .db
+        .push()
+        .unregister(&claims.sub, &device_id)
+        .await
+        .map_err(|e| db_error("Ошибка удаления устройства", e))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
@@ -0,0 +1,151 @@
+// server/src/api/reads.rs
+//! API доставки и прочтения
+//!
+//! Клиент отмечает «доставлено до» и «прочитано до» конкретного сообщения;
+//! всё, что левее, считается доставленным или прочитанным. Отправитель
+//! видит статус своих сообщений в ленте, в группах — список прочитавших.
+
+use axum::{
+    extract::{State, Path},
+    Json,
+    http::StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use crate::{
+    api::{chats, users, AppState},
+    auth::Claims,
+    db::reads::{MessageRead, UnreadCounts},
+    permissions::Permission,
+    websocket::WsMessage,
+};
+
+#[derive(Deserialize)]
+pub struct MarkRequest {
+    pub message_id: String,
+}
+
+/// Счётчики чата после отметки
+#[derive(Serialize)]
+pub struct ReadStateResponse {
+    pub chat_id: String,
+    #[serde(flatten)]
+    pub counts: UnreadCounts,
+}
+
+/// Сдвинуть «прочитано до» пользователя и разослать `messages_read`.
+/// `false` — указатель уже стоял не левее.
+pub(crate) async fn advance_read(
+    state: &AppState,
+    chat_id: &str,
+    user_id: &str,
+    message_id: &str,
+) -> Result<bool, StatusCode> {
+    let receipts = users::fetch_privacy(state, user_id).await?.read_receipts;
+
+    let advanced = state
+        .db
+        .reads()
+        .mark_read(chat_id, user_id, message_id, receipts)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка отметки прочтения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if advanced {
+        let event = WsMessage::MessagesRead {
+            chat_id: chat_id.to_string(),
+            user_id: user_id.to_string(),
+            message_id: message_id.to_string(),
+        };
+        let ws = state.ws.read().await;
+        if receipts {
+            ws.broadcast_to_chat(chat_id, event);
+        } else {
+            ws.send_to_user(user_id, event);
+        }
+    }
+    Ok(advanced)
+}
+
+async fn unread_counts(state: &AppState, chat_id: &str, user_id: &str) -> Result<UnreadCounts, StatusCode> {
+    state.db.reads().unread(chat_id, user_id).await.map_err(|e| {
+        tracing::error!("Ошибка подсчёта непрочитанных: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+/// Прочитано до сообщения (включительно)
+pub async fn mark_read(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<MarkRequest>,
+) -> Result<Json<ReadStateResponse>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    advance_read(&state, &chat_id, &claims.sub, &req.message_id).await?;
+
+    let counts = unread_counts(&state, &chat_id, &claims.sub).await?;
+    Ok(Json(ReadStateResponse { chat_id, counts }))
+}
+
+/// Доставлено до сообщения (включительно)
+pub async fn mark_delivered(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<MarkRequest>,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let advanced = state
+        .db
+        .reads()
+        .mark_delivered(&chat_id, &claims.sub, &req.message_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка отметки доставки: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if advanced {
+        state.ws.read().await.broadcast_to_chat(
+            &chat_id,
+            WsMessage::MessagesDelivered {
+                chat_id: chat_id.clone(),
+                user_id: claims.sub,
+                message_id: req.message_id,
+            },
+        );
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Кто прочитал сообщение (только отправителю)
+pub async fn list_message_reads(
+    State(state): State<AppState>,
+    Path((chat_id, message_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<Json<Vec<MessageRead>>, StatusCode> {
+    let message = state
+        .db
+        .messages()
+        .meta(&chat_id, &message_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения сообщения: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if message.sender_id != claims.sub {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let readers = state.db.reads().readers(&message_id).await.map_err(|e| {
+        tracing::error!("Ошибка получения прочитавших: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Json(readers))
+}
@@ -0,0 +1,416 @@
+// server/src/api/scheduled.rs
+//! Отложенные сообщения
+//!
+//! Сообщение ждёт своего времени (`send_at`), повторяется каждый день
+//! или неделю (`recurrence`) или уходит, когда собеседник в личном чате
+//! появится в сети (`when_online`). Планировщик (`send_due`, раз в минуту)
+//! отправляет наступившие обычным путём `post_message`: с проверкой прав,
+//! упоминаниями и push-уведомлениями. Неудачная попытка повторяется
+//! с растущей паузой, после `MAX_ATTEMPTS` сообщение помечается `failed`
+//! и автор получает `scheduled_failed`; исправленное — снова ждёт отправки.
+
+use axum::{
+    extract::{State, Path},
+    Json,
+    http::StatusCode,
+};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::{
+    api::{
+        chats::{self, PRIVATE_CHAT_TYPE},
+        db_error,
+        files,
+        messages::{self, Origin, SendMessageRequest},
+        polls::POLL_MESSAGE_TYPE,
+        AppState,
+    },
+    auth::Claims,
+    db::{
+        scheduled::{NewScheduledMessage, Recurrence, ScheduledSend, ScheduledUpdate},
+        timestamp,
+    },
+    permissions::Permission,
+    websocket::WsMessage,
+};
+
+pub use crate::db::scheduled::ScheduledMessage;
+
+/// Сколько раз пробовать отправить, прежде чем сдаться
+pub const MAX_ATTEMPTS: i64 = 5;
+
+/// Пауза перед первым повтором; дальше удваивается
+const RETRY_DELAY_SECS: i64 = 60;
+
+/// Захват старше этого брошен упавшим процессом
+const STALE_CLAIM_SECS: i64 = 300;
+
+/// Сколько наступивших сообщений читается из базы за раз
+const DUE_BATCH: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct ScheduleMessageRequest {
+    pub content: String,
+    /// Для `when_online` необязательно: не раньше этого времени
+    pub send_at: Option<DateTime<Utc>>,
+    pub message_type: Option<String>,
+    pub file_url: Option<String>,
+    #[serde(default = "once")]
+    pub recurrence: Recurrence,
+    /// Отправить, когда собеседник появится в сети (только личные чаты)
+    #[serde(default)]
+    pub when_online: bool,
+}
+
+/// Не указанные поля не меняются
+#[derive(Deserialize)]
+pub struct UpdateScheduledRequest {
+    pub content: Option<String>,
+    pub send_at: Option<DateTime<Utc>>,
+    pub recurrence: Option<Recurrence>,
+}
+
+fn once() -> Recurrence {
+    Recurrence::Once
+}
+
+/// Запланировать сообщение
+pub async fn schedule_message(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+    Json(req): Json<ScheduleMessageRequest>,
+) -> Result<Json<ScheduledMessage>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::SendMessages).await?;
+    let message_type = req.message_type.unwrap_or_else(|| "text".to_string());
+    // Опрос создаётся сразу, отложить можно только текст и файлы
+    if message_type == POLL_MESSAGE_TYPE {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let recipient_id = if req.when_online {
+        if req.recurrence != Recurrence::Once {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Some(private_peer(&state, &chat_id, &claims.sub).await?)
+    } else {
+        None
+    };
+    files::authorize_attachment(&state, req.file_url.as_deref(), &claims.sub).await?;
+    let send_at = match (req.send_at, req.when_online) {
+        (Some(send_at), _) => send_at,
+        (None, true) => Utc::now(),
+        (None, false) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let scheduled = state
+        .db
+        .scheduled()
+        .schedule(&NewScheduledMessage {
+            id: &Uuid::new_v4().to_string(),
+            chat_id: &chat_id,
+            sender_id: &claims.sub,
+            content: &req.content,
+            message_type: &message_type,
+            file_url: req.file_url.as_deref(),
+            send_at: &timestamp(send_at),
+            recurrence: req.recurrence,
+            when_online: req.when_online,
+        })
+        .await
+        .map_err(|e| db_error("Ошибка планирования сообщения", e))?;
+
+    // Собеседник уже в сети — ждать нечего
+    if let Some(recipient_id) = recipient_id {
+        if state.ws.read().await.is_online(&recipient_id) {
+            let state = state.clone();
+            tokio::spawn(async move {
+                if let Err(e) = send_when_online(&state, &recipient_id).await {
+                    tracing::error!("Ошибка отправки отложенных сообщений: {}", e);
+                }
+            });
+        }
+    }
+    Ok(Json(scheduled))
+}
+
+/// Собеседник автора в личном чате
+async fn private_peer(state: &AppState, chat_id: &str, user_id: &str) -> Result<String, StatusCode> {
+    let chat = state
+        .db
+        .chats()
+        .find(chat_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения чата", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if chat.chat_type != PRIVATE_CHAT_TYPE {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    state
+        .db
+        .chats()
+        .member_ids_after(chat_id, "", 3)
+        .await
+        .map_err(|e| db_error("Ошибка получения участников", e))?
+        .into_iter()
+        .find(|member_id| member_id != user_id)
+        .ok_or(StatusCode::BAD_REQUEST)
+}
+
+/// Свои ещё не отправленные сообщения, в том числе не отправившиеся
+pub async fn get_scheduled_messages(
+    State(state): State<AppState>,
+    Path(chat_id): Path<String>,
+    claims: Claims,
+) -> Result<Json<Vec<ScheduledMessage>>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    state
+        .db
+        .scheduled()
+        .pending(&chat_id, &claims.sub)
+        .await
+        .map(Json)
+        .map_err(|e| db_error("Ошибка получения отложенных сообщений", e))
+}
+
+/// Изменить своё ожидающее сообщение; не отправившееся после правки
+/// снова ждёт отправки
+pub async fn update_scheduled_message(
+    State(state): State<AppState>,
+    Path((chat_id, scheduled_id)): Path<(String, String)>,
+    claims: Claims,
+    Json(req): Json<UpdateScheduledRequest>,
+) -> Result<Json<ScheduledMessage>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::SendMessages).await?;
+    let repo = state.db.scheduled();
+    let current = repo
+        .find(&chat_id, &scheduled_id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка получения отложенного сообщения", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let recurrence = req.recurrence.unwrap_or(current.recurrence);
+    if current.when_online && recurrence != Recurrence::Once {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let send_at = req.send_at.map(timestamp).unwrap_or(current.send_at);
+    let updated = repo
+        .update(
+            &chat_id,
+            &scheduled_id,
+            &claims.sub,
+            &ScheduledUpdate {
+                content: req.content.as_deref().unwrap_or(&current.content),
+                send_at: &send_at,
+                recurrence,
+            },
+        )
+        .await
+        .map_err(|e| db_error("Ошибка изменения отложенного сообщения", e))?;
+    // Уже отправлено, отменено или отправляется прямо сейчас
+    if !updated {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    repo.find(&chat_id, &scheduled_id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка получения отложенного сообщения", e))?
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Отменить своё ожидающее сообщение
+pub async fn cancel_scheduled_message(
+    State(state): State<AppState>,
+    Path((chat_id, scheduled_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+
+    let cancelled = state
+        .db
+        .scheduled()
+        .cancel(&chat_id, &scheduled_id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка отмены отложенного сообщения", e))?;
+    if cancelled {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(StatusCode::NOT_FOUND)
+    }
+}
+
+// ==================== Планировщик ====================
+
+/// Отправить наступившие сообщения; `when_online` — только тем, чей
+/// собеседник в сети. Возвращает число отправленных.
+pub async fn send_due(state: &AppState) -> Result<u64, sqlx::Error> {
+    let repo = state.db.scheduled();
+    let stale = timestamp(Utc::now() - chrono::Duration::seconds(STALE_CLAIM_SECS));
+    let released = repo.release_stale(&stale).await?;
+    if released > 0 {
+        tracing::warn!("Возвращено в очередь брошенных отложенных сообщений: {}", released);
+    }
+
+    let mut sent = 0;
+    let mut after = String::new();
+    loop {
+        let batch = repo.due(&after, DUE_BATCH).await?;
+        let full = batch.len() as i64 == DUE_BATCH;
+        if let Some(last) = batch.last() {
+            after = last.message.id.clone();
+        }
+
+        for due in batch {
+            if let Some(recipient_id) = &due.recipient_id {
+                if !state.ws.read().await.is_online(recipient_id) {
+                    continue;
+                }
+            }
+            if deliver(state, &due.message.id).await? {
+                sent += 1;
+            }
+        }
+
+        if !full {
+            break;
+        }
+    }
+    Ok(sent)
+}
+
+/// Пользователь появился в сети: отправить то, что его ждало
+pub async fn send_when_online(state: &AppState, user_id: &str) -> Result<u64, sqlx::Error> {
+    let mut sent = 0;
+    for due in state.db.scheduled().due_for_recipient(user_id).await? {
+        if deliver(state, &due.message.id).await? {
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+/// Захватить и отправить одно сообщение в том виде, в каком оно
+/// захвачено (автор мог успеть его изменить). `false` — не отправлено:
+/// его перехватили, отменили, перенесли или попытка не удалась.
+async fn deliver(state: &AppState, scheduled_id: &str) -> Result<bool, sqlx::Error> {
+    let repo = state.db.scheduled();
+    let claim = Uuid::new_v4().to_string();
+    let Some(scheduled) = repo.claim(scheduled_id, &claim).await? else {
+        return Ok(false);
+    };
+
+    let send = ScheduledSend {
+        id: scheduled.id.clone(),
+        claim: claim.clone(),
+        next_send_at: next_send_at(&scheduled.send_at, scheduled.recurrence, Utc::now()),
+    };
+    let result = match chats::authorize(&state.db, &scheduled.chat_id, &scheduled.sender_id, Permission::SendMessages).await {
+        Ok(membership) => {
+            let req = SendMessageRequest {
+                content: scheduled.content.clone(),
+                message_type: Some(scheduled.message_type.clone()),
+                file_url: scheduled.file_url.clone(),
+                reply_to_id: None,
+                entities: Vec::new(),
+                poll: None,
+            };
+            messages::post_message(state, &scheduled.chat_id, &scheduled.sender_id, &membership, req, None, Origin::Scheduled(&send))
+                .await
+        }
+        Err(status) => Err(status),
+    };
+
+    match result {
+        Ok(message) => {
+            // Подписчикам чата сообщение уже разослал `post_message`
+            let ws = state.ws.read().await;
+            ws.send_to_user(
+                &scheduled.sender_id,
+                WsMessage::ScheduledSent {
+                    chat_id: scheduled.chat_id.clone(),
+                    scheduled_id: scheduled.id.clone(),
+                    message: Box::new(message),
+                },
+            );
+            Ok(true)
+        }
+        Err(status) => {
+            let attempts = scheduled.attempts + 1;
+            // Права и содержимое повтором не исправить
+            let retry_at = (status.is_server_error() && attempts < MAX_ATTEMPTS)
+                .then(|| timestamp(Utc::now() + retry_delay(attempts)));
+            let error = match status {
+                StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => "нет прав на отправку в чат",
+                StatusCode::BAD_REQUEST => "сообщение отклонено",
+                _ => "ошибка сервера",
+            };
+            if !repo.fail(&scheduled.id, &claim, retry_at.as_deref(), error).await? {
+                // Сообщение уже в чате: не повторять и не сообщать об отказе
+                tracing::error!("Отложенное сообщение {} отправлено с ошибкой: {}", scheduled.id, status);
+                return Ok(true);
+            }
+            tracing::warn!("Отложенное сообщение {} не отправлено ({}): {}", scheduled.id, attempts, status);
+            if retry_at.is_none() {
+                state.ws.read().await.send_to_user(
+                    &scheduled.sender_id,
+                    WsMessage::ScheduledFailed {
+                        chat_id: scheduled.chat_id,
+                        scheduled_id: scheduled.id,
+                        error: error.to_string(),
+                    },
+                );
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// Пауза перед повтором после `attempts` неудач: 1, 2, 4, ... минуты
+fn retry_delay(attempts: i64) -> chrono::Duration {
+    chrono::Duration::seconds(RETRY_DELAY_SECS << (attempts - 1).clamp(0, 10))
+}
+
+/// Следующая отправка повторяющегося сообщения — первая после `now`:
+/// пропущенные, пока сервер стоял, не досылаются
+fn next_send_at(send_at: &str, recurrence: Recurrence, now: DateTime<Utc>) -> Option<String> {
+    let step = recurrence.step()?;
+    let mut next = NaiveDateTime::parse_from_str(send_at, "%Y-%m-%d %H:%M:%S").ok()?.and_utc() + step;
+    while next <= now {
+        next += step;
+    }
+    Some(timestamp(next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_send_at_skips_missed_occurrences() {
+        let now = DateTime::parse_from_rfc3339("2026-05-10T12:00:00Z").unwrap().with_timezone(&Utc);
+        assert_eq!(next_send_at("2026-05-10 09:00:00", Recurrence::Once, now), None);
+        assert_eq!(
+            next_send_at("2026-05-10 09:00:00", Recurrence::Daily, now).as_deref(),
+            Some("2026-05-11 09:00:00")
+        );
+        // Неделю простоя — следующая отправка всё равно одна
+        assert_eq!(
+            next_send_at("2026-05-01 09:00:00", Recurrence::Daily, now).as_deref(),
+            Some("2026-05-11 09:00:00")
+        );
+        assert_eq!(
+            next_send_at("2026-05-08 18:30:00", Recurrence::Weekly, now).as_deref(),
+            Some("2026-05-15 18:30:00")
+        );
+    }
+
+    #[test]
+    fn test_retry_delay_doubles() {
+        assert_eq!(retry_delay(1).num_seconds(), 60);
+        assert_eq!(retry_delay(2).num_seconds(), 120);
+        assert_eq!(retry_delay(4).num_seconds(), 480);
+    }
+}
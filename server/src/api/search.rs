@@ -0,0 +1,191 @@
+// server/src/api/search.rs
+//! Полнотекстовый поиск по сообщениям
+//!
+//! Синтаксис запроса описан в `db::search`.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::{
+    api::AppState,
+    auth::Claims,
+    db::{
+        self,
+        search::{Expression, MessageFilters, SearchOrder, MARK_CLOSE, MARK_OPEN},
+    },
+};
+
+pub use crate::db::search::{SavedSearchHit, SearchHit};
+
+/// Размер страницы результатов по умолчанию и максимальный
+const DEFAULT_LIMIT: u32 = 20;
+const MAX_LIMIT: u32 = 100;
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub chat_id: Option<String>,
+    pub sender_id: Option<String>,
+    #[serde(rename = "type")]
+    pub message_type: Option<String>,
+    /// Нижняя граница даты (включительно), ISO 8601
+    pub from: Option<String>,
+    /// Верхняя граница даты (не включая), ISO 8601
+    pub to: Option<String>,
+    /// `relevance` (по умолчанию) или `date`
+    pub sort: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct SavedSearchQuery {
+    pub q: String,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Serialize)]
+pub struct SearchResults<T> {
+    pub hits: Vec<T>,
+    /// Смещение следующей страницы, если она есть
+    pub next_offset: Option<u32>,
+}
+
+/// Поиск по сообщениям чатов, в которых состоит пользователь
+pub async fn search_messages(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchResults<SearchHit>>, StatusCode> {
+    let expression = Expression::parse(&query.q).ok_or(StatusCode::BAD_REQUEST)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+
+    let from = query.from.as_deref().map(parse_date).transpose()?;
+    let to = query.to.as_deref().map(parse_date).transpose()?;
+    let filters = MessageFilters {
+        chat_id: query.chat_id.as_deref(),
+        sender_id: query.sender_id.as_deref(),
+        message_type: query.message_type.as_deref(),
+        from: from.as_deref(),
+        to: to.as_deref(),
+    };
+
+    let order = match query.sort.as_deref() {
+        None | Some("relevance") => SearchOrder::Relevance,
+        Some("date") => SearchOrder::Date,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let mut hits = state
+        .db
+        .search()
+        .messages(&claims.sub, &expression, &filters, order, limit as i64 + 1, offset as i64)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка поиска сообщений: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let next_offset = page_end(&mut hits, limit, offset);
+    for hit in &mut hits {
+        hit.snippet = highlight(&hit.snippet);
+    }
+
+    Ok(Json(SearchResults { hits, next_offset }))
+}
+
+/// Поиск по избранным сообщениям пользователя (текст и теги)
+pub async fn search_saved_messages(
+    State(state): State<AppState>,
+    claims: Claims,
+    Query(query): Query<SavedSearchQuery>,
+) -> Result<Json<SearchResults<SavedSearchHit>>, StatusCode> {
+    let expression = Expression::parse(&query.q).ok_or(StatusCode::BAD_REQUEST)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+
+    let mut hits = state
+        .db
+        .search()
+        .saved(&claims.sub, &expression, limit as i64 + 1, offset as i64)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка поиска в избранном: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let next_offset = page_end(&mut hits, limit, offset);
+    for hit in &mut hits {
+        hit.snippet = highlight(&hit.snippet);
+    }
+
+    Ok(Json(SearchResults { hits, next_offset }))
+}
+
+/// Дата из запроса (RFC 3339, `YYYY-MM-DD HH:MM:SS` или `YYYY-MM-DD`)
+/// в формате колонок-дат
+fn parse_date(value: &str) -> Result<String, StatusCode> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(db::timestamp(at.with_timezone(&Utc)));
+    }
+    if let Ok(at) = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S") {
+        return Ok(db::timestamp(at.and_utc()));
+    }
+    if let Ok(day) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(db::timestamp(day.and_time(chrono::NaiveTime::MIN).and_utc()));
+    }
+    Err(StatusCode::BAD_REQUEST)
+}
+
+/// Обрезает лишнюю строку, выбранную для проверки следующей страницы
+fn page_end<T>(hits: &mut Vec<T>, limit: u32, offset: u32) -> Option<u32> {
+    if hits.len() > limit as usize {
+        hits.truncate(limit as usize);
+        Some(offset + limit)
+    } else {
+        None
+    }
+}
+
+/// Экранирует HTML и превращает маркеры `snippet()` в `<mark>`
+fn highlight(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len() + 16);
+    for c in raw.chars() {
+        match c {
+            MARK_OPEN => out.push_str("<mark>"),
+            MARK_CLOSE => out.push_str("</mark>"),
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_escapes_html() {
+        let raw = format!("<b>{}кот{}</b> & co", MARK_OPEN, MARK_CLOSE);
+        assert_eq!(highlight(&raw), "&lt;b&gt;<mark>кот</mark>&lt;/b&gt; &amp; co");
+    }
+
+    #[test]
+    fn test_parse_date_formats() {
+        assert_eq!(parse_date("2024-03-01T12:00:00+03:00"), Ok("2024-03-01 09:00:00".to_string()));
+        assert_eq!(parse_date("2024-03-01 12:00:00"), Ok("2024-03-01 12:00:00".to_string()));
+        assert_eq!(parse_date("2024-03-01"), Ok("2024-03-01 00:00:00".to_string()));
+        assert_eq!(parse_date("вчера"), Err(StatusCode::BAD_REQUEST));
+    }
+}
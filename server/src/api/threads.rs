@@ -0,0 +1,239 @@
+// server/src/api/threads.rs
+//! API тредов: ответы на сообщение с пагинацией, подписки и непрочитанные
+//!
+//! Тред — ответы на корневое сообщение. В группе ответ из общей ленты тоже
+//! попадает в тред. У поста канала тред — обсуждение: комментировать могут
+//! подписчики, а в ленте канала комментариев нет. Ответивший и автор корня
+//! подписываются на тред; подписчики получают ответы по WebSocket.
+
+use axum::{
+    extract::{State, Path, Query},
+    Json,
+    http::StatusCode,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::{
+    api::{
+        chats,
+        db_error,
+        messages::{self, ListMessagesQuery, MessagePage, MessageResponse, SendMessageRequest},
+        AppState,
+    },
+    auth::Claims,
+    db::{
+        messages::Feed,
+        threads::{ThreadRoot, ThreadSummary},
+        timestamp,
+    },
+    permissions::Permission,
+    websocket::WsMessage,
+};
+
+/// Ответы треда от новых к старым, с корнем и состоянием для пользователя
+#[derive(Serialize)]
+pub struct ThreadPage {
+    pub root: MessageResponse,
+    pub subscribed: bool,
+    pub unread_count: i64,
+    #[serde(flatten)]
+    pub page: MessagePage,
+}
+
+#[derive(Deserialize)]
+pub struct ThreadReadRequest {
+    pub message_id: String,
+}
+
+#[derive(Serialize)]
+pub struct ThreadUnread {
+    pub thread_id: String,
+    pub unread_count: i64,
+}
+
+/// Корень треда в чате; 404 — нет такого сообщения или это само ответ
+async fn thread_root(state: &AppState, chat_id: &str, thread_id: &str) -> Result<ThreadRoot, StatusCode> {
+    state
+        .db
+        .threads()
+        .root_of(chat_id, thread_id)
+        .await
+        .map_err(|e| db_error("Ошибка получения треда", e))?
+        .filter(|root| root.id == thread_id)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Ответы в треде (курсоры как у ленты чата)
+pub async fn list_thread(
+    State(state): State<AppState>,
+    Path((chat_id, thread_id)): Path<(String, String)>,
+    Query(query): Query<ListMessagesQuery>,
+    claims: Claims,
+) -> Result<Json<ThreadPage>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    let root = thread_root(&state, &chat_id, &thread_id).await?;
+
+    let page = messages::load_page(&state, &chat_id, &claims.sub, Feed::Thread(&root.id), &query).await?;
+    let threads = state.db.threads();
+    let subscribed = threads
+        .is_subscribed(&root.id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка получения подписки", e))?;
+    let unread_count = threads
+        .unread(&root.id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка подсчёта непрочитанных", e))?;
+
+    Ok(Json(ThreadPage {
+        root: messages::fetch_message(&state, &root.id, &claims.sub).await?,
+        subscribed,
+        unread_count,
+        page,
+    }))
+}
+
+/// Ответить в треде. `reply_to_id` — сообщение этого же треда, по умолчанию
+/// корень. У поста канала это комментарий: нужно право комментировать.
+pub async fn reply_in_thread(
+    State(state): State<AppState>,
+    Path((chat_id, thread_id)): Path<(String, String)>,
+    claims: Claims,
+    Json(req): Json<SendMessageRequest>,
+) -> Result<Json<MessageResponse>, StatusCode> {
+    let membership = chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    let root = thread_root(&state, &chat_id, &thread_id).await?;
+    let permission = if root.channel_post { Permission::Comment } else { Permission::SendMessages };
+    if !membership.allows(permission, &timestamp(Utc::now())) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if let Some(reply_to_id) = &req.reply_to_id {
+        let target = state
+            .db
+            .threads()
+            .root_of(&chat_id, reply_to_id)
+            .await
+            .map_err(|e| db_error("Ошибка получения треда", e))?;
+        if target.is_none_or(|target| target.id != root.id) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let message = messages::post_message(&state, &chat_id, &claims.sub, &membership, req, Some(&root), messages::Origin::Direct).await?;
+    Ok(Json(message))
+}
+
+/// После ответа: ответивший подписывается и прочитал тред до своего
+/// ответа, автор корня подписывается при первом ответе, подписчики
+/// получают ответ
+pub(crate) async fn after_reply(
+    state: &AppState,
+    root: &ThreadRoot,
+    user_id: &str,
+    message: &MessageResponse,
+) -> Result<(), StatusCode> {
+    let threads = state.db.threads();
+    threads
+        .set_subscribed(&root.id, user_id, true)
+        .await
+        .map_err(|e| db_error("Ошибка подписки на тред", e))?;
+    if root.sender_id != user_id {
+        threads
+            .subscribe_if_new(&root.id, &root.sender_id)
+            .await
+            .map_err(|e| db_error("Ошибка подписки на тред", e))?;
+    }
+    threads
+        .mark_read(&root.id, user_id, &message.id)
+        .await
+        .map_err(|e| db_error("Ошибка отметки прочтения треда", e))?;
+
+    let subscribers = threads
+        .subscribers(&root.id)
+        .await
+        .map_err(|e| db_error("Ошибка получения подписчиков треда", e))?;
+    let ws = state.ws.read().await;
+    for subscriber in subscribers.iter().filter(|id| *id != user_id) {
+        // Ответ в треде группы придёт и подписчику чата (`post_message`)
+        if !root.channel_post && ws.is_subscribed(&root.chat_id, subscriber) {
+            continue;
+        }
+        ws.send_to_user(subscriber, WsMessage::NewMessage { message: Box::new(message.clone()) });
+    }
+    Ok(())
+}
+
+/// Отметить тред прочитанным до ответа `message_id`
+pub async fn mark_thread_read(
+    State(state): State<AppState>,
+    Path((chat_id, thread_id)): Path<(String, String)>,
+    claims: Claims,
+    Json(req): Json<ThreadReadRequest>,
+) -> Result<Json<ThreadUnread>, StatusCode> {
+    chats::authorize(&state.db, &chat_id, &claims.sub, Permission::View).await?;
+    let root = thread_root(&state, &chat_id, &thread_id).await?;
+
+    let threads = state.db.threads();
+    threads
+        .mark_read(&root.id, &claims.sub, &req.message_id)
+        .await
+        .map_err(|e| db_error("Ошибка отметки прочтения треда", e))?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let unread_count = threads
+        .unread(&root.id, &claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка подсчёта непрочитанных", e))?;
+
+    Ok(Json(ThreadUnread { thread_id: root.id, unread_count }))
+}
+
+/// Подписаться на ответы в треде
+pub async fn subscribe_thread(
+    State(state): State<AppState>,
+    Path((chat_id, thread_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    set_subscribed(&state, &chat_id, &thread_id, &claims.sub, true).await
+}
+
+/// Отписаться от ответов в треде
+pub async fn unsubscribe_thread(
+    State(state): State<AppState>,
+    Path((chat_id, thread_id)): Path<(String, String)>,
+    claims: Claims,
+) -> Result<StatusCode, StatusCode> {
+    set_subscribed(&state, &chat_id, &thread_id, &claims.sub, false).await
+}
+
+async fn set_subscribed(
+    state: &AppState,
+    chat_id: &str,
+    thread_id: &str,
+    user_id: &str,
+    subscribed: bool,
+) -> Result<StatusCode, StatusCode> {
+    chats::authorize(&state.db, chat_id, user_id, Permission::View).await?;
+    let root = thread_root(state, chat_id, thread_id).await?;
+
+    state
+        .db
+        .threads()
+        .set_subscribed(&root.id, user_id, subscribed)
+        .await
+        .map_err(|e| db_error("Ошибка подписки на тред", e))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Треды, на которые подписан пользователь, с непрочитанными ответами
+pub async fn list_threads(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<Vec<ThreadSummary>>, StatusCode> {
+    let threads = state
+        .db
+        .threads()
+        .subscriptions(&claims.sub)
+        .await
+        .map_err(|e| db_error("Ошибка получения тредов", e))?;
+    Ok(Json(threads))
+}
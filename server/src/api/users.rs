@@ -1,22 +1,86 @@
 // server/src/api/users.rs
 //! API пользователей
+//!
+//! Статус online берётся из открытых WebSocket-соединений, время
+//! последнего захода пишется при закрытии последнего из них. Что из этого
+//! видно другим, решают настройки приватности (`/users/me/privacy`);
+//! там же отключаются отчёты о прочтении и скрывается ссылка на автора
+//! в пересланных сообщениях. Присутствие видно только собеседникам по
+//! общим чатам и контактам; подписчикам одного канала друг друга не видно,
+//! заблокированным — тоже.
 
 use axum::{
     extract::{State, Path},
     Json,
     http::StatusCode,
 };
-use serde::Serialize;
-use crate::api::AppState;
-
-#[derive(Serialize)]
-pub struct UserResponse {
-    pub id: String,
-    pub username: String,
-    pub email: Option<String>,
-    pub avatar_url: Option<String>,
-    pub status: String,
-    pub public_key: String,
+use serde::{Deserialize, Serialize};
+use crate::{api::{contacts, AppState}, auth::Claims, db::users::PrivacySettings, websocket::WsMessage};
+
+pub use crate::db::users::User as UserResponse;
+
+/// Кому видны время захода, статус online и ссылка в пересылках
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Everyone,
+    Contacts,
+    Nobody,
+}
+
+impl Visibility {
+    fn parse(value: &str) -> Self {
+        match value {
+            "contacts" => Visibility::Contacts,
+            "nobody" => Visibility::Nobody,
+            _ => Visibility::Everyone,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Everyone => "everyone",
+            Visibility::Contacts => "contacts",
+            Visibility::Nobody => "nobody",
+        }
+    }
+
+    /// Видно ли тому, кто есть (или нет) в контактах владельца
+    pub fn allows(self, is_contact: bool) -> bool {
+        match self {
+            Visibility::Everyone => true,
+            Visibility::Contacts => is_contact,
+            Visibility::Nobody => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrivacyResponse {
+    pub last_seen: Visibility,
+    pub online: Visibility,
+    pub forwards: Visibility,
+    pub read_receipts: bool,
+}
+
+impl From<PrivacySettings> for PrivacyResponse {
+    fn from(privacy: PrivacySettings) -> Self {
+        Self {
+            last_seen: Visibility::parse(&privacy.last_seen),
+            online: Visibility::parse(&privacy.online),
+            forwards: Visibility::parse(&privacy.forwards),
+            read_receipts: privacy.read_receipts,
+        }
+    }
+}
+
+/// Не указанные поля не меняются
+#[derive(Deserialize)]
+pub struct UpdatePrivacyRequest {
+    pub last_seen: Option<Visibility>,
+    pub online: Option<Visibility>,
+    pub forwards: Option<Visibility>,
+    pub read_receipts: Option<bool>,
 }
 
 /// Получить текущего пользователя
@@ -25,7 +89,7 @@ pub async fn get_current_user(
     headers: axum::http::HeaderMap,
 ) -> Result<Json<UserResponse>, StatusCode> {
     use crate::auth;
-    
+
     let auth_header = headers
         .get(axum::http::header::AUTHORIZATION)
         .and_then(|h| h.to_str().ok())
@@ -35,43 +99,173 @@ pub async fn get_current_user(
     let claims = auth::verify_token(auth_header)
         .map_err(|_| StatusCode::UNAUTHORIZED)?;
 
-    let user: (String, String, Option<String>, Option<String>, String, String) = sqlx::query_as(
-        "SELECT id, username, email, avatar_url, status, public_key FROM users WHERE id = ?"
-    )
-    .bind(&claims.sub)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    Ok(Json(UserResponse {
-        id: user.0,
-        username: user.1,
-        email: user.2,
-        avatar_url: user.3,
-        status: user.4,
-        public_key: user.5,
-    }))
+    find_user(&state, &claims.sub).await.map(Json)
 }
 
-/// Получить пользователя по ID
+/// Получить пользователя по ID; почту видит только он сам, статус и время
+/// захода — по общим чатам, его настройкам приватности и чёрному списку
 pub async fn get_user(
     State(state): State<AppState>,
+    claims: Claims,
     Path(user_id): Path<String>,
 ) -> Result<Json<UserResponse>, StatusCode> {
-    let user: (String, String, Option<String>, Option<String>, String, String) = sqlx::query_as(
-        "SELECT id, username, email, avatar_url, status, public_key FROM users WHERE id = ?"
-    )
-    .bind(&user_id)
-    .fetch_one(&state.db)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    Ok(Json(UserResponse {
-        id: user.0,
-        username: user.1,
-        email: user.2,
-        avatar_url: user.3,
-        status: user.4,
-        public_key: user.5,
-    }))
+    let mut user = find_user(&state, &user_id).await?;
+    if user_id == claims.sub {
+        return Ok(Json(user));
+    }
+    // Почта видна только самому пользователю
+    user.email = None;
+
+    // Заблокированному — как при `nobody`
+    if contacts::is_blocked(&state, &user_id, &claims.sub).await? {
+        user.status = "offline".to_string();
+        user.last_seen_at = None;
+        return Ok(Json(user));
+    }
+
+    let privacy = fetch_privacy(&state, &user_id).await?;
+    let users = state.db.users();
+    let is_contact = users.is_contact(&user_id, &claims.sub).await.map_err(|e| {
+        tracing::error!("Ошибка проверки контакта: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    // Присутствие видно тем же, кому оно рассылается: собеседникам
+    // по общим чатам (без подписчиков одного канала) и контактам
+    let is_peer = is_contact
+        || users.shares_presence(&user_id, &claims.sub).await.map_err(|e| {
+            tracing::error!("Ошибка проверки общих чатов: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if !is_peer || !privacy.online.allows(is_contact) {
+        user.status = "offline".to_string();
+    }
+    if !is_peer || !privacy.last_seen.allows(is_contact) {
+        user.last_seen_at = None;
+    }
+    Ok(Json(user))
+}
+
+/// Свои настройки приватности
+pub async fn get_privacy(
+    State(state): State<AppState>,
+    claims: Claims,
+) -> Result<Json<PrivacyResponse>, StatusCode> {
+    fetch_privacy(&state, &claims.sub).await.map(Json)
+}
+
+pub async fn update_privacy(
+    State(state): State<AppState>,
+    claims: Claims,
+    Json(req): Json<UpdatePrivacyRequest>,
+) -> Result<Json<PrivacyResponse>, StatusCode> {
+    let current = fetch_privacy(&state, &claims.sub).await?;
+    let updated = PrivacyResponse {
+        last_seen: req.last_seen.unwrap_or(current.last_seen),
+        online: req.online.unwrap_or(current.online),
+        forwards: req.forwards.unwrap_or(current.forwards),
+        read_receipts: req.read_receipts.unwrap_or(current.read_receipts),
+    };
+
+    state
+        .db
+        .users()
+        .set_privacy(
+            &claims.sub,
+            &PrivacySettings {
+                last_seen: updated.last_seen.as_str().to_string(),
+                online: updated.online.as_str().to_string(),
+                forwards: updated.forwards.as_str().to_string(),
+                read_receipts: updated.read_receipts,
+            },
+        )
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка сохранения приватности: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(updated))
+}
+
+pub(crate) async fn fetch_privacy(state: &AppState, user_id: &str) -> Result<PrivacyResponse, StatusCode> {
+    state
+        .db
+        .users()
+        .privacy(user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения приватности: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .map(PrivacyResponse::from)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+/// Профиль со статусом по живым соединениям
+async fn find_user(state: &AppState, user_id: &str) -> Result<UserResponse, StatusCode> {
+    let mut user = state
+        .db
+        .users()
+        .find(user_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Ошибка получения пользователя: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let online = state.ws.read().await.is_online(user_id);
+    user.status = if online { "online" } else { "offline" }.to_string();
+    Ok(user)
+}
+
+/// Первое соединение пользователя открыто
+pub async fn set_online(state: &AppState, user_id: &str) -> Result<(), sqlx::Error> {
+    state.db.users().set_online(user_id).await?;
+    broadcast_presence(state, user_id, true, None).await
+}
+
+/// Последнее соединение пользователя закрыто
+pub async fn set_offline(state: &AppState, user_id: &str) -> Result<(), sqlx::Error> {
+    // Успел переподключиться
+    if state.ws.read().await.is_online(user_id) {
+        return Ok(());
+    }
+    let at = crate::db::timestamp(chrono::Utc::now());
+    state.db.users().set_offline(user_id, &at).await?;
+    broadcast_presence(state, user_id, false, Some(at)).await
+}
+
+/// Разослать присутствие собеседникам по общим чатам, которым его
+/// разрешено видеть
+async fn broadcast_presence(
+    state: &AppState,
+    user_id: &str,
+    online: bool,
+    last_seen_at: Option<String>,
+) -> Result<(), sqlx::Error> {
+    let Some(privacy) = state.db.users().privacy(user_id).await?.map(PrivacyResponse::from) else {
+        return Ok(());
+    };
+    if privacy.online == Visibility::Nobody {
+        return Ok(());
+    }
+
+    let audience = state.db.users().presence_audience(user_id).await?;
+    let ws = state.ws.read().await;
+    for (peer, is_contact) in audience {
+        if !privacy.online.allows(is_contact) {
+            continue;
+        }
+        ws.send_to_user(
+            &peer,
+            WsMessage::Presence {
+                user_id: user_id.to_string(),
+                online,
+                last_seen_at: last_seen_at.clone().filter(|_| privacy.last_seen.allows(is_contact)),
+            },
+        );
+    }
+    Ok(())
 }
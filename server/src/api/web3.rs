@@ -33,7 +33,7 @@ pub struct SwapResponse {
 
 /// Получить баланс
 pub async fn get_balance(
-    // State(state): State<AppState>,
+    // State(_state): State<AppState>,
     // claims: Claims,
 ) -> Result<Json<BalanceResponse>, StatusCode> {
     // В реальности здесь будет запрос к Web3 провайдеру
@@ -46,7 +46,7 @@ pub async fn get_balance(
 
 /// Обменять токены через 0x Protocol
 pub async fn swap_tokens(
-    State(state): State<AppState>,
+    State(_state): State<AppState>,
     Json(req): Json<SwapRequest>,
 ) -> Result<Json<SwapResponse>, StatusCode> {
     // Запрос к 0x API
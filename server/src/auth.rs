@@ -1,11 +1,12 @@
 // server/src/auth.rs
 //! Аутентификация JWT + Ed25519
 
-use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer};
+use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
 use jsonwebtoken::{encode, decode, Header, Validation, Algorithm};
 use serde::{Deserialize, Serialize};
 use chrono::{Utc, Duration};
 use rand::rngs::OsRng;
+use axum::{extract::FromRequestParts, http::{request::Parts, StatusCode}};
 
 /// Секрет для JWT
 pub fn get_jwt_secret() -> Vec<u8> {
@@ -15,7 +16,7 @@ pub fn get_jwt_secret() -> Vec<u8> {
 }
 
 /// Claims для JWT токена
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // user_id
     pub username: String,
@@ -23,6 +24,31 @@ pub struct Claims {
     pub iat: usize,  // issued at
 }
 
+/// Извлечение claims в обработчиках: из extensions (если отработал
+/// `auth_middleware`) или из заголовка `Authorization: Bearer`
+#[axum::async_trait]
+impl<S> FromRequestParts<S> for Claims
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(claims) = parts.extensions.get::<Claims>() {
+            return Ok(claims.clone());
+        }
+
+        let token = parts
+            .headers
+            .get(axum::http::header::AUTHORIZATION)
+            .and_then(|h| h.to_str().ok())
+            .and_then(|h| h.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        verify_token(token).map_err(|_| StatusCode::UNAUTHORIZED)
+    }
+}
+
 /// Генерация пары ключей Ed25519
 pub fn generate_keypair() -> (SigningKey, VerifyingKey) {
     let mut csprng = OsRng {};
@@ -62,7 +88,7 @@ pub fn verify_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error>
 }
 
 /// Хэширование пароля
-pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
     use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
     
     let salt = SaltString::generate(&mut OsRng);
@@ -76,7 +102,9 @@ pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
 pub fn verify_password(password: &str, hash: &str) -> bool {
     use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
     
-    let parsed_hash = PasswordHash::new(hash).ok()?;
+    let Ok(parsed_hash) = PasswordHash::new(hash) else {
+        return false;
+    };
     Argon2::default()
         .verify_password(password.as_bytes(), &parsed_hash)
         .is_ok()
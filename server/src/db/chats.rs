@@ -0,0 +1,668 @@
+// server/src/db/chats.rs
+//! Репозиторий чатов и участников
+
+use serde::Serialize;
+use crate::permissions::{Membership, Rights, Role};
+use super::{reads::{UnreadCounts, MENTIONS_USER, UNREAD_MESSAGES}, with_pool, Database, Dialect};
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Chat {
+    pub id: String,
+    pub chat_type: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub owner_id: Option<String>,
+    /// Канал: подписывать посты именем администратора
+    pub sign_messages: bool,
+    /// Сообщения чата нельзя пересылать
+    pub restrict_forwarding: bool,
+    pub created_at: String,
+}
+
+/// Личные настройки чата
+#[derive(Debug, Default, Clone, Serialize, sqlx::FromRow)]
+pub struct ChatSettings {
+    pub muted: bool,
+    /// Без звука до этого времени (формат колонок)
+    pub muted_until: Option<String>,
+    /// Не уведомлять и об упоминаниях, пока чат без звука
+    pub mute_mentions: bool,
+    pub pinned: bool,
+    pub archived: bool,
+    pub draft: Option<String>,
+}
+
+/// Чат в списке пользователя: счётчики, настройки и последнее сообщение
+#[derive(Debug, sqlx::FromRow)]
+pub struct ChatSummary {
+    #[sqlx(flatten)]
+    pub chat: Chat,
+    #[sqlx(flatten)]
+    pub counts: UnreadCounts,
+    #[sqlx(flatten)]
+    pub settings: ChatSettings,
+    pub last_message_id: Option<String>,
+    pub last_message_content: Option<String>,
+    pub last_message_sender_id: Option<String>,
+    pub last_message_at: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct ChatMemberRow {
+    pub chat_id: String,
+    #[sqlx(flatten)]
+    pub member: ChatMember,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ChatMember {
+    pub user_id: String,
+    pub username: String,
+    pub role: String,
+    /// Права администратора
+    #[sqlx(try_from = "i64")]
+    pub rights: Rights,
+    pub restricted_until: Option<String>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct MembershipRow {
+    role: String,
+    rights: i64,
+    restricted_until: Option<String>,
+    promoted_by: Option<String>,
+    channel: bool,
+    blocked: bool,
+}
+
+impl From<MembershipRow> for Membership {
+    fn from(row: MembershipRow) -> Self {
+        Self {
+            role: Role::parse(&row.role),
+            rights: Rights::from_bits(row.rights),
+            restricted_until: row.restricted_until,
+            promoted_by: row.promoted_by,
+            channel: row.channel,
+            blocked: row.blocked,
+        }
+    }
+}
+
+/// Блокировка в чате
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ChatBan {
+    pub user_id: String,
+    pub username: String,
+    pub banned_by: Option<String>,
+    /// `None` — бессрочно
+    pub until: Option<String>,
+    pub created_at: String,
+}
+
+pub struct NewChat<'a> {
+    pub id: &'a str,
+    pub chat_type: &'a str,
+    pub name: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub owner_id: &'a str,
+}
+
+const CHAT_COLUMNS: &str = "id, chat_type, name, description, owner_id, sign_messages, restrict_forwarding, created_at";
+
+/// Чат `$1` изменился у всех участников: версия чата, а не строка на
+/// каждого участника, поэтому пост в большой канал — одна запись.
+/// Выполняется в той же транзакции, что и изменение, после
+/// `Dialect::next_version`.
+pub(super) fn touch_chat(dialect: Dialect) -> String {
+    format!("UPDATE chats SET version = {} WHERE id = $1", dialect.change_version())
+}
+
+/// Чат `$1` изменился только у пользователя `$2` (настройки, прочтение,
+/// выход из чата)
+pub(super) fn touch_chat_for(dialect: Dialect) -> String {
+    format!(
+        "INSERT INTO chat_list_changes (user_id, chat_id, version) VALUES ($2, $1, {})
+         ON CONFLICT (user_id, chat_id) DO UPDATE SET version = excluded.version",
+        dialect.change_version(),
+    )
+}
+
+/// Пользователь `$1` — владелец или участник чата `c`
+const VISIBLE_TO_USER: &str =
+    "(c.owner_id = $1 OR EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = c.id AND cm.user_id = $1))";
+
+/// Изменён у всех или у пользователя `$1` начиная с версии `$2`
+const CHANGED_SINCE: &str =
+    "(c.version >= $2
+      OR EXISTS (SELECT 1 FROM chat_list_changes ch WHERE ch.user_id = $1 AND ch.chat_id = c.id AND ch.version >= $2))";
+
+/// Какие чаты пользователя выбрать
+#[derive(Debug, Clone, Copy)]
+pub enum ChatFilter<'a> {
+    All,
+    /// Изменённые после версии
+    ChangedSince(i64),
+    One(&'a str),
+}
+
+impl ChatFilter<'_> {
+    fn sql(self) -> String {
+        match self {
+            ChatFilter::All => VISIBLE_TO_USER.to_string(),
+            ChatFilter::ChangedSince(_) => format!("{} AND {}", VISIBLE_TO_USER, CHANGED_SINCE),
+            ChatFilter::One(_) => format!("{} AND c.id = $2", VISIBLE_TO_USER),
+        }
+    }
+}
+
+/// Привязать параметр фильтра (`$2`)
+macro_rules! bind_filter {
+    ($query:expr, $filter:expr) => {
+        match $filter {
+            ChatFilter::All => $query,
+            ChatFilter::ChangedSince(version) => $query.bind(version),
+            ChatFilter::One(chat_id) => $query.bind(chat_id),
+        }
+    };
+}
+
+pub struct ChatRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> ChatRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Положение пользователя в чате (`None`, если он не участник).
+    /// Владелец из `chats.owner_id` считается `owner`, даже без строки в `chat_members`.
+    pub async fn membership(&self, chat_id: &str, user_id: &str) -> Result<Option<Membership>, sqlx::Error> {
+        let row: Option<MembershipRow> = with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT CASE WHEN c.owner_id = $2 THEN 'owner' ELSE COALESCE(cm.role, 'member') END AS role,
+                        COALESCE(cm.rights, 0) AS rights, cm.restricted_until, cm.promoted_by,
+                        c.chat_type = 'channel' AS channel,
+                        c.chat_type = 'private' AND EXISTS (
+                            SELECT 1 FROM blocked_users b
+                            WHERE b.blocked_id = $2
+                              AND (b.user_id = c.owner_id
+                                   OR b.user_id IN (SELECT peer.user_id FROM chat_members peer WHERE peer.chat_id = c.id))
+                        ) AS blocked
+                 FROM chats c
+                 LEFT JOIN chat_members cm ON cm.chat_id = c.id AND cm.user_id = $2
+                 WHERE c.id = $1 AND (c.owner_id = $2 OR cm.user_id IS NOT NULL)"
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+        })?;
+        Ok(row.map(Membership::from))
+    }
+
+    /// Чаты, где пользователь владелец или участник: сначала закреплённые
+    /// (последний закреплённый выше), затем по последней активности
+    pub async fn summaries(&self, user_id: &str, filter: ChatFilter<'_>) -> Result<Vec<ChatSummary>, sqlx::Error> {
+        let sql = format!(
+            "SELECT c.id, c.chat_type, c.name, c.description, c.owner_id, c.sign_messages, c.restrict_forwarding, c.created_at,
+                    (SELECT COUNT(*) {unread}) AS unread_count,
+                    (SELECT COUNT(*) {unread} {mentions}) AS unread_mentions,
+                    COALESCE(s.muted, FALSE) AS muted,
+                    s.muted_until,
+                    COALESCE(s.mute_mentions, FALSE) AS mute_mentions,
+                    s.pinned_at IS NOT NULL AS pinned,
+                    COALESCE(s.archived, FALSE) AS archived,
+                    s.draft,
+                    lm.id AS last_message_id,
+                    lm.content AS last_message_content,
+                    CASE WHEN lm.channel_post THEN lm.chat_id ELSE lm.sender_id END AS last_message_sender_id,
+                    lm.created_at AS last_message_at
+             FROM chats c
+             LEFT JOIN chat_reads r ON r.chat_id = c.id AND r.user_id = $1
+             LEFT JOIN chat_user_settings s ON s.chat_id = c.id AND s.user_id = $1
+             LEFT JOIN messages lm ON lm.id = (
+                 SELECT m.id FROM messages m
+                 WHERE m.chat_id = c.id AND m.is_comment = FALSE
+                   AND NOT EXISTS (SELECT 1 FROM message_hidden h WHERE h.message_id = m.id AND h.user_id = $1)
+                 ORDER BY m.created_at DESC, m.id DESC
+                 LIMIT 1
+             )
+             WHERE {filter}
+             ORDER BY s.pinned_at IS NULL, s.pinned_at DESC, COALESCE(lm.created_at, c.created_at) DESC, c.id",
+            unread = UNREAD_MESSAGES,
+            mentions = MENTIONS_USER,
+            filter = filter.sql(),
+        );
+        with_pool!(self.db, pool => {
+            let query = sqlx::query_as(&sql).bind(user_id);
+            bind_filter!(query, filter).fetch_all(pool).await
+        })
+    }
+
+    /// Участники тех же чатов, что выбирает `summaries`; у каналов — только
+    /// владелец и администраторы (подписчики — через `members`)
+    pub async fn summary_members(&self, user_id: &str, filter: ChatFilter<'_>) -> Result<Vec<ChatMemberRow>, sqlx::Error> {
+        let sql = format!(
+            "SELECT m.chat_id, m.user_id, u.username,
+                    CASE WHEN c.owner_id = m.user_id THEN 'owner' ELSE COALESCE(m.role, 'member') END AS role,
+                    m.rights, m.restricted_until
+             FROM chat_members m
+             JOIN users u ON m.user_id = u.id
+             JOIN chats c ON c.id = m.chat_id
+             WHERE {} AND (c.chat_type <> 'channel' OR m.role = 'admin' OR c.owner_id = m.user_id)
+             ORDER BY m.chat_id, m.joined_at, m.user_id",
+            filter.sql(),
+        );
+        with_pool!(self.db, pool => {
+            let query = sqlx::query_as(&sql).bind(user_id);
+            bind_filter!(query, filter).fetch_all(pool).await
+        })
+    }
+
+    /// Участники чата вместе с владельцем по возрастанию id, после `after`:
+    /// для постраничного обхода больших чатов
+    pub async fn member_ids_after(&self, chat_id: &str, after: &str, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(
+                "SELECT user_id FROM (
+                     SELECT user_id FROM chat_members WHERE chat_id = $1
+                     UNION
+                     SELECT owner_id FROM chats WHERE id = $1 AND owner_id IS NOT NULL
+                 ) ids
+                 WHERE user_id > $2
+                 ORDER BY user_id
+                 LIMIT $3"
+            )
+            .bind(chat_id)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Курсор синхронизации списка чатов (см. `Dialect::sync_version`)
+    pub async fn version(&self) -> Result<i64, sqlx::Error> {
+        let sql = self.db.dialect().sync_version();
+        with_pool!(self.db, pool => sqlx::query_scalar(sql).fetch_one(pool).await)
+    }
+
+    /// Чаты, изменённые у пользователя начиная с версии, где он больше не состоит
+    pub async fn removed_since(&self, user_id: &str, version: i64) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(
+                "SELECT ch.chat_id FROM chat_list_changes ch
+                 WHERE ch.user_id = $1 AND ch.version >= $2
+                   AND NOT EXISTS (SELECT 1 FROM chats c WHERE c.id = ch.chat_id AND (c.owner_id = $1
+                       OR EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = c.id AND cm.user_id = $1)))
+                 ORDER BY ch.version"
+            )
+            .bind(user_id)
+            .bind(version)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    pub async fn settings(&self, chat_id: &str, user_id: &str) -> Result<ChatSettings, sqlx::Error> {
+        let settings = with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT muted, muted_until, mute_mentions, pinned_at IS NOT NULL AS pinned, archived, draft
+                 FROM chat_user_settings WHERE chat_id = $1 AND user_id = $2"
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+        })?;
+        Ok(settings.unwrap_or_default())
+    }
+
+    /// Сохранить настройки; закреплённый раньше чат сохраняет своё место
+    pub async fn set_settings(&self, chat_id: &str, user_id: &str, settings: &ChatSettings) -> Result<(), sqlx::Error> {
+        let now = self.db.dialect().now();
+        let sql = format!(
+            "INSERT INTO chat_user_settings (chat_id, user_id, muted, pinned_at, archived, draft, muted_until, mute_mentions)
+             VALUES ($1, $2, $3, CASE WHEN $4 THEN {now} END, $5, $6, $7, $8)
+             ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                 muted = excluded.muted,
+                 muted_until = excluded.muted_until,
+                 mute_mentions = excluded.mute_mentions,
+                 pinned_at = CASE WHEN $4 THEN COALESCE(chat_user_settings.pinned_at, excluded.pinned_at) END,
+                 archived = excluded.archived,
+                 draft = excluded.draft",
+        );
+
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(&sql)
+                .bind(chat_id)
+                .bind(user_id)
+                .bind(settings.muted)
+                .bind(settings.pinned)
+                .bind(settings.archived)
+                .bind(settings.draft.as_deref())
+                .bind(settings.muted_until.as_deref())
+                .bind(settings.mute_mentions)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat_for(dialect)).bind(chat_id).bind(user_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    pub async fn find(&self, chat_id: &str) -> Result<Option<Chat>, sqlx::Error> {
+        let sql = format!("SELECT {} FROM chats WHERE id = $1", CHAT_COLUMNS);
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(chat_id).fetch_optional(pool).await)
+    }
+
+    /// Создать чат с участниками одной транзакцией
+    pub async fn create(&self, chat: &NewChat<'_>, member_ids: &[String]) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO chats (id, chat_type, name, description, owner_id) VALUES ($1, $2, $3, $4, $5)"
+            )
+            .bind(chat.id)
+            .bind(chat.chat_type)
+            .bind(chat.name)
+            .bind(chat.description)
+            .bind(chat.owner_id)
+            .execute(&mut *tx)
+            .await?;
+
+            for member_id in member_ids {
+                sqlx::query("INSERT INTO chat_members (chat_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                    .bind(chat.id)
+                    .bind(member_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat.id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Участники по возрастанию id после `after`, не больше `limit`
+    pub async fn members(&self, chat_id: &str, after: &str, limit: i64) -> Result<Vec<ChatMember>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT cm.user_id, u.username,
+                        CASE WHEN c.owner_id = cm.user_id THEN 'owner' ELSE COALESCE(cm.role, 'member') END AS role,
+                        cm.rights, cm.restricted_until
+                 FROM chat_members cm
+                 JOIN users u ON cm.user_id = u.id
+                 JOIN chats c ON c.id = cm.chat_id
+                 WHERE cm.chat_id = $1 AND cm.user_id > $2
+                 ORDER BY cm.user_id
+                 LIMIT $3"
+            )
+            .bind(chat_id)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Состоит ли пользователь в `chat_members`
+    pub async fn is_member(&self, chat_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM chat_members WHERE chat_id = $1 AND user_id = $2)"
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+        })
+    }
+
+    /// Добавить участника; `false` — уже состоит
+    pub async fn add_member(&self, chat_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let added = sqlx::query("INSERT INTO chat_members (chat_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            if added {
+                sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+                sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+            }
+
+            tx.commit().await?;
+            Ok(added)
+        })
+    }
+
+    /// Убрать участника (вышел сам или удалён). Изменение отмечается и у него:
+    /// при синхронизации чат придёт в `removed`.
+    pub async fn remove_member(&self, chat_id: &str, user_id: &str) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat_for(dialect)).bind(chat_id).bind(user_id).execute(&mut *tx).await?;
+
+            sqlx::query("DELETE FROM chat_members WHERE chat_id = $1 AND user_id = $2")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Сменить роль участника (не владельца)
+    pub async fn set_role(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        membership: &Membership,
+    ) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "UPDATE chat_members SET role = $1, rights = $2, restricted_until = $3, promoted_by = $4
+                 WHERE chat_id = $5 AND user_id = $6"
+            )
+            .bind(membership.role.as_str())
+            .bind(membership.rights.bits())
+            .bind(membership.restricted_until.as_deref())
+            .bind(membership.promoted_by.as_deref())
+            .bind(chat_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Заблокировать до `until` (`None` — бессрочно), убрать из чата и
+    /// отклонить его заявку на вступление
+    pub async fn ban(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        banned_by: &str,
+        until: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let now = self.db.dialect().now();
+        let sql = format!(
+            "INSERT INTO chat_bans (chat_id, user_id, banned_by, until, created_at) VALUES ($1, $2, $3, $4, {now})
+             ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                 banned_by = excluded.banned_by, until = excluded.until, created_at = excluded.created_at",
+        );
+
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat_for(dialect)).bind(chat_id).bind(user_id).execute(&mut *tx).await?;
+
+            sqlx::query("DELETE FROM chat_members WHERE chat_id = $1 AND user_id = $2")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(&sql)
+                .bind(chat_id)
+                .bind(user_id)
+                .bind(banned_by)
+                .bind(until)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("DELETE FROM chat_join_requests WHERE chat_id = $1 AND user_id = $2")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Снять блокировку (в чат не возвращает); `false` — её не было
+    pub async fn unban(&self, chat_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM chat_bans WHERE chat_id = $1 AND user_id = $2")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Действующие блокировки
+    pub async fn bans(&self, chat_id: &str) -> Result<Vec<ChatBan>, sqlx::Error> {
+        let sql = format!(
+            "SELECT b.user_id, u.username, b.banned_by, b.until, b.created_at
+             FROM chat_bans b
+             JOIN users u ON u.id = b.user_id
+             WHERE b.chat_id = $1 AND (b.until IS NULL OR b.until > {})
+             ORDER BY b.created_at DESC, b.user_id",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(chat_id).fetch_all(pool).await)
+    }
+
+    /// Действует ли блокировка пользователя
+    pub async fn is_banned(&self, chat_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            "SELECT EXISTS (SELECT 1 FROM chat_bans
+                            WHERE chat_id = $1 AND user_id = $2 AND (until IS NULL OR until > {}))",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query_scalar(&sql).bind(chat_id).bind(user_id).fetch_one(pool).await)
+    }
+
+    /// Передать владение участнику `to`; прежний владелец остаётся
+    /// администратором со всеми правами
+    pub async fn transfer_ownership(&self, chat_id: &str, from: &str, to: &str) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query("UPDATE chats SET owner_id = $1 WHERE id = $2")
+                .bind(to)
+                .bind(chat_id)
+                .execute(&mut *tx)
+                .await?;
+
+            // Роль владельца берётся из chats.owner_id
+            sqlx::query(
+                "UPDATE chat_members SET role = 'member', rights = 0, restricted_until = NULL, promoted_by = NULL
+                 WHERE chat_id = $1 AND user_id = $2"
+            )
+            .bind(chat_id)
+            .bind(to)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(
+                "INSERT INTO chat_members (chat_id, user_id, role, rights, promoted_by) VALUES ($1, $2, 'admin', $3, $4)
+                 ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                     role = excluded.role, rights = excluded.rights, restricted_until = NULL, promoted_by = excluded.promoted_by"
+            )
+            .bind(chat_id)
+            .bind(from)
+            .bind(Rights::ALL.bits())
+            .bind(to)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Название, описание и настройки чата; `None` — не менять
+    pub async fn update_info(
+        &self,
+        chat_id: &str,
+        name: Option<&str>,
+        description: Option<&str>,
+        sign_messages: Option<bool>,
+        restrict_forwarding: Option<bool>,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "UPDATE chats SET name = COALESCE($1, name), description = COALESCE($2, description),
+                    sign_messages = COALESCE($3, sign_messages),
+                    restrict_forwarding = COALESCE($4, restrict_forwarding), updated_at = {}
+             WHERE id = $5",
+            self.db.dialect().now(),
+        );
+
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(&sql)
+                .bind(name)
+                .bind(description)
+                .bind(sign_messages)
+                .bind(restrict_forwarding)
+                .bind(chat_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+}
\ No newline at end of file
@@ -0,0 +1,186 @@
+// server/src/db/contacts.rs
+//! Репозиторий контактов и чёрного списка
+//!
+//! Контакт односторонний: `contacts(user_id, contact_id)` — запись
+//! в книжке `user_id`. Взаимный — если и у собеседника есть обратная.
+//! Блокировка тоже односторонняя: `blocked_users(user_id, blocked_id)`.
+
+use serde::Serialize;
+use super::{with_pool, Database};
+
+/// Запись в контактах пользователя
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct Contact {
+    pub user_id: String,
+    pub username: String,
+    pub avatar_url: Option<String>,
+    /// Пользователь тоже добавил владельца книжки
+    pub mutual: bool,
+    pub added_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct BlockedUser {
+    pub user_id: String,
+    pub username: String,
+    pub blocked_at: String,
+}
+
+/// Контакты `$1`: поля `Contact`
+const CONTACT_COLUMNS: &str =
+    "u.id AS user_id, u.username, u.avatar_url,
+     EXISTS (SELECT 1 FROM contacts back WHERE back.user_id = c.contact_id AND back.contact_id = c.user_id) AS mutual,
+     c.added_at
+     FROM contacts c
+     JOIN users u ON u.id = c.contact_id
+     WHERE c.user_id = $1";
+
+pub struct ContactRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> ContactRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Контакты по имени
+    pub async fn list(&self, user_id: &str) -> Result<Vec<Contact>, sqlx::Error> {
+        let sql = format!("SELECT {} ORDER BY LOWER(u.username), u.id", CONTACT_COLUMNS);
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(user_id)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    pub async fn find(&self, user_id: &str, contact_id: &str) -> Result<Option<Contact>, sqlx::Error> {
+        let sql = format!("SELECT {} AND c.contact_id = $2", CONTACT_COLUMNS);
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(user_id)
+                .bind(contact_id)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    /// Добавить; уже добавленный остаётся как был
+    pub async fn add(&self, user_id: &str, contact_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("INSERT INTO contacts (user_id, contact_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(user_id)
+                .bind(contact_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn remove(&self, user_id: &str, contact_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM contacts WHERE user_id = $1 AND contact_id = $2")
+                .bind(user_id)
+                .bind(contact_id)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Добавить найденных по именам и хешам почты. Себя и заблокировавших
+    /// `user_id` не находит. Возвращает id найденных, без повторов.
+    pub async fn import(
+        &self,
+        user_id: &str,
+        usernames: &[String],
+        email_hashes: &[String],
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let lookup = |condition: &str| {
+            format!(
+                "SELECT u.id FROM users u
+                 WHERE {} AND u.id <> $1
+                   AND NOT EXISTS (SELECT 1 FROM blocked_users b WHERE b.user_id = u.id AND b.blocked_id = $1)",
+                condition,
+            )
+        };
+        let by_username = lookup("LOWER(u.username) = LOWER($2)");
+        let by_email = lookup("u.email_hash = $2");
+
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+            let mut found: Vec<String> = Vec::new();
+
+            let targets = usernames.iter().map(|name| (&by_username, name)).chain(email_hashes.iter().map(|hash| (&by_email, hash)));
+            for (sql, target) in targets {
+                let contact_id: Option<String> = sqlx::query_scalar(sql)
+                    .bind(user_id)
+                    .bind(target)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                let Some(contact_id) = contact_id.filter(|id| !found.contains(id)) else {
+                    continue;
+                };
+                sqlx::query("INSERT INTO contacts (user_id, contact_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                    .bind(user_id)
+                    .bind(&contact_id)
+                    .execute(&mut *tx)
+                    .await?;
+                found.push(contact_id);
+            }
+
+            tx.commit().await?;
+            Ok(found)
+        })
+    }
+
+    /// Чёрный список, последние заблокированные первыми
+    pub async fn blocked(&self, user_id: &str) -> Result<Vec<BlockedUser>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT u.id AS user_id, u.username, b.blocked_at
+                 FROM blocked_users b
+                 JOIN users u ON u.id = b.blocked_id
+                 WHERE b.user_id = $1
+                 ORDER BY b.blocked_at DESC, u.id"
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    pub async fn block(&self, user_id: &str, blocked_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("INSERT INTO blocked_users (user_id, blocked_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(user_id)
+                .bind(blocked_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn unblock(&self, user_id: &str, blocked_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM blocked_users WHERE user_id = $1 AND blocked_id = $2")
+                .bind(user_id)
+                .bind(blocked_id)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// `user_id` заблокировал `blocked_id`
+    pub async fn is_blocked(&self, user_id: &str, blocked_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM blocked_users WHERE user_id = $1 AND blocked_id = $2)")
+                .bind(user_id)
+                .bind(blocked_id)
+                .fetch_one(pool)
+                .await
+        })
+    }
+}
@@ -0,0 +1,389 @@
+// server/src/db/extra.rs
+//! Репозиторий: реакции, закреплённые и избранные сообщения,
+//! профиль (био, тема), демонстрация экрана, таймер самоуничтожения
+
+use serde::{Deserialize, Serialize};
+use super::{chats::touch_chat, with_pool, Database};
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct PinnedMessage {
+    pub message_id: String,
+    pub chat_id: String,
+    pub content: String,
+    pub sender_id: String,
+    pub pinned_at: String,
+    pub pinned_by: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow)]
+pub struct SavedMessage {
+    pub id: String,
+    pub user_id: String,
+    pub content: String,
+    pub message_type: String,
+    pub file_url: Option<String>,
+    pub tags: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ScreenShareSession {
+    pub id: String,
+    pub chat_id: String,
+    pub user_id: String,
+    pub stream_url: String,
+    pub started_at: String,
+}
+
+/// Реакции одного эмодзи: пользователи через запятую
+#[derive(Debug, sqlx::FromRow)]
+pub struct ReactionGroup {
+    pub emoji: String,
+    pub count: i64,
+    pub users: String,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct MessageSummary {
+    pub id: String,
+    pub chat_id: String,
+    pub content: String,
+    pub sender_id: String,
+}
+
+pub struct NewSavedMessage<'a> {
+    pub id: &'a str,
+    pub user_id: &'a str,
+    pub content: &'a str,
+    pub message_type: &'a str,
+    pub file_url: Option<&'a str>,
+    pub tags: Option<&'a str>,
+}
+
+pub struct NewSelfDestructMessage<'a> {
+    pub id: &'a str,
+    pub chat_id: &'a str,
+    pub sender_id: &'a str,
+    pub content: &'a str,
+    pub message_type: &'a str,
+    pub file_url: Option<&'a str>,
+    pub reply_to_id: Option<&'a str>,
+    pub timer_seconds: i64,
+    /// Дата в формате колонок, см. `db::timestamp`
+    pub delete_at: &'a str,
+}
+
+const SAVED_COLUMNS: &str =
+    "id, user_id, content, COALESCE(message_type, 'text') AS message_type, file_url, tags, created_at";
+
+pub struct ExtraRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> ExtraRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    // ==================== Реакции ====================
+
+    pub async fn add_reaction(&self, message_id: &str, user_id: &str, emoji: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO message_reactions (message_id, user_id, emoji, created_at)
+             VALUES ($1, $2, $3, {now})
+             ON CONFLICT (message_id, user_id, emoji) DO UPDATE SET created_at = {now}",
+            now = self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(message_id)
+                .bind(user_id)
+                .bind(emoji)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn reactions(&self, message_id: &str) -> Result<Vec<ReactionGroup>, sqlx::Error> {
+        let sql = format!(
+            "SELECT emoji, COUNT(*) AS count, {} AS users
+             FROM message_reactions WHERE message_id = $1
+             GROUP BY emoji",
+            self.db.dialect().group_concat("user_id"),
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(message_id).fetch_all(pool).await)
+    }
+
+    // ==================== Закреплённые ====================
+
+    pub async fn message_in_chat(&self, chat_id: &str, message_id: &str) -> Result<Option<MessageSummary>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as("SELECT id, chat_id, content, CASE WHEN channel_post THEN chat_id ELSE sender_id END AS sender_id
+                 FROM messages WHERE id = $1 AND chat_id = $2")
+                .bind(message_id)
+                .bind(chat_id)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    pub async fn pin(&self, chat_id: &str, message_id: &str, user_id: &str) -> Result<(), sqlx::Error> {
+        let now = self.db.dialect().now();
+        let pin = format!(
+            "INSERT INTO pinned_messages (chat_id, message_id, pinned_by, pinned_at)
+             VALUES ($1, $2, $3, {now})
+             ON CONFLICT (chat_id, message_id) DO UPDATE SET
+                 pinned_by = excluded.pinned_by,
+                 pinned_at = excluded.pinned_at",
+            now = now,
+        );
+        let mark = format!(
+            "UPDATE messages SET is_pinned = TRUE, pinned_at = {}, pinned_by = $1 WHERE id = $2",
+            now,
+        );
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(&pin)
+                .bind(chat_id)
+                .bind(message_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(&mark)
+                .bind(user_id)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    pub async fn unpin(&self, chat_id: &str, message_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query("DELETE FROM pinned_messages WHERE chat_id = $1 AND message_id = $2")
+                .bind(chat_id)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("UPDATE messages SET is_pinned = FALSE WHERE id = $1")
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    pub async fn pinned(&self, chat_id: &str) -> Result<Vec<PinnedMessage>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT m.id AS message_id, m.chat_id, m.content,
+                        CASE WHEN m.channel_post THEN m.chat_id ELSE m.sender_id END AS sender_id, pm.pinned_at, pm.pinned_by
+                 FROM pinned_messages pm
+                 JOIN messages m ON pm.message_id = m.id
+                 WHERE pm.chat_id = $1
+                 ORDER BY pm.pinned_at DESC"
+            )
+            .bind(chat_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    // ==================== Избранное ====================
+
+    pub async fn save(&self, saved: &NewSavedMessage<'_>) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO saved_messages (id, user_id, content, message_type, file_url, tags, created_at)
+             VALUES ($1, $2, $3, $4, $5, $6, {})",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(saved.id)
+                .bind(saved.user_id)
+                .bind(saved.content)
+                .bind(saved.message_type)
+                .bind(saved.file_url)
+                .bind(saved.tags)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Избранное пользователя, опционально с подстрокой в тегах
+    pub async fn saved(&self, user_id: &str, tag: Option<&str>) -> Result<Vec<SavedMessage>, sqlx::Error> {
+        match tag {
+            Some(tag) => {
+                let sql = format!(
+                    "SELECT {} FROM saved_messages WHERE user_id = $1 AND tags LIKE $2 ORDER BY created_at DESC",
+                    SAVED_COLUMNS,
+                );
+                let pattern = format!("%{}%", tag);
+                with_pool!(self.db, pool => {
+                    sqlx::query_as(&sql).bind(user_id).bind(&pattern).fetch_all(pool).await
+                })
+            }
+            None => {
+                let sql = format!(
+                    "SELECT {} FROM saved_messages WHERE user_id = $1 ORDER BY created_at DESC",
+                    SAVED_COLUMNS,
+                );
+                with_pool!(self.db, pool => sqlx::query_as(&sql).bind(user_id).fetch_all(pool).await)
+            }
+        }
+    }
+
+    pub async fn delete_saved(&self, user_id: &str, saved_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM saved_messages WHERE user_id = $1 AND id = $2")
+                .bind(user_id)
+                .bind(saved_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    // ==================== Профиль ====================
+
+    /// `None` — пользователя нет, `Some(None)` — био не заполнено
+    pub async fn bio(&self, user_id: &str) -> Result<Option<Option<String>>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT bio FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    pub async fn set_bio(&self, user_id: &str, bio: &str) -> Result<(), sqlx::Error> {
+        self.update_user_field("bio", user_id, bio).await
+    }
+
+    pub async fn set_theme(&self, user_id: &str, theme: &str) -> Result<(), sqlx::Error> {
+        self.update_user_field("theme", user_id, theme).await
+    }
+
+    pub async fn set_night_mode(&self, user_id: &str, enabled: bool) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "UPDATE users SET night_mode = $1, updated_at = {} WHERE id = $2",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query(&sql).bind(enabled).bind(user_id).execute(pool).await.map(|_| ()))
+    }
+
+    async fn update_user_field(&self, column: &'static str, user_id: &str, value: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "UPDATE users SET {} = $1, updated_at = {} WHERE id = $2",
+            column,
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query(&sql).bind(value).bind(user_id).execute(pool).await.map(|_| ()))
+    }
+
+    // ==================== Демонстрация экрана ====================
+
+    pub async fn start_screen_share(
+        &self,
+        id: &str,
+        chat_id: &str,
+        user_id: &str,
+        stream_url: &str,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO screen_share_sessions (id, chat_id, user_id, stream_url, started_at)
+             VALUES ($1, $2, $3, $4, {})",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(id)
+                .bind(chat_id)
+                .bind(user_id)
+                .bind(stream_url)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn stop_screen_share(&self, session_id: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "UPDATE screen_share_sessions SET ended_at = {} WHERE id = $1",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query(&sql).bind(session_id).execute(pool).await.map(|_| ()))
+    }
+
+    pub async fn active_screen_shares(&self, chat_id: &str) -> Result<Vec<ScreenShareSession>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, chat_id, user_id, COALESCE(stream_url, '') AS stream_url, started_at
+                 FROM screen_share_sessions WHERE chat_id = $1 AND ended_at IS NULL"
+            )
+            .bind(chat_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    // ==================== Самоуничтожение ====================
+
+    pub async fn self_destruct_timer(&self, chat_id: &str) -> Result<Option<i64>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT COALESCE(self_destruct_timer, 0) FROM chats WHERE id = $1")
+                .bind(chat_id)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    /// `None` — отключить таймер
+    pub async fn set_self_destruct_timer(&self, chat_id: &str, seconds: Option<i64>) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE chats SET self_destruct_timer = $1 WHERE id = $2")
+                .bind(seconds)
+                .bind(chat_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn insert_self_destruct_message(&self, message: &NewSelfDestructMessage<'_>) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO messages (id, chat_id, sender_id, content, message_type, file_url, reply_to_id, self_destruct_timer, delete_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+            )
+            .bind(message.id)
+            .bind(message.chat_id)
+            .bind(message.sender_id)
+            .bind(message.content)
+            .bind(message.message_type)
+            .bind(message.file_url)
+            .bind(message.reply_to_id)
+            .bind(message.timer_seconds)
+            .bind(message.delete_at)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(message.chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+}
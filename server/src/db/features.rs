@@ -0,0 +1,183 @@
+// server/src/db/features.rs
+//! Репозиторий: семейные статусы, обои чатов, автоудаление
+
+use super::{chats::touch_chat, with_pool, Database};
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Wallpaper {
+    pub wallpaper_url: String,
+    pub wallpaper_type: String,
+    pub synced: bool,
+}
+
+pub struct NewAutoDeleteMessage<'a> {
+    pub id: &'a str,
+    pub chat_id: &'a str,
+    pub sender_id: &'a str,
+    pub content: &'a str,
+    pub message_type: &'a str,
+    pub file_url: Option<&'a str>,
+    pub reply_to_id: Option<&'a str>,
+    pub auto_delete_hours: i64,
+    /// Дата в формате колонок, см. `db::timestamp`
+    pub delete_at: &'a str,
+}
+
+pub struct FeatureRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> FeatureRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn set_family_status(&self, user_id: &str, status: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE users SET family_status = $1 WHERE id = $2")
+                .bind(status)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Связать партнёров и выставить статус обоим
+    pub async fn set_partner(&self, user_id: &str, partner_id: &str, status: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO family_relations (id, user_id, relative_id, relation_type)
+                 VALUES ($1, $2, $3, 'partner')
+                 ON CONFLICT (user_id, relative_id) DO UPDATE SET relation_type = excluded.relation_type"
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(partner_id)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query("UPDATE users SET family_status = $1 WHERE id = $2")
+                .bind(status)
+                .bind(partner_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    pub async fn wallpaper(&self, chat_id: &str, user_id: &str) -> Result<Option<Wallpaper>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT wallpaper_url, COALESCE(wallpaper_type, 'custom') AS wallpaper_type,
+                        COALESCE(synced, FALSE) AS synced
+                 FROM chat_wallpapers
+                 WHERE chat_id = $1 AND user_id = $2"
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+        })
+    }
+
+    pub async fn set_wallpaper(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        wallpaper_url: &str,
+        wallpaper_type: &str,
+        synced: bool,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO chat_wallpapers (chat_id, user_id, wallpaper_url, wallpaper_type, synced, updated_at)
+             VALUES ($1, $2, $3, $4, $5, {now})
+             ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                 wallpaper_url = excluded.wallpaper_url,
+                 wallpaper_type = excluded.wallpaper_type,
+                 synced = excluded.synced,
+                 updated_at = {now}",
+            now = self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(chat_id)
+                .bind(user_id)
+                .bind(wallpaper_url)
+                .bind(wallpaper_type)
+                .bind(synced)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Общие обои чата для всех участников
+    pub async fn sync_wallpaper(&self, chat_id: &str, wallpaper_url: &str) -> Result<(), sqlx::Error> {
+        let update_members = format!(
+            "UPDATE chat_wallpapers SET wallpaper_url = $1, synced = TRUE, updated_at = {}
+             WHERE chat_id = $2",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query("UPDATE chats SET wallpaper_url = $1, wallpaper_sync = TRUE WHERE id = $2")
+                .bind(wallpaper_url)
+                .bind(chat_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query(&update_members)
+                .bind(wallpaper_url)
+                .bind(chat_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Автоудаление для чата (права проверяет вызывающий)
+    pub async fn set_auto_delete(&self, chat_id: &str, hours: i64) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE chats SET auto_delete_hours = $1 WHERE id = $2")
+                .bind(hours)
+                .bind(chat_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn insert_auto_delete_message(&self, message: &NewAutoDeleteMessage<'_>) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO messages (id, chat_id, sender_id, content, message_type, file_url, reply_to_id, auto_delete_hours, delete_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)"
+            )
+            .bind(message.id)
+            .bind(message.chat_id)
+            .bind(message.sender_id)
+            .bind(message.content)
+            .bind(message.message_type)
+            .bind(message.file_url)
+            .bind(message.reply_to_id)
+            .bind(message.auto_delete_hours)
+            .bind(message.delete_at)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(message.chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+}
@@ -0,0 +1,564 @@
+// server/src/db/files.rs
+//! Репозиторий загруженных файлов
+
+use super::{with_pool, Database};
+use crate::encryption::WrappedKey;
+
+/// Файл `f` доступен пользователю `$2`: владельцу или участнику (и
+/// создателю) чата, где файл есть в неудалённом сообщении
+const FILE_ACCESS: &str = "(f.owner_id = $2 OR EXISTS (
+        SELECT 1 FROM messages m
+        JOIN chats c ON c.id = m.chat_id
+        LEFT JOIN chat_members cm ON cm.chat_id = m.chat_id AND cm.user_id = $2
+        WHERE m.file_url = f.url AND m.is_deleted = FALSE
+          AND (c.owner_id = $2 OR cm.user_id IS NOT NULL)
+    ))";
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct FileRecord {
+    pub id: String,
+    pub owner_id: String,
+    pub filename: String,
+    pub original_name: String,
+    pub mime_type: String,
+    pub size: i64,
+    pub url: String,
+    /// SHA-256 содержимого; `None` у ещё не перенесённых старых файлов
+    pub content_hash: Option<String>,
+    pub created_at: String,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub duration_ms: Option<i64>,
+    /// Волна аудио в base64
+    pub waveform: Option<String>,
+    pub blurhash: Option<String>,
+    /// `pending`, `ready`, `failed`; `None` — файл не обрабатывается
+    pub media_status: Option<String>,
+    /// Зашифрован клиентом: сервер не видит ключей и не разбирает содержимое
+    pub client_encrypted: bool,
+}
+
+pub struct NewFile<'a> {
+    pub id: &'a str,
+    pub owner_id: &'a str,
+    pub filename: &'a str,
+    pub original_name: &'a str,
+    pub mime_type: &'a str,
+    pub size: i64,
+    pub url: &'a str,
+    pub content_hash: &'a str,
+    /// `Some("pending")` ставит файл в очередь обработки медиа
+    pub media_status: Option<&'a str>,
+    pub client_encrypted: bool,
+    /// Ключ данных на случай, если содержимое новое (шифрование включено)
+    pub data_key: Option<&'a WrappedKey>,
+}
+
+/// Результат обработки медиа
+#[derive(Default)]
+pub struct MediaMetadata<'a> {
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub duration_ms: Option<i64>,
+    pub waveform: Option<&'a str>,
+    pub blurhash: Option<&'a str>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Thumbnail {
+    pub size: String,
+    pub width: i64,
+    pub height: i64,
+    pub mime_type: String,
+    pub content_hash: String,
+}
+
+pub struct NewThumbnail<'a> {
+    pub size: &'a str,
+    pub width: i64,
+    pub height: i64,
+    pub mime_type: &'a str,
+    pub content_hash: &'a str,
+    /// Размер содержимого в байтах (для `file_blobs`)
+    pub byte_size: i64,
+}
+
+/// Файл, загруженный до хранилища по хешу
+#[derive(Debug, sqlx::FromRow)]
+pub struct LegacyFile {
+    pub id: String,
+    pub url: String,
+}
+
+/// Ключ объекта из строки `file_blobs` (`key_id`, `wrapped_key`)
+fn blob_key(row: (Option<String>, Option<String>)) -> Option<WrappedKey> {
+    match row {
+        (Some(key_id), Some(wrapped_key)) => Some(WrappedKey { key_id, wrapped_key }),
+        _ => None,
+    }
+}
+
+pub struct FileRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> FileRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// `INSERT` ссылки на содержимое: новая строка (с ключом `$4`, `$5`)
+    /// или `ref_count + 1`. Возвращает ключ, с которым объект хранится.
+    fn reference_blob_sql(&self) -> String {
+        format!(
+            "INSERT INTO file_blobs (hash, size, mime_type, ref_count, key_id, wrapped_key)
+             VALUES ($1, $2, $3, 1, $4, $5)
+             ON CONFLICT (hash) DO UPDATE SET ref_count = file_blobs.ref_count + 1, updated_at = {}
+             RETURNING key_id, wrapped_key",
+            self.db.dialect().now(),
+        )
+    }
+
+    /// Записать файл; возвращает ключ данных содержимого (`None` — открытое)
+    pub async fn insert(&self, file: &NewFile<'_>) -> Result<Option<WrappedKey>, sqlx::Error> {
+        self.insert_with(file, None).await
+    }
+
+    /// Записать файл и убрать загрузку, из которой он собран, одной транзакцией
+    pub async fn insert_from_upload(
+        &self,
+        file: &NewFile<'_>,
+        upload_id: &str,
+    ) -> Result<Option<WrappedKey>, sqlx::Error> {
+        self.insert_with(file, Some(upload_id)).await
+    }
+
+    async fn insert_with(&self, file: &NewFile<'_>, upload_id: Option<&str>) -> Result<Option<WrappedKey>, sqlx::Error> {
+        let reference_blob = self.reference_blob_sql();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let key = sqlx::query_as(&reference_blob)
+                .bind(file.content_hash)
+                .bind(file.size)
+                .bind(file.mime_type)
+                .bind(file.data_key.map(|key| key.key_id.as_str()))
+                .bind(file.data_key.map(|key| key.wrapped_key.as_str()))
+                .fetch_one(&mut *tx)
+                .await?;
+
+            sqlx::query(
+                "INSERT INTO files (id, owner_id, filename, original_name, mime_type, size, url, content_hash,
+                                    media_status, client_encrypted)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)"
+            )
+            .bind(file.id)
+            .bind(file.owner_id)
+            .bind(file.filename)
+            .bind(file.original_name)
+            .bind(file.mime_type)
+            .bind(file.size)
+            .bind(file.url)
+            .bind(file.content_hash)
+            .bind(file.media_status)
+            .bind(file.client_encrypted)
+            .execute(&mut *tx)
+            .await?;
+
+            if let Some(upload_id) = upload_id {
+                sqlx::query("DELETE FROM uploads WHERE id = $1")
+                    .bind(upload_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await.map(|_| blob_key(key))
+        })
+    }
+
+    pub async fn find(&self, file_id: &str) -> Result<Option<FileRecord>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, COALESCE(owner_id, '') AS owner_id, filename,
+                        COALESCE(original_name, filename) AS original_name,
+                        COALESCE(mime_type, '') AS mime_type, COALESCE(size, 0) AS size,
+                        COALESCE(url, '') AS url, content_hash, created_at,
+                        width, height, duration_ms, waveform, blurhash, media_status, client_encrypted
+                 FROM files WHERE id = $1"
+            )
+            .bind(file_id)
+            .fetch_optional(pool)
+            .await
+        })
+    }
+
+    /// Владелец файла или участник чата, где файл отправлен сообщением
+    pub async fn can_access(&self, file_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let sql = format!("SELECT EXISTS (SELECT 1 FROM files f WHERE f.id = $1 AND {})", FILE_ACCESS);
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(&sql)
+                .bind(file_id)
+                .bind(user_id)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    /// Можно ли отправить сообщение со ссылкой `url`: на чужой файл — только
+    /// если он доступен отправителю. Ссылки не на файлы сервера доступа
+    /// ни к чему не дают.
+    pub async fn can_attach(&self, url: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            "SELECT NOT EXISTS (SELECT 1 FROM files WHERE url = $1)
+                 OR EXISTS (SELECT 1 FROM files f WHERE f.url = $1 AND {})",
+            FILE_ACCESS,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(&sql)
+                .bind(url)
+                .bind(user_id)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    /// Удалить файл владельца и снять ссылку с содержимого.
+    /// `false`, если такого файла у владельца нет.
+    pub async fn delete(&self, file_id: &str, owner_id: &str) -> Result<bool, sqlx::Error> {
+        let release_blob = format!(
+            "UPDATE file_blobs SET ref_count = ref_count - 1, updated_at = {} WHERE hash = $1",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let hash: Option<Option<String>> = sqlx::query_scalar(
+                "SELECT content_hash FROM files WHERE id = $1 AND owner_id = $2"
+            )
+            .bind(file_id)
+            .bind(owner_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some(hash) = hash else {
+                return Ok(false);
+            };
+
+            let thumbnails: Vec<String> = sqlx::query_scalar(
+                "SELECT content_hash FROM file_thumbnails WHERE file_id = $1"
+            )
+            .bind(file_id)
+            .fetch_all(&mut *tx)
+            .await?;
+            for thumbnail in thumbnails {
+                sqlx::query(&release_blob)
+                    .bind(thumbnail)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            sqlx::query("DELETE FROM file_thumbnails WHERE file_id = $1")
+                .bind(file_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("DELETE FROM files WHERE id = $1")
+                .bind(file_id)
+                .execute(&mut *tx)
+                .await?;
+
+            if let Some(hash) = hash {
+                sqlx::query(&release_blob)
+                    .bind(hash)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await.map(|_| true)
+        })
+    }
+
+    /// Превью файла от меньшего к большему
+    pub async fn thumbnails(&self, file_id: &str) -> Result<Vec<Thumbnail>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT size, width, height, mime_type, content_hash
+                 FROM file_thumbnails WHERE file_id = $1 ORDER BY width * height"
+            )
+            .bind(file_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Файлы, ждущие обработки медиа, от старых к новым
+    pub async fn pending_media(&self, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(
+                "SELECT id FROM files WHERE media_status = 'pending' ORDER BY created_at LIMIT $1"
+            )
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Завести (или освежить) строку содержимого без ссылок перед записью
+    /// в хранилище: сборщик не тронет её до истечения отсрочки.
+    /// Возвращает ключ данных, с которым объект хранится.
+    pub async fn reserve_blob(
+        &self,
+        hash: &str,
+        size: i64,
+        mime_type: &str,
+        data_key: Option<&WrappedKey>,
+    ) -> Result<Option<WrappedKey>, sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO file_blobs (hash, size, mime_type, key_id, wrapped_key) VALUES ($1, $2, $3, $4, $5)
+             ON CONFLICT (hash) DO UPDATE SET updated_at = {}
+             RETURNING key_id, wrapped_key",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(hash)
+                .bind(size)
+                .bind(mime_type)
+                .bind(data_key.map(|key| key.key_id.as_str()))
+                .bind(data_key.map(|key| key.wrapped_key.as_str()))
+                .fetch_one(pool)
+                .await
+                .map(blob_key)
+        })
+    }
+
+    /// Ключ данных объекта; `None` — объект хранится открытым
+    pub async fn blob_key(&self, hash: &str) -> Result<Option<WrappedKey>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as("SELECT key_id, wrapped_key FROM file_blobs WHERE hash = $1")
+                .bind(hash)
+                .fetch_optional(pool)
+                .await
+                .map(|row| row.and_then(blob_key))
+        })
+    }
+
+    /// Объекты, чьи ключи обёрнуты не мастер-ключом `key_id`,
+    /// по возрастанию хеша после `after`
+    pub async fn keys_to_rewrap(
+        &self,
+        key_id: &str,
+        after: &str,
+        limit: i64,
+    ) -> Result<Vec<(String, WrappedKey)>, sqlx::Error> {
+        let rows: Vec<(String, Option<String>, Option<String>)> = with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT hash, key_id, wrapped_key FROM file_blobs
+                 WHERE wrapped_key IS NOT NULL AND key_id <> $1 AND hash > $2 ORDER BY hash LIMIT $3"
+            )
+            .bind(key_id)
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+        })?;
+        Ok(rows
+            .into_iter()
+            .filter_map(|(hash, key_id, wrapped_key)| blob_key((key_id, wrapped_key)).map(|key| (hash, key)))
+            .collect())
+    }
+
+    /// Заменить обёртку ключа, если она всё ещё `old`
+    pub async fn rewrap_key(&self, hash: &str, old: &WrappedKey, new: &WrappedKey) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE file_blobs SET key_id = $1, wrapped_key = $2 WHERE hash = $3 AND wrapped_key = $4")
+                .bind(&new.key_id)
+                .bind(&new.wrapped_key)
+                .bind(hash)
+                .bind(&old.wrapped_key)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() == 1)
+        })
+    }
+
+    /// Открытые объекты по возрастанию хеша после `after`
+    pub async fn unencrypted_blobs(&self, after: &str, limit: i64) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT hash FROM file_blobs WHERE wrapped_key IS NULL AND hash > $1 ORDER BY hash LIMIT $2")
+                .bind(after)
+                .bind(limit)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Отметить открытый объект зашифрованным. `false` — строки уже нет
+    /// или объект зашифрован раньше.
+    pub async fn set_blob_key(&self, hash: &str, key: &WrappedKey) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE file_blobs SET key_id = $1, wrapped_key = $2 WHERE hash = $3 AND wrapped_key IS NULL")
+                .bind(&key.key_id)
+                .bind(&key.wrapped_key)
+                .bind(hash)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() == 1)
+        })
+    }
+
+    /// Записать результат обработки и превью. `false`, если файл удалён
+    /// или уже обработан — тогда ничего не меняется.
+    pub async fn save_media(
+        &self,
+        file_id: &str,
+        media: &MediaMetadata<'_>,
+        thumbnails: &[NewThumbnail<'_>],
+    ) -> Result<bool, sqlx::Error> {
+        let reference_blob = self.reference_blob_sql();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let updated = sqlx::query(
+                "UPDATE files SET width = $1, height = $2, duration_ms = $3, waveform = $4,
+                        blurhash = $5, media_status = 'ready'
+                 WHERE id = $6 AND media_status = 'pending'"
+            )
+            .bind(media.width)
+            .bind(media.height)
+            .bind(media.duration_ms)
+            .bind(media.waveform)
+            .bind(media.blurhash)
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+            if updated.rows_affected() == 0 {
+                return Ok(false);
+            }
+
+            // Строки превью заведены `reserve_blob`, ключ уже выбран
+            for thumbnail in thumbnails {
+                sqlx::query(&reference_blob)
+                    .bind(thumbnail.content_hash)
+                    .bind(thumbnail.byte_size)
+                    .bind(thumbnail.mime_type)
+                    .bind(None::<&str>)
+                    .bind(None::<&str>)
+                    .execute(&mut *tx)
+                    .await?;
+                sqlx::query(
+                    "INSERT INTO file_thumbnails (file_id, size, width, height, mime_type, content_hash)
+                     VALUES ($1, $2, $3, $4, $5, $6)"
+                )
+                .bind(file_id)
+                .bind(thumbnail.size)
+                .bind(thumbnail.width)
+                .bind(thumbnail.height)
+                .bind(thumbnail.mime_type)
+                .bind(thumbnail.content_hash)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            tx.commit().await.map(|_| true)
+        })
+    }
+
+    /// Обработка не удалась: файл доступен, но без превью и размеров
+    pub async fn fail_media(&self, file_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE files SET media_status = 'failed' WHERE id = $1 AND media_status = 'pending'")
+                .bind(file_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Суммарный размер загруженных пользователем файлов
+    pub async fn used_bytes(&self, owner_id: &str) -> Result<i64, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT CAST(COALESCE(SUM(size), 0) AS BIGINT) FROM files WHERE owner_id = $1")
+                .bind(owner_id)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    /// Содержимое без ссылок, не менявшееся с `before` (дата в формате колонок)
+    pub async fn unreferenced_blobs(&self, before: &str) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT hash FROM file_blobs WHERE ref_count <= 0 AND updated_at < $1")
+                .bind(before)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Забыть содержимое, если на него так и не появилось ссылок.
+    /// `true` — строка удалена и объект можно стирать из хранилища.
+    pub async fn forget_blob(&self, hash: &str, before: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM file_blobs WHERE hash = $1 AND ref_count <= 0 AND updated_at < $2")
+                .bind(hash)
+                .bind(before)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() == 1)
+        })
+    }
+
+    /// Файлы без хеша содержимого по возрастанию id, начиная после `after`
+    pub async fn legacy(&self, after: &str, limit: i64) -> Result<Vec<LegacyFile>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, COALESCE(url, '') AS url FROM files
+                 WHERE content_hash IS NULL AND id > $1 ORDER BY id LIMIT $2"
+            )
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Привязать старый файл к содержимому (строка заведена `reserve_blob`);
+    /// ссылки в сообщениях переписываются на новый адрес
+    pub async fn attach_legacy(
+        &self,
+        legacy: &LegacyFile,
+        content_hash: &str,
+        size: i64,
+        mime_type: &str,
+        url: &str,
+    ) -> Result<(), sqlx::Error> {
+        let reference_blob = self.reference_blob_sql();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(&reference_blob)
+                .bind(content_hash)
+                .bind(size)
+                .bind(mime_type)
+                .bind(None::<&str>)
+                .bind(None::<&str>)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("UPDATE files SET content_hash = $1, size = $2, mime_type = $3, url = $4 WHERE id = $5")
+                .bind(content_hash)
+                .bind(size)
+                .bind(mime_type)
+                .bind(url)
+                .bind(&legacy.id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("UPDATE messages SET file_url = $1 WHERE file_url = $2")
+                .bind(url)
+                .bind(&legacy.url)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+}
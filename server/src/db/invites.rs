@@ -0,0 +1,273 @@
+// server/src/db/invites.rs
+//! Репозиторий пригласительных ссылок и заявок на вступление
+
+use serde::Serialize;
+use super::{chats::touch_chat, with_pool, Database, Dialect};
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ChatInvite {
+    pub code: String,
+    pub chat_id: String,
+    pub created_by: Option<String>,
+    pub name: Option<String>,
+    pub expires_at: Option<String>,
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub requires_approval: bool,
+    pub revoked: bool,
+    pub created_at: String,
+    /// Можно ли вступить по ссылке сейчас
+    pub active: bool,
+}
+
+/// Что видно по ссылке до вступления
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct InvitePreview {
+    pub chat_id: String,
+    #[serde(rename = "type")]
+    pub chat_type: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub member_count: i64,
+    pub requires_approval: bool,
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct JoinRequest {
+    pub user_id: String,
+    pub username: String,
+    pub invite_code: Option<String>,
+    pub created_at: String,
+}
+
+pub struct NewInvite<'a> {
+    pub code: &'a str,
+    pub chat_id: &'a str,
+    pub created_by: &'a str,
+    pub name: Option<&'a str>,
+    pub expires_at: Option<&'a str>,
+    pub max_uses: Option<i64>,
+    pub requires_approval: bool,
+}
+
+const INVITE_COLUMNS: &str =
+    "i.code, i.chat_id, i.created_by, i.name, i.expires_at, i.max_uses, i.uses, i.requires_approval, i.revoked, i.created_at";
+
+/// Ссылка `i` не отозвана, не истекла и не исчерпана
+fn active(dialect: Dialect) -> String {
+    format!(
+        "(NOT i.revoked AND (i.expires_at IS NULL OR i.expires_at > {}) AND (i.max_uses IS NULL OR i.uses < i.max_uses))",
+        dialect.now(),
+    )
+}
+
+/// Засчитать использование ссылки `$1`, если она ещё действует (как в
+/// `active`); возвращает чат ссылки
+fn use_invite(dialect: Dialect) -> String {
+    format!(
+        "UPDATE chat_invites SET uses = uses + 1
+         WHERE code = $1 AND NOT revoked AND (expires_at IS NULL OR expires_at > {})
+           AND (max_uses IS NULL OR uses < max_uses)
+         RETURNING chat_id",
+        dialect.now(),
+    )
+}
+
+/// Итог одобрения заявки
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Approval {
+    Approved,
+    /// Заявки нет
+    NotFound,
+    /// Ссылка заявки отозвана, истекла или исчерпана; заявка остаётся
+    InviteInactive,
+}
+
+pub struct InviteRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> InviteRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, invite: &NewInvite<'_>) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "INSERT INTO chat_invites (code, chat_id, created_by, name, expires_at, max_uses, requires_approval)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)"
+            )
+            .bind(invite.code)
+            .bind(invite.chat_id)
+            .bind(invite.created_by)
+            .bind(invite.name)
+            .bind(invite.expires_at)
+            .bind(invite.max_uses)
+            .bind(invite.requires_approval)
+            .execute(pool)
+            .await
+            .map(|_| ())
+        })
+    }
+
+    pub async fn find(&self, code: &str) -> Result<Option<ChatInvite>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {}, {} AS active FROM chat_invites i WHERE i.code = $1",
+            INVITE_COLUMNS,
+            active(self.db.dialect()),
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(code).fetch_optional(pool).await)
+    }
+
+    /// Ссылки чата, новые первыми (вместе с недействующими)
+    pub async fn list(&self, chat_id: &str) -> Result<Vec<ChatInvite>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {}, {} AS active FROM chat_invites i WHERE i.chat_id = $1 ORDER BY i.created_at DESC, i.code",
+            INVITE_COLUMNS,
+            active(self.db.dialect()),
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(chat_id).fetch_all(pool).await)
+    }
+
+    /// Отозвать ссылку; `false` — такой ссылки у чата нет
+    pub async fn revoke(&self, chat_id: &str, code: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE chat_invites SET revoked = TRUE WHERE chat_id = $1 AND code = $2")
+                .bind(chat_id)
+                .bind(code)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Чат по ссылке: название, описание и число участников
+    pub async fn preview(&self, code: &str) -> Result<Option<InvitePreview>, sqlx::Error> {
+        let sql = format!(
+            "SELECT c.id AS chat_id, c.chat_type, c.name, c.description,
+                    (SELECT COUNT(*) FROM chat_members cm
+                     WHERE cm.chat_id = c.id AND (c.owner_id IS NULL OR cm.user_id <> c.owner_id))
+                        + CASE WHEN c.owner_id IS NULL THEN 0 ELSE 1 END AS member_count,
+                    i.requires_approval, {} AS active
+             FROM chat_invites i
+             JOIN chats c ON c.id = i.chat_id
+             WHERE i.code = $1",
+            active(self.db.dialect()),
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(code).fetch_optional(pool).await)
+    }
+
+    /// Вступить по действующей ссылке, засчитав использование. `None` —
+    /// ссылка уже не действует. Проверка блокировок — у вызывающего.
+    pub async fn join(&self, code: &str, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+        let sql = use_invite(self.db.dialect());
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let chat_id: Option<String> = sqlx::query_scalar(&sql).bind(code).fetch_optional(&mut *tx).await?;
+            let Some(chat_id) = chat_id else {
+                return Ok(None);
+            };
+
+            sqlx::query("INSERT INTO chat_members (chat_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(&chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(&chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await?;
+            Ok(Some(chat_id))
+        })
+    }
+
+    /// Подать заявку; повторная заявка не создаёт новую
+    pub async fn request_join(&self, chat_id: &str, user_id: &str, code: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "INSERT INTO chat_join_requests (chat_id, user_id, invite_code) VALUES ($1, $2, $3)
+                 ON CONFLICT DO NOTHING"
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .bind(code)
+            .execute(pool)
+            .await
+            .map(|_| ())
+        })
+    }
+
+    /// Заявки чата, старые первыми
+    pub async fn join_requests(&self, chat_id: &str) -> Result<Vec<JoinRequest>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT r.user_id, u.username, r.invite_code, r.created_at
+                 FROM chat_join_requests r
+                 JOIN users u ON u.id = r.user_id
+                 WHERE r.chat_id = $1
+                 ORDER BY r.created_at ASC, u.username ASC"
+            )
+            .bind(chat_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Одобрить заявку: пользователь становится участником, использование
+    /// засчитывается ссылке, если она ещё действует
+    pub async fn approve(&self, chat_id: &str, user_id: &str) -> Result<Approval, sqlx::Error> {
+        let use_sql = use_invite(self.db.dialect());
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let request: Option<Option<String>> = sqlx::query_scalar(
+                "DELETE FROM chat_join_requests WHERE chat_id = $1 AND user_id = $2 RETURNING invite_code"
+            )
+            .bind(chat_id)
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+            let Some(invite_code) = request else {
+                return Ok(Approval::NotFound);
+            };
+
+            if let Some(invite_code) = invite_code {
+                let used: Option<String> = sqlx::query_scalar(&use_sql)
+                    .bind(invite_code)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                if used.is_none() {
+                    return Ok(Approval::InviteInactive);
+                }
+            }
+
+            sqlx::query("INSERT INTO chat_members (chat_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await?;
+            Ok(Approval::Approved)
+        })
+    }
+
+    /// Отклонить заявку; `false` — заявки нет
+    pub async fn decline(&self, chat_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM chat_join_requests WHERE chat_id = $1 AND user_id = $2")
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+}
@@ -0,0 +1,550 @@
+// server/src/db/messages.rs
+//! Репозиторий сообщений
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::entities::Entities;
+use super::{
+    chats::{touch_chat, touch_chat_for},
+    polls::{self, NewPoll, Poll},
+    scheduled::{ScheduledSend, COMPLETE_SCHEDULED},
+    with_pool, Database,
+};
+
+/// Общий список колонок для `Message` (сообщение `m`). Пост канала
+/// отправлен от имени канала: вместо автора — id чата. Для корня треда —
+/// число ответов и последний ответ. `forward_from` подставляет репозиторий.
+const MESSAGE_COLUMNS: &str =
+    "m.id, m.chat_id, CASE WHEN m.channel_post THEN m.chat_id ELSE m.sender_id END AS sender_id, m.content,
+     m.entities, m.translated_content, m.message_type, m.file_url, m.reply_to_id, m.is_edited, m.is_deleted, m.created_at,
+     m.updated_at, m.author_signature, CASE WHEN m.channel_post THEN m.views END AS views, m.thread_id,
+     (SELECT COUNT(*) FROM messages t WHERE t.thread_id = m.id AND t.is_deleted = FALSE) AS reply_count,
+     (SELECT t.id FROM messages t WHERE t.thread_id = m.id AND t.is_deleted = FALSE
+      ORDER BY t.created_at DESC, t.id DESC LIMIT 1) AS last_reply_id,
+     (SELECT MAX(t.created_at) FROM messages t WHERE t.thread_id = m.id AND t.is_deleted = FALSE) AS last_reply_at,
+     EXISTS (SELECT 1 FROM message_forwards f WHERE f.message_id = m.id) AS forwarded";
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct Message {
+    pub id: String,
+    pub chat_id: String,
+    pub sender_id: String,
+    pub content: String,
+    /// Разметка `content`
+    #[sqlx(try_from = "Option<String>")]
+    #[serde(default, skip_serializing_if = "Entities::is_empty")]
+    pub entities: Entities,
+    pub translated_content: Option<String>,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    pub file_url: Option<String>,
+    pub reply_to_id: Option<String>,
+    pub is_edited: bool,
+    /// Удалённое у всех сообщение остаётся в ленте без содержимого
+    pub is_deleted: bool,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Подпись администратора под постом канала
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author_signature: Option<String>,
+    /// Просмотры поста канала
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub views: Option<i64>,
+    /// Корень треда, в котором это ответ
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    /// Ответы в треде этого сообщения (комментарии — у поста канала)
+    #[serde(default)]
+    pub reply_count: i64,
+    #[serde(default)]
+    pub last_reply_id: Option<String>,
+    #[serde(default)]
+    pub last_reply_at: Option<String>,
+    /// Для своих сообщений в ленте: `sent`, `delivered` или `read`
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    /// Опрос сообщения типа `poll`, глазами читателя
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub poll: Option<Poll>,
+    /// Откуда переслано сообщение
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub forward_from: Option<ForwardFrom>,
+    #[serde(skip)]
+    pub forwarded: bool,
+}
+
+/// Подпись пересланного сообщения: снимок на момент пересылки
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ForwardFrom {
+    /// Ссылка на автора; `None`, если он скрыл её настройкой приватности
+    pub sender_id: Option<String>,
+    pub sender_name: String,
+    /// Канал и пост в нём, если переслан пост канала
+    pub chat_id: Option<String>,
+    pub chat_name: Option<String>,
+    pub message_id: Option<String>,
+    /// Время исходного сообщения
+    pub date: String,
+}
+
+/// Статус сообщения `m` для пользователя `$2`: только для отправителя.
+/// «Прочитано» — если кто-то отправил отчёт, «доставлено» — по указателям.
+const STATUS_COLUMN: &str =
+    "CASE WHEN m.sender_id <> $2 THEN NULL
+          WHEN EXISTS (SELECT 1 FROM message_reads r WHERE r.message_id = m.id AND r.user_id <> m.sender_id) THEN 'read'
+          WHEN EXISTS (SELECT 1 FROM chat_reads d
+                       WHERE d.chat_id = m.chat_id AND d.user_id <> m.sender_id
+                         AND (d.delivered_created_at, d.delivered_id) >= (m.created_at, m.id)) THEN 'delivered'
+          ELSE 'sent' END AS status";
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct MessageEdit {
+    pub id: String,
+    pub editor_id: String,
+    pub previous_content: String,
+    pub edited_at: String,
+}
+
+/// Счётчик просмотров поста канала
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct MessageViews {
+    pub message_id: String,
+    pub views: i64,
+}
+
+/// Данные сообщения, нужные для проверки прав
+#[derive(Debug, sqlx::FromRow)]
+pub struct MessageMeta {
+    pub sender_id: String,
+    pub content: String,
+    pub is_deleted: bool,
+    /// Время отправки, unix-секунды
+    pub sent_at: i64,
+}
+
+pub struct NewMessage<'a> {
+    pub id: &'a str,
+    pub chat_id: &'a str,
+    pub sender_id: &'a str,
+    pub content: &'a str,
+    pub entities: &'a Entities,
+    pub message_type: &'a str,
+    pub file_url: Option<&'a str>,
+    pub reply_to_id: Option<&'a str>,
+    /// Пост канала: публикуется от имени канала
+    pub channel_post: bool,
+    pub author_signature: Option<&'a str>,
+    /// Корень треда, если это ответ в треде
+    pub thread_id: Option<&'a str>,
+    /// Комментарий к посту канала: только в треде, не в ленте
+    pub is_comment: bool,
+    /// Опрос, если сообщение типа `poll`
+    pub poll: Option<&'a NewPoll<'a>>,
+    /// Подпись, если сообщение переслано
+    pub forward_from: Option<&'a ForwardFrom>,
+    /// Отложенное сообщение, которое этой вставкой отправляется
+    pub scheduled: Option<&'a ScheduledSend>,
+}
+
+const CHAT_OF_MESSAGE: &str = "SELECT chat_id FROM messages WHERE id = $1";
+
+/// Какую ленту читать
+#[derive(Debug, Clone, Copy)]
+pub enum Feed<'a> {
+    /// Лента чата, без комментариев к постам канала
+    Chat,
+    /// Ответы в треде сообщения
+    Thread(&'a str),
+}
+
+/// Направление чтения ленты от курсора
+#[derive(Debug, Clone, Copy)]
+pub enum Direction {
+    Older,
+    Newer,
+}
+
+pub struct MessageRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> MessageRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn find(&self, message_id: &str) -> Result<Option<Message>, sqlx::Error> {
+        let sql = format!("SELECT {} FROM messages m WHERE m.id = $1", MESSAGE_COLUMNS);
+        let message: Option<Message> =
+            with_pool!(self.db, pool => sqlx::query_as(&sql).bind(message_id).fetch_optional(pool).await)?;
+        let mut messages: Vec<Message> = message.into_iter().collect();
+        self.attach_forwards(&mut messages).await?;
+        Ok(messages.pop())
+    }
+
+    /// Подставить подписи пересланным сообщениям
+    async fn attach_forwards(&self, messages: &mut [Message]) -> Result<(), sqlx::Error> {
+        for message in messages.iter_mut().filter(|message| message.forwarded) {
+            message.forward_from = with_pool!(self.db, pool => {
+                sqlx::query_as(
+                    "SELECT sender_id, sender_name, chat_id, chat_name, from_message_id AS message_id,
+                            original_date AS date
+                     FROM message_forwards WHERE message_id = $1"
+                )
+                .bind(&message.id)
+                .fetch_optional(pool)
+                .await
+            })?;
+        }
+        Ok(())
+    }
+
+    pub async fn meta(&self, chat_id: &str, message_id: &str) -> Result<Option<MessageMeta>, sqlx::Error> {
+        let sql = format!(
+            "SELECT sender_id, content, is_deleted, {} AS sent_at
+             FROM messages WHERE id = $1 AND chat_id = $2",
+            self.db.dialect().epoch("created_at"),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(message_id)
+                .bind(chat_id)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    /// Выборка до `limit` сообщений ленты `feed` по одну сторону от позиции
+    /// `(created_at, id)` (строго), от неё наружу; без позиции — с края
+    /// ленты. Скрытые пользователем не попадают. Возвращает признак того,
+    /// что за пределами выборки есть ещё сообщения.
+    pub async fn page(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        feed: Feed<'_>,
+        from: Option<(&str, &str)>,
+        direction: Direction,
+        limit: u32,
+    ) -> Result<(Vec<Message>, bool), sqlx::Error> {
+        let (comparison, order) = match direction {
+            Direction::Older => ("<", "DESC"),
+            Direction::Newer => (">", "ASC"),
+        };
+        let feed_filter = match feed {
+            Feed::Chat => "AND m.is_comment = FALSE",
+            Feed::Thread(_) => "AND m.thread_id = $3",
+        };
+        let mut next_param = if matches!(feed, Feed::Thread(_)) { 4 } else { 3 };
+        let cursor_filter = if from.is_some() {
+            next_param += 2;
+            format!("AND (m.created_at, m.id) {} (${}, ${})", comparison, next_param - 2, next_param - 1)
+        } else {
+            String::new()
+        };
+
+        let sql = format!(
+            "SELECT {}, {} FROM messages m
+             WHERE m.chat_id = $1 {}
+               AND NOT EXISTS (SELECT 1 FROM message_hidden h WHERE h.message_id = m.id AND h.user_id = $2)
+               {}
+             ORDER BY m.created_at {order}, m.id {order}
+             LIMIT ${}",
+            MESSAGE_COLUMNS,
+            STATUS_COLUMN,
+            feed_filter,
+            cursor_filter,
+            next_param,
+            order = order,
+        );
+
+        let mut messages: Vec<Message> = with_pool!(self.db, pool => {
+            let mut query = sqlx::query_as(&sql).bind(chat_id).bind(user_id);
+            if let Feed::Thread(thread_id) = feed {
+                query = query.bind(thread_id);
+            }
+            if let Some((created_at, id)) = from {
+                query = query.bind(created_at).bind(id);
+            }
+            query.bind(limit as i64 + 1).fetch_all(pool).await
+        })?;
+
+        let has_more = messages.len() > limit as usize;
+        messages.truncate(limit as usize);
+        self.attach_forwards(&mut messages).await?;
+        Ok((messages, has_more))
+    }
+
+    pub async fn insert(&self, message: &NewMessage<'_>) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO messages
+                     (id, chat_id, sender_id, content, message_type, file_url, reply_to_id, channel_post, author_signature,
+                      thread_id, is_comment, entities)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)"
+            )
+            .bind(message.id)
+            .bind(message.chat_id)
+            .bind(message.sender_id)
+            .bind(message.content)
+            .bind(message.message_type)
+            .bind(message.file_url)
+            .bind(message.reply_to_id)
+            .bind(message.channel_post)
+            .bind(message.author_signature)
+            .bind(message.thread_id)
+            .bind(message.is_comment)
+            .bind(message.entities.to_column())
+            .execute(&mut *tx)
+            .await?;
+
+            if let Some(forward) = message.forward_from {
+                sqlx::query(
+                    "INSERT INTO message_forwards
+                         (message_id, sender_id, sender_name, chat_id, chat_name, from_message_id, original_date)
+                     VALUES ($1, $2, $3, $4, $5, $6, $7)"
+                )
+                .bind(message.id)
+                .bind(forward.sender_id.as_deref())
+                .bind(&forward.sender_name)
+                .bind(forward.chat_id.as_deref())
+                .bind(forward.chat_name.as_deref())
+                .bind(forward.message_id.as_deref())
+                .bind(&forward.date)
+                .execute(&mut *tx)
+                .await?;
+            }
+
+            if let Some(poll) = message.poll {
+                sqlx::query(polls::INSERT_POLL)
+                    .bind(message.id)
+                    .bind(poll.multiple)
+                    .bind(poll.anonymous)
+                    .bind(poll.correct_option)
+                    .bind(poll.explanation)
+                    .bind(poll.closes_at)
+                    .execute(&mut *tx)
+                    .await?;
+                for (position, text) in poll.options.iter().enumerate() {
+                    sqlx::query(polls::INSERT_OPTION)
+                        .bind(message.id)
+                        .bind(position as i64)
+                        .bind(text)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+            }
+
+            // Захват отложенного потерян — сообщения не будет
+            if let Some(scheduled) = message.scheduled {
+                let completed = sqlx::query(COMPLETE_SCHEDULED)
+                    .bind(&scheduled.id)
+                    .bind(&scheduled.claim)
+                    .bind(scheduled.next_send_at.as_deref())
+                    .bind(message.id)
+                    .execute(&mut *tx)
+                    .await?;
+                if completed.rows_affected() == 0 {
+                    return Err(sqlx::Error::RowNotFound);
+                }
+            }
+
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(message.chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Заменить текст и разметку, сохранив прежний текст в `message_edits`
+    pub async fn edit(
+        &self,
+        message_id: &str,
+        editor_id: &str,
+        previous_content: &str,
+        content: &str,
+        entities: &Entities,
+    ) -> Result<(), sqlx::Error> {
+        let update = format!(
+            "UPDATE messages SET content = $1, entities = $2, translated_content = NULL, is_edited = TRUE, updated_at = {}
+             WHERE id = $3",
+            self.db.dialect().now(),
+        );
+
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO message_edits (id, message_id, editor_id, previous_content) VALUES ($1, $2, $3, $4)"
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(message_id)
+            .bind(editor_id)
+            .bind(previous_content)
+            .execute(&mut *tx)
+            .await?;
+
+            sqlx::query(&update)
+                .bind(content)
+                .bind(entities.to_column())
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            let chat_id: String = sqlx::query_scalar(CHAT_OF_MESSAGE).bind(message_id).fetch_one(&mut *tx).await?;
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(&chat_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Скрыть сообщение только у пользователя
+    pub async fn hide(&self, message_id: &str, user_id: &str) -> Result<(), sqlx::Error> {
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(
+                "INSERT INTO message_hidden (message_id, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"
+            )
+            .bind(message_id)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+            let chat_id: String = sqlx::query_scalar(CHAT_OF_MESSAGE).bind(message_id).fetch_one(&mut *tx).await?;
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat_for(dialect)).bind(&chat_id).bind(user_id).execute(&mut *tx).await?;
+
+            tx.commit().await
+        })
+    }
+
+    /// Удалить у всех: стереть содержимое и историю правок, снять закрепление
+    pub async fn delete_for_everyone(&self, message_id: &str) -> Result<(), sqlx::Error> {
+        let update = format!(
+            "UPDATE messages
+             SET content = '', entities = NULL, translated_content = NULL, file_url = NULL,
+                 is_deleted = TRUE, is_pinned = FALSE, updated_at = {}
+             WHERE id = $1",
+            self.db.dialect().now(),
+        );
+
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            sqlx::query(&update).bind(message_id).execute(&mut *tx).await?;
+            let chat_id: String = sqlx::query_scalar(CHAT_OF_MESSAGE).bind(message_id).fetch_one(&mut *tx).await?;
+            sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+            sqlx::query(&touch_chat(dialect)).bind(&chat_id).execute(&mut *tx).await?;
+
+            sqlx::query("DELETE FROM message_edits WHERE message_id = $1")
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("DELETE FROM pinned_messages WHERE message_id = $1")
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            tx.commit().await
+        })
+    }
+
+    pub async fn edits(&self, message_id: &str) -> Result<Vec<MessageEdit>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, editor_id, previous_content, edited_at
+                 FROM message_edits WHERE message_id = $1
+                 ORDER BY edited_at ASC"
+            )
+            .bind(message_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Удалить сообщения с истёкшим `delete_at` (24h и самоуничтожение)
+    pub async fn delete_expired(&self) -> Result<u64, sqlx::Error> {
+        let expired = format!("delete_at IS NOT NULL AND delete_at < {}", self.db.dialect().now());
+        // Чаты по порядку: версии чатов блокируются всегда в одном порядке
+        let chats = format!("SELECT DISTINCT chat_id FROM messages WHERE {} ORDER BY chat_id", expired);
+        let delete = format!("DELETE FROM messages WHERE {}", expired);
+        let dialect = self.db.dialect();
+        let touch = touch_chat(dialect);
+
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let chat_ids: Vec<String> = sqlx::query_scalar(&chats).fetch_all(&mut *tx).await?;
+            let deleted = sqlx::query(&delete).execute(&mut *tx).await?.rows_affected();
+
+            if !chat_ids.is_empty() {
+                sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+                for chat_id in &chat_ids {
+                    sqlx::query(&touch).bind(chat_id).execute(&mut *tx).await?;
+                }
+            }
+
+            tx.commit().await?;
+            Ok(deleted)
+        })
+    }
+
+    /// Засчитать просмотры постов канала: каждому пользователю — один раз
+    /// на пост. Сообщения не из канала `chat_id` пропускаются.
+    pub async fn record_views(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        message_ids: &[String],
+    ) -> Result<Vec<MessageViews>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+            let mut counts = Vec::with_capacity(message_ids.len());
+
+            for message_id in message_ids {
+                let viewed = sqlx::query(
+                    "INSERT INTO message_views (message_id, user_id)
+                     SELECT id, $3 FROM messages WHERE id = $1 AND chat_id = $2 AND channel_post
+                     ON CONFLICT DO NOTHING"
+                )
+                .bind(message_id)
+                .bind(chat_id)
+                .bind(user_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+
+                let views: Option<i64> = if viewed > 0 {
+                    sqlx::query_scalar("UPDATE messages SET views = views + 1 WHERE id = $1 RETURNING views")
+                        .bind(message_id)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                } else {
+                    sqlx::query_scalar("SELECT views FROM messages WHERE id = $1 AND chat_id = $2 AND channel_post")
+                        .bind(message_id)
+                        .bind(chat_id)
+                        .fetch_optional(&mut *tx)
+                        .await?
+                };
+                if let Some(views) = views {
+                    counts.push(MessageViews { message_id: message_id.clone(), views });
+                }
+            }
+
+            tx.commit().await?;
+            Ok(counts)
+        })
+    }
+}
@@ -0,0 +1,402 @@
+// server/src/db/mod.rs
+//! База данных SQLite/PostgreSQL
+//!
+//! Бэкенд выбирается по схеме `DATABASE_URL` (`sqlite:` или `postgres:`).
+//! Запросы собраны в репозиториях (`db.messages()`, `db.chats()`, ...):
+//! текст SQL общий для обоих диалектов (плейсхолдеры `$N`), отличия
+//! оформлены через `Dialect`. Схема — нумерованные миграции в
+//! `migrations/{sqlite,postgres}`: изменение схемы — новый файл `NNNN_*.sql`
+//! в обоих каталогах, уже применённые файлы не редактируются.
+
+pub mod chats;
+pub mod contacts;
+pub mod extra;
+pub mod features;
+pub mod files;
+pub mod invites;
+pub mod messages;
+pub mod nodes;
+pub mod polls;
+pub mod push;
+pub mod reads;
+pub mod scheduled;
+pub mod search;
+pub mod threads;
+pub mod uploads;
+pub mod users;
+
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migrator},
+    postgres::{PgPool, PgPoolOptions},
+    sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions},
+};
+use std::{fmt, str::FromStr, sync::Arc};
+
+pub type DbPool = Arc<Database>;
+
+static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
+/// Пул соединений выбранного бэкенда
+pub enum Database {
+    Sqlite(SqlitePool),
+    Postgres(PgPool),
+}
+
+/// Диалект SQL для фрагментов, которые различаются между бэкендами
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dialect {
+    Sqlite,
+    Postgres,
+}
+
+/// Выполняет выражение с пулом конкретного бэкенда. Тело раскрывается
+/// для каждого варианта, поэтому один и тот же код запроса типизируется
+/// и под SQLite, и под PostgreSQL.
+macro_rules! with_pool {
+    ($db:expr, $pool:ident => $body:expr) => {
+        match $db {
+            $crate::db::Database::Sqlite($pool) => $body,
+            $crate::db::Database::Postgres($pool) => $body,
+        }
+    };
+}
+pub(crate) use with_pool;
+
+impl Dialect {
+    /// Текущее время UTC в формате колонок-дат (`YYYY-MM-DD HH:MM:SS`)
+    pub fn now(self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "CURRENT_TIMESTAMP",
+            Dialect::Postgres => "utc_now_text()",
+        }
+    }
+
+    /// Unix-время (BIGINT) из колонки-даты
+    pub fn epoch(self, column: &str) -> String {
+        match self {
+            Dialect::Sqlite => format!("CAST(strftime('%s', {}) AS BIGINT)", column),
+            Dialect::Postgres => format!("CAST(EXTRACT(EPOCH FROM CAST({} AS TIMESTAMP)) AS BIGINT)", column),
+        }
+    }
+
+    /// Подготовить версию изменений транзакции (см. `change_version`).
+    /// SQLite пишет по одному, и общий счётчик ничего не сериализует;
+    /// в PostgreSQL версия — номер транзакции, общей строки нет.
+    pub fn next_version(self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "UPDATE sync_state SET version = version + 1 WHERE id = 1",
+            Dialect::Postgres => "SELECT txid_current()",
+        }
+    }
+
+    /// Версия изменений текущей транзакции
+    pub fn change_version(self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "(SELECT version FROM sync_state WHERE id = 1)",
+            Dialect::Postgres => "txid_current()",
+        }
+    }
+
+    /// Курсор синхронизации: изменения с версией меньше него уже видны
+    /// и новых таких не появится. В PostgreSQL это `xmin` снимка — самая
+    /// старая незавершённая транзакция; позже закоммиченные придут в
+    /// следующей синхронизации.
+    pub fn sync_version(self) -> &'static str {
+        match self {
+            Dialect::Sqlite => "SELECT version + 1 FROM sync_state WHERE id = 1",
+            Dialect::Postgres => "SELECT CAST(txid_snapshot_xmin(txid_current_snapshot()) AS BIGINT)",
+        }
+    }
+
+    /// Строки группы через запятую
+    pub fn group_concat(self, column: &str) -> String {
+        match self {
+            Dialect::Sqlite => format!("GROUP_CONCAT({})", column),
+            Dialect::Postgres => format!("string_agg({}, ',')", column),
+        }
+    }
+}
+
+/// Состояние базы относительно миграций, вшитых в бинарник
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SchemaStatus {
+    /// Версии, ещё не применённые к базе
+    pub pending: Vec<i64>,
+    /// Применённые версии, текст которых изменился после применения
+    pub modified: Vec<i64>,
+    /// Применённые версии, которых нет в бинарнике (база новее сервера)
+    pub unknown: Vec<i64>,
+    /// Версия, миграция которой оборвалась на середине
+    pub dirty: Option<i64>,
+}
+
+impl SchemaStatus {
+    pub fn is_current(&self) -> bool {
+        *self == SchemaStatus::default()
+    }
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_current() {
+            return write!(f, "схема актуальна");
+        }
+        let mut problems = Vec::new();
+        if let Some(version) = self.dirty {
+            problems.push(format!("миграция {} не завершена", version));
+        }
+        for (label, versions) in [
+            ("не применены", &self.pending),
+            ("изменены после применения", &self.modified),
+            ("неизвестны серверу", &self.unknown),
+        ] {
+            if !versions.is_empty() {
+                let list: Vec<String> = versions.iter().map(i64::to_string).collect();
+                problems.push(format!("{}: {}", label, list.join(", ")));
+            }
+        }
+        write!(f, "{}", problems.join("; "))
+    }
+}
+
+/// Дата в формате колонок-дат
+pub fn timestamp(at: chrono::DateTime<chrono::Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S").to_string()
+}
+
+impl Database {
+    /// Подключение по URL; бэкенд определяется схемой
+    pub async fn connect(url: &str) -> anyhow::Result<Self> {
+        if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            let pool = PgPoolOptions::new()
+                .max_connections(10)
+                .connect(url)
+                .await?;
+            return Ok(Database::Postgres(pool));
+        }
+
+        if !url.starts_with("sqlite:") {
+            anyhow::bail!("Неподдерживаемый DATABASE_URL: ожидается sqlite: или postgres:");
+        }
+
+        let options = SqliteConnectOptions::from_str(url)?
+            .create_if_missing(true)
+            .foreign_keys(true);
+
+        // У каждого соединения с `:memory:` своя база — держим ровно одно
+        let pool = if url.contains(":memory:") || url.contains("mode=memory") {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None)
+                .connect_with(options)
+                .await?
+        } else {
+            SqlitePoolOptions::new()
+                .max_connections(10)
+                .min_connections(2)
+                .connect_with(options)
+                .await?
+        };
+        Ok(Database::Sqlite(pool))
+    }
+
+    pub fn dialect(&self) -> Dialect {
+        match self {
+            Database::Sqlite(_) => Dialect::Sqlite,
+            Database::Postgres(_) => Dialect::Postgres,
+        }
+    }
+
+    /// Применить миграции своего диалекта
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        match self {
+            Database::Sqlite(pool) => SQLITE_MIGRATOR.run(pool).await,
+            Database::Postgres(pool) => POSTGRES_MIGRATOR.run(pool).await,
+        }
+    }
+
+    /// Сверить применённые миграции с вшитыми, ничего не меняя в базе
+    pub async fn schema_status(&self) -> Result<SchemaStatus, MigrateError> {
+        let table_exists = match self.dialect() {
+            Dialect::Sqlite => {
+                "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'"
+            }
+            Dialect::Postgres => {
+                "SELECT COUNT(*) FROM information_schema.tables
+                 WHERE table_schema = current_schema() AND table_name = '_sqlx_migrations'"
+            }
+        };
+        let tracked: i64 = with_pool!(self, pool => {
+            sqlx::query_scalar(table_exists).fetch_one(pool).await?
+        });
+
+        match self {
+            Database::Sqlite(pool) => {
+                let mut conn = pool.acquire().await?;
+                compare_migrations(&SQLITE_MIGRATOR, &mut *conn, tracked > 0).await
+            }
+            Database::Postgres(pool) => {
+                let mut conn = pool.acquire().await?;
+                compare_migrations(&POSTGRES_MIGRATOR, &mut *conn, tracked > 0).await
+            }
+        }
+    }
+
+    pub async fn close(&self) {
+        with_pool!(self, pool => pool.close().await)
+    }
+
+    pub fn users(&self) -> users::UserRepository<'_> {
+        users::UserRepository::new(self)
+    }
+
+    pub fn chats(&self) -> chats::ChatRepository<'_> {
+        chats::ChatRepository::new(self)
+    }
+
+    pub fn contacts(&self) -> contacts::ContactRepository<'_> {
+        contacts::ContactRepository::new(self)
+    }
+
+    pub fn invites(&self) -> invites::InviteRepository<'_> {
+        invites::InviteRepository::new(self)
+    }
+
+    pub fn messages(&self) -> messages::MessageRepository<'_> {
+        messages::MessageRepository::new(self)
+    }
+
+    pub fn reads(&self) -> reads::ReadRepository<'_> {
+        reads::ReadRepository::new(self)
+    }
+
+    pub fn files(&self) -> files::FileRepository<'_> {
+        files::FileRepository::new(self)
+    }
+
+    pub fn uploads(&self) -> uploads::UploadRepository<'_> {
+        uploads::UploadRepository::new(self)
+    }
+
+    pub fn nodes(&self) -> nodes::NodeRepository<'_> {
+        nodes::NodeRepository::new(self)
+    }
+
+    pub fn features(&self) -> features::FeatureRepository<'_> {
+        features::FeatureRepository::new(self)
+    }
+
+    pub fn extra(&self) -> extra::ExtraRepository<'_> {
+        extra::ExtraRepository::new(self)
+    }
+
+    pub fn polls(&self) -> polls::PollRepository<'_> {
+        polls::PollRepository::new(self)
+    }
+
+    pub fn push(&self) -> push::PushRepository<'_> {
+        push::PushRepository::new(self)
+    }
+
+    pub fn scheduled(&self) -> scheduled::ScheduledRepository<'_> {
+        scheduled::ScheduledRepository::new(self)
+    }
+
+    pub fn threads(&self) -> threads::ThreadRepository<'_> {
+        threads::ThreadRepository::new(self)
+    }
+
+    pub fn search(&self) -> search::SearchRepository<'_> {
+        search::SearchRepository::new(self)
+    }
+}
+
+async fn compare_migrations<C: Migrate>(
+    migrator: &Migrator,
+    conn: &mut C,
+    tracked: bool,
+) -> Result<SchemaStatus, MigrateError> {
+    let (applied, dirty) = if tracked {
+        (conn.list_applied_migrations().await?, conn.dirty_version().await?)
+    } else {
+        (Vec::new(), None)
+    };
+
+    let mut status = SchemaStatus { dirty, ..Default::default() };
+    for migration in migrator.iter().filter(|m| !m.migration_type.is_down_migration()) {
+        match applied.iter().find(|a| a.version == migration.version) {
+            None => status.pending.push(migration.version),
+            Some(a) if a.checksum != migration.checksum => status.modified.push(migration.version),
+            Some(_) => {}
+        }
+    }
+    status.unknown = applied
+        .iter()
+        .map(|a| a.version)
+        .filter(|version| !migrator.iter().any(|m| m.version == *version))
+        .collect();
+
+    Ok(status)
+}
+
+/// `DATABASE_URL` или локальный файл SQLite
+pub fn database_url() -> String {
+    std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:./liberty_reach.db".to_string())
+}
+
+/// Инициализация базы данных из `DATABASE_URL` с применением миграций
+pub async fn init_database() -> anyhow::Result<DbPool> {
+    let db = Database::connect(&database_url()).await?;
+    db.migrate().await?;
+
+    tracing::info!("База данных готова ({:?})", db.dialect());
+
+    Ok(Arc::new(db))
+}
+
+/// Получить пул соединений
+/// Примечание: Используйте пул из AppState вместо этой функции
+#[deprecated(note = "Используйте pool из AppState")]
+pub fn get_pool() -> DbPool {
+    panic!("get_pool() устарела. Используйте pool из AppState.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timestamp_format_matches_columns() {
+        let at = chrono::DateTime::parse_from_rfc3339("2024-03-01T12:05:09+03:00")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        assert_eq!(timestamp(at), "2024-03-01 09:05:09");
+    }
+
+    #[tokio::test]
+    async fn test_schema_status_tracks_migrations() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+
+        let status = db.schema_status().await.unwrap();
+        assert_eq!(status.pending.len(), SQLITE_MIGRATOR.iter().count());
+        assert!(!status.is_current());
+
+        db.migrate().await.unwrap();
+        let status = db.schema_status().await.unwrap();
+        assert!(status.is_current(), "{}", status);
+    }
+
+    #[test]
+    fn test_schema_status_display() {
+        let status = SchemaStatus { pending: vec![2, 3], dirty: Some(1), ..Default::default() };
+        assert_eq!(status.to_string(), "миграция 1 не завершена; не применены: 2, 3");
+        assert_eq!(SchemaStatus::default().to_string(), "схема актуальна");
+    }
+
+    #[tokio::test]
+    async fn test_connect_rejects_unknown_scheme() {
+        assert!(Database::connect("mysql://localhost/db").await.is_err());
+    }
+}
@@ -0,0 +1,117 @@
+// server/src/db/nodes.rs
+//! Репозиторий P2P нод
+
+use super::{with_pool, Database};
+
+/// Строка `peer_nodes`: списки хранятся JSON-строками
+#[derive(Debug, sqlx::FromRow)]
+pub struct PeerNodeRecord {
+    pub id: String,
+    pub user_id: String,
+    pub username: String,
+    pub public_key: String,
+    pub peer_id: String,
+    pub multiaddr: Option<String>,
+    pub status: String,
+    pub joined_at: String,
+    pub last_seen: String,
+    pub version: String,
+    pub capabilities: Option<String>,
+}
+
+pub struct NewPeerNode<'a> {
+    pub id: &'a str,
+    pub user_id: &'a str,
+    pub username: &'a str,
+    pub public_key: &'a str,
+    pub peer_id: &'a str,
+    pub multiaddr: &'a str,
+    pub version: &'a str,
+    pub capabilities: &'a str,
+}
+
+const NODE_COLUMNS: &str = "id, user_id, username, public_key, peer_id, multiaddr,
+    COALESCE(status, 'offline') AS status, joined_at, last_seen,
+    COALESCE(version, '1.0.0') AS version, capabilities";
+
+pub struct NodeRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> NodeRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn find_by_user(&self, user_id: &str) -> Result<Option<PeerNodeRecord>, sqlx::Error> {
+        let sql = format!("SELECT {} FROM peer_nodes WHERE user_id = $1", NODE_COLUMNS);
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(user_id).fetch_optional(pool).await)
+    }
+
+    pub async fn list(&self) -> Result<Vec<PeerNodeRecord>, sqlx::Error> {
+        let sql = format!("SELECT {} FROM peer_nodes ORDER BY last_seen DESC", NODE_COLUMNS);
+        with_pool!(self.db, pool => sqlx::query_as(&sql).fetch_all(pool).await)
+    }
+
+    pub async fn insert(&self, node: &NewPeerNode<'_>) -> Result<(), sqlx::Error> {
+        let now = self.db.dialect().now();
+        let sql = format!(
+            "INSERT INTO peer_nodes
+             (id, user_id, username, public_key, peer_id, multiaddr, status, joined_at, last_seen, version, capabilities)
+             VALUES ($1, $2, $3, $4, $5, $6, 'online', {now}, {now}, $7, $8)",
+            now = now,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(node.id)
+                .bind(node.user_id)
+                .bind(node.username)
+                .bind(node.public_key)
+                .bind(node.peer_id)
+                .bind(node.multiaddr)
+                .bind(node.version)
+                .bind(node.capabilities)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Обновить адреса и версию уже зарегистрированной ноды
+    pub async fn refresh(
+        &self,
+        user_id: &str,
+        multiaddr: &str,
+        version: &str,
+        capabilities: &str,
+    ) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "UPDATE peer_nodes SET
+             status = 'online',
+             last_seen = {},
+             multiaddr = $1,
+             version = $2,
+             capabilities = $3
+             WHERE user_id = $4",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(multiaddr)
+                .bind(version)
+                .bind(capabilities)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    pub async fn heartbeat(&self, user_id: &str) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "UPDATE peer_nodes SET last_seen = {}, status = 'online' WHERE user_id = $1",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query(&sql).bind(user_id).execute(pool).await.map(|_| ()))
+    }
+}
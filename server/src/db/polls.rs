@@ -0,0 +1,252 @@
+// server/src/db/polls.rs
+//! Репозиторий опросов и викторин
+//!
+//! Опрос создаётся вместе с сообщением (`MessageRepository::insert`).
+//! Голос — набор номеров вариантов; переголосовать можно только отозвав
+//! голос, в викторине голос окончательный.
+
+use serde::{Deserialize, Serialize};
+use super::{with_pool, Database};
+
+/// Опрос глазами пользователя
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Poll {
+    pub options: Vec<PollOption>,
+    pub multiple: bool,
+    pub anonymous: bool,
+    pub quiz: bool,
+    pub closes_at: Option<String>,
+    pub closed: bool,
+    /// Сколько пользователей проголосовало
+    pub total_voters: i64,
+    /// Варианты, выбранные пользователем
+    pub chosen: Vec<i64>,
+    /// Правильный вариант викторины: после ответа или закрытия
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correct_option: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PollOption {
+    pub text: String,
+    pub votes: i64,
+    /// Проголосовавшие: только в открытом (не анонимном) опросе
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub voters: Option<Vec<String>>,
+}
+
+impl Poll {
+    /// Без выбора и ответа конкретного пользователя: для рассылки всем
+    pub fn for_everyone(mut self) -> Self {
+        self.chosen.clear();
+        if !self.closed {
+            self.correct_option = None;
+            self.explanation = None;
+        }
+        self
+    }
+}
+
+pub struct NewPoll<'a> {
+    pub options: &'a [String],
+    pub multiple: bool,
+    pub anonymous: bool,
+    /// Есть — викторина
+    pub correct_option: Option<i64>,
+    pub explanation: Option<&'a str>,
+    pub closes_at: Option<&'a str>,
+}
+
+pub(super) const INSERT_POLL: &str =
+    "INSERT INTO polls (message_id, multiple, anonymous, correct_option, explanation, closes_at)
+     VALUES ($1, $2, $3, $4, $5, $6)";
+
+pub(super) const INSERT_OPTION: &str = "INSERT INTO poll_options (message_id, position, text) VALUES ($1, $2, $3)";
+
+/// Настройки опроса и его состояние
+#[derive(Debug, sqlx::FromRow)]
+pub struct PollSettings {
+    pub chat_id: String,
+    /// Настоящий автор сообщения
+    pub sender_id: String,
+    pub is_deleted: bool,
+    pub multiple: bool,
+    pub anonymous: bool,
+    pub correct_option: Option<i64>,
+    pub explanation: Option<String>,
+    pub closes_at: Option<String>,
+    pub closed: bool,
+    pub option_count: i64,
+}
+
+impl PollSettings {
+    pub fn quiz(&self) -> bool {
+        self.correct_option.is_some()
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct OptionRow {
+    text: String,
+    votes: i64,
+}
+
+pub struct PollRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> PollRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn settings(&self, message_id: &str) -> Result<Option<PollSettings>, sqlx::Error> {
+        let sql = format!(
+            "SELECT m.chat_id, m.sender_id, m.is_deleted, p.multiple, p.anonymous, p.correct_option, p.explanation, p.closes_at,
+                    p.closed OR (p.closes_at IS NOT NULL AND p.closes_at <= {}) AS closed,
+                    (SELECT COUNT(*) FROM poll_options o WHERE o.message_id = p.message_id) AS option_count
+             FROM polls p
+             JOIN messages m ON m.id = p.message_id
+             WHERE p.message_id = $1",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(message_id).fetch_optional(pool).await)
+    }
+
+    /// Опрос для пользователя `viewer`: его выбор и, если он ответил или
+    /// создал викторину, правильный вариант
+    pub async fn load(&self, message_id: &str, viewer: &str) -> Result<Option<Poll>, sqlx::Error> {
+        let Some(settings) = self.settings(message_id).await? else {
+            return Ok(None);
+        };
+
+        let options: Vec<OptionRow> = with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT o.text,
+                        (SELECT COUNT(*) FROM poll_votes v WHERE v.message_id = o.message_id AND v.position = o.position)
+                            AS votes
+                 FROM poll_options o WHERE o.message_id = $1
+                 ORDER BY o.position"
+            )
+            .bind(message_id)
+            .fetch_all(pool)
+            .await
+        })?;
+        let total_voters: i64 = with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT COUNT(DISTINCT user_id) FROM poll_votes WHERE message_id = $1")
+                .bind(message_id)
+                .fetch_one(pool)
+                .await
+        })?;
+        let chosen: Vec<i64> = with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT position FROM poll_votes WHERE message_id = $1 AND user_id = $2 ORDER BY position")
+                .bind(message_id)
+                .bind(viewer)
+                .fetch_all(pool)
+                .await
+        })?;
+        let votes: Vec<(String, i64)> = if settings.anonymous {
+            Vec::new()
+        } else {
+            with_pool!(self.db, pool => {
+                sqlx::query_as("SELECT user_id, position FROM poll_votes WHERE message_id = $1 ORDER BY voted_at, user_id")
+                    .bind(message_id)
+                    .fetch_all(pool)
+                    .await
+            })?
+        };
+
+        let reveal = settings.closed || !chosen.is_empty() || settings.sender_id == viewer;
+        let options = options
+            .into_iter()
+            .enumerate()
+            .map(|(position, option)| PollOption {
+                text: option.text,
+                votes: option.votes,
+                voters: (!settings.anonymous).then(|| {
+                    votes
+                        .iter()
+                        .filter(|(_, voted)| *voted == position as i64)
+                        .map(|(user_id, _)| user_id.clone())
+                        .collect()
+                }),
+            })
+            .collect();
+
+        Ok(Some(Poll {
+            options,
+            multiple: settings.multiple,
+            anonymous: settings.anonymous,
+            quiz: settings.quiz(),
+            closes_at: settings.closes_at,
+            closed: settings.closed,
+            total_voters,
+            chosen,
+            correct_option: if reveal { settings.correct_option } else { None },
+            explanation: if reveal { settings.explanation } else { None },
+        }))
+    }
+
+    /// Проголосовать за варианты `positions`. `false` — пользователь уже
+    /// голосовал. Проверки закрытия и номеров — у вызывающего.
+    pub async fn vote(&self, message_id: &str, user_id: &str, positions: &[i64]) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            // Пустое обновление блокирует опрос до конца транзакции: иначе
+            // параллельные голоса одного пользователя прошли бы проверку оба
+            sqlx::query("UPDATE polls SET closed = closed WHERE message_id = $1")
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?;
+
+            let voted: bool = sqlx::query_scalar(
+                "SELECT EXISTS (SELECT 1 FROM poll_votes WHERE message_id = $1 AND user_id = $2)"
+            )
+            .bind(message_id)
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+            if voted {
+                return Ok(false);
+            }
+
+            for position in positions {
+                sqlx::query("INSERT INTO poll_votes (message_id, user_id, position) VALUES ($1, $2, $3)")
+                    .bind(message_id)
+                    .bind(user_id)
+                    .bind(position)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            tx.commit().await?;
+            Ok(true)
+        })
+    }
+
+    /// Отозвать голос; `false` — пользователь не голосовал
+    pub async fn retract(&self, message_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM poll_votes WHERE message_id = $1 AND user_id = $2")
+                .bind(message_id)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Закрыть опрос досрочно
+    pub async fn close(&self, message_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE polls SET closed = TRUE WHERE message_id = $1")
+                .bind(message_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+}
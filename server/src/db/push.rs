@@ -0,0 +1,160 @@
+// server/src/db/push.rs
+//! Репозиторий устройств для push-уведомлений
+
+use serde::Serialize;
+use super::{with_pool, Database};
+
+/// Устройство пользователя; ключи наружу не отдаются
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct PushDevice {
+    pub id: String,
+    #[serde(skip)]
+    pub user_id: String,
+    /// `webpush`, `unifiedpush` или `fcm`
+    pub provider: String,
+    pub endpoint: String,
+    #[serde(skip)]
+    pub p256dh: Option<String>,
+    #[serde(skip)]
+    pub auth: Option<String>,
+    #[serde(skip)]
+    pub hint_key: String,
+    pub created_at: String,
+}
+
+/// Устройство, на которое нужно отправить уведомление о сообщении
+#[derive(Debug, sqlx::FromRow)]
+pub struct PushTarget {
+    #[sqlx(flatten)]
+    pub device: PushDevice,
+    /// Владельца упомянули в сообщении (или ответили ему)
+    pub mentioned: bool,
+}
+
+pub struct NewPushDevice<'a> {
+    pub id: &'a str,
+    pub user_id: &'a str,
+    pub provider: &'a str,
+    pub endpoint: &'a str,
+    pub p256dh: Option<&'a str>,
+    pub auth: Option<&'a str>,
+    pub hint_key: &'a str,
+}
+
+const DEVICE_COLUMNS: &str = "id, user_id, provider, endpoint, p256dh, auth, hint_key, created_at";
+
+pub struct PushRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> PushRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Зарегистрировать устройство. Тот же `endpoint` повторно — обновить
+    /// ключи и владельца (на устройстве вошёл другой пользователь).
+    pub async fn register(&self, device: &NewPushDevice<'_>) -> Result<PushDevice, sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO push_devices (id, user_id, provider, endpoint, p256dh, auth, hint_key)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT (endpoint) DO UPDATE SET
+                 user_id = excluded.user_id,
+                 provider = excluded.provider,
+                 p256dh = excluded.p256dh,
+                 auth = excluded.auth,
+                 hint_key = excluded.hint_key
+             RETURNING {}",
+            DEVICE_COLUMNS,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(device.id)
+                .bind(device.user_id)
+                .bind(device.provider)
+                .bind(device.endpoint)
+                .bind(device.p256dh)
+                .bind(device.auth)
+                .bind(device.hint_key)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    pub async fn devices(&self, user_id: &str) -> Result<Vec<PushDevice>, sqlx::Error> {
+        let sql = format!("SELECT {} FROM push_devices WHERE user_id = $1 ORDER BY created_at, id", DEVICE_COLUMNS);
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(user_id)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Удалить устройство пользователя; `false` — такого нет
+    pub async fn unregister(&self, user_id: &str, device_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM push_devices WHERE id = $1 AND user_id = $2")
+                .bind(device_id)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Удалить устройство, подписка которого больше не действует
+    pub async fn remove(&self, device_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM push_devices WHERE id = $1")
+                .bind(device_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Устройства участников чата (кроме автора) для уведомления
+    /// о сообщении, по id после `after`. Чат без звука (`muted` или
+    /// `muted_until` в будущем) уведомляет только об упоминаниях, если они
+    /// не выключены; с `mentions_only` — только упомянутых.
+    pub async fn targets(
+        &self,
+        chat_id: &str,
+        message_id: &str,
+        sender_id: &str,
+        mentions_only: bool,
+        after: &str,
+        limit: i64,
+    ) -> Result<Vec<PushTarget>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {columns}, mentioned FROM (
+                SELECT d.*,
+                       EXISTS (SELECT 1 FROM message_mentions mm WHERE mm.message_id = $2 AND mm.user_id = d.user_id) AS mentioned,
+                       COALESCE(s.muted, FALSE) OR COALESCE(s.muted_until > {now}, FALSE) AS muted,
+                       COALESCE(s.mute_mentions, FALSE) AS mute_mentions
+                FROM push_devices d
+                LEFT JOIN chat_user_settings s ON s.chat_id = $1 AND s.user_id = d.user_id
+                WHERE d.id > $4 AND d.user_id <> $3
+                  AND (EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = $1 AND cm.user_id = d.user_id)
+                       OR EXISTS (SELECT 1 FROM chats c WHERE c.id = $1 AND c.owner_id = d.user_id))
+             ) t
+             WHERE (mentioned AND NOT mute_mentions) OR (NOT $5 AND NOT muted)
+             ORDER BY id
+             LIMIT $6",
+            columns = DEVICE_COLUMNS,
+            now = self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(chat_id)
+                .bind(message_id)
+                .bind(sender_id)
+                .bind(after)
+                .bind(mentions_only)
+                .bind(limit)
+                .fetch_all(pool)
+                .await
+        })
+    }
+}
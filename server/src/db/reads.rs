@@ -0,0 +1,277 @@
+// server/src/db/reads.rs
+//! Репозиторий доставки и прочтения
+//!
+//! У каждого участника в чате два указателя — «доставлено до» и
+//! «прочитано до» — на позицию `(created_at, id)` сообщения. Указатели
+//! двигаются только вперёд. Непрочитанные считаются по указателю, а кто и
+//! когда прочитал конкретное сообщение, хранит `message_reads`.
+
+use serde::Serialize;
+use super::{chats::touch_chat_for, with_pool, Database};
+
+/// Непрочитанные пользователем `$1` сообщения чата `c.id` при указателе `r`
+/// из `chat_reads`: чужие, не удалённые, не скрытые им и не комментарии
+/// (их считают треды)
+pub(super) const UNREAD_MESSAGES: &str =
+    "FROM messages m
+     WHERE m.chat_id = c.id AND m.sender_id <> $1 AND m.is_deleted = FALSE AND m.is_comment = FALSE
+       AND (r.read_id IS NULL OR (m.created_at, m.id) > (r.read_created_at, r.read_id))
+       AND NOT EXISTS (SELECT 1 FROM message_hidden h WHERE h.message_id = m.id AND h.user_id = $1)";
+
+/// Среди непрочитанных — упоминающие пользователя `$1` или отвечающие ему
+pub(super) const MENTIONS_USER: &str =
+    "AND EXISTS (SELECT 1 FROM message_mentions mm WHERE mm.message_id = m.id AND mm.user_id = $1)";
+
+#[derive(Debug, Default, Serialize, sqlx::FromRow)]
+pub struct UnreadCounts {
+    /// Чужие сообщения после «прочитано до»
+    pub unread_count: i64,
+    /// Из них упоминающие пользователя или отвечающие ему
+    pub unread_mentions: i64,
+}
+
+/// Кто и когда прочитал сообщение
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct MessageRead {
+    pub user_id: String,
+    pub username: String,
+    pub read_at: String,
+}
+
+pub struct ReadRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> ReadRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Сдвинуть «прочитано до» (и «доставлено до») к `message_id`.
+    /// С `receipts` прочтение каждого ещё не прочитанного чужого сообщения
+    /// записывается в `message_reads`. `None` — сообщения нет в этом чате,
+    /// `Some(false)` — указатель уже стоял не левее.
+    pub async fn mark_read(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        message_id: &str,
+        receipts: bool,
+    ) -> Result<Option<bool>, sqlx::Error> {
+        let now = self.db.dialect().now();
+        let advance = format!(
+            "INSERT INTO chat_reads (chat_id, user_id, read_created_at, read_id, delivered_created_at, delivered_id, updated_at)
+             VALUES ($1, $2, $3, $4, $3, $4, {now})
+             ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                 read_created_at = $3,
+                 read_id = $4,
+                 delivered_created_at = CASE
+                     WHEN chat_reads.delivered_id IS NULL OR (chat_reads.delivered_created_at, chat_reads.delivered_id) < ($3, $4)
+                     THEN $3 ELSE chat_reads.delivered_created_at END,
+                 delivered_id = CASE
+                     WHEN chat_reads.delivered_id IS NULL OR (chat_reads.delivered_created_at, chat_reads.delivered_id) < ($3, $4)
+                     THEN $4 ELSE chat_reads.delivered_id END,
+                 updated_at = {now}
+             WHERE chat_reads.read_id IS NULL OR (chat_reads.read_created_at, chat_reads.read_id) < ($3, $4)",
+        );
+
+        let dialect = self.db.dialect();
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let target: Option<(String, String)> =
+                sqlx::query_as("SELECT created_at, id FROM messages WHERE id = $1 AND chat_id = $2")
+                    .bind(message_id)
+                    .bind(chat_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let Some((created_at, id)) = target else {
+                return Ok(None);
+            };
+
+            if receipts {
+                let previous: Option<(Option<String>, Option<String>)> =
+                    sqlx::query_as("SELECT read_created_at, read_id FROM chat_reads WHERE chat_id = $1 AND user_id = $2")
+                        .bind(chat_id)
+                        .bind(user_id)
+                        .fetch_optional(&mut *tx)
+                        .await?;
+                let previous = previous.and_then(|(created_at, id)| Some((created_at?, id?)));
+
+                let sql = format!(
+                    "INSERT INTO message_reads (message_id, user_id, read_at)
+                     SELECT m.id, $2, {now} FROM messages m
+                     WHERE m.chat_id = $1 AND m.sender_id <> $2 AND m.is_deleted = FALSE
+                       AND (m.created_at, m.id) <= ($3, $4) {}
+                     ON CONFLICT DO NOTHING",
+                    if previous.is_some() { "AND (m.created_at, m.id) > ($5, $6)" } else { "" },
+                );
+                let mut query = sqlx::query(&sql).bind(chat_id).bind(user_id).bind(&created_at).bind(&id);
+                if let Some((read_created_at, read_id)) = &previous {
+                    query = query.bind(read_created_at).bind(read_id);
+                }
+                query.execute(&mut *tx).await?;
+            }
+
+            let advanced = sqlx::query(&advance)
+                .bind(chat_id)
+                .bind(user_id)
+                .bind(&created_at)
+                .bind(&id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            // Изменились счётчики в списке чатов
+            if advanced {
+                sqlx::query(dialect.next_version()).execute(&mut *tx).await?;
+                sqlx::query(&touch_chat_for(dialect)).bind(chat_id).bind(user_id).execute(&mut *tx).await?;
+            }
+
+            tx.commit().await?;
+            Ok(Some(advanced))
+        })
+    }
+
+    /// Сдвинуть «доставлено до» к `message_id`; результат как у `mark_read`
+    pub async fn mark_delivered(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        message_id: &str,
+    ) -> Result<Option<bool>, sqlx::Error> {
+        let now = self.db.dialect().now();
+        let sql = format!(
+            "INSERT INTO chat_reads (chat_id, user_id, delivered_created_at, delivered_id, updated_at)
+             SELECT m.chat_id, $2, m.created_at, m.id, {now} FROM messages m WHERE m.id = $3 AND m.chat_id = $1
+             ON CONFLICT (chat_id, user_id) DO UPDATE SET
+                 delivered_created_at = excluded.delivered_created_at,
+                 delivered_id = excluded.delivered_id,
+                 updated_at = {now}
+             WHERE chat_reads.delivered_id IS NULL
+                OR (chat_reads.delivered_created_at, chat_reads.delivered_id) < (excluded.delivered_created_at, excluded.delivered_id)",
+        );
+
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM messages WHERE id = $1 AND chat_id = $2)")
+                .bind(message_id)
+                .bind(chat_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if !exists {
+                return Ok(None);
+            }
+
+            let advanced = sqlx::query(&sql)
+                .bind(chat_id)
+                .bind(user_id)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            tx.commit().await?;
+            Ok(Some(advanced))
+        })
+    }
+
+    /// Непрочитанные и непрочитанные упоминания пользователя в чате
+    pub async fn unread(&self, chat_id: &str, user_id: &str) -> Result<UnreadCounts, sqlx::Error> {
+        let sql = format!(
+            "SELECT (SELECT COUNT(*) {unread}) AS unread_count,
+                    (SELECT COUNT(*) {unread} {mentions}) AS unread_mentions
+             FROM chats c
+             LEFT JOIN chat_reads r ON r.chat_id = c.id AND r.user_id = $1
+             WHERE c.id = $2",
+            unread = UNREAD_MESSAGES,
+            mentions = MENTIONS_USER,
+        );
+        let counts = with_pool!(self.db, pool => {
+            sqlx::query_as(&sql).bind(user_id).bind(chat_id).fetch_optional(pool).await
+        })?;
+        Ok(counts.unwrap_or_default())
+    }
+
+    /// Прочитавшие сообщение, в порядке прочтения (без отключивших отчёты)
+    pub async fn readers(&self, message_id: &str) -> Result<Vec<MessageRead>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT r.user_id, u.username, r.read_at
+                 FROM message_reads r
+                 JOIN users u ON u.id = r.user_id
+                 WHERE r.message_id = $1
+                 ORDER BY r.read_at ASC, u.username ASC"
+            )
+            .bind(message_id)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    /// Отметить упомянутых участников чата — по `@username` и по id
+    /// (`text_mention`) — и автора сообщения, на которое отвечают. Себя
+    /// упомянуть нельзя. Возвращает впервые отмеченных: им уведомление.
+    pub async fn add_mentions(
+        &self,
+        message_id: &str,
+        chat_id: &str,
+        sender_id: &str,
+        usernames: &[String],
+        user_ids: &[String],
+        reply_to_id: Option<&str>,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        let mention = |condition: &str| {
+            format!(
+                "INSERT INTO message_mentions (message_id, user_id)
+                 SELECT $1, u.id FROM users u
+                 WHERE {} AND u.id <> $4
+                   AND (EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = $2 AND cm.user_id = u.id)
+                        OR EXISTS (SELECT 1 FROM chats c WHERE c.id = $2 AND c.owner_id = u.id))
+                 ON CONFLICT DO NOTHING
+                 RETURNING user_id",
+                condition,
+            )
+        };
+        let by_username = mention("LOWER(u.username) = LOWER($3)");
+        let by_id = mention("u.id = $3");
+
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+            let mut mentioned: Vec<String> = Vec::new();
+
+            let targets = usernames.iter().map(|name| (&by_username, name)).chain(user_ids.iter().map(|id| (&by_id, id)));
+            for (sql, target) in targets {
+                let inserted: Option<String> = sqlx::query_scalar(sql)
+                    .bind(message_id)
+                    .bind(chat_id)
+                    .bind(target)
+                    .bind(sender_id)
+                    .fetch_optional(&mut *tx)
+                    .await?;
+                mentioned.extend(inserted);
+            }
+
+            if let Some(reply_to_id) = reply_to_id {
+                let inserted: Option<String> = sqlx::query_scalar(
+                    "INSERT INTO message_mentions (message_id, user_id)
+                     SELECT $1, m.sender_id FROM messages m
+                     WHERE m.id = $3 AND m.chat_id = $2 AND m.sender_id <> $4
+                     ON CONFLICT DO NOTHING
+                     RETURNING user_id"
+                )
+                .bind(message_id)
+                .bind(chat_id)
+                .bind(reply_to_id)
+                .bind(sender_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+                mentioned.extend(inserted);
+            }
+
+            tx.commit().await?;
+            Ok(mentioned)
+        })
+    }
+}
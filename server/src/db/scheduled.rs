@@ -0,0 +1,342 @@
+// server/src/db/scheduled.rs
+//! Репозиторий отложенных сообщений
+//!
+//! Наступившая запись захватывается меткой (`claim`), чтобы два
+//! планировщика не отправили её дважды; вставка сообщения завершает
+//! захват в той же транзакции (`COMPLETE_SCHEDULED`). Захват, брошенный
+//! упавшим процессом, снимается через `release_stale`.
+
+use serde::{Deserialize, Serialize};
+use super::{with_pool, Database};
+
+/// Повтор отложенного сообщения
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Recurrence {
+    Once,
+    Daily,
+    Weekly,
+}
+
+impl Recurrence {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Recurrence::Once => "once",
+            Recurrence::Daily => "daily",
+            Recurrence::Weekly => "weekly",
+        }
+    }
+
+    /// Шаг до следующей отправки; `None` — разовое
+    pub fn step(self) -> Option<chrono::Duration> {
+        match self {
+            Recurrence::Once => None,
+            Recurrence::Daily => Some(chrono::Duration::days(1)),
+            Recurrence::Weekly => Some(chrono::Duration::weeks(1)),
+        }
+    }
+}
+
+impl From<String> for Recurrence {
+    fn from(column: String) -> Self {
+        match column.as_str() {
+            "daily" => Recurrence::Daily,
+            "weekly" => Recurrence::Weekly,
+            _ => Recurrence::Once,
+        }
+    }
+}
+
+/// Отложенное сообщение. `status`: `pending`, `sending` (отправляется),
+/// `sent`, `failed` (попытки кончились), `cancelled`.
+#[derive(Debug, Clone, Serialize, sqlx::FromRow)]
+pub struct ScheduledMessage {
+    pub id: String,
+    pub chat_id: String,
+    #[serde(skip)]
+    pub sender_id: String,
+    pub content: String,
+    pub message_type: String,
+    pub file_url: Option<String>,
+    pub send_at: String,
+    #[sqlx(try_from = "String")]
+    pub recurrence: Recurrence,
+    /// Отправить, когда собеседник появится в сети
+    pub when_online: bool,
+    pub status: String,
+    pub attempts: i64,
+    pub last_error: Option<String>,
+    /// Последнее отправленное сообщение
+    pub message_id: Option<String>,
+    pub created_at: String,
+}
+
+/// Наступившее сообщение; `recipient_id` — собеседник для `when_online`
+#[derive(Debug, sqlx::FromRow)]
+pub struct DueScheduled {
+    #[sqlx(flatten)]
+    pub message: ScheduledMessage,
+    pub recipient_id: Option<String>,
+}
+
+pub struct NewScheduledMessage<'a> {
+    pub id: &'a str,
+    pub chat_id: &'a str,
+    pub sender_id: &'a str,
+    pub content: &'a str,
+    pub message_type: &'a str,
+    pub file_url: Option<&'a str>,
+    pub send_at: &'a str,
+    pub recurrence: Recurrence,
+    pub when_online: bool,
+}
+
+/// Новые значения при редактировании
+pub struct ScheduledUpdate<'a> {
+    pub content: &'a str,
+    pub send_at: &'a str,
+    pub recurrence: Recurrence,
+}
+
+/// Отправка захваченной записи: завершается вместе со вставкой
+/// сообщения (`messages().insert`)
+#[derive(Debug)]
+pub struct ScheduledSend {
+    pub id: String,
+    pub claim: String,
+    /// Следующая отправка повторяющегося; `None` — эта последняя
+    pub next_send_at: Option<String>,
+}
+
+/// Завершить захват: `$1` id, `$2` метка, `$3` следующая отправка,
+/// `$4` созданное сообщение. Не тронутая запись (захват снят или
+/// перехвачен) откатывает вставку.
+pub(crate) const COMPLETE_SCHEDULED: &str = "UPDATE scheduled_messages SET
+         status = CASE WHEN $3 IS NULL THEN 'sent' ELSE 'pending' END,
+         send_at = COALESCE($3, send_at),
+         attempts = 0,
+         last_error = NULL,
+         claim = NULL,
+         claimed_at = NULL,
+         message_id = $4
+     WHERE id = $1 AND claim = $2 AND status = 'sending'";
+
+const SCHEDULED_COLUMNS: &str = "id, chat_id, sender_id, content, COALESCE(message_type, 'text') AS message_type, file_url,
+     send_at, recurrence, when_online, COALESCE(status, 'pending') AS status, attempts, last_error, message_id, created_at";
+
+/// Собеседник в личном чате: участник или владелец, кроме автора
+const RECIPIENT: &str = "COALESCE(
+         (SELECT cm.user_id FROM chat_members cm
+          WHERE cm.chat_id = scheduled_messages.chat_id AND cm.user_id <> scheduled_messages.sender_id
+          LIMIT 1),
+         (SELECT c.owner_id FROM chats c
+          WHERE c.id = scheduled_messages.chat_id AND c.owner_id <> scheduled_messages.sender_id))";
+
+pub struct ScheduledRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> ScheduledRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn schedule(&self, message: &NewScheduledMessage<'_>) -> Result<ScheduledMessage, sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO scheduled_messages
+                 (id, chat_id, sender_id, content, message_type, file_url, send_at, recurrence, when_online, status)
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'pending')
+             RETURNING {}",
+            SCHEDULED_COLUMNS,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(message.id)
+                .bind(message.chat_id)
+                .bind(message.sender_id)
+                .bind(message.content)
+                .bind(message.message_type)
+                .bind(message.file_url)
+                .bind(message.send_at)
+                .bind(message.recurrence.as_str())
+                .bind(message.when_online)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    pub async fn find(&self, chat_id: &str, id: &str, sender_id: &str) -> Result<Option<ScheduledMessage>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {} FROM scheduled_messages WHERE chat_id = $1 AND id = $2 AND sender_id = $3",
+            SCHEDULED_COLUMNS,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(chat_id)
+                .bind(id)
+                .bind(sender_id)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    /// Ещё не отправленные (в том числе не отправившиеся) сообщения автора
+    pub async fn pending(&self, chat_id: &str, sender_id: &str) -> Result<Vec<ScheduledMessage>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {} FROM scheduled_messages
+             WHERE chat_id = $1 AND sender_id = $2 AND status IN ('pending', 'sending', 'failed')
+             ORDER BY send_at ASC, id ASC",
+            SCHEDULED_COLUMNS,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(chat_id)
+                .bind(sender_id)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Изменить ожидающее или не отправившееся сообщение; оно снова
+    /// ждёт отправки с чистым счётчиком попыток. `false` — такого нет.
+    pub async fn update(
+        &self,
+        chat_id: &str,
+        id: &str,
+        sender_id: &str,
+        update: &ScheduledUpdate<'_>,
+    ) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "UPDATE scheduled_messages SET
+                     content = $4, send_at = $5, recurrence = $6,
+                     status = 'pending', attempts = 0, last_error = NULL
+                 WHERE chat_id = $1 AND id = $2 AND sender_id = $3 AND status IN ('pending', 'failed')"
+            )
+            .bind(chat_id)
+            .bind(id)
+            .bind(sender_id)
+            .bind(update.content)
+            .bind(update.send_at)
+            .bind(update.recurrence.as_str())
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// `false` — нечего отменять (нет, уже отправлено или отправляется)
+    pub async fn cancel(&self, chat_id: &str, id: &str, sender_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "UPDATE scheduled_messages SET status = 'cancelled'
+                 WHERE chat_id = $1 AND id = $2 AND sender_id = $3 AND status IN ('pending', 'failed')"
+            )
+            .bind(chat_id)
+            .bind(id)
+            .bind(sender_id)
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Наступившие ожидающие сообщения по id после `after`
+    pub async fn due(&self, after: &str, limit: i64) -> Result<Vec<DueScheduled>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {columns}, CASE WHEN when_online THEN {recipient} END AS recipient_id
+             FROM scheduled_messages
+             WHERE status = 'pending' AND send_at <= {now} AND id > $1
+             ORDER BY id
+             LIMIT $2",
+            columns = SCHEDULED_COLUMNS,
+            recipient = RECIPIENT,
+            now = self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(after)
+                .bind(limit)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Наступившие `when_online` сообщения, ждущие пользователя
+    pub async fn due_for_recipient(&self, user_id: &str) -> Result<Vec<DueScheduled>, sqlx::Error> {
+        let sql = format!(
+            "SELECT {columns}, $1 AS recipient_id
+             FROM scheduled_messages
+             WHERE when_online AND status = 'pending' AND send_at <= {now} AND sender_id <> $1
+               AND (EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = scheduled_messages.chat_id AND cm.user_id = $1)
+                    OR EXISTS (SELECT 1 FROM chats c WHERE c.id = scheduled_messages.chat_id AND c.owner_id = $1))
+             ORDER BY send_at, id",
+            columns = SCHEDULED_COLUMNS,
+            now = self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(user_id)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Захватить наступившую ожидающую запись меткой `claim` и вернуть её
+    /// в текущем виде; `None` — её уже захватили, отменили или перенесли
+    pub async fn claim(&self, id: &str, claim: &str) -> Result<Option<ScheduledMessage>, sqlx::Error> {
+        let sql = format!(
+            "UPDATE scheduled_messages SET status = 'sending', claim = $2, claimed_at = {now}
+             WHERE id = $1 AND status = 'pending' AND send_at <= {now}
+             RETURNING {columns}",
+            now = self.db.dialect().now(),
+            columns = SCHEDULED_COLUMNS,
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(id)
+                .bind(claim)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    /// Неудачная попытка: повторить в `retry_at` или, без него, сдаться.
+    /// `false` — захвата уже нет: сообщение сохранено, ошибка случилась позже
+    pub async fn fail(&self, id: &str, claim: &str, retry_at: Option<&str>, error: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "UPDATE scheduled_messages SET
+                     status = CASE WHEN $3 IS NULL THEN 'failed' ELSE 'pending' END,
+                     send_at = COALESCE($3, send_at),
+                     attempts = attempts + 1,
+                     last_error = $4,
+                     claim = NULL,
+                     claimed_at = NULL
+                 WHERE id = $1 AND claim = $2"
+            )
+            .bind(id)
+            .bind(claim)
+            .bind(retry_at)
+            .bind(error)
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected() > 0)
+        })
+    }
+
+    /// Вернуть в ожидание записи, захваченные раньше `before` (процесс
+    /// упал посреди отправки); возвращает их число
+    pub async fn release_stale(&self, before: &str) -> Result<u64, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "UPDATE scheduled_messages SET status = 'pending', claim = NULL, claimed_at = NULL
+                 WHERE status = 'sending' AND claimed_at < $1"
+            )
+            .bind(before)
+            .execute(pool)
+            .await
+            .map(|r| r.rows_affected())
+        })
+    }
+}
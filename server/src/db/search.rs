@@ -0,0 +1,440 @@
+// server/src/db/search.rs
+//! Полнотекстовый поиск: SQLite FTS5 и PostgreSQL tsvector
+//!
+//! Синтаксис запроса общий с локальным индексом desktop-v2:
+//! `слово` — все слова обязательны, `"точная фраза"`, `прив*` — префикс,
+//! `-слово` — исключить, `OR` — любое из соседних выражений.
+
+use serde::Serialize;
+use super::{with_pool, Database, Dialect};
+
+/// Маркеры подсветки, которые БД вставляет во фрагмент.
+/// Символы из Private Use Area не встречаются в обычном тексте,
+/// после экранирования заменяются на `<mark>`.
+pub const MARK_OPEN: char = '\u{E000}';
+pub const MARK_CLOSE: char = '\u{E001}';
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SearchHit {
+    pub message_id: String,
+    pub chat_id: String,
+    pub sender_id: String,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    /// Фрагмент текста с маркерами `MARK_OPEN`/`MARK_CLOSE`
+    pub snippet: String,
+    /// Чем меньше, тем релевантнее
+    pub rank: f64,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct SavedSearchHit {
+    pub id: String,
+    pub snippet: String,
+    pub tags: Option<String>,
+    pub rank: f64,
+    pub created_at: String,
+}
+
+/// Фильтры поиска по сообщениям; даты — в формате колонок
+#[derive(Debug, Default)]
+pub struct MessageFilters<'a> {
+    pub chat_id: Option<&'a str>,
+    pub sender_id: Option<&'a str>,
+    pub message_type: Option<&'a str>,
+    pub from: Option<&'a str>,
+    pub to: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchOrder {
+    Relevance,
+    Date,
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Term { text: String, prefix: bool, negated: bool },
+    Or,
+}
+
+#[derive(Debug, PartialEq)]
+struct Term {
+    text: String,
+    prefix: bool,
+}
+
+#[derive(Debug, PartialEq)]
+enum Item {
+    Term(Term),
+    Or,
+}
+
+/// Разобранный пользовательский запрос
+#[derive(Debug, PartialEq)]
+pub struct Expression {
+    positive: Vec<Item>,
+    negative: Vec<Term>,
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let mut negated = false;
+        if c == '-' {
+            chars.next();
+            match chars.peek() {
+                Some(next) if !next.is_whitespace() => negated = true,
+                _ => continue,
+            }
+        }
+
+        let mut text = String::new();
+        let mut prefix = false;
+        let quoted = chars.peek() == Some(&'"');
+        if quoted {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '"' {
+                    break;
+                }
+                text.push(c);
+            }
+            if chars.peek() == Some(&'*') {
+                chars.next();
+                prefix = true;
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+            if text == "OR" && !negated {
+                tokens.push(Token::Or);
+                continue;
+            }
+            let trimmed = text.trim_end_matches('*');
+            prefix = trimmed.len() != text.len();
+            text.truncate(trimmed.len());
+        }
+
+        if !text.trim().is_empty() {
+            tokens.push(Token::Term { text, prefix, negated });
+        }
+    }
+
+    tokens
+}
+
+impl Expression {
+    /// `None` — искать нечего (пустой запрос или только исключения)
+    pub fn parse(input: &str) -> Option<Self> {
+        let mut positive = Vec::new();
+        let mut negative = Vec::new();
+        let mut pending_or = false;
+
+        for token in tokenize(input) {
+            match token {
+                Token::Or => pending_or = !positive.is_empty(),
+                Token::Term { text, prefix, negated } => {
+                    let term = Term { text, prefix };
+                    if negated {
+                        negative.push(term);
+                        continue;
+                    }
+                    if pending_or {
+                        positive.push(Item::Or);
+                        pending_or = false;
+                    }
+                    positive.push(Item::Term(term));
+                }
+            }
+        }
+
+        if positive.is_empty() {
+            return None;
+        }
+        Some(Self { positive, negative })
+    }
+
+    /// Выражение FTS5. Каждый терм берётся в кавычки, поэтому
+    /// служебные символы FTS5 из ввода не могут сломать запрос.
+    pub fn to_fts5(&self) -> String {
+        let quote = |term: &Term| {
+            format!(
+                "\"{}\"{}",
+                term.text.replace('"', "\"\""),
+                if term.prefix { "*" } else { "" }
+            )
+        };
+
+        let positive: Vec<String> = self
+            .positive
+            .iter()
+            .map(|item| match item {
+                Item::Term(term) => quote(term),
+                Item::Or => "OR".to_string(),
+            })
+            .collect();
+
+        let mut expression = format!("({})", positive.join(" "));
+        for term in &self.negative {
+            expression.push_str(" NOT ");
+            expression.push_str(&quote(term));
+        }
+        expression
+    }
+
+    /// Текст для `to_tsquery`. Термы в кавычках разбираются парсером
+    /// словаря: фраза превращается в цепочку `<->`, префикс — в `:*`
+    /// у каждой лексемы. Приоритет `&` над `|` тот же, что у AND над OR в FTS5.
+    pub fn to_tsquery(&self) -> String {
+        let quote = |term: &Term| {
+            format!(
+                "'{}'{}",
+                term.text.replace('\\', "\\\\").replace('\'', "''"),
+                if term.prefix { ":*" } else { "" }
+            )
+        };
+
+        let mut positive = String::new();
+        let mut previous_term = false;
+        for item in &self.positive {
+            match item {
+                Item::Term(term) => {
+                    if previous_term {
+                        positive.push_str(" & ");
+                    }
+                    positive.push_str(&quote(term));
+                    previous_term = true;
+                }
+                Item::Or => {
+                    positive.push_str(" | ");
+                    previous_term = false;
+                }
+            }
+        }
+
+        let mut expression = format!("({})", positive);
+        for term in &self.negative {
+            expression.push_str(" & !");
+            expression.push_str(&quote(term));
+        }
+        expression
+    }
+
+    fn render(&self, dialect: Dialect) -> String {
+        match dialect {
+            Dialect::Sqlite => self.to_fts5(),
+            Dialect::Postgres => self.to_tsquery(),
+        }
+    }
+}
+
+pub struct SearchRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> SearchRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Поиск по сообщениям чатов, в которых состоит пользователь.
+    /// Параметры: `$1` выражение, `$2`/`$3` маркеры, `$4` пользователь.
+    pub async fn messages(
+        &self,
+        user_id: &str,
+        expression: &Expression,
+        filters: &MessageFilters<'_>,
+        order: SearchOrder,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SearchHit>, sqlx::Error> {
+        let (source, matches) = match self.db.dialect() {
+            Dialect::Sqlite => (
+                "snippet(messages_fts, 0, $2, $3, '…', 16) AS snippet,
+                 bm25(messages_fts) AS rank
+                 FROM messages_fts
+                 JOIN messages m ON m.rowid = messages_fts.rowid",
+                "messages_fts MATCH $1",
+            ),
+            Dialect::Postgres => (
+                "ts_headline('simple', m.content, q.query,
+                     'StartSel=' || $2 || ', StopSel=' || $3 || ', MaxWords=16, MinWords=5') AS snippet,
+                 CAST(-ts_rank(to_tsvector('simple', m.content), q.query) AS DOUBLE PRECISION) AS rank
+                 FROM messages m
+                 CROSS JOIN (SELECT to_tsquery('simple', $1) AS query) q",
+                "to_tsvector('simple', m.content) @@ q.query",
+            ),
+        };
+
+        let mut filter_sql = String::new();
+        let mut params: Vec<&str> = Vec::new();
+        let conditions = [
+            ("m.chat_id =", filters.chat_id),
+            ("m.sender_id =", filters.sender_id),
+            ("m.message_type =", filters.message_type),
+            ("m.created_at >=", filters.from),
+            ("m.created_at <", filters.to),
+        ];
+        for (condition, value) in conditions {
+            if let Some(value) = value {
+                params.push(value);
+                filter_sql.push_str(&format!(" AND {} ${}", condition, params.len() + 4));
+            }
+        }
+
+        let order_sql = match order {
+            SearchOrder::Relevance => "rank, m.created_at DESC",
+            SearchOrder::Date => "m.created_at DESC, m.id DESC",
+        };
+
+        let sql = format!(
+            "SELECT m.id AS message_id, m.chat_id, m.sender_id,
+                    COALESCE(m.message_type, 'text') AS message_type, m.created_at,
+                    {source}
+             WHERE {matches}
+               AND m.is_deleted = FALSE
+               AND m.chat_id IN (
+                   SELECT chat_id FROM chat_members WHERE user_id = $4
+                   UNION SELECT id FROM chats WHERE owner_id = $4
+               )
+               AND NOT EXISTS (SELECT 1 FROM message_hidden h WHERE h.message_id = m.id AND h.user_id = $4)
+               {filters}
+             ORDER BY {order}
+             LIMIT ${limit} OFFSET ${offset}",
+            source = source,
+            matches = matches,
+            filters = filter_sql,
+            order = order_sql,
+            limit = params.len() + 5,
+            offset = params.len() + 6,
+        );
+
+        let rendered = expression.render(self.db.dialect());
+        with_pool!(self.db, pool => {
+            let mut query = sqlx::query_as(&sql)
+                .bind(&rendered)
+                .bind(MARK_OPEN.to_string())
+                .bind(MARK_CLOSE.to_string())
+                .bind(user_id);
+            for param in &params {
+                query = query.bind(*param);
+            }
+            query.bind(limit).bind(offset).fetch_all(pool).await
+        })
+    }
+
+    /// Поиск по избранным сообщениям пользователя (текст и теги)
+    pub async fn saved(
+        &self,
+        user_id: &str,
+        expression: &Expression,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<SavedSearchHit>, sqlx::Error> {
+        let (source, matches) = match self.db.dialect() {
+            Dialect::Sqlite => (
+                "snippet(saved_messages_fts, 0, $2, $3, '…', 16) AS snippet,
+                 bm25(saved_messages_fts) AS rank
+                 FROM saved_messages_fts
+                 JOIN saved_messages s ON s.rowid = saved_messages_fts.rowid",
+                "saved_messages_fts MATCH $1",
+            ),
+            Dialect::Postgres => (
+                "ts_headline('simple', s.content, q.query,
+                     'StartSel=' || $2 || ', StopSel=' || $3 || ', MaxWords=16, MinWords=5') AS snippet,
+                 CAST(-ts_rank(to_tsvector('simple', s.content || ' ' || COALESCE(s.tags, '')), q.query)
+                      AS DOUBLE PRECISION) AS rank
+                 FROM saved_messages s
+                 CROSS JOIN (SELECT to_tsquery('simple', $1) AS query) q",
+                "to_tsvector('simple', s.content || ' ' || COALESCE(s.tags, '')) @@ q.query",
+            ),
+        };
+
+        let sql = format!(
+            "SELECT s.id, s.tags, s.created_at,
+                    {}
+             WHERE {} AND s.user_id = $4
+             ORDER BY rank, s.created_at DESC
+             LIMIT $5 OFFSET $6",
+            source, matches,
+        );
+
+        let rendered = expression.render(self.db.dialect());
+        with_pool!(self.db, pool => {
+            sqlx::query_as(&sql)
+                .bind(&rendered)
+                .bind(MARK_OPEN.to_string())
+                .bind(MARK_CLOSE.to_string())
+                .bind(user_id)
+                .bind(limit)
+                .bind(offset)
+                .fetch_all(pool)
+                .await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fts5(input: &str) -> Option<String> {
+        Expression::parse(input).map(|e| e.to_fts5())
+    }
+
+    fn tsquery(input: &str) -> Option<String> {
+        Expression::parse(input).map(|e| e.to_tsquery())
+    }
+
+    #[test]
+    fn test_to_fts5_terms_and_phrases() {
+        assert_eq!(fts5("привет мир").as_deref(), Some("(\"привет\" \"мир\")"));
+        assert_eq!(
+            fts5("\"точная фраза\" кот*").as_deref(),
+            Some("(\"точная фраза\" \"кот\"*)")
+        );
+        assert_eq!(fts5("a OR b").as_deref(), Some("(\"a\" OR \"b\")"));
+        assert_eq!(fts5("a -b -\"c d\"").as_deref(), Some("(\"a\") NOT \"b\" NOT \"c d\""));
+    }
+
+    #[test]
+    fn test_to_fts5_neutralizes_syntax() {
+        // Операторы и спецсимволы FTS5 из ввода остаются обычным текстом
+        assert_eq!(fts5("NEAR(a b)").as_deref(), Some("(\"NEAR(a\" \"b)\")"));
+        assert_eq!(fts5("content:x AND y").as_deref(), Some("(\"content:x\" \"AND\" \"y\")"));
+        assert_eq!(fts5("a\"b").as_deref(), Some("(\"a\" \"b\")"));
+        // Висячие OR отбрасываются
+        assert_eq!(fts5("OR a OR").as_deref(), Some("(\"a\")"));
+    }
+
+    #[test]
+    fn test_nothing_to_search() {
+        assert_eq!(Expression::parse(""), None);
+        assert_eq!(Expression::parse("   - * \"\""), None);
+        assert_eq!(Expression::parse("-spam"), None);
+    }
+
+    #[test]
+    fn test_to_tsquery() {
+        assert_eq!(tsquery("привет мир").as_deref(), Some("('привет' & 'мир')"));
+        assert_eq!(tsquery("\"точная фраза\" кот*").as_deref(), Some("('точная фраза' & 'кот':*)"));
+        assert_eq!(tsquery("a b OR c").as_deref(), Some("('a' & 'b' | 'c')"));
+        assert_eq!(tsquery("a -b").as_deref(), Some("('a') & !'b'"));
+        // Кавычки и обратный слэш экранируются внутри терма
+        assert_eq!(tsquery("it's a\\b").as_deref(), Some("('it''s' & 'a\\\\b')"));
+    }
+}
@@ -0,0 +1,204 @@
+// server/src/db/threads.rs
+//! Репозиторий тредов: подписки и непрочитанные ответы
+//!
+//! Тред — ответы на корневое сообщение (`messages.thread_id`). У участника
+//! треда своя подписка и свой указатель «прочитано до», независимые от
+//! чата: непрочитанные ответы считаются по нему.
+
+use serde::Serialize;
+use super::{with_pool, Database};
+
+/// Корень треда: что нужно для проверок при ответе
+#[derive(Debug, sqlx::FromRow)]
+pub struct ThreadRoot {
+    pub id: String,
+    pub chat_id: String,
+    /// Настоящий автор (и у поста канала)
+    pub sender_id: String,
+    /// Пост канала: ответы в треде — комментарии
+    pub channel_post: bool,
+}
+
+/// Тред, на который подписан пользователь
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct ThreadSummary {
+    pub thread_id: String,
+    pub chat_id: String,
+    pub content: String,
+    pub reply_count: i64,
+    pub last_reply_at: Option<String>,
+    /// Чужие ответы после «прочитано до»
+    pub unread_count: i64,
+}
+
+/// Непрочитанные пользователем `$1` ответы треда с корнем `r` при указателе
+/// из `thread_members tm` (если строки нет — все чужие ответы)
+const UNREAD_REPLIES: &str =
+    "FROM messages m
+     WHERE m.thread_id = r.id AND m.sender_id <> $1 AND m.is_deleted = FALSE
+       AND (tm.read_id IS NULL OR (m.created_at, m.id) > (tm.read_created_at, tm.read_id))
+       AND NOT EXISTS (SELECT 1 FROM message_hidden h WHERE h.message_id = m.id AND h.user_id = $1)";
+
+pub struct ThreadRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> ThreadRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    /// Тред, к которому относится сообщение чата: его корень или оно само.
+    /// `None` — сообщения нет в этом чате.
+    pub async fn root_of(&self, chat_id: &str, message_id: &str) -> Result<Option<ThreadRoot>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT r.id, r.chat_id, r.sender_id, r.channel_post
+                 FROM messages m
+                 JOIN messages r ON r.id = COALESCE(m.thread_id, m.id)
+                 WHERE m.id = $1 AND m.chat_id = $2"
+            )
+            .bind(message_id)
+            .bind(chat_id)
+            .fetch_optional(pool)
+            .await
+        })
+    }
+
+    /// Подписаться на ответы или отписаться
+    pub async fn set_subscribed(&self, thread_id: &str, user_id: &str, subscribed: bool) -> Result<(), sqlx::Error> {
+        let sql = format!(
+            "INSERT INTO thread_members (thread_id, user_id, subscribed) VALUES ($1, $2, $3)
+             ON CONFLICT (thread_id, user_id) DO UPDATE SET subscribed = $3, updated_at = {}",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(thread_id)
+                .bind(user_id)
+                .bind(subscribed)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Подписать, если пользователь ещё не решал сам (автора корня
+    /// при первом ответе)
+    pub async fn subscribe_if_new(&self, thread_id: &str, user_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "INSERT INTO thread_members (thread_id, user_id, subscribed) VALUES ($1, $2, TRUE)
+                 ON CONFLICT DO NOTHING"
+            )
+            .bind(thread_id)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+        })
+    }
+
+    /// Подписан ли пользователь на тред
+    pub async fn is_subscribed(&self, thread_id: &str, user_id: &str) -> Result<bool, sqlx::Error> {
+        let subscribed: Option<bool> = with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT subscribed FROM thread_members WHERE thread_id = $1 AND user_id = $2")
+                .bind(thread_id)
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await
+        })?;
+        Ok(subscribed.unwrap_or(false))
+    }
+
+    /// Подписчики треда, ещё состоящие в чате
+    pub async fn subscribers(&self, thread_id: &str) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar(
+                "SELECT tm.user_id FROM thread_members tm
+                 JOIN messages r ON r.id = tm.thread_id
+                 JOIN chats c ON c.id = r.chat_id
+                 WHERE tm.thread_id = $1 AND tm.subscribed
+                   AND (c.owner_id = tm.user_id
+                        OR EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = c.id AND cm.user_id = tm.user_id))"
+            )
+                .bind(thread_id)
+                .fetch_all(pool)
+                .await
+        })
+    }
+
+    /// Сдвинуть «прочитано до» в треде к ответу `message_id`. `None` — такого
+    /// ответа в треде нет, `Some(false)` — указатель уже стоял не левее.
+    pub async fn mark_read(&self, thread_id: &str, user_id: &str, message_id: &str) -> Result<Option<bool>, sqlx::Error> {
+        let now = self.db.dialect().now();
+        let sql = format!(
+            "INSERT INTO thread_members (thread_id, user_id, read_created_at, read_id, updated_at)
+             SELECT m.thread_id, $2, m.created_at, m.id, {now} FROM messages m WHERE m.id = $3 AND m.thread_id = $1
+             ON CONFLICT (thread_id, user_id) DO UPDATE SET
+                 read_created_at = excluded.read_created_at,
+                 read_id = excluded.read_id,
+                 updated_at = {now}
+             WHERE thread_members.read_id IS NULL
+                OR (thread_members.read_created_at, thread_members.read_id) < (excluded.read_created_at, excluded.read_id)",
+        );
+
+        with_pool!(self.db, pool => {
+            let mut tx = pool.begin().await?;
+
+            let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM messages WHERE id = $1 AND thread_id = $2)")
+                .bind(message_id)
+                .bind(thread_id)
+                .fetch_one(&mut *tx)
+                .await?;
+            if !exists {
+                return Ok(None);
+            }
+
+            let advanced = sqlx::query(&sql)
+                .bind(thread_id)
+                .bind(user_id)
+                .bind(message_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected() > 0;
+
+            tx.commit().await?;
+            Ok(Some(advanced))
+        })
+    }
+
+    /// Непрочитанные ответы треда
+    pub async fn unread(&self, thread_id: &str, user_id: &str) -> Result<i64, sqlx::Error> {
+        let sql = format!(
+            "SELECT (SELECT COUNT(*) {})
+             FROM messages r
+             LEFT JOIN thread_members tm ON tm.thread_id = r.id AND tm.user_id = $1
+             WHERE r.id = $2",
+            UNREAD_REPLIES,
+        );
+        with_pool!(self.db, pool => sqlx::query_scalar(&sql).bind(user_id).bind(thread_id).fetch_one(pool).await)
+    }
+
+    /// Треды, на которые подписан пользователь, в чатах, где он состоит:
+    /// сначала с самым свежим ответом
+    pub async fn subscriptions(&self, user_id: &str) -> Result<Vec<ThreadSummary>, sqlx::Error> {
+        let sql = format!(
+            "SELECT * FROM (
+                 SELECT r.id AS thread_id, r.chat_id, r.content,
+                        (SELECT COUNT(*) FROM messages m WHERE m.thread_id = r.id AND m.is_deleted = FALSE) AS reply_count,
+                        (SELECT MAX(m.created_at) FROM messages m WHERE m.thread_id = r.id AND m.is_deleted = FALSE)
+                            AS last_reply_at,
+                        (SELECT COUNT(*) {unread}) AS unread_count
+                 FROM thread_members tm
+                 JOIN messages r ON r.id = tm.thread_id
+                 JOIN chats c ON c.id = r.chat_id
+                 WHERE tm.user_id = $1 AND tm.subscribed
+                   AND (c.owner_id = $1 OR EXISTS (SELECT 1 FROM chat_members cm WHERE cm.chat_id = c.id AND cm.user_id = $1))
+             ) threads
+             ORDER BY COALESCE(last_reply_at, '') DESC, thread_id",
+            unread = UNREAD_REPLIES,
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(user_id).fetch_all(pool).await)
+    }
+}
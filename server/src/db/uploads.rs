@@ -0,0 +1,110 @@
+// server/src/db/uploads.rs
+//! Репозиторий незавершённых (докачиваемых) загрузок
+
+use super::{with_pool, Database};
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Upload {
+    pub id: String,
+    pub owner_id: String,
+    pub filename: String,
+    pub size: i64,
+    pub received: i64,
+    pub updated_at: String,
+    pub client_encrypted: bool,
+}
+
+pub struct NewUpload<'a> {
+    pub id: &'a str,
+    pub owner_id: &'a str,
+    pub filename: &'a str,
+    pub size: i64,
+    pub client_encrypted: bool,
+}
+
+pub struct UploadRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> UploadRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, upload: &NewUpload<'_>) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "INSERT INTO uploads (id, owner_id, filename, size, client_encrypted) VALUES ($1, $2, $3, $4, $5)"
+            )
+            .bind(upload.id)
+            .bind(upload.owner_id)
+            .bind(upload.filename)
+            .bind(upload.size)
+            .bind(upload.client_encrypted)
+            .execute(pool)
+            .await
+            .map(|_| ())
+        })
+    }
+
+    /// Загрузка пользователя (чужие не видны)
+    pub async fn find(&self, upload_id: &str, owner_id: &str) -> Result<Option<Upload>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, owner_id, filename, size, received, updated_at, client_encrypted
+                 FROM uploads WHERE id = $1 AND owner_id = $2"
+            )
+            .bind(upload_id)
+            .bind(owner_id)
+            .fetch_optional(pool)
+            .await
+        })
+    }
+
+    /// Сдвинуть смещение, если оно всё ещё равно `from`
+    pub async fn advance(&self, upload_id: &str, from: i64, to: i64) -> Result<bool, sqlx::Error> {
+        let sql = format!(
+            "UPDATE uploads SET received = $1, updated_at = {} WHERE id = $2 AND received = $3",
+            self.db.dialect().now(),
+        );
+        with_pool!(self.db, pool => {
+            sqlx::query(&sql)
+                .bind(to)
+                .bind(upload_id)
+                .bind(from)
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected() == 1)
+        })
+    }
+
+    pub async fn delete(&self, upload_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("DELETE FROM uploads WHERE id = $1")
+                .bind(upload_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Байты, зарезервированные незавершёнными загрузками пользователя
+    pub async fn reserved_bytes(&self, owner_id: &str) -> Result<i64, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT CAST(COALESCE(SUM(size), 0) AS BIGINT) FROM uploads WHERE owner_id = $1")
+                .bind(owner_id)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    /// Загрузки, не продвигавшиеся с `before` (дата в формате колонок)
+    pub async fn stale(&self, before: &str) -> Result<Vec<String>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT id FROM uploads WHERE updated_at < $1")
+                .bind(before)
+                .fetch_all(pool)
+                .await
+        })
+    }
+}
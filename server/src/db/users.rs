@@ -0,0 +1,233 @@
+// server/src/db/users.rs
+//! Репозиторий пользователей
+
+use serde::Serialize;
+use super::{with_pool, Database};
+
+/// Публичный профиль пользователя
+#[derive(Debug, Serialize, sqlx::FromRow)]
+pub struct User {
+    pub id: String,
+    pub username: String,
+    pub email: Option<String>,
+    pub avatar_url: Option<String>,
+    pub status: String,
+    pub public_key: String,
+    /// Когда закрылось последнее соединение
+    pub last_seen_at: Option<String>,
+}
+
+/// Настройки приватности; кому видны время захода, статус online и ссылка
+/// на автора в пересланных сообщениях: `everyone`, `contacts`, `nobody`
+#[derive(Debug, sqlx::FromRow)]
+pub struct PrivacySettings {
+    pub last_seen: String,
+    pub online: String,
+    pub forwards: String,
+    /// Отправлять ли другим отчёты о прочтении
+    pub read_receipts: bool,
+}
+
+/// Данные для входа
+#[derive(Debug, sqlx::FromRow)]
+pub struct Credentials {
+    pub id: String,
+    pub username: String,
+    pub password_hash: String,
+    pub public_key: String,
+}
+
+pub struct NewUser<'a> {
+    pub id: &'a str,
+    pub username: &'a str,
+    pub email: Option<&'a str>,
+    /// Для импорта контактов, см. `api::contacts::email_hash`
+    pub email_hash: Option<&'a str>,
+    pub password_hash: &'a str,
+    pub public_key: &'a str,
+}
+
+/// Собеседники `$1` по общим чатам (колонка `peer`, возможны повторы
+/// и сам `$1`). В каналах друг друга видят только владелец и администраторы:
+/// подписчики не узнают друг о друге, а подключение подписчика не
+/// рассылается всему каналу.
+const PRESENCE_PEERS: &str =
+    "SELECT other.user_id AS peer FROM chat_members mine
+     JOIN chats c ON c.id = mine.chat_id
+     JOIN chat_members other ON other.chat_id = mine.chat_id
+     WHERE mine.user_id = $1
+       AND (c.chat_type <> 'channel' OR mine.role = 'admin' OR c.owner_id = mine.user_id)
+       AND (c.chat_type <> 'channel' OR other.role = 'admin' OR c.owner_id = other.user_id)
+     UNION
+     SELECT c.owner_id FROM chats c
+     JOIN chat_members mine ON mine.chat_id = c.id
+     WHERE mine.user_id = $1 AND (c.chat_type <> 'channel' OR mine.role = 'admin')
+     UNION
+     SELECT cm.user_id FROM chats c
+     JOIN chat_members cm ON cm.chat_id = c.id
+     WHERE c.owner_id = $1 AND (c.chat_type <> 'channel' OR cm.role = 'admin')";
+
+pub struct UserRepository<'a> {
+    db: &'a Database,
+}
+
+impl<'a> UserRepository<'a> {
+    pub(crate) fn new(db: &'a Database) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, user: &NewUser<'_>) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "INSERT INTO users (id, username, email, email_hash, password_hash, public_key) VALUES ($1, $2, $3, $4, $5, $6)"
+            )
+            .bind(user.id)
+            .bind(user.username)
+            .bind(user.email)
+            .bind(user.email_hash)
+            .bind(user.password_hash)
+            .bind(user.public_key)
+            .execute(pool)
+            .await
+            .map(|_| ())
+        })
+    }
+
+    pub async fn credentials(&self, username: &str) -> Result<Option<Credentials>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as("SELECT id, username, password_hash, public_key FROM users WHERE username = $1")
+                .bind(username)
+                .fetch_optional(pool)
+                .await
+        })
+    }
+
+    pub async fn find(&self, user_id: &str) -> Result<Option<User>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, username, email, avatar_url, COALESCE(status, 'offline') AS status, public_key, last_seen_at
+                 FROM users WHERE id = $1"
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+        })
+    }
+
+    pub async fn privacy(&self, user_id: &str) -> Result<Option<PrivacySettings>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT last_seen_privacy AS last_seen, online_privacy AS online, forwards_privacy AS forwards, read_receipts
+                 FROM users WHERE id = $1"
+            )
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+        })
+    }
+
+    pub async fn set_privacy(&self, user_id: &str, privacy: &PrivacySettings) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query(
+                "UPDATE users SET last_seen_privacy = $1, online_privacy = $2, forwards_privacy = $3, read_receipts = $4
+                 WHERE id = $5"
+            )
+            .bind(&privacy.last_seen)
+            .bind(&privacy.online)
+            .bind(&privacy.forwards)
+            .bind(privacy.read_receipts)
+            .bind(user_id)
+            .execute(pool)
+            .await
+            .map(|_| ())
+        })
+    }
+
+    pub async fn set_online(&self, user_id: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE users SET status = 'online' WHERE id = $1")
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Ушёл из сети в `at` (дата в формате колонок)
+    pub async fn set_offline(&self, user_id: &str, at: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE users SET status = 'offline', last_seen_at = $1 WHERE id = $2")
+                .bind(at)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Сбросить `online`, оставшийся после остановки сервера
+    pub async fn reset_presence(&self) -> Result<u64, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE users SET status = 'offline' WHERE status = 'online'")
+                .execute(pool)
+                .await
+                .map(|r| r.rows_affected())
+        })
+    }
+
+    /// `contact_id` в контактах у `user_id`
+    pub async fn is_contact(&self, user_id: &str, contact_id: &str) -> Result<bool, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM contacts WHERE user_id = $1 AND contact_id = $2)")
+                .bind(user_id)
+                .bind(contact_id)
+                .fetch_one(pool)
+                .await
+        })
+    }
+
+    /// Пользователи с почтой, но без `email_hash` (зарегистрированные до
+    /// импорта контактов): `(id, email)` по id после `after`
+    pub async fn missing_email_hashes(&self, after: &str, limit: i64) -> Result<Vec<(String, String)>, sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query_as(
+                "SELECT id, email FROM users
+                 WHERE email IS NOT NULL AND email_hash IS NULL AND id > $1
+                 ORDER BY id LIMIT $2"
+            )
+            .bind(after)
+            .bind(limit)
+            .fetch_all(pool)
+            .await
+        })
+    }
+
+    pub async fn set_email_hash(&self, user_id: &str, email_hash: &str) -> Result<(), sqlx::Error> {
+        with_pool!(self.db, pool => {
+            sqlx::query("UPDATE users SET email_hash = $1 WHERE id = $2")
+                .bind(email_hash)
+                .bind(user_id)
+                .execute(pool)
+                .await
+                .map(|_| ())
+        })
+    }
+
+    /// Собеседники по общим чатам и признак «в контактах у `user_id`» —
+    /// кому рассылается присутствие. Заблокированным `user_id` — не рассылается.
+    pub async fn presence_audience(&self, user_id: &str) -> Result<Vec<(String, bool)>, sqlx::Error> {
+        let sql = format!(
+            "SELECT peer, EXISTS (SELECT 1 FROM contacts c WHERE c.user_id = $1 AND c.contact_id = peer)
+             FROM ({PRESENCE_PEERS}) peers
+             WHERE peer IS NOT NULL AND peer <> $1
+               AND NOT EXISTS (SELECT 1 FROM blocked_users b WHERE b.user_id = $1 AND b.blocked_id = peer)"
+        );
+        with_pool!(self.db, pool => sqlx::query_as(&sql).bind(user_id).fetch_all(pool).await)
+    }
+
+    /// Видит ли `peer_id` присутствие `user_id` по общему чату (как в `presence_audience`)
+    pub async fn shares_presence(&self, user_id: &str, peer_id: &str) -> Result<bool, sqlx::Error> {
+        let sql = format!("SELECT EXISTS (SELECT 1 FROM ({PRESENCE_PEERS}) peers WHERE peer = $2)");
+        with_pool!(self.db, pool => sqlx::query_scalar(&sql).bind(user_id).bind(peer_id).fetch_one(pool).await)
+    }
+}
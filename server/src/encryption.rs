@@ -0,0 +1,408 @@
+// server/src/encryption.rs
+//! Шифрование содержимого файлов на сервере
+//!
+//! Конвертная схема: у каждого объекта хранилища свой ключ данных
+//! (AES-256), в `file_blobs` он лежит обёрнутым мастер-ключом
+//! (`KeyProvider`). Мастер-ключи задаются `FILE_MASTER_KEYS`
+//! (`id:base64,...`), первый — текущий; без них файлы хранятся открытыми.
+//! Смена мастер-ключа: поставить новый первым, запустить сервер
+//! с `--rotate-keys`, после этого старый ключ можно убрать.
+//!
+//! Формат объекта: `MAGIC`, 7 байт префикса nonce и куски по `CHUNK_LEN`
+//! байт открытого текста, каждый со своим тегом AES-GCM. Nonce куска —
+//! префикс, номер куска и флаг последнего (схема STREAM): куски нельзя
+//! переставить, отрезать или дописать незаметно.
+
+use aes_gcm::{
+    aead::{Aead, AeadInPlace, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use axum::body::Bytes;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use futures::{stream::BoxStream, StreamExt};
+use rand::RngCore;
+use std::{collections::HashMap, io, path::Path, sync::Arc};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use zeroize::Zeroizing;
+
+const MAGIC: &[u8; 4] = b"LRE1";
+const PREFIX_LEN: usize = 7;
+const HEADER_LEN: usize = MAGIC.len() + PREFIX_LEN;
+/// Открытый текст одного куска
+pub const CHUNK_LEN: usize = 64 * 1024;
+const TAG_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// Ключ данных одного объекта
+pub struct DataKey(Zeroizing<[u8; 32]>);
+
+impl DataKey {
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0u8; 32]);
+        rand::thread_rng().fill_bytes(key.as_mut());
+        Self(key)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new(self.0.as_ref().into())
+    }
+}
+
+/// Ключ данных, обёрнутый мастер-ключом `key_id` (base64)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrappedKey {
+    pub key_id: String,
+    pub wrapped_key: String,
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Источник мастер-ключей: локальный `Keyring` или внешний KMS
+#[axum::async_trait]
+pub trait KeyProvider: Send + Sync {
+    /// Мастер-ключ, которым оборачиваются новые ключи данных
+    fn current_key_id(&self) -> &str;
+
+    /// Обернуть ключ текущим мастер-ключом; `context` (хеш содержимого)
+    /// привязывает обёртку к объекту
+    async fn wrap(&self, key: &DataKey, context: &str) -> io::Result<WrappedKey>;
+
+    async fn unwrap(&self, wrapped: &WrappedKey, context: &str) -> io::Result<DataKey>;
+}
+
+/// Мастер-ключи из конфигурации
+pub struct Keyring {
+    current: String,
+    keys: HashMap<String, Zeroizing<[u8; 32]>>,
+}
+
+impl Keyring {
+    /// Первый ключ — текущий
+    pub fn new(keys: Vec<(String, [u8; 32])>) -> anyhow::Result<Self> {
+        let current = keys
+            .first()
+            .map(|(id, _)| id.clone())
+            .ok_or_else(|| anyhow::anyhow!("Не задано ни одного мастер-ключа"))?;
+        let mut ring = HashMap::new();
+        for (id, key) in keys {
+            if id.is_empty() || ring.insert(id.clone(), Zeroizing::new(key)).is_some() {
+                anyhow::bail!("Пустой или повторный id мастер-ключа: {:?}", id);
+            }
+        }
+        Ok(Self { current, keys: ring })
+    }
+
+    /// `id:base64,id:base64,...` — ключи по 32 байта
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        let mut keys = Vec::new();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (id, encoded) = entry
+                .split_once(':')
+                .ok_or_else(|| anyhow::anyhow!("Мастер-ключ без id: ожидается id:base64"))?;
+            let decoded = Zeroizing::new(BASE64.decode(encoded.trim())?);
+            let key: [u8; 32] = decoded
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow::anyhow!("Мастер-ключ {} должен быть 32 байта", id))?;
+            keys.push((id.trim().to_string(), key));
+        }
+        Self::new(keys)
+    }
+
+    fn cipher(&self, key_id: &str) -> io::Result<Aes256Gcm> {
+        let key = self
+            .keys
+            .get(key_id)
+            .ok_or_else(|| invalid_data(&format!("неизвестный мастер-ключ {}", key_id)))?;
+        Ok(Aes256Gcm::new(key.as_ref().into()))
+    }
+}
+
+#[axum::async_trait]
+impl KeyProvider for Keyring {
+    fn current_key_id(&self) -> &str {
+        &self.current
+    }
+
+    async fn wrap(&self, key: &DataKey, context: &str) -> io::Result<WrappedKey> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let sealed = self
+            .cipher(&self.current)?
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: key.0.as_ref(),
+                    aad: context.as_bytes(),
+                },
+            )
+            .map_err(|_| io::Error::other("ошибка обёртки ключа"))?;
+
+        Ok(WrappedKey {
+            key_id: self.current.clone(),
+            wrapped_key: BASE64.encode([&nonce[..], &sealed].concat()),
+        })
+    }
+
+    async fn unwrap(&self, wrapped: &WrappedKey, context: &str) -> io::Result<DataKey> {
+        let data = BASE64
+            .decode(&wrapped.wrapped_key)
+            .map_err(|_| invalid_data("обёрнутый ключ не в base64"))?;
+        if data.len() < NONCE_LEN {
+            return Err(invalid_data("обёрнутый ключ обрезан"));
+        }
+        let (nonce, sealed) = data.split_at(NONCE_LEN);
+        let key = Zeroizing::new(
+            self.cipher(&wrapped.key_id)?
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: sealed,
+                        aad: context.as_bytes(),
+                    },
+                )
+                .map_err(|_| invalid_data("обёрнутый ключ не расшифровывается"))?,
+        );
+        let key: [u8; 32] = key
+            .as_slice()
+            .try_into()
+            .map_err(|_| invalid_data("ключ данных не 32 байта"))?;
+        Ok(DataKey(Zeroizing::new(key)))
+    }
+}
+
+/// `Keyring` из `FILE_MASTER_KEYS`; `None` — шифрование выключено
+pub fn from_env() -> anyhow::Result<Option<Arc<dyn KeyProvider>>> {
+    match std::env::var("FILE_MASTER_KEYS") {
+        Ok(spec) if !spec.trim().is_empty() => Ok(Some(Arc::new(Keyring::parse(&spec)?))),
+        _ => Ok(None),
+    }
+}
+
+fn chunk_nonce(prefix: &[u8; PREFIX_LEN], index: u32, last: bool) -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[..PREFIX_LEN].copy_from_slice(prefix);
+    nonce[PREFIX_LEN..PREFIX_LEN + 4].copy_from_slice(&index.to_be_bytes());
+    nonce[NONCE_LEN - 1] = last as u8;
+    nonce
+}
+
+/// Дочитать `buf` до конца или до конца файла
+async fn read_full(input: &mut (impl AsyncRead + Unpin), buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let n = input.read(&mut buf[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+/// Зашифровать файл `source` в `target`
+pub async fn encrypt_file(key: &DataKey, source: &Path, target: &Path) -> io::Result<()> {
+    let cipher = key.cipher();
+    let mut prefix = [0u8; PREFIX_LEN];
+    rand::thread_rng().fill_bytes(&mut prefix);
+
+    let mut input = tokio::fs::File::open(source).await?;
+    let mut output = tokio::fs::File::create(target).await?;
+    output.write_all(MAGIC).await?;
+    output.write_all(&prefix).await?;
+
+    let mut current = vec![0u8; CHUNK_LEN];
+    let mut next = vec![0u8; CHUNK_LEN];
+    let mut len = read_full(&mut input, &mut current).await?;
+    let mut index = 0u32;
+    loop {
+        // Последний кусок узнаём, заглянув в следующий
+        let next_len = if len == CHUNK_LEN { read_full(&mut input, &mut next).await? } else { 0 };
+        let last = next_len == 0;
+
+        let mut chunk = current[..len].to_vec();
+        cipher
+            .encrypt_in_place(Nonce::from_slice(&chunk_nonce(&prefix, index, last)), b"", &mut chunk)
+            .map_err(|_| io::Error::other("ошибка шифрования"))?;
+        output.write_all(&chunk).await?;
+        if last {
+            break;
+        }
+
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+        index = index.checked_add(1).ok_or_else(|| io::Error::other("слишком большой файл"))?;
+    }
+    output.flush().await
+}
+
+/// Размер открытого текста по размеру зашифрованного объекта;
+/// `None`, если такого размера у объекта быть не может
+pub fn plaintext_len(encrypted_len: u64) -> Option<u64> {
+    let frame = (CHUNK_LEN + TAG_LEN) as u64;
+    let body = encrypted_len.checked_sub(HEADER_LEN as u64)?;
+    let chunks = body.div_ceil(frame).max(1);
+    let last = body.checked_sub((chunks - 1) * frame)?;
+    (last >= TAG_LEN as u64).then(|| body - chunks * TAG_LEN as u64)
+}
+
+struct Decryptor {
+    cipher: Aes256Gcm,
+    body: BoxStream<'static, io::Result<Bytes>>,
+    buf: Vec<u8>,
+    ended: bool,
+    prefix: Option<[u8; PREFIX_LEN]>,
+    index: u32,
+    done: bool,
+}
+
+impl Decryptor {
+    /// Дочитать, пока в буфере меньше `want` байт
+    async fn fill(&mut self, want: usize) -> io::Result<()> {
+        while !self.ended && self.buf.len() < want {
+            match self.body.next().await {
+                Some(chunk) => self.buf.extend_from_slice(&chunk?),
+                None => self.ended = true,
+            }
+        }
+        Ok(())
+    }
+
+    async fn next_chunk(&mut self) -> io::Result<Option<Bytes>> {
+        if self.done {
+            return Ok(None);
+        }
+        let prefix = match self.prefix {
+            Some(prefix) => prefix,
+            None => {
+                self.fill(HEADER_LEN).await?;
+                if self.buf.len() < HEADER_LEN || &self.buf[..MAGIC.len()] != MAGIC {
+                    return Err(invalid_data("объект не зашифрован или повреждён"));
+                }
+                let prefix: [u8; PREFIX_LEN] = self.buf[MAGIC.len()..HEADER_LEN].try_into().unwrap();
+                self.buf.drain(..HEADER_LEN);
+                *self.prefix.insert(prefix)
+            }
+        };
+
+        // Кусок последний, если за ним в потоке ничего нет
+        let frame = CHUNK_LEN + TAG_LEN;
+        self.fill(frame + 1).await?;
+        let last = self.buf.len() <= frame;
+        if last && self.buf.len() < TAG_LEN {
+            return Err(invalid_data("объект обрезан"));
+        }
+
+        let mut chunk: Vec<u8> = self.buf.drain(..self.buf.len().min(frame)).collect();
+        self.cipher
+            .decrypt_in_place(Nonce::from_slice(&chunk_nonce(&prefix, self.index, last)), b"", &mut chunk)
+            .map_err(|_| invalid_data("объект повреждён или подменён"))?;
+        if last {
+            self.done = true;
+        } else {
+            self.index = self.index.checked_add(1).ok_or_else(|| invalid_data("слишком много кусков"))?;
+        }
+        Ok(Some(Bytes::from(chunk)))
+    }
+}
+
+/// Расшифровать объект потоком. Подмена обнаруживается на испорченном
+/// куске: отданные до него куски подлинные.
+pub fn decrypt_stream(
+    key: DataKey,
+    body: BoxStream<'static, io::Result<Bytes>>,
+) -> BoxStream<'static, io::Result<Bytes>> {
+    let decryptor = Decryptor {
+        cipher: key.cipher(),
+        body,
+        buf: Vec::new(),
+        ended: false,
+        prefix: None,
+        index: 0,
+        done: false,
+    };
+    futures::stream::try_unfold(decryptor, |mut decryptor| async move {
+        Ok(decryptor.next_chunk().await?.map(|chunk| (chunk, decryptor)))
+    })
+    .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::TryStreamExt;
+
+    async fn encrypt(key: &DataKey, data: &[u8]) -> Vec<u8> {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, target) = (dir.path().join("plain"), dir.path().join("sealed"));
+        std::fs::write(&source, data).unwrap();
+        encrypt_file(key, &source, &target).await.unwrap();
+        std::fs::read(&target).unwrap()
+    }
+
+    /// Расшифровать, подавая объект кусками по `piece` байт
+    async fn decrypt(key: DataKey, sealed: &[u8], piece: usize) -> io::Result<Vec<u8>> {
+        let pieces: Vec<io::Result<Bytes>> = sealed.chunks(piece).map(|p| Ok(Bytes::copy_from_slice(p))).collect();
+        let chunks: Vec<Bytes> = decrypt_stream(key, futures::stream::iter(pieces).boxed()).try_collect().await?;
+        Ok(chunks.concat())
+    }
+
+    fn same_key(key: &DataKey) -> DataKey {
+        DataKey(Zeroizing::new(*key.0))
+    }
+
+    #[tokio::test]
+    async fn test_stream_round_trip() {
+        let key = DataKey::generate();
+        for len in [0, 1, CHUNK_LEN - 1, CHUNK_LEN, CHUNK_LEN + 1, 3 * CHUNK_LEN + 17] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+            let sealed = encrypt(&key, &data).await;
+            assert_ne!(&sealed[HEADER_LEN..], &data[..], "len {}", len);
+            assert_eq!(plaintext_len(sealed.len() as u64), Some(len as u64), "len {}", len);
+            for piece in [1000, CHUNK_LEN + TAG_LEN, sealed.len()] {
+                assert_eq!(decrypt(same_key(&key), &sealed, piece).await.unwrap(), data, "len {}", len);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_detects_tampering() {
+        let key = DataKey::generate();
+        let data = vec![7u8; 2 * CHUNK_LEN + 5];
+        let sealed = encrypt(&key, &data).await;
+
+        let mut flipped = sealed.clone();
+        flipped[HEADER_LEN + CHUNK_LEN + 40] ^= 1;
+        assert!(decrypt(same_key(&key), &flipped, 4096).await.is_err());
+
+        // Отрезанный хвост из целых кусков: предпоследний кусок не помечен последним
+        let truncated = &sealed[..HEADER_LEN + 2 * (CHUNK_LEN + TAG_LEN)];
+        assert!(decrypt(same_key(&key), truncated, 4096).await.is_err());
+
+        assert!(decrypt(DataKey::generate(), &sealed, 4096).await.is_err());
+        assert!(decrypt(same_key(&key), b"plain text", 4096).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_keyring_wrap_and_rotation() {
+        let old = Keyring::new(vec![("k1".to_string(), [1; 32])]).unwrap();
+        let key = DataKey::generate();
+        let wrapped = old.wrap(&key, "hash-a").await.unwrap();
+        assert_eq!(wrapped.key_id, "k1");
+        assert!(old.unwrap(&wrapped, "hash-b").await.is_err(), "обёртка привязана к объекту");
+
+        let spec = format!("k2:{},k1:{}", BASE64.encode([2; 32]), BASE64.encode([1; 32]));
+        let rotated = Keyring::parse(&spec).unwrap();
+        assert_eq!(rotated.current_key_id(), "k2");
+        let unwrapped = rotated.unwrap(&wrapped, "hash-a").await.unwrap();
+        let rewrapped = rotated.wrap(&unwrapped, "hash-a").await.unwrap();
+        assert_eq!(rewrapped.key_id, "k2");
+        assert_eq!(*rotated.unwrap(&rewrapped, "hash-a").await.unwrap().0, *key.0);
+
+        assert!(Keyring::parse("").is_err());
+        assert!(Keyring::parse("k1:c2hvcnQ=").is_err());
+        assert!(Keyring::parse(&format!("k1:{0},k1:{0}", BASE64.encode([1; 32]))).is_err());
+    }
+}
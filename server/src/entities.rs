@@ -0,0 +1,339 @@
+// server/src/entities.rs
+//! Разметка текста сообщений (entities) в формате Telegram
+//!
+//! Сущность — тип и диапазон `offset`/`length` в единицах UTF-16, как
+//! в Bot API, поэтому разметку из экспорта Telegram можно принять как есть.
+//! Оформление и ссылки присылает клиент; упоминания `@username` и хэштеги
+//! сервер находит в тексте сам, присланные клиентом заменяются найденными.
+//! Диапазоны не пересекаются частично, внутри `code` и `pre` других
+//! сущностей нет. Неизвестные типы отбрасываются.
+
+use serde::{Deserialize, Serialize};
+
+/// Сколько сущностей может быть в одном сообщении
+pub const MAX_ENTITIES: usize = 100;
+
+/// Схемы, допустимые в `text_link`
+const LINK_SCHEMES: [&str; 4] = ["http://", "https://", "mailto:", "tg://"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EntityKind {
+    Bold,
+    Italic,
+    Underline,
+    Strikethrough,
+    Spoiler,
+    Code,
+    Pre {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        language: Option<String>,
+    },
+    Url,
+    TextLink { url: String },
+    /// `@username` в тексте
+    Mention,
+    /// Упоминание без имени пользователя: ссылка на id
+    TextMention { user_id: String },
+    Hashtag,
+    #[serde(other)]
+    Unknown,
+}
+
+impl EntityKind {
+    /// Находит сервер, а не присылает клиент
+    fn is_detected(&self) -> bool {
+        matches!(self, EntityKind::Mention | EntityKind::Hashtag)
+    }
+
+    /// Код: внутри не бывает других сущностей
+    fn is_code(&self) -> bool {
+        matches!(self, EntityKind::Code | EntityKind::Pre { .. })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageEntity {
+    #[serde(flatten)]
+    pub kind: EntityKind,
+    /// Начало, в единицах UTF-16
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl MessageEntity {
+    fn end(&self) -> usize {
+        self.offset + self.length
+    }
+
+    fn contains(&self, other: &MessageEntity) -> bool {
+        self.offset <= other.offset && other.end() <= self.end()
+    }
+
+    fn overlaps(&self, other: &MessageEntity) -> bool {
+        self.offset < other.end() && other.offset < self.end()
+    }
+}
+
+/// Почему разметка отклонена
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntityError {
+    TooMany,
+    /// Пустой диапазон, за концом текста или посреди суррогатной пары
+    BadRange,
+    /// Частичное пересечение или сущность внутри кода
+    BadNesting,
+    BadLink,
+}
+
+/// Разметка сообщения; в базе — JSON в `messages.entities`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Entities(pub Vec<MessageEntity>);
+
+impl Entities {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Значение колонки: без разметки — NULL
+    pub fn to_column(&self) -> Option<String> {
+        if self.0.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&self.0).ok()
+        }
+    }
+}
+
+impl TryFrom<Option<String>> for Entities {
+    type Error = serde_json::Error;
+
+    fn try_from(column: Option<String>) -> Result<Self, Self::Error> {
+        match column {
+            Some(json) => serde_json::from_str(&json).map(Entities),
+            None => Ok(Entities::default()),
+        }
+    }
+}
+
+/// Разметка для сохранения: проверенные сущности клиента и найденные
+/// в тексте упоминания и хэштеги (кроме попавших в код), по порядку
+pub fn normalize(text: &str, entities: Vec<MessageEntity>) -> Result<Entities, EntityError> {
+    let mut entities: Vec<MessageEntity> = entities
+        .into_iter()
+        .filter(|entity| entity.kind != EntityKind::Unknown && !entity.kind.is_detected())
+        .collect();
+    validate(text, &entities)?;
+
+    for found in detect(text) {
+        if entities.iter().all(|entity| {
+            !entity.overlaps(&found) || (!entity.kind.is_code() && (entity.contains(&found) || found.contains(entity)))
+        }) {
+            entities.push(found);
+        }
+    }
+    if entities.len() > MAX_ENTITIES {
+        return Err(EntityError::TooMany);
+    }
+
+    // Объемлющая сущность раньше вложенной
+    entities.sort_by(|a, b| a.offset.cmp(&b.offset).then(b.length.cmp(&a.length)));
+    Ok(Entities(entities))
+}
+
+/// Проверить сущности клиента
+fn validate(text: &str, entities: &[MessageEntity]) -> Result<(), EntityError> {
+    if entities.len() > MAX_ENTITIES {
+        return Err(EntityError::TooMany);
+    }
+
+    let boundaries = utf16_boundaries(text);
+    for entity in entities {
+        // Смещение и длина от клиента: сумма может переполниться
+        let Some(end) = entity.offset.checked_add(entity.length) else {
+            return Err(EntityError::BadRange);
+        };
+        if entity.length == 0
+            || boundaries.binary_search(&entity.offset).is_err()
+            || boundaries.binary_search(&end).is_err()
+        {
+            return Err(EntityError::BadRange);
+        }
+        match &entity.kind {
+            EntityKind::TextLink { url } if !LINK_SCHEMES.iter().any(|scheme| url.starts_with(scheme)) => {
+                return Err(EntityError::BadLink);
+            }
+            EntityKind::TextMention { user_id } if user_id.is_empty() => return Err(EntityError::BadLink),
+            _ => {}
+        }
+    }
+
+    for (index, a) in entities.iter().enumerate() {
+        for b in &entities[index + 1..] {
+            if !a.overlaps(b) {
+                continue;
+            }
+            let nested = a.contains(b) || b.contains(a);
+            if !nested || a.kind.is_code() || b.kind.is_code() {
+                return Err(EntityError::BadNesting);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Смещения UTF-16, на которых начинаются символы, и длина текста
+fn utf16_boundaries(text: &str) -> Vec<usize> {
+    let mut boundaries = Vec::with_capacity(text.len() + 1);
+    let mut offset = 0;
+    for c in text.chars() {
+        boundaries.push(offset);
+        offset += c.len_utf16();
+    }
+    boundaries.push(offset);
+    boundaries
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Упоминания `@username` и хэштеги `#тег` в начале слова
+pub fn detect(text: &str) -> Vec<MessageEntity> {
+    let mut entities = Vec::new();
+    let mut offset = 0;
+    let mut previous: Option<char> = None;
+    for (index, c) in text.char_indices() {
+        let start = offset;
+        offset += c.len_utf16();
+        let at_word_start = previous.is_none_or(|p| !is_tag_char(p) && p != '@' && p != '#');
+        previous = Some(c);
+
+        let (kind, accepts): (EntityKind, fn(char) -> bool) = match c {
+            '@' if at_word_start => (EntityKind::Mention, is_name_char),
+            '#' if at_word_start => (EntityKind::Hashtag, is_tag_char),
+            _ => continue,
+        };
+        let rest = &text[index + 1..];
+        let body = &rest[..rest.find(|c: char| !accepts(c)).unwrap_or(rest.len())];
+        if !body.is_empty() {
+            entities.push(MessageEntity { kind, offset: start, length: 1 + body.encode_utf16().count() });
+        }
+    }
+    entities
+}
+
+/// Текст сущности по диапазону UTF-16
+pub fn slice(text: &str, entity: &MessageEntity) -> String {
+    let units: Vec<u16> = text.encode_utf16().skip(entity.offset).take(entity.length).collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// Имена из упоминаний `@username`, без повторов
+pub fn mentioned_usernames(text: &str, entities: &Entities) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for entity in entities.0.iter().filter(|entity| entity.kind == EntityKind::Mention) {
+        let name = slice(text, entity).trim_start_matches('@').to_string();
+        if !names.iter().any(|known| known.eq_ignore_ascii_case(&name)) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Пользователи из `text_mention`, без повторов
+pub fn mentioned_user_ids(entities: &Entities) -> Vec<String> {
+    let mut ids: Vec<String> = Vec::new();
+    for entity in &entities.0 {
+        if let EntityKind::TextMention { user_id } = &entity.kind {
+            if !ids.contains(user_id) {
+                ids.push(user_id.clone());
+            }
+        }
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(kind: EntityKind, offset: usize, length: usize) -> MessageEntity {
+        MessageEntity { kind, offset, length }
+    }
+
+    #[test]
+    fn test_mentioned_usernames() {
+        let text = "@alice, смотри (@Bob_2) и ещё раз @ALICE";
+        let entities = normalize(text, Vec::new()).unwrap();
+        assert_eq!(mentioned_usernames(text, &entities), vec!["alice", "Bob_2"]);
+        assert!(detect("mail@example.com @ @@x").is_empty());
+    }
+
+    #[test]
+    fn test_detect_uses_utf16_offsets() {
+        // Эмодзи — суррогатная пара: две единицы UTF-16
+        let text = "😀 @bob #тег_1, a#b";
+        assert_eq!(detect(text), vec![
+            entity(EntityKind::Mention, 3, 4),
+            entity(EntityKind::Hashtag, 8, 6),
+        ]);
+        assert_eq!(slice(text, &detect(text)[1]), "#тег_1");
+    }
+
+    #[test]
+    fn test_normalize_validates_ranges_and_nesting() {
+        let text = "жирный 😀 код";
+        let bold = entity(EntityKind::Bold, 0, 6);
+        assert!(normalize(text, vec![bold.clone()]).is_ok());
+        assert_eq!(normalize(text, vec![entity(EntityKind::Bold, 0, 0)]), Err(EntityError::BadRange));
+        assert_eq!(normalize(text, vec![entity(EntityKind::Bold, 0, 14)]), Err(EntityError::BadRange));
+        // Посреди суррогатной пары
+        assert_eq!(normalize(text, vec![entity(EntityKind::Bold, 0, 8)]), Err(EntityError::BadRange));
+        // Конец за пределами `usize`: ни паники, ни переноса через ноль
+        assert_eq!(normalize(text, vec![entity(EntityKind::Bold, 1, usize::MAX)]), Err(EntityError::BadRange));
+
+        let italic = entity(EntityKind::Italic, 2, 2);
+        assert!(normalize(text, vec![bold.clone(), italic]).is_ok());
+        let crossing = entity(EntityKind::Italic, 4, 5);
+        assert_eq!(normalize(text, vec![bold.clone(), crossing]), Err(EntityError::BadNesting));
+        let code = entity(EntityKind::Code, 0, 6);
+        assert_eq!(normalize(text, vec![code, bold]), Err(EntityError::BadNesting));
+
+        let link = entity(EntityKind::TextLink { url: "javascript:alert(1)".into() }, 0, 6);
+        assert_eq!(normalize(text, vec![link]), Err(EntityError::BadLink));
+    }
+
+    #[test]
+    fn test_normalize_replaces_detected_entities() {
+        let text = "`@alice` и @bob";
+        let code = entity(EntityKind::Code, 0, 8);
+        // Присланное клиентом упоминание заменяется найденным; в коде — не ищем
+        let fake = entity(EntityKind::Mention, 11, 2);
+        let entities = normalize(text, vec![fake, code.clone()]).unwrap();
+        assert_eq!(entities.0, vec![code, entity(EntityKind::Mention, 11, 4)]);
+    }
+
+    #[test]
+    fn test_telegram_format_round_trip() {
+        let json = serde_json::json!([
+            { "type": "bold", "offset": 0, "length": 4 },
+            { "type": "pre", "offset": 5, "length": 3, "language": "rust" },
+            { "type": "text_link", "offset": 9, "length": 2, "url": "https://example.com" },
+            { "type": "custom_emoji", "offset": 12, "length": 2, "custom_emoji_id": "1" },
+        ]);
+        let entities: Vec<MessageEntity> = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(entities[3].kind, EntityKind::Unknown);
+
+        let normalized = normalize("bold pre ab 😀", entities).unwrap();
+        assert_eq!(normalized.0.len(), 3);
+        let column = normalized.to_column().unwrap();
+        assert_eq!(Entities::try_from(Some(column)).unwrap(), normalized);
+        assert_eq!(serde_json::to_value(&normalized).unwrap()[1], json[1]);
+    }
+}
@@ -0,0 +1,86 @@
+// server/src/fanout.rs
+//! Очередь доставки событий всем участникам чата
+//!
+//! Обработчик запроса только ставит задачу в очередь. Фоновый обработчик
+//! читает участников пачками по `FANOUT_BATCH` и отправляет событие тем,
+//! кто подключён, так что публикация поста в канал не зависит от числа
+//! подписчиков.
+
+use std::sync::Arc;
+use tokio::sync::{mpsc, RwLock};
+use crate::{
+    db::DbPool,
+    websocket::{WebSocketManager, WsMessage},
+};
+
+/// Сколько участников читается из базы за раз
+pub const FANOUT_BATCH: i64 = 500;
+
+/// Сколько задач ждёт в очереди; дальше постановка ждёт освобождения места
+const FANOUT_QUEUE_SIZE: usize = 1024;
+
+/// Событие для всех участников чата
+#[derive(Debug, Clone)]
+pub struct FanoutJob {
+    pub chat_id: String,
+    /// Кому не отправлять (обычно автору)
+    pub except_user_id: Option<String>,
+    pub event: WsMessage,
+}
+
+#[derive(Clone)]
+pub struct FanoutQueue {
+    sender: mpsc::Sender<FanoutJob>,
+}
+
+impl FanoutQueue {
+    /// Запустить фоновый обработчик; он завершается вместе с последней
+    /// копией очереди
+    pub fn start(db: DbPool, ws: Arc<RwLock<WebSocketManager>>) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<FanoutJob>(FANOUT_QUEUE_SIZE);
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                if let Err(e) = deliver(&db, &ws, &job).await {
+                    tracing::error!("Ошибка рассылки в чат {}: {}", job.chat_id, e);
+                }
+            }
+        });
+        Self { sender }
+    }
+
+    pub async fn enqueue(&self, job: FanoutJob) {
+        if self.sender.send(job).await.is_err() {
+            tracing::error!("Очередь рассылки остановлена");
+        }
+    }
+}
+
+/// Отправить событие подключённым участникам чата. Возвращает, скольким
+/// пользователям оно ушло.
+pub async fn deliver(db: &DbPool, ws: &RwLock<WebSocketManager>, job: &FanoutJob) -> Result<usize, sqlx::Error> {
+    let chats = db.chats();
+    let mut after = String::new();
+    let mut delivered = 0;
+
+    loop {
+        let mut batch = chats.member_ids_after(&job.chat_id, &after, FANOUT_BATCH).await?;
+        {
+            // Блокировка только на пачку, чтобы не держать подключения
+            let ws = ws.read().await;
+            for user_id in &batch {
+                if job.except_user_id.as_deref() != Some(user_id.as_str()) && ws.is_online(user_id) {
+                    ws.send_to_user(user_id, job.event.clone());
+                    delivered += 1;
+                }
+            }
+        }
+
+        // Неполная пачка — последняя
+        let full = batch.len() as i64 == FANOUT_BATCH;
+        match batch.pop() {
+            Some(last) if full => after = last,
+            _ => break,
+        }
+    }
+    Ok(delivered)
+}
@@ -6,10 +6,12 @@
 pub mod api;
 pub mod auth;
 pub mod db;
+pub mod encryption;
+pub mod entities;
+pub mod fanout;
+pub mod media;
 pub mod middleware;
+pub mod permissions;
+pub mod push;
+pub mod storage;
 pub mod websocket;
-
-// Ре-экспорт для тестов
-pub use api;
-pub use auth;
-pub use db;
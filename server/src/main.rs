@@ -1,23 +1,14 @@
 // server/src/main.rs
 #![recursion_limit = "256"]
 
-mod api;
-mod db;
-mod websocket;
-mod auth;
-mod middleware;
+use liberty_reach_server::{api, db, encryption, push, storage, websocket};
 
 use axum::{
     Router,
-    routing::{get, post},
-    extract::WebSocketUpgrade,
+    routing::get,
+    extract::{State, WebSocketUpgrade},
     response::Response,
 };
-use tower_http::{
-    cors::{Any, CorsLayer},
-    trace::TraceLayer,
-    services::ServeDir,
-};
 use std::net::SocketAddr;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 use tokio::time::{Duration, interval};
@@ -35,22 +26,29 @@ async fn main() -> anyhow::Result<()> {
     // Загрузка .env
     dotenvy::dotenv().ok();
 
+    // Служебные режимы: миграции, проверка схемы, смена ключей шифрования
+    if let Some(mode) = std::env::args().nth(1) {
+        if mode == "--rotate-keys" {
+            return rotate_keys().await;
+        }
+        return run_schema_command(&mode).await;
+    }
+
     // Инициализация базы данных
     let db = db::init_database().await?;
     tracing::info!("База данных инициализирована");
 
+    // Соединений после перезапуска нет — никто не в сети
+    db.users().reset_presence().await?;
+
     // Запуск задачи очистки просроченных сообщений
     let db_clone = db.clone();
     tokio::spawn(async move {
         let mut interval = interval(Duration::from_secs(300)); // Каждые 5 минут
         loop {
             interval.tick().await;
-            match sqlx::query("DELETE FROM messages WHERE delete_at IS NOT NULL AND delete_at < CURRENT_TIMESTAMP")
-                .execute(&*db_clone)
-                .await
-            {
-                Ok(result) => {
-                    let count = result.rows_affected();
+            match api::features::cleanup_expired_messages(&db_clone).await {
+                Ok(count) => {
                     if count > 0 {
                         tracing::info!("Удалено {} просроченных сообщений (24h)", count);
                     }
@@ -62,13 +60,24 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    // Создание состояния приложения
+    let uploads_dir = std::env::var("UPLOADS_DIR").unwrap_or_else(|_| "./uploads".to_string());
+    let app_state = api::AppState::new(
+        db.clone(),
+        std::env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string()),
+        uploads_dir.clone(),
+    )
+    .with_storage(storage::from_env(&uploads_dir)?)
+    .with_key_provider(encryption::from_env()?)
+    .with_push(push::from_env()?);
+
     // Запуск задачи отправки отложенных сообщений
-    let db_clone = db.clone();
+    let state_clone = app_state.clone();
     tokio::spawn(async move {
         let mut interval = interval(Duration::from_secs(60)); // Каждую минуту
         loop {
             interval.tick().await;
-            match crate::api::extra::send_scheduled_messages(&*db_clone).await {
+            match api::scheduled::send_due(&state_clone).await {
                 Ok(count) => {
                     if count > 0 {
                         tracing::info!("Отправлено {} отложенных сообщений", count);
@@ -81,52 +90,79 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
-    // Создание состояния приложения
-    let app_state = api::AppState {
-        db: db.clone(),
-        jwt_secret: std::env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string()),
-        uploads_dir: std::env::var("UPLOADS_DIR").unwrap_or_else(|_| "./uploads".to_string()),
-    };
+    // Перенос файлов, загруженных до хранилища по хешу
+    let state_clone = app_state.clone();
+    tokio::spawn(async move {
+        match api::files::import_legacy_files(&state_clone).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Перенесено в хранилище {} файлов", count),
+            Err(e) => tracing::error!("Ошибка переноса файлов: {}", e),
+        }
+    });
+
+    // Хеши почты для импорта контактов у старых пользователей
+    let state_clone = app_state.clone();
+    tokio::spawn(async move {
+        match api::contacts::backfill_email_hashes(&state_clone).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Досчитаны хеши почты: {}", count),
+            Err(e) => tracing::error!("Ошибка расчёта хешей почты: {}", e),
+        }
+    });
+
+    // Запуск задачи очистки брошенных загрузок и содержимого без ссылок,
+    // дообработка медиа
+    let state_clone = app_state.clone();
+    tokio::spawn(async move {
+        let mut interval = interval(Duration::from_secs(3600)); // Каждый час
+        loop {
+            interval.tick().await;
+            match api::files::cleanup_abandoned_uploads(&state_clone).await {
+                Ok(count) => {
+                    if count > 0 {
+                        tracing::info!("Удалено {} брошенных загрузок", count);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Ошибка очистки загрузок: {}", e);
+                }
+            }
+            // Первый тик сразу: подбирает очередь, оставшуюся с прошлого запуска
+            match api::files::process_pending_media(&state_clone).await {
+                Ok(count) => {
+                    if count > 0 {
+                        tracing::info!("Обработано из очереди медиа: {}", count);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Ошибка обработки медиа: {}", e);
+                }
+            }
+            match api::files::collect_unreferenced_content(&state_clone).await {
+                Ok(count) => {
+                    if count > 0 {
+                        tracing::info!("Удалено из хранилища {} файлов без ссылок", count);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Ошибка очистки хранилища: {}", e);
+                }
+            }
+        }
+    });
 
-    // CORS
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
-
-    // Роуты API
-    let api_routes = Router::new()
-        .route("/health", get(api::health))
-        .route("/auth/register", post(api::auth::register))
-        .route("/auth/login", post(api::auth::login))
-        .route("/auth/verify", post(api::auth::verify_token))
-        .route("/users/me", get(api::users::get_current_user))
-        .route("/users/:id", get(api::users::get_user))
-        .route("/chats", get(api::chats::list_chats))
-        .route("/chats", post(api::chats::create_chat))
-        .route("/chats/:chat_id", get(api::chats::get_chat))
-        .route("/chats/:chat_id/messages", get(api::messages::list_messages))
-        .route("/chats/:chat_id/messages", post(api::messages::send_message))
-        .route("/files/upload", post(api::files::upload_file))
-        .route("/files/:file_id", get(api::files::get_file))
-        .route("/web3/balance", get(api::web3::get_balance))
-        .route("/web3/swap", post(api::web3::swap_tokens))
-        .route("/ai/translate", post(api::ai::translate))
-        .route("/ai/summarize", post(api::ai::summarize))
-        .route("/ai/chat", post(api::ai::chat));
+    // Роуты API (общие с тестами, см. api::create_router)
+    let api_routes = api::create_router(app_state.clone());
 
     // WebSocket для реального времени
     let ws_routes = Router::new()
-        .route("/ws", get(websocket_handler));
+        .route("/ws", get(websocket_handler))
+        .with_state(app_state);
 
     // Основное приложение
     let app = Router::new()
         .nest("/api/v1", api_routes)
-        .nest("/ws", ws_routes)
-        .nest_service("/uploads", ServeDir::new("./uploads"))
-        .layer(cors)
-        .layer(TraceLayer::new_for_http())
-        .with_state(app_state);
+        .nest("/ws", ws_routes);
 
     // Запуск сервера
     let addr: SocketAddr = std::env::var("SERVER_ADDR")
@@ -141,9 +177,65 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
+/// `--migrate` применяет миграции, `--check-schema` сверяет схему
+/// с миграциями бинарника и завершается ошибкой при расхождении
+async fn run_schema_command(mode: &str) -> anyhow::Result<()> {
+    let db = db::Database::connect(&db::database_url()).await?;
+
+    let result = match mode {
+        "--migrate" => {
+            let before = db.schema_status().await?;
+            db.migrate().await?;
+            tracing::info!("Применено миграций: {}", before.pending.len());
+            Ok(())
+        }
+        "--check-schema" => {
+            let status = db.schema_status().await?;
+            if status.is_current() {
+                tracing::info!("Схема актуальна");
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("Схема не соответствует миграциям: {}", status))
+            }
+        }
+        _ => Err(anyhow::anyhow!(
+            "Неизвестный аргумент {}: ожидается --migrate, --check-schema или --rotate-keys",
+            mode
+        )),
+    };
+
+    db.close().await;
+    result
+}
+
+/// Перешифровать ключи данных файлов текущим мастер-ключом
+/// и зашифровать файлы, сохранённые открытыми
+async fn rotate_keys() -> anyhow::Result<()> {
+    let db = std::sync::Arc::new(db::Database::connect(&db::database_url()).await?);
+    let uploads_dir = std::env::var("UPLOADS_DIR").unwrap_or_else(|_| "./uploads".to_string());
+    let state = api::AppState::new(
+        db.clone(),
+        std::env::var("JWT_SECRET").unwrap_or_else(|_| "secret".to_string()),
+        uploads_dir.clone(),
+    )
+    .with_storage(storage::from_env(&uploads_dir)?)
+    .with_key_provider(encryption::from_env()?)
+    .with_push(push::from_env()?);
+
+    let result = api::files::rotate_file_keys(&state).await;
+    db.close().await;
+    let rotation = result?;
+    tracing::info!(
+        "Ключей перешифровано: {}, файлов зашифровано: {}",
+        rotation.rewrapped,
+        rotation.encrypted
+    );
+    Ok(())
+}
+
 async fn websocket_handler(
     ws: WebSocketUpgrade,
-    // state: State<api::AppState>,
+    State(state): State<api::AppState>,
 ) -> Response {
-    ws.on_upgrade(websocket::handle_socket)
+    ws.on_upgrade(move |socket| websocket::handle_socket(socket, state))
 }
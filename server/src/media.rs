@@ -0,0 +1,371 @@
+// server/src/media.rs
+//! Обработка медиа: удаление метаданных, превью, blurhash, длительность
+//! и волна аудио
+//!
+//! Все функции синхронные и тяжёлые — вызывать через `spawn_blocking`.
+//! Очередь обработки и запись результатов — в `api::files`.
+
+use image::{codecs::jpeg::JpegEncoder, DynamicImage, ImageFormat, ImageReader, Limits};
+use img_parts::{jpeg::Jpeg, png::Png, webp::WebP, Bytes, ImageEXIF};
+use std::io::Cursor;
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{DecoderOptions, CODEC_TYPE_NULL},
+    errors::Error as AudioError,
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::MetadataOptions,
+    probe::Hint,
+};
+
+/// Превью: метка и наибольшая сторона в пикселях
+pub const THUMBNAIL_SIZES: [(&str, u32); 3] = [("s", 100), ("m", 320), ("x", 800)];
+
+/// Число столбцов волны голосового сообщения
+pub const WAVEFORM_LEN: usize = 100;
+
+/// Больше этого по любой стороне картинки не декодируются
+const MAX_IMAGE_SIDE: u32 = 16384;
+
+const JPEG_QUALITY: u8 = 80;
+
+/// Форматы, которые умеют `analyze_image` и `analyze_audio`
+pub fn is_supported(mime_type: &str) -> bool {
+    matches!(
+        mime_type,
+        "image/jpeg" | "image/png" | "image/gif" | "image/webp" | "image/bmp" | "image/tiff"
+            | "audio/mpeg" | "audio/wav" | "audio/flac" | "audio/ogg" | "audio/mp4"
+    )
+}
+
+/// Форматы, из которых `strip_metadata` умеет убирать метаданные
+pub fn has_strippable_metadata(mime_type: &str) -> bool {
+    matches!(mime_type, "image/jpeg" | "image/png" | "image/webp")
+}
+
+/// Убрать EXIF, XMP, IPTC и текстовые поля без перекодирования.
+/// Ориентация JPEG сохраняется (минимальный EXIF из одного тега).
+/// `None` — убирать нечего.
+pub fn strip_metadata(data: &[u8], mime_type: &str) -> Result<Option<Vec<u8>>, img_parts::Error> {
+    let data = Bytes::copy_from_slice(data);
+    let stripped = match mime_type {
+        "image/jpeg" => {
+            let mut jpeg = Jpeg::from_bytes(data.clone())?;
+            let orientation = jpeg
+                .exif()
+                .and_then(|exif| image::metadata::Orientation::from_exif_chunk(&exif))
+                .map(|o| o.to_exif())
+                .filter(|&o| o != 1);
+            // APP1 — EXIF и XMP, APP13 — IPTC, COM — комментарии
+            for marker in [0xE1, 0xED, 0xFE] {
+                jpeg.remove_segments_by_marker(marker);
+            }
+            jpeg.set_exif(orientation.map(|o| Bytes::from(orientation_exif(o))));
+            jpeg.encoder().bytes()
+        }
+        "image/png" => {
+            let mut png = Png::from_bytes(data.clone())?;
+            for kind in [*b"eXIf", *b"tEXt", *b"zTXt", *b"iTXt", *b"tIME"] {
+                png.remove_chunks_by_type(kind);
+            }
+            png.encoder().bytes()
+        }
+        "image/webp" => {
+            let mut webp = WebP::from_bytes(data.clone())?;
+            webp.remove_chunks_by_id(*b"XMP ");
+            // Снимает и флаги EXIF/XMP в заголовке VP8X
+            webp.set_exif(None);
+            webp.encoder().bytes()
+        }
+        _ => return Ok(None),
+    };
+
+    Ok((stripped != data).then(|| stripped.to_vec()))
+}
+
+/// EXIF (TIFF, big-endian) с единственным тегом Orientation
+fn orientation_exif(orientation: u8) -> Vec<u8> {
+    let mut exif = b"MM\0\x2A\0\0\0\x08".to_vec();
+    exif.extend_from_slice(&1u16.to_be_bytes());
+    // Тег 0x0112, тип SHORT, одно значение
+    exif.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0, 0, 0, 1, 0, orientation, 0, 0]);
+    exif.extend_from_slice(&0u32.to_be_bytes());
+    exif
+}
+
+pub struct Thumbnail {
+    /// Метка из `THUMBNAIL_SIZES`
+    pub size: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub mime_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+pub struct ImageInfo {
+    /// Размеры с учётом ориентации
+    pub width: u32,
+    pub height: u32,
+    pub blurhash: String,
+    /// Только размеры меньше исходного
+    pub thumbnails: Vec<Thumbnail>,
+}
+
+/// Размеры, blurhash и превью картинки
+pub fn analyze_image(data: &[u8]) -> anyhow::Result<ImageInfo> {
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIDE);
+    limits.max_image_height = Some(MAX_IMAGE_SIDE);
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder()?;
+    let orientation = image::ImageDecoder::orientation(&mut decoder)?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let small = image.thumbnail(32, 32).to_rgba8();
+    let blurhash = blurhash::encode(4, 3, small.width(), small.height(), small.as_raw())
+        .map_err(|e| anyhow::anyhow!("blurhash: {:?}", e))?;
+
+    let mut thumbnails = Vec::new();
+    for (size, side) in THUMBNAIL_SIZES {
+        if image.width().max(image.height()) <= side {
+            continue;
+        }
+        let thumbnail = image.thumbnail(side, side);
+        let (mime_type, data) = encode_thumbnail(&thumbnail)?;
+        thumbnails.push(Thumbnail {
+            size,
+            width: thumbnail.width(),
+            height: thumbnail.height(),
+            mime_type,
+            data,
+        });
+    }
+
+    Ok(ImageInfo {
+        width: image.width(),
+        height: image.height(),
+        blurhash,
+        thumbnails,
+    })
+}
+
+/// JPEG, а для картинок с прозрачностью — PNG
+fn encode_thumbnail(image: &DynamicImage) -> image::ImageResult<(&'static str, Vec<u8>)> {
+    let mut data = Vec::new();
+    if image.color().has_alpha() {
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+        Ok(("image/png", data))
+    } else {
+        JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY).encode_image(&image.to_rgb8())?;
+        Ok(("image/jpeg", data))
+    }
+}
+
+pub struct AudioInfo {
+    pub duration_ms: u64,
+    /// `WAVEFORM_LEN` пиковых уровней 0–255, нормированных по самому громкому
+    pub waveform: Vec<u8>,
+}
+
+/// Длительность и волна аудио; файл декодируется целиком
+pub fn analyze_audio(file: std::fs::File, mime_type: &str) -> anyhow::Result<AudioInfo> {
+    let mut hint = Hint::new();
+    hint.mime_type(mime_type);
+    let source = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(&hint, source, &FormatOptions::default(), &MetadataOptions::default())?
+        .format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| anyhow::anyhow!("нет аудиодорожки"))?;
+    let track_id = track.id;
+    let sample_rate = track
+        .codec_params
+        .sample_rate
+        .ok_or_else(|| anyhow::anyhow!("неизвестна частота дискретизации"))?;
+    let mut decoder = symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    // Пики блоков по 10 мс: пакеты бывают длиннее столбца волны
+    let block_len = u64::from(sample_rate / 100).max(1);
+    let mut peaks: Vec<f32> = Vec::new();
+    let mut block_peak = 0f32;
+    let mut frames = 0u64;
+    let mut samples: Option<SampleBuffer<f32>> = None;
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(AudioError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Битый пакет пропускается, как в плеерах
+            Err(AudioError::DecodeError(_)) => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let spec = *decoded.spec();
+        let needed = decoded.capacity() * spec.channels.count();
+        let buffer = match &mut samples {
+            Some(buffer) if buffer.capacity() >= needed => buffer,
+            _ => samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+        };
+        buffer.copy_interleaved_ref(decoded);
+
+        for frame in buffer.samples().chunks(spec.channels.count()) {
+            block_peak = frame.iter().fold(block_peak, |peak, sample| peak.max(sample.abs()));
+            frames += 1;
+            if frames.is_multiple_of(block_len) {
+                peaks.push(block_peak);
+                block_peak = 0.0;
+            }
+        }
+    }
+    if !frames.is_multiple_of(block_len) {
+        peaks.push(block_peak);
+    }
+
+    Ok(AudioInfo {
+        duration_ms: frames * 1000 / u64::from(sample_rate),
+        waveform: waveform(&peaks),
+    })
+}
+
+/// Сжать пики блоков в `WAVEFORM_LEN` столбцов; короткая запись
+/// растягивается повтором блоков
+fn waveform(peaks: &[f32]) -> Vec<u8> {
+    if peaks.is_empty() {
+        return vec![0; WAVEFORM_LEN];
+    }
+    let columns: Vec<f32> = (0..WAVEFORM_LEN)
+        .map(|column| {
+            let start = column * peaks.len() / WAVEFORM_LEN;
+            let end = ((column + 1) * peaks.len() / WAVEFORM_LEN).max(start + 1);
+            peaks[start..end].iter().fold(0f32, |loudest, &peak| loudest.max(peak))
+        })
+        .collect();
+
+    let loudest = columns.iter().fold(0f32, |loudest, &peak| loudest.max(peak));
+    columns
+        .iter()
+        .map(|&peak| if loudest > 0.0 { (peak / loudest * 255.0).round() as u8 } else { 0 })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageEncoder, RgbImage};
+
+    /// EXIF с описанием (место съёмки) и ориентацией
+    fn exif_with(orientation: u8, description: &str) -> Vec<u8> {
+        let mut exif = b"MM\0\x2A\0\0\0\x08".to_vec();
+        exif.extend_from_slice(&2u16.to_be_bytes());
+        let text_len = description.len() as u32 + 1;
+        exif.extend_from_slice(&[0x01, 0x0E, 0x00, 0x02]);
+        exif.extend_from_slice(&text_len.to_be_bytes());
+        exif.extend_from_slice(&38u32.to_be_bytes());
+        exif.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0, 0, 0, 1, 0, orientation, 0, 0]);
+        exif.extend_from_slice(&0u32.to_be_bytes());
+        exif.extend_from_slice(description.as_bytes());
+        exif.push(0);
+        exif
+    }
+
+    fn jpeg(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_fn(width, height, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, 128]));
+        let mut data = Vec::new();
+        JpegEncoder::new(&mut data).encode_image(&image).unwrap();
+        data
+    }
+
+    fn with_exif(data: Vec<u8>, exif: Vec<u8>) -> Vec<u8> {
+        let mut jpeg = Jpeg::from_bytes(data.into()).unwrap();
+        jpeg.set_exif(Some(exif.into()));
+        jpeg.encoder().bytes().to_vec()
+    }
+
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack.windows(needle.len()).any(|w| w == needle)
+    }
+
+    #[test]
+    fn test_strip_jpeg_keeps_only_orientation() {
+        let original = with_exif(jpeg(40, 20), exif_with(6, "GPS 55.7558N 37.6173E"));
+        assert!(contains(&original, b"55.7558N"));
+
+        let stripped = strip_metadata(&original, "image/jpeg").unwrap().unwrap();
+        assert!(!contains(&stripped, b"55.7558N"));
+        let exif = Jpeg::from_bytes(stripped.clone().into()).unwrap().exif().unwrap();
+        assert_eq!(exif.to_vec(), orientation_exif(6));
+
+        // Повторная очистка ничего не меняет, картинка декодируется
+        assert!(strip_metadata(&stripped, "image/jpeg").unwrap().is_none());
+        let info = analyze_image(&stripped).unwrap();
+        assert_eq!((info.width, info.height), (20, 40));
+    }
+
+    #[test]
+    fn test_strip_png_text_chunks() {
+        let image = RgbImage::new(4, 4);
+        let mut data = Vec::new();
+        image::codecs::png::PngEncoder::new(&mut data)
+            .write_image(image.as_raw(), 4, 4, image::ExtendedColorType::Rgb8)
+            .unwrap();
+        let mut png = Png::from_bytes(data.into()).unwrap();
+        let chunks = png.chunks_mut();
+        let at = chunks.len() - 1;
+        chunks.insert(at, img_parts::png::PngChunk::new(*b"tEXt", Bytes::from_static(b"Location\0Moscow")));
+        let original = png.encoder().bytes().to_vec();
+
+        let stripped = strip_metadata(&original, "image/png").unwrap().unwrap();
+        assert!(!contains(&stripped, b"Moscow"));
+        assert!(analyze_image(&stripped).is_ok());
+        assert!(strip_metadata(b"not an image", "text/plain").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_thumbnails_follow_orientation_and_skip_upscaling() {
+        let rotated = with_exif(jpeg(1000, 500), orientation_exif(6));
+        let info = analyze_image(&rotated).unwrap();
+        assert_eq!((info.width, info.height), (500, 1000));
+        let sizes: Vec<_> = info.thumbnails.iter().map(|t| (t.size, t.width, t.height)).collect();
+        assert_eq!(sizes, [("s", 50, 100), ("m", 160, 320), ("x", 400, 800)]);
+        for thumbnail in &info.thumbnails {
+            assert_eq!(thumbnail.mime_type, "image/jpeg");
+            let decoded = image::load_from_memory(&thumbnail.data).unwrap();
+            assert_eq!((decoded.width(), decoded.height()), (thumbnail.width, thumbnail.height));
+        }
+        assert_eq!(info.blurhash.len(), 4 + 2 * 4 * 3);
+
+        let small = analyze_image(&jpeg(200, 100)).unwrap();
+        assert_eq!(small.thumbnails.iter().map(|t| t.size).collect::<Vec<_>>(), ["s"]);
+    }
+
+    #[test]
+    fn test_waveform_is_normalized() {
+        // Короче волны: каждый блок занимает треть столбцов
+        let waveform = waveform(&[0.1, 0.2, 0.4]);
+        assert_eq!(waveform.len(), WAVEFORM_LEN);
+        assert_eq!(waveform[0], 64);
+        assert_eq!(waveform[40], 128);
+        assert_eq!(waveform[99], 255);
+
+        // Длиннее волны: в столбце — самый громкий из его блоков
+        let mut peaks = vec![0.0; 2 * WAVEFORM_LEN];
+        peaks[1] = 0.5;
+        peaks[2 * WAVEFORM_LEN - 2] = 1.0;
+        let waveform = super::waveform(&peaks);
+        assert_eq!((waveform[0], waveform[1], waveform[99]), (128, 0, 255));
+        assert_eq!(super::waveform(&[]), vec![0; WAVEFORM_LEN]);
+    }
+}
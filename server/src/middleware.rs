@@ -2,9 +2,10 @@
 //! Middleware для аутентификации, rate limiting, и логирования
 
 use axum::{
+    body::Body,
     http::{Request, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
@@ -31,7 +32,7 @@ impl RateLimiter {
     
     pub fn is_allowed(&mut self, key: &str) -> bool {
         let now = Instant::now();
-        let requests = self.requests.entry(key.to_string()).or_insert_with(Vec::new);
+        let requests = self.requests.entry(key.to_string()).or_default();
         
         // Удаляем старые запросы
         requests.retain(|&time| now.duration_since(time) < self.window);
@@ -55,9 +56,9 @@ pub async fn init_rate_limiter(limit: u64, window_secs: u64) {
 }
 
 /// Middleware для проверки JWT токена
-pub async fn auth_middleware<B>(
-    mut request: Request<B>,
-    next: Next<B>,
+pub async fn auth_middleware(
+    mut request: Request<Body>,
+    next: Next,
 ) -> Result<Response, StatusCode> {
     let auth_header = request
         .headers()
@@ -75,9 +76,9 @@ pub async fn auth_middleware<B>(
 }
 
 /// Middleware для rate limiting
-pub async fn rate_limit_middleware<B>(
-    request: Request<B>,
-    next: Next<B>,
+pub async fn rate_limit_middleware(
+    request: Request<Body>,
+    next: Next,
 ) -> Result<Response, StatusCode> {
     let client_ip = request
         .headers()
@@ -118,9 +119,9 @@ pub fn cors_middleware() -> tower_http::cors::CorsLayer {
 }
 
 /// Middleware для security headers
-pub async fn security_headers_middleware<B>(
-    mut request: Request<B>,
-    next: Next<B>,
+pub async fn security_headers_middleware(
+    request: Request<Body>,
+    next: Next,
 ) -> Response {
     let mut response = next.run(request).await;
 
@@ -0,0 +1,313 @@
+// server/src/permissions.rs
+//! Роли и права в группах и каналах
+//!
+//! Владелец (`chats.owner_id`) может всё. Администратор — только то, что
+//! отмечено в его наборе прав (`Rights`). Участник читает и пишет,
+//! ограниченный до `restricted_until` только читает. В канале пишут только
+//! владелец и администраторы, подписчики читают, ставят реакции и
+//! комментируют посты.
+//! Заблокированные в чате не состоят. В личном чате не пишет тот, кого
+//! собеседник занёс в чёрный список. Проверка одна на все действия:
+//! `Membership::allows`.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::ops::BitOr;
+
+/// Роль участника
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Owner,
+    Admin,
+    Member,
+    Restricted,
+}
+
+impl Role {
+    /// Неизвестное значение из базы — обычный участник
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "owner" => Role::Owner,
+            "admin" => Role::Admin,
+            "restricted" => Role::Restricted,
+            _ => Role::Member,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Owner => "owner",
+            Role::Admin => "admin",
+            Role::Member => "member",
+            Role::Restricted => "restricted",
+        }
+    }
+}
+
+/// Права администратора, битовый набор. Наружу — списком имён.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Rights(u32);
+
+impl Rights {
+    pub const NONE: Rights = Rights(0);
+    pub const CHANGE_INFO: Rights = Rights(1);
+    pub const DELETE_MESSAGES: Rights = Rights(1 << 1);
+    pub const BAN_USERS: Rights = Rights(1 << 2);
+    pub const INVITE_USERS: Rights = Rights(1 << 3);
+    pub const PIN_MESSAGES: Rights = Rights(1 << 4);
+    pub const PROMOTE_MEMBERS: Rights = Rights(1 << 5);
+    pub const ALL: Rights = Rights((1 << 6) - 1);
+
+    const NAMES: [(Rights, &'static str); 6] = [
+        (Rights::CHANGE_INFO, "change_info"),
+        (Rights::DELETE_MESSAGES, "delete_messages"),
+        (Rights::BAN_USERS, "ban_users"),
+        (Rights::INVITE_USERS, "invite_users"),
+        (Rights::PIN_MESSAGES, "pin_messages"),
+        (Rights::PROMOTE_MEMBERS, "promote_members"),
+    ];
+
+    /// Из колонки `chat_members.rights`; лишние биты отбрасываются
+    pub fn from_bits(bits: i64) -> Self {
+        Rights(bits as u32 & Rights::ALL.0)
+    }
+
+    pub fn bits(self) -> i64 {
+        self.0 as i64
+    }
+
+    pub fn contains(self, other: Rights) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn names(self) -> Vec<&'static str> {
+        Self::NAMES
+            .iter()
+            .filter(|(right, _)| self.contains(*right))
+            .map(|(_, name)| *name)
+            .collect()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES.iter().find(|(_, known)| *known == name).map(|(right, _)| *right)
+    }
+}
+
+impl From<i64> for Rights {
+    fn from(bits: i64) -> Self {
+        Rights::from_bits(bits)
+    }
+}
+
+impl BitOr for Rights {
+    type Output = Rights;
+
+    fn bitor(self, other: Rights) -> Rights {
+        Rights(self.0 | other.0)
+    }
+}
+
+impl Serialize for Rights {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.names().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rights {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let names = Vec::<String>::deserialize(deserializer)?;
+        names.iter().try_fold(Rights::NONE, |rights, name| {
+            Rights::from_name(name)
+                .map(|right| rights | right)
+                .ok_or_else(|| de::Error::custom(format!("неизвестное право: {}", name)))
+        })
+    }
+}
+
+/// Действие в чате
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// Читать чат и его участников
+    View,
+    SendMessages,
+    /// Ставить реакции: в канале доступно и подписчикам
+    React,
+    /// Отвечать в тредах; в канале — комментировать посты
+    Comment,
+    ChangeInfo,
+    /// Удалять чужие сообщения у всех
+    DeleteMessages,
+    BanUsers,
+    InviteUsers,
+    PinMessages,
+    PromoteMembers,
+}
+
+impl Permission {
+    /// Право администратора, которое нужно для действия
+    fn right(self) -> Option<Rights> {
+        match self {
+            Permission::View | Permission::SendMessages | Permission::React | Permission::Comment => None,
+            Permission::ChangeInfo => Some(Rights::CHANGE_INFO),
+            Permission::DeleteMessages => Some(Rights::DELETE_MESSAGES),
+            Permission::BanUsers => Some(Rights::BAN_USERS),
+            Permission::InviteUsers => Some(Rights::INVITE_USERS),
+            Permission::PinMessages => Some(Rights::PIN_MESSAGES),
+            Permission::PromoteMembers => Some(Rights::PROMOTE_MEMBERS),
+        }
+    }
+}
+
+/// Положение пользователя в чате
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Membership {
+    pub role: Role,
+    pub rights: Rights,
+    /// Для `Restricted`: до какого времени (формат колонок); `None` — бессрочно
+    pub restricted_until: Option<String>,
+    /// Кто назначил администратора: он может его и снять
+    pub promoted_by: Option<String>,
+    /// Чат — канал
+    #[serde(skip)]
+    pub channel: bool,
+    /// Личный чат, собеседник заблокировал пользователя
+    #[serde(skip)]
+    pub blocked: bool,
+}
+
+impl Membership {
+    /// Действующие права: у владельца все, у администратора — его набор
+    pub fn effective_rights(&self) -> Rights {
+        match self.role {
+            Role::Owner => Rights::ALL,
+            Role::Admin => self.rights,
+            Role::Member | Role::Restricted => Rights::NONE,
+        }
+    }
+
+    /// Действует ли ограничение на момент `now` (формат колонок)
+    pub fn is_restricted(&self, now: &str) -> bool {
+        self.role == Role::Restricted && self.restricted_until.as_deref().is_none_or(|until| until > now)
+    }
+
+    pub fn allows(&self, permission: Permission, now: &str) -> bool {
+        match permission.right() {
+            None if permission == Permission::View => true,
+            None if self.blocked => false,
+            None if permission == Permission::SendMessages && self.channel => {
+                matches!(self.role, Role::Owner | Role::Admin)
+            }
+            None => !self.is_restricted(now),
+            Some(right) => self.effective_rights().contains(right),
+        }
+    }
+
+    /// Может ли `actor_id` с этим положением менять роль участника `target`
+    /// или удалять его: владелец — любого, администратор — обычных
+    /// участников и назначенных им самим администраторов
+    pub fn outranks(&self, actor_id: &str, target: &Membership) -> bool {
+        match (self.role, target.role) {
+            (_, Role::Owner) => false,
+            (Role::Owner, _) => true,
+            (Role::Admin, Role::Admin) => target.promoted_by.as_deref() == Some(actor_id),
+            (Role::Admin, _) => true,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn membership(role: Role, rights: Rights) -> Membership {
+        Membership {
+            role,
+            rights,
+            restricted_until: None,
+            promoted_by: None,
+            channel: false,
+            blocked: false,
+        }
+    }
+
+    #[test]
+    fn test_rights_round_trip_as_names() {
+        let rights = Rights::PIN_MESSAGES | Rights::BAN_USERS;
+        let json = serde_json::to_value(rights).unwrap();
+        assert_eq!(json, serde_json::json!(["ban_users", "pin_messages"]));
+        assert_eq!(serde_json::from_value::<Rights>(json).unwrap(), rights);
+        assert!(serde_json::from_value::<Rights>(serde_json::json!(["fly"])).is_err());
+        assert_eq!(Rights::from_bits(-1), Rights::ALL);
+    }
+
+    #[test]
+    fn test_allows_by_role() {
+        let now = "2026-05-01 12:00:00";
+        let owner = membership(Role::Owner, Rights::NONE);
+        let admin = membership(Role::Admin, Rights::PIN_MESSAGES);
+        let member = membership(Role::Member, Rights::ALL);
+
+        assert!(owner.allows(Permission::PromoteMembers, now));
+        assert!(admin.allows(Permission::PinMessages, now));
+        assert!(!admin.allows(Permission::BanUsers, now));
+        // Набор прав действует только у администратора
+        assert!(!member.allows(Permission::PinMessages, now));
+        assert!(member.allows(Permission::SendMessages, now));
+
+        let mut restricted = membership(Role::Restricted, Rights::NONE);
+        assert!(!restricted.allows(Permission::SendMessages, now));
+        assert!(restricted.allows(Permission::View, now));
+        restricted.restricted_until = Some("2026-05-01 11:59:59".to_string());
+        assert!(restricted.allows(Permission::SendMessages, now));
+    }
+
+    #[test]
+    fn test_channel_subscribers_are_read_only() {
+        let now = "2026-05-01 12:00:00";
+        let mut subscriber = membership(Role::Member, Rights::NONE);
+        subscriber.channel = true;
+        let mut admin = membership(Role::Admin, Rights::NONE);
+        admin.channel = true;
+
+        assert!(!subscriber.allows(Permission::SendMessages, now));
+        assert!(subscriber.allows(Permission::React, now));
+        assert!(subscriber.allows(Permission::Comment, now));
+        assert!(subscriber.allows(Permission::View, now));
+        assert!(admin.allows(Permission::SendMessages, now));
+
+        subscriber.role = Role::Restricted;
+        assert!(!subscriber.allows(Permission::React, now));
+    }
+
+    #[test]
+    fn test_blocked_peer_is_read_only() {
+        let now = "2026-05-01 12:00:00";
+        let mut owner = membership(Role::Owner, Rights::NONE);
+        owner.blocked = true;
+
+        assert!(owner.allows(Permission::View, now));
+        assert!(!owner.allows(Permission::SendMessages, now));
+        assert!(!owner.allows(Permission::React, now));
+    }
+
+    #[test]
+    fn test_outranks() {
+        let owner = membership(Role::Owner, Rights::NONE);
+        let admin = membership(Role::Admin, Rights::ALL);
+        let mut promoted = membership(Role::Admin, Rights::PIN_MESSAGES);
+        let member = membership(Role::Member, Rights::NONE);
+
+        assert!(owner.outranks("owner", &admin));
+        assert!(!admin.outranks("admin", &owner));
+        assert!(admin.outranks("admin", &member));
+        assert!(!admin.outranks("admin", &promoted));
+        promoted.promoted_by = Some("admin".to_string());
+        assert!(admin.outranks("admin", &promoted));
+        assert!(!member.outranks("member", &member));
+    }
+}
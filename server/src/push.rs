@@ -0,0 +1,776 @@
+// server/src/push.rs
+//! Push-уведомления для клиентов без открытого соединения
+//!
+//! Устройство регистрируется с ключом подсказки (`hint_key`, 32 байта).
+//! В уведомление уходит только подсказка — тип, чат и id сообщения,
+//! зашифрованные этим ключом (`seal_hint`); текст клиент загружает сам,
+//! так что ни провайдер, ни push-сервис его не видят. Провайдеры
+//! (`PushProvider`): Web Push с VAPID, UnifiedPush и FCM-совместимый шлюз;
+//! для Web Push и UnifiedPush тело дополнительно шифруется по RFC 8291.
+//! Рассылка идёт через очередь (`PushQueue`), как `fanout`.
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes128Gcm, Aes256Gcm, Nonce,
+};
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL},
+    Engine,
+};
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use ring::{
+    agreement,
+    rand::SystemRandom,
+    signature::{EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING},
+};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::{mpsc, RwLock};
+use crate::{
+    db::{push::PushDevice, DbPool},
+    websocket::WebSocketManager,
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Сколько устройств читается из базы за раз
+pub const PUSH_BATCH: i64 = 500;
+
+/// Сколько задач ждёт в очереди; дальше постановка ждёт освобождения места
+const PUSH_QUEUE_SIZE: usize = 1024;
+
+/// Сколько уведомлений отправляется параллельно
+const PUSH_CONCURRENCY: usize = 16;
+
+/// Сколько push-сервис хранит недоставленное уведомление, секунд
+const PUSH_TTL: u32 = 24 * 3600;
+
+/// Размер записи aes128gcm; подсказка всегда умещается в одну
+const RECORD_SIZE: u32 = 4096;
+
+/// Срок действия подписи VAPID
+const VAPID_TTL: i64 = 12 * 3600;
+
+pub const WEBPUSH: &str = "webpush";
+pub const UNIFIEDPUSH: &str = "unifiedpush";
+pub const FCM: &str = "fcm";
+
+// ==================== Подсказка ====================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HintKind {
+    Message,
+    Mention,
+}
+
+/// Что клиенту загрузить после уведомления
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PushHint {
+    #[serde(rename = "type")]
+    pub kind: HintKind,
+    pub chat_id: String,
+    pub message_id: String,
+}
+
+/// Ключ подсказки из base64; `None`, если это не 32 байта
+pub fn parse_hint_key(key: &str) -> Option<[u8; 32]> {
+    BASE64.decode(key).ok()?.try_into().ok()
+}
+
+/// Зашифровать подсказку ключом устройства: base64 от nonce ‖ AES-256-GCM
+pub fn seal_hint(hint_key: &str, hint: &PushHint) -> Result<String, PushError> {
+    let key = parse_hint_key(hint_key).ok_or_else(|| PushError::Failed("неверный ключ подсказки".to_string()))?;
+    let mut nonce = [0u8; 12];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let plaintext = serde_json::to_vec(hint).map_err(|e| PushError::Failed(e.to_string()))?;
+    let sealed = Aes256Gcm::new(&key.into())
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|_| PushError::Failed("ошибка шифрования подсказки".to_string()))?;
+    Ok(BASE64.encode([&nonce[..], &sealed].concat()))
+}
+
+/// Расшифровать подсказку (так делает клиент); `None` — не тот ключ
+/// или повреждённые данные
+pub fn open_hint(hint_key: &str, sealed: &str) -> Option<PushHint> {
+    let key = parse_hint_key(hint_key)?;
+    let data = BASE64.decode(sealed).ok()?;
+    if data.len() < 12 {
+        return None;
+    }
+    let (nonce, sealed) = data.split_at(12);
+    let plaintext = Aes256Gcm::new(&key.into()).decrypt(Nonce::from_slice(nonce), sealed).ok()?;
+    serde_json::from_slice(&plaintext).ok()
+}
+
+// ==================== Провайдеры ====================
+
+/// Уведомление на одно устройство
+pub struct PushMessage<'a> {
+    pub device: &'a PushDevice,
+    /// Зашифрованная подсказка (`seal_hint`)
+    pub hint: String,
+    /// Упоминание — доставлять без задержек
+    pub urgent: bool,
+}
+
+impl PushMessage<'_> {
+    /// Тело для Web Push и UnifiedPush: `{"hint": "..."}`
+    fn body(&self) -> Vec<u8> {
+        serde_json::json!({ "hint": self.hint }).to_string().into_bytes()
+    }
+
+    fn urgency(&self) -> &'static str {
+        if self.urgent {
+            "high"
+        } else {
+            "normal"
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PushError {
+    /// Подписка больше не действует — устройство удаляется
+    Gone,
+    Failed(String),
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Gone => write!(f, "подписка не действует"),
+            PushError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for PushError {}
+
+#[axum::async_trait]
+pub trait PushProvider: Send + Sync {
+    /// Имя, под которым устройства регистрируются (`provider`)
+    fn name(&self) -> &str;
+
+    /// Открытый ключ сервера для подписки (VAPID), base64url
+    fn public_key(&self) -> Option<String> {
+        None
+    }
+
+    async fn send(&self, message: &PushMessage<'_>) -> Result<(), PushError>;
+}
+
+/// Ответ push-сервиса: 404 и 410 — подписки больше нет
+fn check_status(status: reqwest::StatusCode) -> Result<(), PushError> {
+    match status {
+        s if s.is_success() => Ok(()),
+        reqwest::StatusCode::NOT_FOUND | reqwest::StatusCode::GONE => Err(PushError::Gone),
+        s => Err(PushError::Failed(format!("push-сервис ответил {}", s))),
+    }
+}
+
+fn request_error(e: reqwest::Error) -> PushError {
+    PushError::Failed(e.to_string())
+}
+
+/// Ключи подписки устройства (`p256dh`, `auth`) в base64url
+fn subscription_keys(device: &PushDevice) -> Option<(Vec<u8>, Vec<u8>)> {
+    let p256dh = BASE64_URL.decode(device.p256dh.as_deref()?.trim_end_matches('=')).ok()?;
+    let auth = BASE64_URL.decode(device.auth.as_deref()?.trim_end_matches('=')).ok()?;
+    Some((p256dh, auth))
+}
+
+/// Годятся ли ключи подписки для шифрования RFC 8291
+pub fn valid_subscription_keys(p256dh: &str, auth: &str) -> bool {
+    let decode = |value: &str| BASE64_URL.decode(value.trim_end_matches('=')).ok();
+    matches!(
+        (decode(p256dh), decode(auth)),
+        (Some(p256dh), Some(auth)) if p256dh.len() == 65 && p256dh[0] == 0x04 && auth.len() == 16
+    )
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC принимает ключ любой длины");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+/// Ключ и nonce записи по RFC 8291 (HKDF из общего секрета ECDH)
+fn derive_content_key(
+    ecdh_secret: &[u8],
+    auth: &[u8],
+    ua_public: &[u8],
+    as_public: &[u8],
+    salt: &[u8],
+) -> ([u8; 16], [u8; 12]) {
+    let prk_key = hmac_sha256(auth, &[ecdh_secret]);
+    let ikm = hmac_sha256(&prk_key, &[b"WebPush: info\0", ua_public, as_public, &[1]]);
+    let prk = hmac_sha256(salt, &[&ikm]);
+    let cek = hmac_sha256(&prk, &[b"Content-Encoding: aes128gcm\0", &[1]]);
+    let nonce = hmac_sha256(&prk, &[b"Content-Encoding: nonce\0", &[1]]);
+    (cek[..16].try_into().unwrap(), nonce[..12].try_into().unwrap())
+}
+
+/// Зашифровать тело для подписки (RFC 8291, `Content-Encoding: aes128gcm`):
+/// заголовок salt ‖ rs ‖ idlen ‖ открытый ключ сервера, затем одна запись
+pub fn encrypt_aes128gcm(ua_public: &[u8], auth: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, PushError> {
+    let failed = |reason: &str| PushError::Failed(reason.to_string());
+    if plaintext.len() + 17 > RECORD_SIZE as usize {
+        return Err(failed("уведомление не умещается в запись"));
+    }
+
+    let rng = SystemRandom::new();
+    let private_key = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng)
+        .map_err(|_| failed("ошибка генерации ключа"))?;
+    let as_public = private_key.compute_public_key().map_err(|_| failed("ошибка генерации ключа"))?;
+    let mut salt = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut salt);
+
+    let peer = agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, ua_public);
+    let (cek, nonce) = agreement::agree_ephemeral(private_key, &peer, |secret| {
+        derive_content_key(secret, auth, ua_public, as_public.as_ref(), &salt)
+    })
+    .map_err(|_| failed("неверный ключ подписки"))?;
+
+    // Разделитель 0x02 — последняя запись
+    let mut record = plaintext.to_vec();
+    record.push(2);
+    let sealed = Aes128Gcm::new(&cek.into())
+        .encrypt(Nonce::from_slice(&nonce), record.as_slice())
+        .map_err(|_| failed("ошибка шифрования"))?;
+
+    let mut body = Vec::with_capacity(86 + sealed.len());
+    body.extend_from_slice(&salt);
+    body.extend_from_slice(&RECORD_SIZE.to_be_bytes());
+    body.push(as_public.as_ref().len() as u8);
+    body.extend_from_slice(as_public.as_ref());
+    body.extend_from_slice(&sealed);
+    Ok(body)
+}
+
+/// Ключи VAPID (RFC 8292) в base64url: закрытый — 32 байта, открытый —
+/// несжатая точка P-256
+pub struct VapidConfig {
+    pub private_key: String,
+    pub public_key: String,
+    /// `mailto:` или `https:` для связи с владельцем сервера
+    pub subject: String,
+}
+
+impl VapidConfig {
+    /// `None`, если `VAPID_PRIVATE_KEY` не задан
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        let Ok(private_key) = std::env::var("VAPID_PRIVATE_KEY") else {
+            return Ok(None);
+        };
+        let required = |name: &str| {
+            std::env::var(name).map_err(|_| anyhow::anyhow!("VAPID_PRIVATE_KEY требует {}", name))
+        };
+        Ok(Some(Self {
+            private_key,
+            public_key: required("VAPID_PUBLIC_KEY")?,
+            subject: required("VAPID_SUBJECT")?,
+        }))
+    }
+}
+
+/// Web Push: тело по RFC 8291, авторизация VAPID
+pub struct WebPushProvider {
+    client: reqwest::Client,
+    key_pair: EcdsaKeyPair,
+    public_key: String,
+    subject: String,
+}
+
+impl WebPushProvider {
+    pub fn new(config: VapidConfig) -> anyhow::Result<Self> {
+        let decode = |value: &str| {
+            BASE64_URL
+                .decode(value.trim_end_matches('='))
+                .map_err(|_| anyhow::anyhow!("Ключ VAPID не в base64url"))
+        };
+        let key_pair = EcdsaKeyPair::from_private_key_and_public_key(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            &decode(&config.private_key)?,
+            &decode(&config.public_key)?,
+            &SystemRandom::new(),
+        )
+        .map_err(|e| anyhow::anyhow!("Неверные ключи VAPID: {}", e))?;
+        Ok(Self {
+            client: reqwest::Client::new(),
+            key_pair,
+            public_key: config.public_key.trim_end_matches('=').to_string(),
+            subject: config.subject,
+        })
+    }
+
+    /// JWT ES256 для origin push-сервиса
+    fn vapid_token(&self, endpoint: &str) -> Result<String, PushError> {
+        let url = reqwest::Url::parse(endpoint).map_err(|_| PushError::Failed("неверный endpoint".to_string()))?;
+        let header = BASE64_URL.encode(br#"{"typ":"JWT","alg":"ES256"}"#);
+        let claims = serde_json::json!({
+            "aud": url.origin().ascii_serialization(),
+            "exp": chrono::Utc::now().timestamp() + VAPID_TTL,
+            "sub": self.subject,
+        });
+        let unsigned = format!("{}.{}", header, BASE64_URL.encode(claims.to_string()));
+        let signature = self
+            .key_pair
+            .sign(&SystemRandom::new(), unsigned.as_bytes())
+            .map_err(|_| PushError::Failed("ошибка подписи VAPID".to_string()))?;
+        Ok(format!("{}.{}", unsigned, BASE64_URL.encode(signature.as_ref())))
+    }
+}
+
+#[axum::async_trait]
+impl PushProvider for WebPushProvider {
+    fn name(&self) -> &str {
+        WEBPUSH
+    }
+
+    fn public_key(&self) -> Option<String> {
+        Some(self.public_key.clone())
+    }
+
+    async fn send(&self, message: &PushMessage<'_>) -> Result<(), PushError> {
+        let device = message.device;
+        let (p256dh, auth) =
+            subscription_keys(device).ok_or_else(|| PushError::Failed("подписка без ключей".to_string()))?;
+        let body = encrypt_aes128gcm(&p256dh, &auth, &message.body())?;
+        let token = self.vapid_token(&device.endpoint)?;
+
+        let response = self
+            .client
+            .post(&device.endpoint)
+            .header("Authorization", format!("vapid t={}, k={}", token, self.public_key))
+            .header("TTL", PUSH_TTL)
+            .header("Urgency", message.urgency())
+            .header("Content-Encoding", "aes128gcm")
+            .header("Content-Type", "application/octet-stream")
+            .body(body)
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(response.status())
+    }
+}
+
+/// UnifiedPush: POST на адрес, выданный дистрибьютором. С ключами
+/// подписки тело шифруется как в Web Push.
+pub struct UnifiedPushProvider {
+    client: reqwest::Client,
+}
+
+impl UnifiedPushProvider {
+    pub fn new() -> Self {
+        Self { client: reqwest::Client::new() }
+    }
+}
+
+impl Default for UnifiedPushProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[axum::async_trait]
+impl PushProvider for UnifiedPushProvider {
+    fn name(&self) -> &str {
+        UNIFIEDPUSH
+    }
+
+    async fn send(&self, message: &PushMessage<'_>) -> Result<(), PushError> {
+        let mut request = self
+            .client
+            .post(&message.device.endpoint)
+            .header("TTL", PUSH_TTL)
+            .header("Urgency", message.urgency());
+        request = match subscription_keys(message.device) {
+            Some((p256dh, auth)) => request
+                .header("Content-Encoding", "aes128gcm")
+                .body(encrypt_aes128gcm(&p256dh, &auth, &message.body())?),
+            None => request.body(message.body()),
+        };
+        let response = request.send().await.map_err(request_error)?;
+        check_status(response.status())
+    }
+}
+
+/// FCM-совместимый шлюз (HTTP API с ключом сервера): `endpoint`
+/// устройства — токен регистрации
+pub struct FcmProvider {
+    client: reqwest::Client,
+    endpoint: String,
+    server_key: String,
+}
+
+impl FcmProvider {
+    pub fn new(endpoint: String, server_key: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint,
+            server_key,
+        }
+    }
+
+    /// `None`, если `FCM_SERVER_KEY` не задан; `FCM_ENDPOINT` — для
+    /// совместимых шлюзов
+    pub fn from_env() -> Option<Self> {
+        let server_key = std::env::var("FCM_SERVER_KEY").ok()?;
+        let endpoint =
+            std::env::var("FCM_ENDPOINT").unwrap_or_else(|_| "https://fcm.googleapis.com/fcm/send".to_string());
+        Some(Self::new(endpoint, server_key))
+    }
+}
+
+#[derive(Deserialize)]
+struct FcmResponse {
+    #[serde(default)]
+    results: Vec<FcmResult>,
+}
+
+#[derive(Deserialize)]
+struct FcmResult {
+    error: Option<String>,
+}
+
+#[axum::async_trait]
+impl PushProvider for FcmProvider {
+    fn name(&self) -> &str {
+        FCM
+    }
+
+    async fn send(&self, message: &PushMessage<'_>) -> Result<(), PushError> {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header("Authorization", format!("key={}", self.server_key))
+            .json(&serde_json::json!({
+                "to": message.device.endpoint,
+                "priority": message.urgency(),
+                "time_to_live": PUSH_TTL,
+                "data": { "hint": message.hint },
+            }))
+            .send()
+            .await
+            .map_err(request_error)?;
+        check_status(response.status())?;
+
+        // Ошибка по токену приходит в теле ответа 200
+        let body: FcmResponse = response.json().await.map_err(request_error)?;
+        match body.results.first().and_then(|result| result.error.as_deref()) {
+            None => Ok(()),
+            Some("NotRegistered" | "InvalidRegistration") => Err(PushError::Gone),
+            Some(error) => Err(PushError::Failed(format!("FCM: {}", error))),
+        }
+    }
+}
+
+/// Отправленное `MockPushProvider` уведомление
+#[derive(Debug, Clone)]
+pub struct SentPush {
+    pub user_id: String,
+    pub device_id: String,
+    pub endpoint: String,
+    pub hint: String,
+    pub urgent: bool,
+}
+
+/// Провайдер для тестов: запоминает уведомления вместо отправки
+pub struct MockPushProvider {
+    name: String,
+    sent: Mutex<Vec<SentPush>>,
+    /// Адреса, подписка на которые «истекла»
+    gone: Mutex<HashSet<String>>,
+}
+
+impl MockPushProvider {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            sent: Mutex::default(),
+            gone: Mutex::default(),
+        }
+    }
+
+    pub fn sent(&self) -> Vec<SentPush> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Дальше отвечать на этот адрес, что подписки нет
+    pub fn expire(&self, endpoint: &str) {
+        self.gone.lock().unwrap().insert(endpoint.to_string());
+    }
+}
+
+#[axum::async_trait]
+impl PushProvider for MockPushProvider {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn send(&self, message: &PushMessage<'_>) -> Result<(), PushError> {
+        let device = message.device;
+        if self.gone.lock().unwrap().contains(&device.endpoint) {
+            return Err(PushError::Gone);
+        }
+        self.sent.lock().unwrap().push(SentPush {
+            user_id: device.user_id.clone(),
+            device_id: device.id.clone(),
+            endpoint: device.endpoint.clone(),
+            hint: message.hint.clone(),
+            urgent: message.urgent,
+        });
+        Ok(())
+    }
+}
+
+/// Включённые провайдеры по имени
+#[derive(Clone, Default)]
+pub struct PushGateway {
+    providers: HashMap<String, Arc<dyn PushProvider>>,
+}
+
+impl PushGateway {
+    pub fn with(mut self, provider: Arc<dyn PushProvider>) -> Self {
+        self.providers.insert(provider.name().to_string(), provider);
+        self
+    }
+
+    pub fn provider(&self, name: &str) -> Option<&Arc<dyn PushProvider>> {
+        self.providers.get(name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.providers.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Открытый ключ VAPID для `PushManager.subscribe`
+    pub fn vapid_public_key(&self) -> Option<String> {
+        self.provider(WEBPUSH).and_then(|provider| provider.public_key())
+    }
+}
+
+/// UnifiedPush — всегда; Web Push — с ключами `VAPID_*`; FCM — с `FCM_SERVER_KEY`
+pub fn from_env() -> anyhow::Result<PushGateway> {
+    let mut gateway = PushGateway::default().with(Arc::new(UnifiedPushProvider::new()));
+    if let Some(config) = VapidConfig::from_env()? {
+        gateway = gateway.with(Arc::new(WebPushProvider::new(config)?));
+    }
+    if let Some(fcm) = FcmProvider::from_env() {
+        gateway = gateway.with(Arc::new(fcm));
+    }
+    Ok(gateway)
+}
+
+// ==================== Очередь ====================
+
+/// Уведомить участников чата о новом сообщении
+#[derive(Debug, Clone)]
+pub struct PushJob {
+    pub chat_id: String,
+    pub message_id: String,
+    pub sender_id: String,
+    /// Только упомянутых (комментарии к постам канала)
+    pub mentions_only: bool,
+    /// По WebSocket сообщение получают только подписчики чата, а не все
+    /// подключённые участники (группы и личные чаты)
+    pub subscribers_only: bool,
+}
+
+#[derive(Clone)]
+pub struct PushQueue {
+    sender: mpsc::Sender<PushJob>,
+    gateway: PushGateway,
+}
+
+impl PushQueue {
+    /// Запустить фоновый обработчик; он завершается вместе с последней
+    /// копией очереди
+    pub fn start(db: DbPool, ws: Arc<RwLock<WebSocketManager>>, gateway: PushGateway) -> Self {
+        let (sender, mut receiver) = mpsc::channel::<PushJob>(PUSH_QUEUE_SIZE);
+        let worker_gateway = gateway.clone();
+        tokio::spawn(async move {
+            while let Some(job) = receiver.recv().await {
+                if let Err(e) = deliver(&db, &ws, &worker_gateway, &job).await {
+                    tracing::error!("Ошибка push-рассылки в чат {}: {}", job.chat_id, e);
+                }
+            }
+        });
+        Self { sender, gateway }
+    }
+
+    pub fn gateway(&self) -> &PushGateway {
+        &self.gateway
+    }
+
+    pub async fn enqueue(&self, job: PushJob) {
+        if self.sender.send(job).await.is_err() {
+            tracing::error!("Очередь push-уведомлений остановлена");
+        }
+    }
+}
+
+/// Отправить уведомления на устройства участников, которым сообщение не
+/// пришло по WebSocket. Устройства с недействующей подпиской удаляются.
+/// Возвращает, сколько уведомлений принято push-сервисами.
+pub async fn deliver(
+    db: &DbPool,
+    ws: &RwLock<WebSocketManager>,
+    gateway: &PushGateway,
+    job: &PushJob,
+) -> Result<usize, sqlx::Error> {
+    let push = db.push();
+    let mut after = String::new();
+    let mut delivered = 0;
+
+    loop {
+        let batch = push
+            .targets(&job.chat_id, &job.message_id, &job.sender_id, job.mentions_only, &after, PUSH_BATCH)
+            .await?;
+        let full = batch.len() as i64 == PUSH_BATCH;
+        if let Some(last) = batch.last() {
+            after = last.device.id.clone();
+        }
+
+        // Открытый клиент получит сообщение по WebSocket
+        let offline: Vec<_> = {
+            let ws = ws.read().await;
+            batch
+                .into_iter()
+                .filter(|target| {
+                    let user_id = &target.device.user_id;
+                    if job.subscribers_only {
+                        !ws.is_subscribed(&job.chat_id, user_id)
+                    } else {
+                        !ws.is_online(user_id)
+                    }
+                })
+                .collect()
+        };
+
+        let results: Vec<_> = futures::stream::iter(offline)
+            .map(|target| async move {
+                let provider = gateway.provider(&target.device.provider)?;
+                let hint = PushHint {
+                    kind: if target.mentioned { HintKind::Mention } else { HintKind::Message },
+                    chat_id: job.chat_id.clone(),
+                    message_id: job.message_id.clone(),
+                };
+                let result = match seal_hint(&target.device.hint_key, &hint) {
+                    Ok(hint) => {
+                        provider
+                            .send(&PushMessage { device: &target.device, hint, urgent: target.mentioned })
+                            .await
+                    }
+                    Err(e) => Err(e),
+                };
+                Some((target.device.id, result))
+            })
+            .buffer_unordered(PUSH_CONCURRENCY)
+            .collect()
+            .await;
+
+        for (device_id, result) in results.into_iter().flatten() {
+            match result {
+                Ok(()) => delivered += 1,
+                Err(PushError::Gone) => push.remove(&device_id).await?,
+                Err(e) => tracing::warn!("Не доставлено push-уведомление на {}: {}", device_id, e),
+            }
+        }
+
+        if !full {
+            break;
+        }
+    }
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+
+    const HINT_KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+    #[test]
+    fn test_hint_round_trip() {
+        let hint = PushHint {
+            kind: HintKind::Mention,
+            chat_id: "chat".to_string(),
+            message_id: "message".to_string(),
+        };
+        let sealed = seal_hint(HINT_KEY, &hint).unwrap();
+        assert!(!sealed.contains("chat"));
+        assert_eq!(open_hint(HINT_KEY, &sealed), Some(hint));
+
+        let other = BASE64.encode([7u8; 32]);
+        assert_eq!(open_hint(&other, &sealed), None);
+        assert!(parse_hint_key("c2hvcnQ=").is_none());
+    }
+
+    #[test]
+    fn test_aes128gcm_decrypts_with_subscription_key() {
+        // Подписка браузера: своя пара ключей и auth
+        let rng = SystemRandom::new();
+        let ua_private = agreement::EphemeralPrivateKey::generate(&agreement::ECDH_P256, &rng).unwrap();
+        let ua_public = ua_private.compute_public_key().unwrap();
+        let auth = [9u8; 16];
+
+        let body = encrypt_aes128gcm(ua_public.as_ref(), &auth, b"{\"hint\":\"x\"}").unwrap();
+        let (salt, rest) = body.split_at(16);
+        assert_eq!(u32::from_be_bytes(rest[..4].try_into().unwrap()), RECORD_SIZE);
+        let id_len = rest[4] as usize;
+        let (as_public, sealed) = rest[5..].split_at(id_len);
+
+        let peer = agreement::UnparsedPublicKey::new(&agreement::ECDH_P256, as_public);
+        let (cek, nonce) = agreement::agree_ephemeral(ua_private, &peer, |secret| {
+            derive_content_key(secret, &auth, ua_public.as_ref(), as_public, salt)
+        })
+        .unwrap();
+        let record = Aes128Gcm::new(&cek.into()).decrypt(Nonce::from_slice(&nonce), sealed).unwrap();
+        assert_eq!(record, b"{\"hint\":\"x\"}\x02");
+    }
+
+    #[test]
+    fn test_vapid_token_is_signed_for_origin() {
+        let public_key = "BMshS1wyNQTNe-CnhheKmhMpRD78SYaBTNx8DjcIs-Svmg40Z6GXZqEDL6snow8Olta2lGsTsTreiNqn62978Oo";
+        let provider = WebPushProvider::new(VapidConfig {
+            private_key: "Uti3ueOuaTxTzaMgVwIZDfspkkpGGHOZL7TegbUn_XA".to_string(),
+            public_key: public_key.to_string(),
+            subject: "mailto:admin@example.com".to_string(),
+        })
+        .unwrap();
+
+        let token = provider.vapid_token("https://push.example.com:8443/send/abc").unwrap();
+        let (unsigned, signature) = token.rsplit_once('.').unwrap();
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, BASE64_URL.decode(public_key).unwrap())
+            .verify(unsigned.as_bytes(), &BASE64_URL.decode(signature).unwrap())
+            .unwrap();
+
+        let claims: serde_json::Value =
+            serde_json::from_slice(&BASE64_URL.decode(unsigned.split('.').nth(1).unwrap()).unwrap()).unwrap();
+        assert_eq!(claims["aud"], "https://push.example.com:8443");
+        assert_eq!(claims["sub"], "mailto:admin@example.com");
+    }
+
+    #[test]
+    fn test_subscription_keys_validation() {
+        let p256dh = "BMshS1wyNQTNe-CnhheKmhMpRD78SYaBTNx8DjcIs-Svmg40Z6GXZqEDL6snow8Olta2lGsTsTreiNqn62978Oo";
+        assert!(valid_subscription_keys(p256dh, "AAECAwQFBgcICQoLDA0ODw"));
+        assert!(valid_subscription_keys(p256dh, "AAECAwQFBgcICQoLDA0ODw=="));
+        assert!(!valid_subscription_keys(p256dh, "AAEC"));
+        assert!(!valid_subscription_keys("AAECAwQFBgcICQoLDA0ODw", "AAECAwQFBgcICQoLDA0ODw"));
+    }
+}
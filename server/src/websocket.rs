@@ -1,14 +1,14 @@
 // server/src/websocket.rs
 //! WebSocket для реального времени общения
 
-use axum::{
-    extract::ws::{Message, WebSocket, WebSocketUpgrade},
-    response::Response,
-};
-use futures::{sink::SinkExt, stream::StreamExt};
-use tokio::sync::broadcast;
+use axum::extract::ws::{Message, WebSocket};
+use futures::{sink::SinkExt, stream::{SplitSink, StreamExt}};
+use tokio::{sync::broadcast, time::{Duration, Instant}};
 use std::collections::HashMap;
-use uuid::Uuid;
+use crate::{api::{chats, reads, users, AppState}, auth, permissions::Permission};
+
+/// Сколько держится «печатает» без повторного `typing` от клиента
+pub const TYPING_TTL: Duration = Duration::from_secs(6);
 
 /// Тип для отправки сообщений в канал
 pub type Tx = broadcast::Sender<WsMessage>;
@@ -37,12 +37,71 @@ pub enum WsMessage {
         file_url: Option<String>,
     },
     
+    /// От клиента: пользователь печатает (повторять чаще `TYPING_TTL`)
     #[serde(rename = "typing")]
     Typing { chat_id: String },
+
+    /// Остальным подписчикам чата; без продления снимается через `expires_in_ms`
+    #[serde(rename = "user_typing")]
+    UserTyping { chat_id: String, user_id: String, expires_in_ms: u64 },
+
+    #[serde(rename = "user_stopped_typing")]
+    UserStoppedTyping { chat_id: String, user_id: String },
+
+    /// Пользователь появился в сети или ушёл; `last_seen_at` — если его
+    /// настройки приватности это показывают
+    #[serde(rename = "presence")]
+    Presence { user_id: String, online: bool, last_seen_at: Option<String> },
     
+    /// От клиента: прочитано до самого нового из `message_ids`
     #[serde(rename = "read")]
     Read { chat_id: String, message_ids: Vec<String> },
+
+    /// Участник прочитал чат до `message_id`. Если он отключил отчёты
+    /// о прочтении, событие получают только его же устройства.
+    #[serde(rename = "messages_read")]
+    MessagesRead { chat_id: String, user_id: String, message_id: String },
+
+    #[serde(rename = "messages_delivered")]
+    MessagesDelivered { chat_id: String, user_id: String, message_id: String },
     
+    /// Новое сообщение чата; посты каналов доставляются очередью `fanout`
+    #[serde(rename = "new_message")]
+    NewMessage { message: Box<crate::db::messages::Message> },
+
+    #[serde(rename = "message_edited")]
+    MessageEdited {
+        chat_id: String,
+        message_id: String,
+        content: String,
+        #[serde(default, skip_serializing_if = "crate::entities::Entities::is_empty")]
+        entities: crate::entities::Entities,
+        edited_at: String,
+    },
+
+    /// Пользователя упомянули или ответили ему (только ему)
+    #[serde(rename = "mentioned")]
+    Mentioned { chat_id: String, message_id: String, sender_id: String },
+
+    #[serde(rename = "message_deleted")]
+    MessageDeleted { chat_id: String, message_id: String },
+
+    /// Новые результаты опроса, без выбора получателя
+    #[serde(rename = "poll_updated")]
+    PollUpdated { chat_id: String, message_id: String, poll: Box<crate::db::polls::Poll> },
+
+    /// Отложенное сообщение отправлено (только автору)
+    #[serde(rename = "scheduled_sent")]
+    ScheduledSent { chat_id: String, scheduled_id: String, message: Box<crate::db::messages::Message> },
+
+    /// Отложенное сообщение не отправлено и больше не повторяется (только автору)
+    #[serde(rename = "scheduled_failed")]
+    ScheduledFailed { chat_id: String, scheduled_id: String, error: String },
+
+    /// Обработка медиа закончена (`ready` или `failed`), только владельцу файла
+    #[serde(rename = "file_processed")]
+    FileProcessed { file_id: String, media_status: String },
+
     #[serde(rename = "error")]
     Error { message: String },
     
@@ -52,30 +111,81 @@ pub enum WsMessage {
 
 /// Состояние WebSocket менеджера
 pub struct WebSocketManager {
-    /// Канал для рассылки сообщений
-    tx: Tx,
     /// Подключенные пользователи: user_id -> websocket sender
     users: HashMap<String, Tx>,
     /// Подписки на чаты: chat_id -> список user_id
     chat_subscriptions: HashMap<String, Vec<String>>,
+    /// Кто печатает: (chat_id, user_id) -> когда снять
+    typing: HashMap<(String, String), Instant>,
+}
+
+impl Default for WebSocketManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WebSocketManager {
     pub fn new() -> Self {
-        let (tx, _) = broadcast::channel(1000);
-        
         Self {
-            tx,
             users: HashMap::new(),
             chat_subscriptions: HashMap::new(),
+            typing: HashMap::new(),
+        }
+    }
+
+    /// Есть ли у пользователя открытые соединения
+    pub fn is_online(&self, user_id: &str) -> bool {
+        self.users.get(user_id).is_some_and(|tx| tx.receiver_count() > 0)
+    }
+
+    /// Регистрирует подключение пользователя и возвращает его поток событий.
+    /// Несколько устройств одного пользователя делят один канал.
+    pub fn connect(&mut self, user_id: &str) -> Rx {
+        self.users
+            .entry(user_id.to_string())
+            .or_insert_with(|| broadcast::channel(256).0)
+            .subscribe()
+    }
+
+    /// Убирает пользователя, если у него не осталось открытых соединений.
+    /// `true` — пользователь ушёл из сети.
+    pub fn disconnect(&mut self, user_id: &str) -> bool {
+        let idle = self
+            .users
+            .get(user_id)
+            .is_some_and(|tx| tx.receiver_count() == 0);
+
+        if idle {
+            self.users.remove(user_id);
+            let typing_in: Vec<String> = self
+                .typing
+                .keys()
+                .filter(|(_, typist)| typist == user_id)
+                .map(|(chat_id, _)| chat_id.clone())
+                .collect();
+            for chat_id in typing_in {
+                self.stop_typing(&chat_id, user_id);
+            }
+            for subscribers in self.chat_subscriptions.values_mut() {
+                subscribers.retain(|id| id != user_id);
+            }
         }
+        idle
     }
 
     pub fn subscribe_chat(&mut self, chat_id: String, user_id: String) {
+        let subscribers = self.chat_subscriptions.entry(chat_id).or_default();
+        if !subscribers.contains(&user_id) {
+            subscribers.push(user_id);
+        }
+    }
+
+    /// Подписан ли пользователь на события чата
+    pub fn is_subscribed(&self, chat_id: &str, user_id: &str) -> bool {
         self.chat_subscriptions
-            .entry(chat_id)
-            .or_insert_with(Vec::new)
-            .push(user_id);
+            .get(chat_id)
+            .is_some_and(|subscribers| subscribers.iter().any(|id| id == user_id))
     }
 
     pub fn unsubscribe_chat(&mut self, chat_id: &str, user_id: &str) {
@@ -84,51 +194,210 @@ impl WebSocketManager {
         }
     }
 
+    /// Всем устройствам пользователя, если он подключён
+    pub fn send_to_user(&self, user_id: &str, message: WsMessage) {
+        if let Some(tx) = self.users.get(user_id) {
+            let _ = tx.send(message);
+        }
+    }
+
     pub fn broadcast_to_chat(&self, chat_id: &str, message: WsMessage) {
+        self.broadcast_to_chat_except(chat_id, "", message);
+    }
+
+    /// Подписчикам чата, кроме `except_user_id` (его собственные устройства)
+    pub fn broadcast_to_chat_except(&self, chat_id: &str, except_user_id: &str, message: WsMessage) {
         if let Some(subscribers) = self.chat_subscriptions.get(chat_id) {
-            for user_id in subscribers {
+            for user_id in subscribers.iter().filter(|id| *id != except_user_id) {
                 if let Some(tx) = self.users.get(user_id) {
                     let _ = tx.send(message.clone());
                 }
             }
         }
     }
+
+    /// Пользователь печатает: продлить на `TYPING_TTL` и сообщить чату
+    pub fn start_typing(&mut self, chat_id: &str, user_id: &str) {
+        self.typing
+            .insert((chat_id.to_string(), user_id.to_string()), Instant::now() + TYPING_TTL);
+        self.broadcast_to_chat_except(chat_id, user_id, WsMessage::UserTyping {
+            chat_id: chat_id.to_string(),
+            user_id: user_id.to_string(),
+            expires_in_ms: TYPING_TTL.as_millis() as u64,
+        });
+    }
+
+    /// Снять «печатает», если оно не продлевалось дольше `TYPING_TTL`
+    pub fn expire_typing(&mut self, chat_id: &str, user_id: &str) {
+        let key = (chat_id.to_string(), user_id.to_string());
+        if self.typing.get(&key).is_some_and(|deadline| *deadline <= Instant::now()) {
+            self.stop_typing(chat_id, user_id);
+        }
+    }
+
+    /// Снять «печатает» сразу (сообщение отправлено, соединение закрыто)
+    pub fn stop_typing(&mut self, chat_id: &str, user_id: &str) {
+        if self.typing.remove(&(chat_id.to_string(), user_id.to_string())).is_some() {
+            self.broadcast_to_chat_except(chat_id, user_id, WsMessage::UserStoppedTyping {
+                chat_id: chat_id.to_string(),
+                user_id: user_id.to_string(),
+            });
+        }
+    }
 }
 
-/// Обработка WebSocket подключения
-pub async fn handle_socket(socket: WebSocket) {
+/// Обработка WebSocket подключения.
+///
+/// Первое сообщение клиента — `auth` с JWT; затем клиент подписывается на чаты,
+/// участником которых является, и получает события этих чатов.
+pub async fn handle_socket(socket: WebSocket, state: AppState) {
     let (mut sender, mut receiver) = socket.split();
 
+    // Авторизация
+    let user_id = loop {
+        match receiver.next().await {
+            Some(Ok(Message::Text(text))) => match serde_json::from_str::<WsMessage>(&text) {
+                Ok(WsMessage::Auth { token }) => match auth::verify_token(&token) {
+                    Ok(claims) => break claims.sub,
+                    Err(_) => {
+                        let _ = send_json(&mut sender, &WsMessage::Error { message: "Неверный токен".into() }).await;
+                        return;
+                    }
+                },
+                _ => {
+                    let _ = send_json(&mut sender, &WsMessage::Error { message: "Требуется авторизация".into() }).await;
+                }
+            },
+            Some(Ok(_)) => continue,
+            _ => return,
+        }
+    };
+
+    let (mut events, came_online) = {
+        let mut ws = state.ws.write().await;
+        let was_online = ws.is_online(&user_id);
+        (ws.connect(&user_id), !was_online)
+    };
+    if came_online {
+        if let Err(e) = users::set_online(&state, &user_id).await {
+            tracing::error!("Ошибка обновления присутствия: {}", e);
+        }
+        // Отложенные «когда будет в сети»
+        let state = state.clone();
+        let user_id = user_id.clone();
+        tokio::spawn(async move {
+            if let Err(e) = crate::api::scheduled::send_when_online(&state, &user_id).await {
+                tracing::error!("Ошибка отправки отложенных сообщений: {}", e);
+            }
+        });
+    }
+    let _ = send_json(&mut sender, &WsMessage::Success { message: "authorized".into() }).await;
+
+    // Задача для отправки событий клиенту
+    let send_task = tokio::spawn(async move {
+        let mut ping = tokio::time::interval(std::time::Duration::from_secs(30));
+        loop {
+            tokio::select! {
+                event = events.recv() => match event {
+                    Ok(event) => {
+                        if send_json(&mut sender, &event).await.is_err() {
+                            break;
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        tracing::warn!("WS клиент пропустил {} событий", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = ping.tick() => {
+                    // Ping для поддержания соединения
+                    if sender.send(Message::Ping(vec![])).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+
     // Задача для получения сообщений от клиента
+    let recv_state = state.clone();
+    let recv_user_id = user_id.clone();
     let recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
-                // Парсинг сообщения
-                if let Ok(ws_msg) = serde_json::from_str::<WsMessage>(&text) {
-                    tracing::info!("Получено WS сообщение: {:?}", ws_msg);
-                    // Здесь должна быть логика обработки
+                match serde_json::from_str::<WsMessage>(&text) {
+                    Ok(WsMessage::Subscribe { chat_id }) => {
+                        match chats::authorize(&recv_state.db, &chat_id, &recv_user_id, Permission::View).await {
+                            Ok(_) => recv_state.ws.write().await.subscribe_chat(chat_id, recv_user_id.clone()),
+                            _ => tracing::warn!("Подписка на чужой чат {} от {}", chat_id, recv_user_id),
+                        }
+                    }
+                    Ok(WsMessage::Unsubscribe { chat_id }) => {
+                        recv_state.ws.write().await.unsubscribe_chat(&chat_id, &recv_user_id);
+                    }
+                    Ok(WsMessage::Typing { chat_id }) => {
+                        match chats::authorize(&recv_state.db, &chat_id, &recv_user_id, Permission::SendMessages).await {
+                            Ok(_) => {
+                                recv_state.ws.write().await.start_typing(&chat_id, &recv_user_id);
+                                let (state, user_id) = (recv_state.clone(), recv_user_id.clone());
+                                tokio::spawn(async move {
+                                    tokio::time::sleep(TYPING_TTL).await;
+                                    state.ws.write().await.expire_typing(&chat_id, &user_id);
+                                });
+                            }
+                            _ => tracing::warn!("Набор текста в чужом чате {} от {}", chat_id, recv_user_id),
+                        }
+                    }
+                    Ok(WsMessage::Read { chat_id, message_ids }) => {
+                        match chats::authorize(&recv_state.db, &chat_id, &recv_user_id, Permission::View).await {
+                            Ok(_) => {
+                                // Указатель двигается только вперёд, порядок не важен
+                                for message_id in message_ids {
+                                    if let Err(status) = reads::advance_read(&recv_state, &chat_id, &recv_user_id, &message_id).await {
+                                        tracing::debug!("Прочтение {} не отмечено: {}", message_id, status);
+                                    }
+                                }
+                            }
+                            _ => tracing::warn!("Прочтение в чужом чате {} от {}", chat_id, recv_user_id),
+                        }
+                    }
+                    Ok(ws_msg) => tracing::debug!("Необработанное WS сообщение: {:?}", ws_msg),
+                    Err(e) => tracing::debug!("Некорректное WS сообщение: {}", e),
                 }
             }
         }
     });
 
-    // Задача для отправки сообщений клиенту
-    let send_task = tokio::spawn(async move {
-        // В реальности здесь будет получение сообщений из канала
-        loop {
-            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-            // Ping для поддержания соединения
-            let _ = sender.send(Message::Ping(vec![])).await;
-        }
-    });
-
-    // Ожидание завершения любой из задач
+    // Ожидание завершения любой из задач; вторая останавливается,
+    // чтобы её приёмник событий был освобождён до `disconnect`
+    let (mut send_task, mut recv_task) = (send_task, recv_task);
     tokio::select! {
-        _ = recv_task => {},
-        _ = send_task => {},
+        _ = &mut recv_task => {
+            send_task.abort();
+            let _ = send_task.await;
+        },
+        _ = &mut send_task => {
+            recv_task.abort();
+            let _ = recv_task.await;
+        },
+    }
+
+    let went_offline = state.ws.write().await.disconnect(&user_id);
+    if went_offline {
+        if let Err(e) = users::set_offline(&state, &user_id).await {
+            tracing::error!("Ошибка обновления присутствия: {}", e);
+        }
     }
 }
 
+async fn send_json(
+    sender: &mut SplitSink<WebSocket, Message>,
+    message: &WsMessage,
+) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).unwrap_or_default();
+    sender.send(Message::Text(text)).await
+}
+
 /// Middleware для авторизации WebSocket
 pub async fn authorize_ws(token: &str, jwt_secret: &str) -> Option<String> {
     use jsonwebtoken::{decode, Validation, Algorithm};
@@ -151,3 +420,59 @@ pub async fn authorize_ws(token: &str, jwt_secret: &str) -> Option<String> {
         Err(_) => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(rx: &mut Rx) -> Vec<WsMessage> {
+        std::iter::from_fn(|| rx.try_recv().ok()).collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_typing_expires_unless_renewed() {
+        let mut ws = WebSocketManager::new();
+        let mut alice = ws.connect("alice");
+        let mut bob = ws.connect("bob");
+        ws.subscribe_chat("c1".into(), "alice".into());
+        ws.subscribe_chat("c1".into(), "bob".into());
+
+        ws.start_typing("c1", "alice");
+        assert!(matches!(drain(&mut bob)[..], [WsMessage::UserTyping { .. }]));
+        assert!(drain(&mut alice).is_empty(), "себе не отправляется");
+
+        // Продление до истечения: первый таймер ничего не снимает
+        tokio::time::advance(TYPING_TTL / 2).await;
+        ws.start_typing("c1", "alice");
+        tokio::time::advance(TYPING_TTL / 2).await;
+        ws.expire_typing("c1", "alice");
+        assert!(matches!(drain(&mut bob)[..], [WsMessage::UserTyping { .. }]));
+
+        tokio::time::advance(TYPING_TTL / 2).await;
+        ws.expire_typing("c1", "alice");
+        assert!(matches!(drain(&mut bob)[..], [WsMessage::UserStoppedTyping { .. }]));
+        ws.stop_typing("c1", "alice");
+        assert!(drain(&mut bob).is_empty(), "снятое повторно не рассылается");
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_goes_offline_after_last_connection() {
+        let mut ws = WebSocketManager::new();
+        let mut bob = ws.connect("bob");
+        let first = ws.connect("alice");
+        let second = ws.connect("alice");
+        ws.subscribe_chat("c1".into(), "alice".into());
+        ws.subscribe_chat("c1".into(), "bob".into());
+        ws.start_typing("c1", "alice");
+        drain(&mut bob);
+
+        drop(first);
+        assert!(!ws.disconnect("alice"));
+        assert!(ws.is_online("alice"));
+
+        drop(second);
+        assert!(ws.disconnect("alice"));
+        assert!(!ws.is_online("alice"));
+        assert!(matches!(drain(&mut bob)[..], [WsMessage::UserStoppedTyping { .. }]));
+    }
+}
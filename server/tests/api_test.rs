@@ -1,39 +1,22 @@
 // server/tests/api_test.rs
 //! Интеграционные тесты для API
 
+mod common;
+
 use axum::{
     body::Body,
     http::{Request, StatusCode},
     Router,
 };
-use sqlx::{SqlitePool, sqlite::SqlitePoolOptions};
-use std::sync::Arc;
 use tower::ServiceExt;
 
-type AppState = liberty_reach_server::api::AppState;
-
-async fn create_test_db() -> SqlitePool {
-    SqlitePoolOptions::new()
-        .max_connections(5)
-        .connect("sqlite::memory:")
-        .await
-        .unwrap()
-}
-
-async fn create_app(db: SqlitePool) -> Router {
-    let state = AppState {
-        db: Arc::new(db),
-        jwt_secret: "test-secret".to_string(),
-        uploads_dir: "./uploads".to_string(),
-    };
-    
-    liberty_reach_server::api::create_router(state)
+async fn create_app() -> Router {
+    common::test_app().await.0
 }
 
 #[tokio::test]
 async fn test_health_endpoint() {
-    let db = create_test_db().await;
-    let app = create_app(db).await;
+    let app = create_app().await;
     
     let response = app
         .oneshot(Request::builder().uri("/health").body(Body::empty()).unwrap())
@@ -45,8 +28,7 @@ async fn test_health_endpoint() {
 
 #[tokio::test]
 async fn test_register_user() {
-    let db = create_test_db().await;
-    let app = create_app(db).await;
+    let app = create_app().await;
     
     let body = serde_json::json!({
         "username": "testuser",
@@ -78,8 +60,7 @@ async fn test_register_user() {
 
 #[tokio::test]
 async fn test_login() {
-    let db = create_test_db().await;
-    let app = create_app(db).await;
+    let app = create_app().await;
     
     // Сначала регистрируем пользователя
     let register_body = serde_json::json!({
@@ -128,8 +109,7 @@ async fn test_login() {
 
 #[tokio::test]
 async fn test_login_wrong_password() {
-    let db = create_test_db().await;
-    let app = create_app(db).await;
+    let app = create_app().await;
     
     // Регистрируем
     let register_body = serde_json::json!({
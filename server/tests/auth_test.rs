@@ -48,7 +48,7 @@ fn test_message_signing() {
 
 #[tokio::test]
 async fn test_expired_token() {
-    use jsonwebtoken::{encode, Header, Algorithm};
+    use jsonwebtoken::{encode, Header};
     use serde::{Deserialize, Serialize};
     use chrono::{Utc, Duration};
     
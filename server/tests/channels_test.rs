@@ -0,0 +1,226 @@
+// server/tests/channels_test.rs
+//! Каналы: публикация только администраторами, подписи, просмотры и
+//! рассылка подписчикам через очередь (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{register, request, send, test_app};
+use liberty_reach_server::{
+    api::{self, AppState},
+    websocket::WsMessage,
+};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+async fn create_channel(app: &axum::Router, token: &str, member_ids: &[&str]) -> String {
+    let (status, chat) = request(
+        app,
+        "POST",
+        "/chats",
+        Some(token),
+        Some(json!({ "type": "channel", "name": "Новости", "member_ids": member_ids })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    chat["id"].as_str().unwrap().to_string()
+}
+
+async fn view(app: &axum::Router, token: &str, chat_id: &str, message_ids: &[&str]) -> (StatusCode, Value) {
+    request(
+        app,
+        "POST",
+        &format!("/chats/{}/views", chat_id),
+        Some(token),
+        Some(json!({ "message_ids": message_ids })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_channel_posts_signatures_and_views() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let (_, dave) = register(&app, "dave").await;
+    let chat_id = create_channel(&app, &alice, &[&bob_id, &carol_id]).await;
+
+    // Подписчики только читают
+    assert_eq!(send(&app, &bob, &chat_id, json!({ "content": "можно?" })).await.0, StatusCode::FORBIDDEN);
+
+    // Пост без подписи — от имени канала
+    let (status, message) = send(&app, &alice, &chat_id, json!({ "content": "первый пост" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(message["sender_id"], chat_id.as_str());
+    assert_eq!(message["views"], 0);
+    assert!(message.get("author_signature").is_none());
+    let message_id = message["id"].as_str().unwrap();
+
+    // Подписчик ставит реакцию
+    let (status, _) = request(
+        &app,
+        "POST",
+        &format!("/messages/{}/reactions", message_id),
+        Some(&bob),
+        Some(json!({ "emoji": "👍" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Просмотр засчитывается один раз на пользователя
+    let (status, views) = view(&app, &bob, &chat_id, &[message_id]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(views, json!([{ "message_id": message_id, "views": 1 }]));
+    let (_, views) = view(&app, &bob, &chat_id, &[message_id]).await;
+    assert_eq!(views[0]["views"], 1);
+    let (_, views) = view(&app, &carol, &chat_id, &[message_id, "нет-такого"]).await;
+    assert_eq!(views, json!([{ "message_id": message_id, "views": 2 }]));
+    assert_eq!(view(&app, &dave, &chat_id, &[message_id]).await.0, StatusCode::FORBIDDEN);
+
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&bob), None).await;
+    assert_eq!(page["messages"][0]["views"], 2);
+
+    // Подпись включает администратор с правом менять информацию
+    let chat_uri = format!("/chats/{}", chat_id);
+    let (status, _) = request(&app, "PATCH", &chat_uri, Some(&bob), Some(json!({ "sign_messages": true }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, chat) = request(&app, "PATCH", &chat_uri, Some(&alice), Some(json!({ "sign_messages": true }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(chat["sign_messages"], true);
+
+    // Назначенный администратор публикует с подписью
+    let (status, _) = request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/members/{}", chat_id, bob_id),
+        Some(&alice),
+        Some(json!({ "role": "admin" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, message) = send(&app, &bob, &chat_id, json!({ "content": "второй пост" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(message["sender_id"], chat_id.as_str());
+    assert_eq!(message["author_signature"], "bob");
+
+    let (_, chat) = request(&app, "GET", &chat_uri, Some(&carol), None).await;
+    assert_eq!(chat["last_message"]["sender_id"], chat_id.as_str());
+}
+
+#[tokio::test]
+async fn test_channel_posts_fan_out_to_subscribers() {
+    let db = common::test_db().await;
+    let state = AppState::new(db, "test-secret".to_string(), "./uploads".to_string());
+    let app = api::create_router(state.clone());
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, _) = register(&app, "carol").await;
+    let chat_id = create_channel(&app, &alice, &[&bob_id]).await;
+    let (_, delta) = request(&app, "GET", "/chats/sync", Some(&bob), None).await;
+    let version = delta["version"].as_i64().unwrap();
+
+    // Подписчику не нужно подписываться на чат по WebSocket
+    let (mut alice_rx, mut bob_rx, mut carol_rx) = {
+        let mut ws = state.ws.write().await;
+        (ws.connect(&alice_id), ws.connect(&bob_id), ws.connect(&carol_id))
+    };
+
+    let (_, message) = send(&app, &alice, &chat_id, json!({ "content": "всем привет" })).await;
+    let event = tokio::time::timeout(Duration::from_secs(5), bob_rx.recv())
+        .await
+        .expect("пост не доставлен")
+        .unwrap();
+    match event {
+        WsMessage::NewMessage { message: delivered } => {
+            assert_eq!(delivered.id, message["id"].as_str().unwrap());
+            assert_eq!(delivered.sender_id, chat_id);
+        }
+        other => panic!("неожиданное событие: {:?}", other),
+    }
+
+    // Ни автору, ни постороннему
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(alice_rx.try_recv().is_err());
+    assert!(carol_rx.try_recv().is_err());
+
+    // Пост меняет версию канала, а не строку у каждого подписчика
+    let (_, delta) = request(&app, "GET", &format!("/chats/sync?since={}", version), Some(&bob), None).await;
+    assert_eq!(delta["chats"][0]["id"], chat_id.as_str());
+    assert_eq!(delta["chats"][0]["last_message"]["id"], message["id"]);
+}
+
+#[tokio::test]
+async fn test_subscribers_are_hidden_from_each_other() {
+    let db = common::test_db().await;
+    let state = AppState::new(db, "test-secret".to_string(), "./uploads".to_string());
+    let app = api::create_router(state.clone());
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, _) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let (dave_id, _) = register(&app, "dave").await;
+    let chat_id = create_channel(&app, &alice, &[&bob_id, &carol_id, &dave_id]).await;
+    let (status, _) = request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/members/{}", chat_id, dave_id),
+        Some(&alice),
+        Some(json!({ "role": "admin" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (mut alice_rx, mut carol_rx, _bob_rx, _dave_rx) = {
+        let mut ws = state.ws.write().await;
+        (ws.connect(&alice_id), ws.connect(&carol_id), ws.connect(&bob_id), ws.connect(&dave_id))
+    };
+    let presence_from = |rx: &mut liberty_reach_server::websocket::Rx| -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .filter_map(|event| match event {
+                WsMessage::Presence { user_id, .. } => Some(user_id),
+                _ => None,
+            })
+            .collect()
+    };
+
+    // Подписчик не виден ни другим подписчикам, ни владельцу
+    api::users::set_online(&state, &bob_id).await.unwrap();
+    assert!(presence_from(&mut carol_rx).is_empty());
+    assert!(presence_from(&mut alice_rx).is_empty());
+    let (_, bob) = request(&app, "GET", &format!("/users/{}", bob_id), Some(&carol), None).await;
+    assert_eq!(bob["status"], "offline");
+
+    // Администраторы видят друг друга
+    api::users::set_online(&state, &dave_id).await.unwrap();
+    assert_eq!(presence_from(&mut alice_rx), vec![dave_id.clone()]);
+    assert!(presence_from(&mut carol_rx).is_empty());
+    let (_, dave) = request(&app, "GET", &format!("/users/{}", dave_id), Some(&alice), None).await;
+    assert_eq!(dave["status"], "online");
+
+    // Список подписчиков — только администраторам и постранично
+    let members_uri = format!("/chats/{}/members", chat_id);
+    assert_eq!(request(&app, "GET", &members_uri, Some(&carol), None).await.0, StatusCode::FORBIDDEN);
+    let mut seen = Vec::new();
+    let mut after = String::new();
+    loop {
+        let uri = format!("{}?limit=2&after={}", members_uri, after);
+        let (status, page) = request(&app, "GET", &uri, Some(&alice), None).await;
+        assert_eq!(status, StatusCode::OK);
+        let page: Vec<String> = page.as_array().unwrap().iter().map(|m| m["user_id"].as_str().unwrap().to_string()).collect();
+        assert!(page.len() <= 2);
+        match page.last() {
+            Some(last) => after = last.clone(),
+            None => break,
+        }
+        seen.extend(page);
+    }
+    for user_id in [&bob_id, &carol_id, &dave_id] {
+        assert!(seen.contains(user_id));
+    }
+
+    // В карточке канала подписчики не перечисляются
+    let (_, chat) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&carol), None).await;
+    let listed: Vec<&str> = chat["members"].as_array().unwrap().iter().map(|m| m["user_id"].as_str().unwrap()).collect();
+    assert!(listed.contains(&dave_id.as_str()));
+    assert!(!listed.contains(&bob_id.as_str()) && !listed.contains(&carol_id.as_str()));
+}
@@ -0,0 +1,165 @@
+// server/tests/chats_test.rs
+//! Список чатов пользователя и синхронизация дельтами (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, next_second, register, request, send, test_app};
+use serde_json::{json, Value};
+
+async fn list(app: &axum::Router, token: &str) -> Vec<Value> {
+    let (status, chats) = request(app, "GET", "/chats", Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    chats.as_array().unwrap().clone()
+}
+
+fn ids(chats: &[Value]) -> Vec<&str> {
+    chats.iter().map(|chat| chat["id"].as_str().unwrap()).collect()
+}
+
+async fn sync(app: &axum::Router, token: &str, since: Option<i64>) -> Value {
+    let uri = match since {
+        Some(since) => format!("/chats/sync?since={}", since),
+        None => "/chats/sync".to_string(),
+    };
+    let (status, delta) = request(app, "GET", &uri, Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    delta
+}
+
+#[tokio::test]
+async fn test_chat_list_is_per_user_and_ordered() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let with_bob = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+    let with_carol = create_chat(&app, &alice, "group", &[&alice_id, &carol_id]).await;
+    let without_alice = create_chat(&app, &bob, "group", &[&bob_id, &carol_id]).await;
+
+    let chats = list(&app, &alice).await;
+    assert_eq!(chats.len(), 2);
+    assert!(!ids(&chats).contains(&without_alice.as_str()));
+    let (status, _) = request(&app, "GET", &format!("/chats/{}", without_alice), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // По последней активности
+    next_second().await;
+    assert_eq!(send(&app, &bob, &with_bob, json!({ "content": "старое" })).await.0, StatusCode::OK);
+    next_second().await;
+    let (status, latest) = send(&app, &carol, &with_carol, json!({ "content": "новое" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let chats = list(&app, &alice).await;
+    assert_eq!(ids(&chats), vec![with_carol.as_str(), with_bob.as_str()]);
+    assert_eq!(chats[0]["last_message"]["id"], latest["id"]);
+    assert_eq!(chats[0]["last_message"]["content"], "новое");
+    assert_eq!(chats[0]["last_message"]["sender_id"], carol_id.as_str());
+    assert_eq!(chats[0]["unread_count"], 1);
+    let mut members: Vec<&str> =
+        chats[0]["members"].as_array().unwrap().iter().map(|m| m["username"].as_str().unwrap()).collect();
+    members.sort();
+    assert_eq!(members, vec!["alice", "carol"]);
+
+    // Закреплённые выше, настройки у каждого свои
+    let (status, settings) = request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/settings", with_bob),
+        Some(&alice),
+        Some(json!({ "pinned": true, "muted": true, "draft": "надо ответить" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(settings, json!({ "muted": true, "pinned": true, "archived": false, "draft": "надо ответить", "muted_until": null, "mute_mentions": false }));
+
+    let chats = list(&app, &alice).await;
+    assert_eq!(ids(&chats), vec![with_bob.as_str(), with_carol.as_str()]);
+    assert_eq!(chats[0]["pinned"], true);
+    assert_eq!(chats[0]["muted"], true);
+    assert_eq!(chats[0]["draft"], "надо ответить");
+    assert_eq!(chats[1]["pinned"], false);
+
+    let bob_view = list(&app, &bob).await;
+    let shared = bob_view.iter().find(|chat| chat["id"] == with_bob.as_str()).unwrap();
+    assert_eq!(shared["pinned"], false);
+    assert!(shared["draft"].is_null());
+
+    let (_, settings) = request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/settings", with_bob),
+        Some(&alice),
+        Some(json!({ "archived": true, "draft": "" })),
+    )
+    .await;
+    assert_eq!(settings, json!({ "muted": true, "pinned": true, "archived": true, "draft": null, "muted_until": null, "mute_mentions": false }));
+
+    let (status, _) = request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/settings", without_alice),
+        Some(&alice),
+        Some(json!({ "pinned": true })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_chat_list_delta_sync() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+    let quiet = create_chat(&app, &alice, "group", &[&alice_id]).await;
+
+    let full = sync(&app, &alice, None).await;
+    assert_eq!(full["chats"].as_array().unwrap().len(), 2);
+    assert!(full["removed"].as_array().unwrap().is_empty());
+    let version = full["version"].as_i64().unwrap();
+
+    let delta = sync(&app, &alice, Some(version)).await;
+    assert!(delta["chats"].as_array().unwrap().is_empty());
+    assert_eq!(delta["version"], version);
+
+    // Новое сообщение меняет чат у всех участников
+    let (status, message) = send(&app, &bob, &chat_id, json!({ "content": "привет" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let delta = sync(&app, &alice, Some(version)).await;
+    let chats = delta["chats"].as_array().unwrap();
+    assert_eq!(ids(chats), vec![chat_id.as_str()]);
+    assert_eq!(chats[0]["unread_count"], 1);
+    assert_eq!(chats[0]["last_message"]["content"], "привет");
+    let version = delta["version"].as_i64().unwrap();
+
+    // Прочтение — только у прочитавшего
+    let (status, _) = request(
+        &app,
+        "POST",
+        &format!("/chats/{}/read", chat_id),
+        Some(&alice),
+        Some(json!({ "message_id": message["id"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let delta = sync(&app, &alice, Some(version)).await;
+    assert_eq!(delta["chats"][0]["unread_count"], 0);
+    assert!(sync(&app, &bob, Some(version)).await["chats"].as_array().unwrap().is_empty());
+    let version = delta["version"].as_i64().unwrap();
+
+    request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/settings", quiet),
+        Some(&alice),
+        Some(json!({ "muted": true })),
+    )
+    .await;
+    let delta = sync(&app, &alice, Some(version)).await;
+    assert_eq!(ids(delta["chats"].as_array().unwrap()), vec![quiet.as_str()]);
+    assert_eq!(delta["chats"][0]["muted"], true);
+
+    // Посторонним изменения не приходят
+    assert!(sync(&app, &carol, Some(0)).await["chats"].as_array().unwrap().is_empty());
+}
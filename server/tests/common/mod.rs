@@ -0,0 +1,129 @@
+// server/tests/common/mod.rs
+//! Общая подготовка для интеграционных тестов
+//!
+//! Бэкенд берётся из `TEST_DATABASE_URL` (по умолчанию `sqlite::memory:`).
+//! Для PostgreSQL каждый тест получает свою схему `test_<uuid>`,
+//! поэтому тесты можно гонять параллельно в одной базе:
+//!
+//! ```text
+//! docker run --rm -d -p 5432:5432 -e POSTGRES_HOST_AUTH_METHOD=trust postgres:15
+//! TEST_DATABASE_URL=postgres://postgres@localhost:5432/postgres cargo test
+//! ```
+//!
+//! База должна быть в кодировке UTF8, иначе парсер tsvector пропускает кириллицу.
+
+#![allow(dead_code)]
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use liberty_reach_server::{
+    api::{self, AppState},
+    db::{Database, DbPool},
+};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Чистая база с применёнными миграциями
+pub async fn test_db() -> DbPool {
+    let url = std::env::var("TEST_DATABASE_URL").unwrap_or_else(|_| "sqlite::memory:".to_string());
+
+    let db = if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+        let schema = format!("test_{}", uuid::Uuid::new_v4().simple());
+        let admin = Database::connect(&url).await.expect("подключение к PostgreSQL");
+        if let Database::Postgres(pool) = &admin {
+            sqlx::query(&format!("CREATE SCHEMA {}", schema))
+                .execute(pool)
+                .await
+                .expect("создание схемы");
+        }
+        admin.close().await;
+
+        let separator = if url.contains('?') { '&' } else { '?' };
+        Database::connect(&format!("{}{}options[search_path]={}", url, separator, schema))
+            .await
+            .expect("подключение к схеме теста")
+    } else {
+        Database::connect(&url).await.expect("подключение к SQLite")
+    };
+
+    db.migrate().await.expect("миграции");
+    Arc::new(db)
+}
+
+pub async fn test_app() -> (Router, DbPool) {
+    let db = test_db().await;
+    let state = AppState::new(db.clone(), "test-secret".to_string(), "./uploads".to_string());
+    (api::create_router(state), db)
+}
+
+/// Выполнить запрос, вернуть статус и JSON-тело (`Null`, если тела нет)
+pub async fn request(
+    app: &Router,
+    method: &str,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<serde_json::Value>,
+) -> (StatusCode, serde_json::Value) {
+    let mut builder = Request::builder().method(method).uri(uri);
+    if let Some(token) = token {
+        builder = builder.header("Authorization", format!("Bearer {}", token));
+    }
+    let body = match body {
+        Some(json) => {
+            builder = builder.header("Content-Type", "application/json");
+            Body::from(json.to_string())
+        }
+        None => Body::empty(),
+    };
+
+    let response = app.clone().oneshot(builder.body(body).unwrap()).await.unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    let json = serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null);
+    (status, json)
+}
+
+/// Зарегистрировать пользователя, вернуть `(user_id, token)`
+pub async fn register(app: &Router, username: &str) -> (String, String) {
+    let (status, json) = request(
+        app,
+        "POST",
+        "/auth/register",
+        None,
+        Some(serde_json::json!({ "username": username, "password": "password123" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    (
+        json["user_id"].as_str().unwrap().to_string(),
+        json["token"].as_str().unwrap().to_string(),
+    )
+}
+
+/// Создать чат типа `chat_type`, вернуть его id
+pub async fn create_chat(app: &Router, token: &str, chat_type: &str, member_ids: &[&str]) -> String {
+    let (status, chat) = request(
+        app,
+        "POST",
+        "/chats",
+        Some(token),
+        Some(serde_json::json!({ "type": chat_type, "name": "Чат", "member_ids": member_ids })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    chat["id"].as_str().unwrap().to_string()
+}
+
+/// Отправить в чат сообщение с телом `body`
+pub async fn send(app: &Router, token: &str, chat_id: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    request(app, "POST", &format!("/chats/{}/messages", chat_id), Some(token), Some(body)).await
+}
+
+/// Время в базе хранится с точностью до секунды
+pub async fn next_second() {
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+}
@@ -0,0 +1,122 @@
+// server/tests/contacts_test.rs
+//! Контакты, импорт по именам и хешам почты, чёрный список и его действие
+//! на личные чаты и присутствие (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{request, test_app};
+use liberty_reach_server::api::contacts::email_hash;
+use serde_json::{json, Value};
+
+/// Регистрация с почтой: `(id, token)`
+async fn register(app: &axum::Router, username: &str) -> (String, String) {
+    let (status, body) = request(
+        app,
+        "POST",
+        "/auth/register",
+        None,
+        Some(json!({ "username": username, "email": format!("{}@example.com", username), "password": "password123" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    (body["user_id"].as_str().unwrap().to_string(), body["token"].as_str().unwrap().to_string())
+}
+
+async fn create_private(app: &axum::Router, token: &str, member_ids: &[&str]) -> (StatusCode, Value) {
+    request(app, "POST", "/chats", Some(token), Some(json!({ "type": "private", "member_ids": member_ids }))).await
+}
+
+#[tokio::test]
+async fn test_contacts_and_import() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, _) = register(&app, "carol").await;
+    let (_, dave) = register(&app, "dave").await;
+
+    let (status, contact) = request(&app, "POST", "/contacts", Some(&alice), Some(json!({ "user_id": bob_id }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(contact["username"], "bob");
+    assert_eq!(contact["mutual"], false);
+    let (status, _) = request(&app, "POST", "/contacts", Some(&alice), Some(json!({ "user_id": alice_id }))).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = request(&app, "POST", "/contacts", Some(&alice), Some(json!({ "user_id": "nobody" }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Импорт: по имени без учёта регистра и по хешу почты; себя и
+    // заблокировавшего не находит
+    request(&app, "POST", "/blocked", Some(&dave), Some(json!({ "user_id": bob_id }))).await;
+    let (status, imported) = request(
+        &app,
+        "POST",
+        "/contacts/import",
+        Some(&bob),
+        Some(json!({
+            "usernames": ["@ALICE", "bob", "dave", "ghost"],
+            "email_hashes": [email_hash("Carol@Example.com"), email_hash("alice@example.com")],
+        })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let imported: Vec<&str> = imported.as_array().unwrap().iter().map(|c| c["user_id"].as_str().unwrap()).collect();
+    assert_eq!(imported, [alice_id.as_str(), carol_id.as_str()]);
+
+    // Теперь Алиса и Боб — взаимные контакты
+    let (_, contacts) = request(&app, "GET", "/contacts", Some(&alice), None).await;
+    assert_eq!(contacts[0]["mutual"], true);
+
+    // Почту по хешу найти можно, но даже взаимному контакту она не видна
+    let (_, profile) = request(&app, "GET", &format!("/users/{}", alice_id), Some(&bob), None).await;
+    assert_eq!(profile["username"], "alice");
+    assert!(profile["email"].is_null());
+    let (_, own) = request(&app, "GET", &format!("/users/{}", alice_id), Some(&alice), None).await;
+    assert_eq!(own["email"], "alice@example.com");
+
+    let uri = format!("/contacts/{}", bob_id);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NOT_FOUND);
+    let (_, contacts) = request(&app, "GET", "/contacts", Some(&bob), None).await;
+    assert_eq!(contacts.as_array().unwrap().len(), 2);
+    assert_eq!(contacts[0]["mutual"], false);
+}
+
+#[tokio::test]
+async fn test_blocking_private_chats_and_presence() {
+    let (app, db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    db.users().set_offline(&alice_id, "2026-05-01 12:00:00").await.unwrap();
+
+    let (_, chat) = create_private(&app, &alice, &[&alice_id, &bob_id]).await;
+    let messages_uri = format!("/chats/{}/messages", chat["id"].as_str().unwrap());
+
+    let (status, _) = request(&app, "POST", "/blocked", Some(&alice), Some(json!({ "user_id": bob_id }))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, blocked) = request(&app, "GET", "/blocked", Some(&alice), None).await;
+    assert_eq!(blocked[0]["username"], "bob");
+
+    // Боб не пишет Алисе и не создаёт с ней личных чатов, Алиса — может
+    let (status, _) = request(&app, "POST", &messages_uri, Some(&bob), Some(json!({ "content": "эй" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = request(&app, "POST", &messages_uri, Some(&alice), Some(json!({ "content": "нет" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(create_private(&app, &bob, &[&alice_id]).await.0, StatusCode::FORBIDDEN);
+    // Читать старую переписку можно
+    let (status, _) = request(&app, "GET", &messages_uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Время захода скрыто, как при `nobody`
+    let user_uri = format!("/users/{}", alice_id);
+    let (_, user) = request(&app, "GET", &user_uri, Some(&bob), None).await;
+    assert!(user["last_seen_at"].is_null());
+
+    let uri = format!("/blocked/{}", bob_id);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NOT_FOUND);
+    let (status, _) = request(&app, "POST", &messages_uri, Some(&bob), Some(json!({ "content": "мир" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(create_private(&app, &bob, &[&alice_id]).await.0, StatusCode::OK);
+    let (_, user) = request(&app, "GET", &user_uri, Some(&bob), None).await;
+    assert_eq!(user["last_seen_at"], "2026-05-01 12:00:00");
+}
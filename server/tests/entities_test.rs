@@ -0,0 +1,115 @@
+// server/tests/entities_test.rs
+//! Разметка сообщений: хранение сущностей, проверка диапазонов,
+//! упоминания по `@username` и `text_mention` (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, send, test_app};
+use serde_json::json;
+
+/// `unread_mentions` чата глазами пользователя
+async fn unread_mentions(app: &axum::Router, token: &str, chat_id: &str) -> i64 {
+    let (_, chats) = request(app, "GET", "/chats", Some(token), None).await;
+    let chat = chats.as_array().unwrap().iter().find(|chat| chat["id"] == chat_id).unwrap();
+    chat["unread_mentions"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn test_entities_are_stored_and_validated() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let chat_id = create_chat(&app, &alice, "group", &[]).await;
+
+    // «😀 жирно #тег»: эмодзи занимает две единицы UTF-16
+    let (status, message) = send(
+        &app,
+        &alice,
+        &chat_id,
+        json!({
+            "content": "😀 жирно #тег",
+            "entities": [
+                { "type": "bold", "offset": 3, "length": 5 },
+                { "type": "text_link", "offset": 0, "length": 2, "url": "https://example.com" },
+            ],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let expected = json!([
+        { "type": "text_link", "offset": 0, "length": 2, "url": "https://example.com" },
+        { "type": "bold", "offset": 3, "length": 5 },
+        { "type": "hashtag", "offset": 9, "length": 4 },
+    ]);
+    assert_eq!(message["entities"], expected);
+
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&alice), None).await;
+    assert_eq!(page["messages"][0]["entities"], expected);
+
+    // Без разметки поля нет
+    let (_, plain) = send(&app, &alice, &chat_id, json!({ "content": "просто текст" })).await;
+    assert!(plain.get("entities").is_none());
+
+    // Посреди суррогатной пары, за концом текста, частичное пересечение
+    for entities in [
+        json!([{ "type": "bold", "offset": 1, "length": 2 }]),
+        json!([{ "type": "bold", "offset": 9, "length": 5 }]),
+        json!([{ "type": "bold", "offset": 0, "length": 5 }, { "type": "italic", "offset": 3, "length": 5 }]),
+        json!([{ "type": "text_link", "offset": 0, "length": 2, "url": "javascript:alert(1)" }]),
+    ] {
+        let (status, _) = send(&app, &alice, &chat_id, json!({ "content": "😀 жирно #тег", "entities": entities })).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    // Правка заменяет разметку
+    let message_id = message["id"].as_str().unwrap();
+    let (status, edited) = request(
+        &app,
+        "PATCH",
+        &format!("/chats/{}/messages/{}", chat_id, message_id),
+        Some(&alice),
+        Some(json!({ "content": "`код`", "entities": [{ "type": "code", "offset": 0, "length": 5 }] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(edited["entities"], json!([{ "type": "code", "offset": 0, "length": 5 }]));
+}
+
+#[tokio::test]
+async fn test_mentions_count_for_members() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id, &carol_id]).await;
+
+    // Упоминание в коде не считается, повтор — один раз
+    let (status, message) = send(
+        &app,
+        &alice,
+        &chat_id,
+        json!({
+            "content": "@bob и ещё @BOB, а `@carol` — в коде",
+            "entities": [{ "type": "code", "offset": 19, "length": 8 }],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(message["entities"][0], json!({ "type": "mention", "offset": 0, "length": 4 }));
+    assert_eq!(unread_mentions(&app, &bob, &chat_id).await, 1);
+    assert_eq!(unread_mentions(&app, &carol, &chat_id).await, 0);
+
+    // Пользователь без имени в тексте — по id
+    let (status, _) = send(
+        &app,
+        &alice,
+        &chat_id,
+        json!({
+            "content": "Кэрол, глянь",
+            "entities": [{ "type": "text_mention", "offset": 0, "length": 5, "user_id": carol_id }],
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(unread_mentions(&app, &carol, &chat_id).await, 1);
+}
@@ -0,0 +1,730 @@
+// server/tests/files_test.rs
+//! Интеграционные тесты загрузки файлов
+
+mod common;
+
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+    Router,
+};
+use common::{register, request};
+use liberty_reach_server::{
+    api::{
+        self,
+        files::{MediaWorkers, UploadLimits},
+        AppState,
+    },
+    db::Database,
+    encryption::{KeyProvider, Keyring},
+    storage,
+};
+use std::sync::Arc;
+use serde_json::json;
+use tower::ServiceExt;
+
+/// 16 байт: сигнатура PNG и начало заголовка
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+async fn files_app(limits: UploadLimits) -> (Router, AppState, tempfile::TempDir) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = common::test_db().await;
+    let mut state = AppState::new(
+        db,
+        "test-secret".to_string(),
+        dir.path().join("uploads").to_string_lossy().into_owned(),
+    );
+    state.upload_limits = limits;
+    // Фоновая обработка и process_pending_media не идут одновременно
+    state.media_workers = MediaWorkers::new(1);
+    (api::create_router(state.clone()), state, dir)
+}
+
+async fn patch_chunk(app: &Router, token: &str, upload_id: &str, offset: usize, chunk: &[u8]) -> (StatusCode, serde_json::Value) {
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("PATCH")
+                .uri(format!("/files/uploads/{}", upload_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "application/offset+octet-stream")
+                .header("Upload-Offset", offset.to_string())
+                .body(Body::from(chunk.to_vec()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+}
+
+async fn multipart_upload(app: &Router, token: &str, filename: &str, content_type: &str, data: &[u8]) -> (StatusCode, serde_json::Value) {
+    multipart_post(app, token, "/files/upload", filename, content_type, data).await
+}
+
+async fn multipart_post(
+    app: &Router,
+    token: &str,
+    uri: &str,
+    filename: &str,
+    content_type: &str,
+    data: &[u8],
+) -> (StatusCode, serde_json::Value) {
+    let mut body = format!(
+        "--BOUNDARY\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+        filename, content_type
+    )
+    .into_bytes();
+    body.extend_from_slice(data);
+    body.extend_from_slice(b"\r\n--BOUNDARY--\r\n");
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Content-Type", "multipart/form-data; boundary=BOUNDARY")
+                .body(Body::from(body))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null))
+}
+
+/// Скачать без токена: статус, заголовки, тело
+async fn fetch(app: &Router, uri: &str) -> (StatusCode, axum::http::HeaderMap, Vec<u8>) {
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let status = response.status();
+    let headers = response.headers().clone();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    (status, headers, bytes.to_vec())
+}
+
+async fn download(app: &Router, token: &str, file_id: &str) -> (StatusCode, Vec<u8>) {
+    download_uri(app, token, &format!("/files/{}/url", file_id)).await
+}
+
+async fn download_uri(app: &Router, token: &str, url_endpoint: &str) -> (StatusCode, Vec<u8>) {
+    let (status, signed) = request(app, "GET", url_endpoint, Some(token), None).await;
+    if status != StatusCode::OK {
+        return (status, Vec::new());
+    }
+    let (status, _, body) = fetch(app, signed["url"].as_str().unwrap()).await;
+    (status, body)
+}
+
+/// Пути объектов в локальном хранилище
+fn stored_paths(state: &AppState) -> Vec<std::path::PathBuf> {
+    fn walk(dir: &std::path::Path, paths: &mut Vec<std::path::PathBuf>) {
+        for entry in std::fs::read_dir(dir).into_iter().flatten() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                walk(&path, paths);
+            } else {
+                paths.push(path);
+            }
+        }
+    }
+    let mut paths = Vec::new();
+    walk(std::path::Path::new(&state.uploads_dir), &mut paths);
+    paths
+}
+
+/// Объекты в локальном хранилище
+fn stored_objects(state: &AppState) -> usize {
+    stored_paths(state).len()
+}
+
+fn keyring(keys: &[(&str, u8)]) -> Option<Arc<dyn KeyProvider>> {
+    let keys = keys.iter().map(|(id, byte)| (id.to_string(), [*byte; 32])).collect();
+    Some(Arc::new(Keyring::new(keys).unwrap()))
+}
+
+async fn create_upload(app: &Router, token: &str, filename: &str, size: usize) -> (StatusCode, serde_json::Value) {
+    request(
+        app,
+        "POST",
+        "/files/uploads",
+        Some(token),
+        Some(json!({ "filename": filename, "size": size })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_resumable_upload_flow() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, token) = register(&app, "uploader").await;
+
+    let (status, created) = create_upload(&app, &token, "photo.txt", PNG.len()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let upload_id = created["upload_id"].as_str().unwrap().to_string();
+
+    let (status, json) = patch_chunk(&app, &token, &upload_id, 0, &PNG[..10]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["offset"], 10);
+
+    // Повтор уже принятого куска отклоняется, смещение узнаётся через GET
+    let (status, _) = patch_chunk(&app, &token, &upload_id, 0, &PNG[..10]).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    let (_, json) = request(&app, "GET", &format!("/files/uploads/{}", upload_id), Some(&token), None).await;
+    assert_eq!(json["offset"], 10);
+
+    // Чужая загрузка не видна
+    let (_, other) = register(&app, "stranger").await;
+    let (status, _) = patch_chunk(&app, &other, &upload_id, 10, &PNG[10..]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Незавершённую загрузку нельзя финализировать
+    let finalize = format!("/files/uploads/{}/finalize", upload_id);
+    let (status, _) = request(&app, "POST", &finalize, Some(&token), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    let (status, json) = patch_chunk(&app, &token, &upload_id, 10, &PNG[10..]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["offset"], PNG.len());
+
+    // Тип — по содержимому, а не по имени файла
+    let (status, file) = request(&app, "POST", &finalize, Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(file["mime_type"], "image/png");
+    assert_eq!(file["original_name"], "photo.txt");
+    let file_id = file["id"].as_str().unwrap();
+    assert_eq!(file["url"], format!("/files/{}", file_id));
+    assert_eq!(download(&app, &token, file_id).await, (StatusCode::OK, PNG.to_vec()));
+    assert_eq!(stored_objects(&state), 1);
+
+    let (status, _) = request(&app, "GET", &format!("/files/uploads/{}", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, json) = request(&app, "GET", &format!("/files/{}", file_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["size"], PNG.len());
+}
+
+#[tokio::test]
+async fn test_upload_quota_and_declared_size() {
+    let limits = UploadLimits { user_quota_bytes: 20, ..Default::default() };
+    let (app, _state, _dir) = files_app(limits).await;
+    let (_, token) = register(&app, "quota").await;
+
+    let (status, _) = create_upload(&app, &token, "big.bin", 21).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Незавершённая загрузка занимает квоту
+    let (status, created) = create_upload(&app, &token, "a.png", PNG.len()).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let (status, _) = create_upload(&app, &token, "b.png", PNG.len()).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Больше заявленного размера принять нельзя
+    let upload_id = created["upload_id"].as_str().unwrap();
+    let mut oversized = PNG.to_vec();
+    oversized.push(0);
+    let (status, _) = patch_chunk(&app, &token, upload_id, 0, &oversized).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Отмена освобождает квоту
+    let (status, _) = request(&app, "DELETE", &format!("/files/uploads/{}", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = create_upload(&app, &token, "b.png", PNG.len()).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_type_limit_applies_to_sniffed_type() {
+    let limits = UploadLimits { max_image_bytes: 8, ..Default::default() };
+    let (app, _state, _dir) = files_app(limits).await;
+    let (_, token) = register(&app, "typed").await;
+
+    // Клиент выдаёт картинку за документ — лимит всё равно для изображений
+    let (_, created) = create_upload(&app, &token, "report.pdf", PNG.len()).await;
+    let upload_id = created["upload_id"].as_str().unwrap();
+    patch_chunk(&app, &token, upload_id, 0, PNG).await;
+    let (status, _) = request(&app, "POST", &format!("/files/uploads/{}/finalize", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    let (status, _) = request(&app, "GET", &format!("/files/uploads/{}", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _) = multipart_upload(&app, &token, "cat.png", "application/pdf", PNG).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    let (status, json) = multipart_upload(&app, &token, "notes.png", "image/png", "просто текст".as_bytes()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(json["mime_type"], "text/plain");
+    assert!(json["filename"].as_str().unwrap().ends_with(".txt"));
+}
+
+#[tokio::test]
+async fn test_cleanup_abandoned_uploads() {
+    let limits = UploadLimits { abandon_after_secs: 1, ..Default::default() };
+    let (app, state, _dir) = files_app(limits).await;
+    let (_, token) = register(&app, "abandoner").await;
+
+    let (_, created) = create_upload(&app, &token, "a.png", PNG.len()).await;
+    let upload_id = created["upload_id"].as_str().unwrap();
+    patch_chunk(&app, &token, upload_id, 0, &PNG[..4]).await;
+
+    assert_eq!(api::files::cleanup_abandoned_uploads(&state).await.unwrap(), 0);
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+    assert_eq!(api::files::cleanup_abandoned_uploads(&state).await.unwrap(), 1);
+
+    let (status, _) = request(&app, "GET", &format!("/files/uploads/{}", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let partial = format!("{}.partial", state.uploads_dir);
+    assert_eq!(std::fs::read_dir(partial).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_identical_content_is_stored_once() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+
+    let (status, first) = multipart_upload(&app, &alice, "cat.png", "image/png", PNG).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, second) = multipart_upload(&app, &bob, "same-cat.png", "image/png", PNG).await;
+    let (_, created) = create_upload(&app, &bob, "again.png", PNG.len()).await;
+    let upload_id = created["upload_id"].as_str().unwrap();
+    patch_chunk(&app, &bob, upload_id, 0, PNG).await;
+    let (status, third) = request(&app, "POST", &format!("/files/uploads/{}/finalize", upload_id), Some(&bob), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Три файла, одно содержимое
+    assert_ne!(first["id"], second["id"]);
+    assert_eq!(stored_objects(&state), 1);
+    for (token, file) in [(&alice, &first), (&bob, &second), (&bob, &third)] {
+        assert_eq!(download(&app, token, file["id"].as_str().unwrap()).await.1, PNG);
+    }
+
+    // Квота считается по файлам пользователя, а не по хранилищу
+    let used = state.db.files().used_bytes(&bob_id).await.unwrap();
+    assert_eq!(used, 2 * PNG.len() as i64);
+}
+
+#[tokio::test]
+async fn test_signed_download_url() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, token) = register(&app, "signer").await;
+    let (_, file) = multipart_upload(&app, &token, "котик.png", "image/png", PNG).await;
+    let file_id = file["id"].as_str().unwrap();
+
+    let (status, signed) = request(&app, "GET", &format!("/files/{}/url", file_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(signed["expires_at"].as_i64().unwrap() > chrono::Utc::now().timestamp());
+    let url = signed["url"].as_str().unwrap();
+
+    let (status, headers, body) = fetch(&app, url).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, PNG);
+    assert_eq!(headers["content-type"], "image/png");
+    assert_eq!(headers["content-length"], PNG.len().to_string());
+    assert_eq!(headers["x-content-type-options"], "nosniff");
+    assert_eq!(
+        headers["content-disposition"],
+        "inline; filename*=UTF-8''%D0%BA%D0%BE%D1%82%D0%B8%D0%BA.png"
+    );
+
+    // Подделанная подпись, чужой файл и истёкшая ссылка
+    let tampered = format!("{}0", url);
+    assert_eq!(fetch(&app, &tampered).await.0, StatusCode::FORBIDDEN);
+    let (_, other) = multipart_upload(&app, &token, "other.txt", "text/plain", b"other").await;
+    let foreign = url.replace(file_id, other["id"].as_str().unwrap());
+    assert_eq!(fetch(&app, &foreign).await.0, StatusCode::FORBIDDEN);
+    let expired = chrono::Utc::now().timestamp() - 1;
+    let expired_url = format!(
+        "/files/{}/download?expires={}&sig={}",
+        file_id,
+        expired,
+        state.url_signer.sign(file_id, expired)
+    );
+    assert_eq!(fetch(&app, &expired_url).await.0, StatusCode::FORBIDDEN);
+
+    // Без подписи старым путём файл не отдаётся
+    assert_eq!(fetch(&app, &format!("/files/{}/download", file_id)).await.0, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_file_access_follows_chat_membership() {
+    let (app, _state, _dir) = files_app(UploadLimits::default()).await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, eve) = register(&app, "eve").await;
+
+    let (_, file) = multipart_upload(&app, &alice, "cat.png", "image/png", PNG).await;
+    let file_id = file["id"].as_str().unwrap();
+    let file_path = format!("/files/{}", file_id);
+
+    // Пока файл никуда не отправлен, он виден только владельцу
+    assert_eq!(request(&app, "GET", &file_path, Some(&bob), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(download(&app, &bob, file_id).await.0, StatusCode::NOT_FOUND);
+
+    let (_, chat) = request(
+        &app,
+        "POST",
+        "/chats",
+        Some(&alice),
+        Some(json!({ "type": "private", "member_ids": [alice_id, bob_id] })),
+    )
+    .await;
+    let (status, _) = request(
+        &app,
+        "POST",
+        &format!("/chats/{}/messages", chat["id"].as_str().unwrap()),
+        Some(&alice),
+        Some(json!({ "content": "", "type": "image", "file_url": file["url"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(request(&app, "GET", &file_path, Some(&bob), None).await.0, StatusCode::OK);
+    assert_eq!(download(&app, &bob, file_id).await, (StatusCode::OK, PNG.to_vec()));
+    assert_eq!(request(&app, "GET", &file_path, Some(&eve), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(download(&app, &eve, file_id).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_only_accessible_files_can_be_attached() {
+    let (app, _state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (eve_id, eve) = register(&app, "eve").await;
+
+    // Создатель группы не в `member_ids`, но видит файлы её участников
+    let (_, group) = request(
+        &app,
+        "POST",
+        "/chats",
+        Some(&alice),
+        Some(json!({ "type": "group", "name": "Котики", "member_ids": [bob_id, eve_id] })),
+    )
+    .await;
+    let group_id = group["id"].as_str().unwrap();
+    let (_, file) = multipart_upload(&app, &bob, "cat.png", "image/png", PNG).await;
+    let file_id = file["id"].as_str().unwrap();
+    let attach = |chat_id: &str| {
+        (format!("/chats/{}/messages", chat_id), json!({ "content": "", "type": "image", "file_url": file["url"] }))
+    };
+    let (uri, body) = attach(group_id);
+    assert_eq!(request(&app, "POST", &uri, Some(&bob), Some(body)).await.0, StatusCode::OK);
+    assert_eq!(download(&app, &alice, file_id).await, (StatusCode::OK, PNG.to_vec()));
+
+    // Удалённая из группы Ева не сохранит доступ, переслав ссылку себе
+    let (_, own) = request(&app, "POST", "/chats", Some(&eve), Some(json!({ "type": "group", "name": "Моё", "member_ids": [] }))).await;
+    let own_id = own["id"].as_str().unwrap();
+    let uri_member = format!("/chats/{}/members/{}", group_id, eve_id);
+    assert_eq!(request(&app, "DELETE", &uri_member, Some(&alice), None).await.0, StatusCode::NO_CONTENT);
+    let (uri, body) = attach(own_id);
+    assert_eq!(request(&app, "POST", &uri, Some(&eve), Some(body)).await.0, StatusCode::FORBIDDEN);
+    let schedule = json!({ "content": "", "file_url": file["url"], "send_at": "2999-01-01T00:00:00Z" });
+    let uri = format!("/chats/{}/schedule", own_id);
+    assert_eq!(request(&app, "POST", &uri, Some(&eve), Some(schedule)).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(download(&app, &eve, file_id).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_deleted_content_is_collected_after_last_reference() {
+    let limits = UploadLimits { orphan_grace_secs: 0, ..Default::default() };
+    let (app, state, _dir) = files_app(limits).await;
+    let (_, alice) = register(&app, "alice").await;
+    let (_, bob) = register(&app, "bob").await;
+
+    let (_, first) = multipart_upload(&app, &alice, "cat.png", "image/png", PNG).await;
+    let (_, second) = multipart_upload(&app, &bob, "cat.png", "image/png", PNG).await;
+    let first_path = format!("/files/{}", first["id"].as_str().unwrap());
+    let second_path = format!("/files/{}", second["id"].as_str().unwrap());
+
+    // Удалить можно только свой файл
+    assert_eq!(request(&app, "DELETE", &first_path, Some(&bob), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(request(&app, "DELETE", &first_path, Some(&alice), None).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(request(&app, "GET", &first_path, Some(&alice), None).await.0, StatusCode::NOT_FOUND);
+
+    // На содержимое ещё ссылается файл Боба
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(api::files::collect_unreferenced_content(&state).await.unwrap(), 0);
+    assert_eq!(download(&app, &bob, second["id"].as_str().unwrap()).await.1, PNG);
+
+    assert_eq!(request(&app, "DELETE", &second_path, Some(&bob), None).await.0, StatusCode::NO_CONTENT);
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(api::files::collect_unreferenced_content(&state).await.unwrap(), 1);
+    assert_eq!(stored_objects(&state), 0);
+}
+
+#[tokio::test]
+async fn test_legacy_files_are_imported() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (alice_id, alice) = register(&app, "alice").await;
+
+    // Файл в старой раскладке: uploads/images/<id>.png, без хеша
+    let images = std::path::Path::new(&state.uploads_dir).join("images");
+    std::fs::create_dir_all(&images).unwrap();
+    std::fs::write(images.join("old.png"), PNG).unwrap();
+    let insert = "INSERT INTO files (id, owner_id, filename, original_name, mime_type, size, url)
+                  VALUES ('old', $1, 'old.png', 'cat.png', 'image/png', 1, '/uploads/images/old.png')";
+    match &*state.db {
+        Database::Sqlite(pool) => sqlx::query(insert).bind(&alice_id).execute(pool).await.map(|_| ()),
+        Database::Postgres(pool) => sqlx::query(insert).bind(&alice_id).execute(pool).await.map(|_| ()),
+    }
+    .unwrap();
+    assert_eq!(download(&app, &alice, "old").await.0, StatusCode::NOT_FOUND);
+
+    assert_eq!(api::files::import_legacy_files(&state).await.unwrap(), 1);
+    assert_eq!(api::files::import_legacy_files(&state).await.unwrap(), 0);
+
+    let file = state.db.files().find("old").await.unwrap().unwrap();
+    assert_eq!(file.url, "/files/old");
+    assert_eq!(file.size, PNG.len() as i64);
+    assert!(!images.join("old.png").exists());
+    let key = storage::object_key(file.content_hash.as_deref().unwrap());
+    assert!(state.storage.exists(&key).await.unwrap());
+    assert_eq!(download(&app, &alice, "old").await, (StatusCode::OK, PNG.to_vec()));
+}
+
+/// JPEG с EXIF: ориентация 6 (повёрнут на 90°) и координаты в описании
+fn photo_with_gps(width: u32, height: u32) -> Vec<u8> {
+    use img_parts::ImageEXIF;
+
+    let image = image::RgbImage::from_fn(width, height, |x, y| image::Rgb([(x % 256) as u8, (y % 256) as u8, 90]));
+    let mut data = Vec::new();
+    image::codecs::jpeg::JpegEncoder::new(&mut data).encode_image(&image).unwrap();
+
+    let description = b"GPS 55.7558N 37.6173E\0";
+    let mut exif = b"MM\0\x2A\0\0\0\x08\0\x02".to_vec();
+    exif.extend_from_slice(&[0x01, 0x0E, 0x00, 0x02, 0, 0, 0, description.len() as u8, 0, 0, 0, 38]);
+    exif.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0, 0, 0, 1, 0, 6, 0, 0]);
+    exif.extend_from_slice(&[0, 0, 0, 0]);
+    exif.extend_from_slice(description);
+
+    let mut jpeg = img_parts::jpeg::Jpeg::from_bytes(data.into()).unwrap();
+    jpeg.set_exif(Some(exif.into()));
+    jpeg.encoder().bytes().to_vec()
+}
+
+/// WAV, 8 кГц, моно: первая половина тихая, вторая громкая
+fn voice_note(millis: u32) -> Vec<u8> {
+    let rate = 8000u32;
+    let frames = rate * millis / 1000;
+    let mut wav = Vec::new();
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + frames * 2).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&rate.to_le_bytes());
+    wav.extend_from_slice(&(rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&(frames * 2).to_le_bytes());
+    for i in 0..frames {
+        let amplitude = if i < frames / 2 { 3000.0 } else { 30000.0 };
+        let sample = (amplitude * (i as f32 * 0.3).sin()) as i16;
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[tokio::test]
+async fn test_photo_is_stripped_and_thumbnailed() {
+    let limits = UploadLimits { orphan_grace_secs: 0, ..Default::default() };
+    let (app, state, _dir) = files_app(limits).await;
+    let (_, token) = register(&app, "photographer").await;
+    let photo = photo_with_gps(1000, 500);
+    assert!(contains(&photo, b"55.7558N"));
+
+    let (status, file) = multipart_upload(&app, &token, "IMG_0001.jpg", "image/jpeg", &photo).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(file["mime_type"], "image/jpeg");
+    assert_eq!(file["media_status"], "pending");
+    assert!(file["size"].as_u64().unwrap() < photo.len() as u64);
+    let file_id = file["id"].as_str().unwrap();
+
+    // Координаты не попадают даже в хранилище
+    let (_, stored) = download(&app, &token, file_id).await;
+    assert_eq!(stored.len() as u64, file["size"].as_u64().unwrap());
+    assert!(!contains(&stored, b"55.7558N"));
+
+    api::files::process_pending_media(&state).await.unwrap();
+    let (_, file) = request(&app, "GET", &format!("/files/{}", file_id), Some(&token), None).await;
+    assert_eq!(file["media_status"], "ready");
+    // Размеры — с учётом ориентации
+    assert_eq!((file["width"].as_i64(), file["height"].as_i64()), (Some(500), Some(1000)));
+    assert!(file["blurhash"].as_str().unwrap().len() > 6);
+    let thumbnails: Vec<_> = file["thumbnails"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| (t["size"].as_str().unwrap(), t["width"].as_i64().unwrap(), t["height"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(thumbnails, [("s", 50, 100), ("m", 160, 320), ("x", 400, 800)]);
+
+    let (status, thumbnail) = download_uri(&app, &token, &format!("/files/{}/url?thumbnail=m", file_id)).await;
+    assert_eq!(status, StatusCode::OK);
+    let decoded = image::load_from_memory(&thumbnail).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (160, 320));
+
+    // Подпись превью не подходит к другому превью
+    let (_, signed) = request(&app, "GET", &format!("/files/{}/url?thumbnail=s", file_id), Some(&token), None).await;
+    let other = signed["url"].as_str().unwrap().replace("thumbnail=s", "thumbnail=x");
+    assert_eq!(fetch(&app, &other).await.0, StatusCode::FORBIDDEN);
+    let (status, _) = download_uri(&app, &token, &format!("/files/{}/url?thumbnail=xl", file_id)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Удаление файла освобождает и превью
+    assert_eq!(stored_objects(&state), 4);
+    let (status, _) = request(&app, "DELETE", &format!("/files/{}", file_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    assert_eq!(api::files::collect_unreferenced_content(&state).await.unwrap(), 4);
+    assert_eq!(stored_objects(&state), 0);
+}
+
+#[tokio::test]
+async fn test_voice_note_duration_and_waveform() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, token) = register(&app, "speaker").await;
+
+    let (status, file) = multipart_upload(&app, &token, "voice.wav", "audio/wav", &voice_note(1500)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(file["mime_type"], "audio/wav");
+    let (_, broken) = multipart_upload(&app, &token, "broken.png", "image/png", PNG).await;
+    assert_eq!(broken["media_status"], "pending");
+    let (_, text) = multipart_upload(&app, &token, "notes.txt", "text/plain", b"plain").await;
+    assert_eq!(text["media_status"], serde_json::Value::Null);
+
+    api::files::process_pending_media(&state).await.unwrap();
+    let (_, file) = request(&app, "GET", &format!("/files/{}", file["id"].as_str().unwrap()), Some(&token), None).await;
+    assert_eq!(file["media_status"], "ready");
+    assert_eq!(file["duration_ms"], 1500);
+    let waveform: Vec<u64> = file["waveform"].as_array().unwrap().iter().map(|v| v.as_u64().unwrap()).collect();
+    assert_eq!(waveform.len(), 100);
+    assert_eq!(waveform.iter().max(), Some(&255));
+    assert!(waveform[10] < 50 && waveform[90] > 200, "{:?}", waveform);
+
+    // Битая картинка остаётся доступной, но без превью
+    let (_, broken) = request(&app, "GET", &format!("/files/{}", broken["id"].as_str().unwrap()), Some(&token), None).await;
+    assert_eq!(broken["media_status"], "failed");
+    assert_eq!(broken["thumbnails"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn test_files_encrypted_at_rest() {
+    let (_, state, _dir) = files_app(UploadLimits::default()).await;
+    let state = state.with_key_provider(keyring(&[("k1", 1)]));
+    let app = api::create_router(state.clone());
+    let (_, token) = register(&app, "keeper").await;
+    let secret = "совершенно секретный текст ".repeat(5000);
+
+    let (status, file) = multipart_upload(&app, &token, "secret.txt", "text/plain", secret.as_bytes()).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = multipart_upload(&app, &token, "copy.txt", "text/plain", secret.as_bytes()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let paths = stored_paths(&state);
+    assert_eq!(paths.len(), 1, "одинаковое содержимое хранится один раз");
+    assert!(paths[0].to_string_lossy().ends_with(".enc"));
+    let at_rest = std::fs::read(&paths[0]).unwrap();
+    assert!(!at_rest.windows(32).any(|w| secret.as_bytes().starts_with(w)), "на диске нет открытого текста");
+
+    let file_id = file["id"].as_str().unwrap();
+    let (_, signed) = request(&app, "GET", &format!("/files/{}/url", file_id), Some(&token), None).await;
+    let (status, headers, body) = fetch(&app, signed["url"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-length"], secret.len().to_string().as_str());
+    assert_eq!(body, secret.as_bytes());
+
+    // Новый мастер-ключ: ключи данных перешифровываются, содержимое не трогается
+    let rotated = state.clone().with_key_provider(keyring(&[("k2", 2), ("k1", 1)]));
+    let rotation = api::files::rotate_file_keys(&rotated).await.unwrap();
+    assert_eq!((rotation.rewrapped, rotation.encrypted), (1, 0));
+    assert_eq!(std::fs::read(&paths[0]).unwrap(), at_rest);
+
+    // Старый ключ больше не нужен
+    let app = api::create_router(state.with_key_provider(keyring(&[("k2", 2)])));
+    assert_eq!(download(&app, &token, file_id).await, (StatusCode::OK, secret.into_bytes()));
+}
+
+#[tokio::test]
+async fn test_rotation_encrypts_plaintext_content() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, token) = register(&app, "early").await;
+    let (_, file) = multipart_upload(&app, &token, "old.txt", "text/plain", b"stored before keys").await;
+    let plain = stored_paths(&state);
+    assert!(!plain[0].to_string_lossy().ends_with(".enc"));
+
+    let state = state.with_key_provider(keyring(&[("k1", 1)]));
+    let rotation = api::files::rotate_file_keys(&state).await.unwrap();
+    assert_eq!((rotation.rewrapped, rotation.encrypted), (0, 1));
+    let paths = stored_paths(&state);
+    assert_eq!(paths.len(), 1);
+    assert!(paths[0].to_string_lossy().ends_with(".enc"));
+    assert!(!plain[0].exists());
+
+    let app = api::create_router(state.clone());
+    let file_id = file["id"].as_str().unwrap();
+    assert_eq!(download(&app, &token, file_id).await, (StatusCode::OK, b"stored before keys".to_vec()));
+
+    // Без мастер-ключей зашифрованное не отдаётся
+    let app = api::create_router(state.with_key_provider(None));
+    assert_eq!(download(&app, &token, file_id).await.0, StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[tokio::test]
+async fn test_client_encrypted_upload_is_opaque() {
+    let (app, state, _dir) = files_app(UploadLimits::default()).await;
+    let (_, token) = register(&app, "e2e").await;
+    let photo = photo_with_gps(64, 48);
+
+    let (status, file) = multipart_post(
+        &app,
+        &token,
+        "/files/upload?client_encrypted=true",
+        "photo.jpg",
+        "image/jpeg",
+        &photo,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(file["client_encrypted"], true);
+    assert_eq!(file["mime_type"], "application/octet-stream");
+    assert!(file["filename"].as_str().unwrap().ends_with(".bin"));
+    assert!(file["media_status"].is_null(), "содержимое не обрабатывается");
+    assert_eq!(file["size"], photo.len());
+    let file_id = file["id"].as_str().unwrap();
+    assert_eq!(download(&app, &token, file_id).await, (StatusCode::OK, photo.clone()));
+
+    // С докачкой флаг задаётся при создании загрузки
+    let (status, created) = request(
+        &app,
+        "POST",
+        "/files/uploads",
+        Some(&token),
+        Some(json!({ "filename": "blob", "size": PNG.len(), "client_encrypted": true })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let upload_id = created["upload_id"].as_str().unwrap();
+    patch_chunk(&app, &token, upload_id, 0, PNG).await;
+    let (status, file) =
+        request(&app, "POST", &format!("/files/uploads/{}/finalize", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(file["client_encrypted"], true);
+    assert_eq!(file["mime_type"], "application/octet-stream");
+    assert!(file["thumbnails"].as_array().unwrap().is_empty());
+    assert_eq!(stored_objects(&state), 2);
+}
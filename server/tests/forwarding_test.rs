@@ -0,0 +1,110 @@
+// server/tests/forwarding_test.rs
+//! Пересылка: подпись автора или канала, приватность ссылки на автора
+//! и запрет пересылки в чате (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, send, test_app};
+use serde_json::{json, Value};
+
+async fn forward(app: &axum::Router, token: &str, from: &str, message_ids: &[&str], to: &[&str]) -> (StatusCode, Value) {
+    request(
+        app,
+        "POST",
+        "/messages/forward",
+        Some(token),
+        Some(json!({ "from_chat_id": from, "message_ids": message_ids, "to_chat_ids": to })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_forward_with_attribution_and_privacy() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, _) = register(&app, "carol").await;
+    let group = create_chat(&app, &alice, "group", &[&bob_id]).await;
+    let first_target = create_chat(&app, &bob, "group", &[&carol_id]).await;
+    let second_target = create_chat(&app, &bob, "group", &[]).await;
+
+    let (status, first) = send(&app, &alice, &group, json!({ "content": "фото", "type": "image", "file_url": "/files/a" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let first = first["id"].as_str().unwrap().to_string();
+    let (status, second) = send(&app, &bob, &group, json!({ "content": "подпись" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let second = second["id"].as_str().unwrap().to_string();
+
+    // Несколько сообщений в несколько чатов за раз, в порядке запроса
+    let (status, copies) = forward(&app, &bob, &group, &[&first, &second], &[&first_target, &second_target]).await;
+    assert_eq!(status, StatusCode::OK);
+    let copies = copies.as_array().unwrap();
+    assert_eq!(copies.len(), 4);
+    assert_eq!(copies[0]["chat_id"], first_target.as_str());
+    assert_eq!(copies[0]["content"], "фото");
+    assert_eq!(copies[0]["type"], "image");
+    assert_eq!(copies[0]["file_url"], "/files/a");
+    assert_eq!(copies[0]["sender_id"], bob_id.as_str());
+    assert_eq!(copies[0]["forward_from"]["sender_id"], alice_id.as_str());
+    assert_eq!(copies[0]["forward_from"]["sender_name"], "alice");
+    assert_eq!(copies[1]["forward_from"]["sender_id"], bob_id.as_str());
+    assert_eq!(copies[3]["chat_id"], second_target.as_str());
+
+    // Подпись видна в ленте и сохраняется при повторной пересылке
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", first_target), Some(&bob), None).await;
+    let copy = page["messages"].as_array().unwrap().iter().find(|m| m["content"] == "фото").unwrap();
+    let copy_id = copy["id"].as_str().unwrap();
+    assert_eq!(copy["forward_from"]["sender_id"], alice_id.as_str());
+    let (_, again) = forward(&app, &bob, &first_target, &[copy_id], &[&second_target]).await;
+    assert_eq!(again[0]["forward_from"]["sender_id"], alice_id.as_str());
+
+    // Автор скрыл ссылку на себя: остаётся только имя
+    request(&app, "PATCH", "/users/me/privacy", Some(&alice), Some(json!({ "forwards": "nobody" }))).await;
+    let (_, hidden) = forward(&app, &bob, &group, &[&first], &[&first_target]).await;
+    assert!(hidden[0]["forward_from"]["sender_id"].is_null());
+    assert_eq!(hidden[0]["forward_from"]["sender_name"], "alice");
+
+    // Без права писать в чат назначения ничего не пересылается
+    let foreign = create_chat(&app, &alice, "group", &[]).await;
+    let (status, _) = forward(&app, &bob, &group, &[&first], &[&second_target, &foreign]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", second_target), Some(&bob), None).await;
+    assert_eq!(page["messages"].as_array().unwrap().len(), 3);
+
+    // Сообщение другого чата
+    let (status, _) = forward(&app, &bob, &first_target, &[&first], &[&second_target]).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_forward_channel_post_and_restriction() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let channel = create_chat(&app, &alice, "channel", &[&bob_id]).await;
+    let target = create_chat(&app, &bob, "group", &[]).await;
+
+    let (status, post) = send(&app, &alice, &channel, json!({ "content": "новость" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let post = post["id"].as_str().unwrap().to_string();
+    let (status, copies) = forward(&app, &bob, &channel, &[&post], &[&target]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(copies[0]["forward_from"], json!({
+        "sender_id": null,
+        "sender_name": "Чат",
+        "chat_id": channel,
+        "chat_name": "Чат",
+        "message_id": post,
+        "date": copies[0]["forward_from"]["date"],
+    }));
+
+    // Запрет пересылки ставит администратор
+    let chat_uri = format!("/chats/{}", channel);
+    let (status, _) = request(&app, "PATCH", &chat_uri, Some(&bob), Some(json!({ "restrict_forwarding": true }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, chat) = request(&app, "PATCH", &chat_uri, Some(&alice), Some(json!({ "restrict_forwarding": true }))).await;
+    assert_eq!(chat["restrict_forwarding"], true);
+    let (status, _) = forward(&app, &bob, &channel, &[&post], &[&target]).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
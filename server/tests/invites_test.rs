@@ -0,0 +1,152 @@
+// server/tests/invites_test.rs
+//! Пригласительные ссылки и заявки на вступление (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, test_app};
+use serde_json::{json, Value};
+
+async fn create_invite(app: &axum::Router, token: &str, chat_id: &str, body: Value) -> (StatusCode, Value) {
+    request(app, "POST", &format!("/chats/{}/invites", chat_id), Some(token), Some(body)).await
+}
+
+async fn join(app: &axum::Router, token: &str, code: &str) -> (StatusCode, Value) {
+    request(app, "POST", &format!("/invites/{}/join", code), Some(token), None).await
+}
+
+#[tokio::test]
+async fn test_invite_limits_and_revocation() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, carol) = register(&app, "carol").await;
+    let (_, dave) = register(&app, "dave").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id]).await;
+
+    let (status, invite) = create_invite(&app, &alice, &chat_id, json!({ "max_uses": 1 })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(invite["uses"], 0);
+    assert_eq!(invite["active"], true);
+    let code = invite["code"].as_str().unwrap();
+
+    // Предпросмотр до вступления
+    let (status, preview) = request(&app, "GET", &format!("/invites/{}", code), Some(&carol), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(preview["chat_id"], chat_id.as_str());
+    assert_eq!(preview["name"], "Чат");
+    assert_eq!(preview["member_count"], 2);
+
+    let (status, joined) = join(&app, &carol, code).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(joined, json!({ "chat_id": chat_id, "status": "joined" }));
+    let (status, _) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&carol), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Лимит исчерпан; участнику повторно ничего не нужно
+    assert_eq!(join(&app, &dave, code).await.0, StatusCode::GONE);
+    assert_eq!(join(&app, &carol, code).await.0, StatusCode::OK);
+    let (_, invites) = request(&app, "GET", &format!("/chats/{}/invites", chat_id), Some(&alice), None).await;
+    assert_eq!(invites[0]["uses"], 1);
+    assert_eq!(invites[0]["active"], false);
+
+    // Отозванная ссылка не действует
+    let (_, invite) = create_invite(&app, &alice, &chat_id, json!({ "name": "для Дэйва" })).await;
+    let code = invite["code"].as_str().unwrap();
+    let (status, _) =
+        request(&app, "DELETE", &format!("/chats/{}/invites/{}", chat_id, code), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "GET", &format!("/invites/{}", code), Some(&dave), None).await;
+    assert_eq!(status, StatusCode::GONE);
+    assert_eq!(join(&app, &dave, code).await.0, StatusCode::GONE);
+
+    let (status, _) = request(&app, "GET", "/invites/нет-такой", Some(&dave), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = create_invite(&app, &bob, &chat_id, json!({})).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = create_invite(&app, &alice, &chat_id, json!({ "expires_at": "2000-01-01T00:00:00Z" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_join_requests_need_approval() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let (dave_id, dave) = register(&app, "dave").await;
+    let (eve_id, eve) = register(&app, "eve").await;
+    let chat_id = create_chat(&app, &alice, "group", &[]).await;
+
+    let (_, invite) = create_invite(&app, &alice, &chat_id, json!({ "requires_approval": true })).await;
+    let code = invite["code"].as_str().unwrap();
+
+    let (status, joined) = join(&app, &carol, code).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(joined["status"], "requested");
+    let (status, _) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&carol), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    join(&app, &dave, code).await;
+
+    let requests_uri = format!("/chats/{}/join-requests", chat_id);
+    let (status, requests) = request(&app, "GET", &requests_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let mut usernames: Vec<&str> =
+        requests.as_array().unwrap().iter().map(|r| r["username"].as_str().unwrap()).collect();
+    usernames.sort();
+    assert_eq!(usernames, vec!["carol", "dave"]);
+    let (status, _) = request(&app, "GET", &requests_uri, Some(&carol), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) =
+        request(&app, "POST", &format!("{}/{}/approve", requests_uri, carol_id), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&carol), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) =
+        request(&app, "POST", &format!("{}/{}/decline", requests_uri, dave_id), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&dave), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, requests) = request(&app, "GET", &requests_uri, Some(&alice), None).await;
+    assert!(requests.as_array().unwrap().is_empty());
+    let (status, _) =
+        request(&app, "POST", &format!("{}/{}/approve", requests_uri, dave_id), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    // Заблокированный не может ни вступить, ни подать заявку
+    request(&app, "POST", &format!("/chats/{}/bans", chat_id), Some(&alice), Some(json!({ "user_id": eve_id }))).await;
+    assert_eq!(join(&app, &eve, code).await.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_approval_respects_invite_limits() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let (dave_id, dave) = register(&app, "dave").await;
+    let chat_id = create_chat(&app, &alice, "group", &[]).await;
+
+    let (_, invite) = create_invite(&app, &alice, &chat_id, json!({ "requires_approval": true, "max_uses": 1 })).await;
+    let code = invite["code"].as_str().unwrap();
+    join(&app, &carol, code).await;
+    join(&app, &dave, code).await;
+
+    // Одно использование на две заявки: вторая остаётся ждать
+    let requests_uri = format!("/chats/{}/join-requests", chat_id);
+    let approve = |user_id: &str| format!("{}/{}/approve", requests_uri, user_id);
+    assert_eq!(request(&app, "POST", &approve(&carol_id), Some(&alice), None).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(request(&app, "POST", &approve(&dave_id), Some(&alice), None).await.0, StatusCode::GONE);
+    let (status, _) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&dave), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (_, requests) = request(&app, "GET", &requests_uri, Some(&alice), None).await;
+    assert_eq!(requests[0]["user_id"], dave_id.as_str());
+
+    // По отозванной ссылке тоже не принять
+    let (_, invite) = create_invite(&app, &alice, &chat_id, json!({ "requires_approval": true })).await;
+    let code = invite["code"].as_str().unwrap();
+    let (eve_id, eve) = register(&app, "eve").await;
+    assert_eq!(join(&app, &eve, code).await.1["status"], "requested");
+    request(&app, "DELETE", &format!("/chats/{}/invites/{}", chat_id, code), Some(&alice), None).await;
+    assert_eq!(request(&app, "POST", &approve(&eve_id), Some(&alice), None).await.0, StatusCode::GONE);
+}
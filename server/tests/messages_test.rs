@@ -0,0 +1,194 @@
+// server/tests/messages_test.rs
+//! Сообщения, правки и поиск на выбранном бэкенде (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, send, test_app};
+use liberty_reach_server::{
+    api::{self, AppState},
+    websocket::{Rx, WsMessage},
+};
+use serde_json::json;
+
+fn new_messages(rx: &mut Rx) -> Vec<String> {
+    std::iter::from_fn(|| rx.try_recv().ok())
+        .filter_map(|event| match event {
+            WsMessage::NewMessage { message } => Some(message.content),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_send_and_page_messages() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id]).await;
+
+    for i in 0..5 {
+        let (status, message) = send(&app, &alice, &chat_id, json!({ "content": format!("сообщение {}", i) })).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(message["sender_id"], alice_id.as_str());
+        assert_eq!(message["is_deleted"], false);
+    }
+
+    let (status, page) = request(&app, "GET", &format!("/chats/{}/messages?limit=3", chat_id), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page["messages"].as_array().unwrap().len(), 3);
+    let cursor = page["next_cursor"].as_str().unwrap().to_string();
+
+    let (_, older) = request(
+        &app,
+        "GET",
+        &format!("/chats/{}/messages?limit=3&before={}", chat_id, cursor),
+        Some(&alice),
+        None,
+    )
+    .await;
+    assert_eq!(older["messages"].as_array().unwrap().len(), 2);
+    assert!(older["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn test_edit_and_delete_message() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+
+    let (status, message) = send(&app, &alice, &chat_id, json!({ "content": "черновик" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let uri = format!("/chats/{}/messages/{}", chat_id, message["id"].as_str().unwrap());
+
+    // Чужое сообщение редактировать нельзя
+    let (status, _) = request(&app, "PATCH", &uri, Some(&bob), Some(json!({ "content": "взлом" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, edited) = request(&app, "PATCH", &uri, Some(&alice), Some(json!({ "content": "итог" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(edited["content"], "итог");
+    assert_eq!(edited["is_edited"], true);
+
+    let (_, edits) = request(&app, "GET", &format!("{}/edits", uri), Some(&bob), None).await;
+    assert_eq!(edits[0]["previous_content"], "черновик");
+
+    // Удаление у себя скрывает сообщение только для вызывающего
+    let (status, _) = request(&app, "DELETE", &uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&bob), None).await;
+    assert!(page["messages"].as_array().unwrap().is_empty());
+
+    // Посторонний не узнаёт о сообщении и не скрывает его
+    let (_, mallory) = register(&app, "mallory").await;
+    let (status, _) = request(&app, "DELETE", &uri, Some(&mallory), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = request(&app, "DELETE", &format!("/chats/{}/messages/missing", chat_id), Some(&mallory), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = request(&app, "DELETE", &format!("{}?for_everyone=true", uri), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&alice), None).await;
+    assert_eq!(page["messages"][0]["is_deleted"], true);
+    assert_eq!(page["messages"][0]["content"], "");
+
+    // Исключённый участник не правит и свои старые сообщения
+    let (status, own) = send(&app, &bob, &chat_id, json!({ "content": "моё" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let own_uri = format!("/chats/{}/messages/{}", chat_id, own["id"].as_str().unwrap());
+    let (status, _) = request(&app, "DELETE", &format!("/chats/{}/members/{}", chat_id, bob_id), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "PATCH", &own_uri, Some(&bob), Some(json!({ "content": "правка" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_search_messages() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (_, mallory) = register(&app, "mallory").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id]).await;
+
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "встреча в понедельник" })).await.0, StatusCode::OK);
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "встречаемся у <кафе>" })).await.0, StatusCode::OK);
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "купить хлеб" })).await.0, StatusCode::OK);
+
+    let (status, results) = request(&app, "GET", "/search?q=%D0%B2%D1%81%D1%82%D1%80%D0%B5%D1%87*", Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let hits = results["hits"].as_array().unwrap();
+    assert_eq!(hits.len(), 2);
+    assert!(hits.iter().any(|hit| hit["snippet"].as_str().unwrap().contains("&lt;кафе&gt;")));
+    assert!(hits.iter().all(|hit| hit["snippet"].as_str().unwrap().contains("<mark>")));
+
+    // Исключение термина
+    let (_, results) = request(
+        &app,
+        "GET",
+        "/search?q=%D0%B2%D1%81%D1%82%D1%80%D0%B5%D1%87*%20-%D0%BF%D0%BE%D0%BD%D0%B5%D0%B4%D0%B5%D0%BB%D1%8C%D0%BD%D0%B8%D0%BA&sort=date",
+        Some(&alice),
+        None,
+    )
+    .await;
+    assert_eq!(results["hits"].as_array().unwrap().len(), 1);
+
+    // Не участник чата ничего не находит
+    let (_, results) = request(&app, "GET", "/search?q=%D1%85%D0%BB%D0%B5%D0%B1", Some(&mallory), None).await;
+    assert!(results["hits"].as_array().unwrap().is_empty());
+
+    let (status, _) = request(&app, "GET", "/search?q=x&from=%D0%B2%D1%87%D0%B5%D1%80%D0%B0", Some(&alice), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_search_saved_messages_by_tag() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+
+    let (status, _) = request(
+        &app,
+        "POST",
+        &format!("/users/{}/saved", alice_id),
+        Some(&alice),
+        Some(json!({ "content": "рецепт борща", "tags": "кухня" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, saved) = request(&app, "GET", &format!("/users/{}/saved?tag=%D0%BA%D1%83%D1%85", alice_id), Some(&alice), None).await;
+    assert_eq!(saved.as_array().unwrap().len(), 1);
+
+    let (status, results) = request(&app, "GET", "/search/saved?q=%D0%BA%D1%83%D1%85%D0%BD%D1%8F", Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(results["hits"][0]["tags"], "кухня");
+}
+
+#[tokio::test]
+async fn test_new_message_reaches_chat_subscribers() {
+    let db = common::test_db().await;
+    let state = AppState::new(db, "test-secret".to_string(), "./uploads".to_string());
+    let app = api::create_router(state.clone());
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id]).await;
+
+    let (mut alice_rx, mut bob_rx) = {
+        let mut ws = state.ws.write().await;
+        let rxs = (ws.connect(&alice_id), ws.connect(&bob_id));
+        ws.subscribe_chat(chat_id.clone(), alice_id.clone());
+        ws.subscribe_chat(chat_id.clone(), bob_id.clone());
+        rxs
+    };
+
+    // Автору своё сообщение не приходит
+    let (status, root) = send(&app, &alice, &chat_id, json!({ "content": "привет" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(new_messages(&mut bob_rx), ["привет"]);
+    assert!(new_messages(&mut alice_rx).is_empty());
+
+    // Автор корня подписан и на тред, но ответ получает один раз
+    let (status, _) = send(&app, &bob, &chat_id, json!({ "content": "и тебе", "reply_to_id": root["id"] })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, &bob, &chat_id, json!({ "content": "как дела?" })).await.0, StatusCode::OK);
+    assert_eq!(new_messages(&mut alice_rx), ["и тебе", "как дела?"]);
+}
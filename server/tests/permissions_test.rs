@@ -0,0 +1,178 @@
+// server/tests/permissions_test.rs
+//! Роли, права администраторов, ограничения и блокировки в группах (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, send, test_app};
+use serde_json::{json, Value};
+
+async fn update_member(app: &axum::Router, token: &str, chat_id: &str, user_id: &str, body: Value) -> (StatusCode, Value) {
+    request(app, "PATCH", &format!("/chats/{}/members/{}", chat_id, user_id), Some(token), Some(body)).await
+}
+
+/// Роль участника по списку участников
+async fn role_of(app: &axum::Router, token: &str, chat_id: &str, user_id: &str) -> Value {
+    let (status, members) = request(app, "GET", &format!("/chats/{}/members", chat_id), Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    members
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|member| member["user_id"] == user_id)
+        .map(|member| member["role"].clone())
+        .unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_admin_rights_and_restrictions() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let (dave_id, dave) = register(&app, "dave").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id, &carol_id, &dave_id]).await;
+    let (_, message) = send(&app, &carol, &chat_id, json!({ "content": "закрепите меня" })).await;
+    let pin = json!({ "message_id": message["id"] });
+
+    // Обычный участник не закрепляет и не меняет чат
+    let pin_uri = format!("/chats/{}/pin", chat_id);
+    let (status, _) = request(&app, "POST", &pin_uri, Some(&bob), Some(pin.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = update_member(&app, &bob, &chat_id, &carol_id, json!({ "role": "admin" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, membership) = update_member(
+        &app,
+        &alice,
+        &chat_id,
+        &bob_id,
+        json!({ "role": "admin", "rights": ["pin_messages", "ban_users", "promote_members"] }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(membership["rights"], json!(["ban_users", "pin_messages", "promote_members"]));
+    let (status, _) = request(&app, "POST", &pin_uri, Some(&bob), Some(pin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(role_of(&app, &carol, &chat_id, &bob_id).await, "admin");
+
+    // Администратор выдаёт только свои права
+    let (status, _) =
+        update_member(&app, &bob, &chat_id, &carol_id, json!({ "role": "admin", "rights": ["change_info"] })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) =
+        update_member(&app, &bob, &chat_id, &carol_id, json!({ "role": "admin", "rights": ["pin_messages"] })).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = update_member(&app, &bob, &chat_id, &carol_id, json!({ "role": "admin", "rights": ["fly"] })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    // Снять можно только назначенного самим собой
+    let (status, _) = update_member(&app, &carol, &chat_id, &bob_id, json!({ "role": "member" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    // Владение передаётся отдельно
+    let (status, _) = update_member(&app, &alice, &chat_id, &bob_id, json!({ "role": "owner" })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Без права менять информацию название не меняется
+    let chat_uri = format!("/chats/{}", chat_id);
+    let (status, _) = request(&app, "PATCH", &chat_uri, Some(&bob), Some(json!({ "name": "Моя" }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, chat) = request(&app, "PATCH", &chat_uri, Some(&alice), Some(json!({ "name": "Новая" }))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(chat["name"], "Новая");
+
+    // Ограниченный читает, но не пишет; истёкшее ограничение не действует
+    let (status, _) = update_member(&app, &bob, &chat_id, &dave_id, json!({ "role": "restricted" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, &dave, &chat_id, json!({ "content": "можно?" })).await.0, StatusCode::FORBIDDEN);
+    let (status, _) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&dave), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = update_member(
+        &app,
+        &bob,
+        &chat_id,
+        &dave_id,
+        json!({ "role": "restricted", "until": "2000-01-01T00:00:00Z" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, &dave, &chat_id, json!({ "content": "можно" })).await.0, StatusCode::OK);
+
+    // Удалить чужое сообщение у всех — только с правом удаления
+    let delete_uri = format!("/chats/{}/messages/{}?for_everyone=true", chat_id, message["id"].as_str().unwrap());
+    let (status, _) = request(&app, "DELETE", &delete_uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = request(&app, "DELETE", &delete_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_bans_leave_and_transfer() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id, &carol_id]).await;
+    update_member(&app, &alice, &chat_id, &bob_id, json!({ "role": "admin", "rights": ["ban_users"] })).await;
+
+    let (_, delta) = request(&app, "GET", "/chats/sync", Some(&carol), None).await;
+    let version = delta["version"].as_i64().unwrap();
+
+    // Заблокированный удалён из чата и не может быть приглашён
+    let bans_uri = format!("/chats/{}/bans", chat_id);
+    let (status, _) = request(
+        &app,
+        "POST",
+        &bans_uri,
+        Some(&bob),
+        Some(json!({ "user_id": carol_id, "until": "2999-01-01T00:00:00Z" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&carol), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (_, delta) = request(&app, "GET", &format!("/chats/sync?since={}", version), Some(&carol), None).await;
+    assert_eq!(delta["removed"], json!([chat_id]));
+
+    let members_uri = format!("/chats/{}/members", chat_id);
+    let (status, _) = request(&app, "POST", &members_uri, Some(&alice), Some(json!({ "user_id": carol_id }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, bans) = request(&app, "GET", &bans_uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(bans[0]["username"], "carol");
+    assert_eq!(bans[0]["until"], "2999-01-01 00:00:00");
+
+    // Владельца заблокировать нельзя
+    let (status, _) = request(&app, "POST", &bans_uri, Some(&bob), Some(json!({ "user_id": alice_id }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let (status, _) = request(&app, "DELETE", &format!("{}/{}", bans_uri, carol_id), Some(&bob), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "POST", &members_uri, Some(&alice), Some(json!({ "user_id": carol_id }))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(role_of(&app, &carol, &chat_id, &carol_id).await, "member");
+
+    // Выйти может любой, кроме владельца
+    let (status, _) = request(&app, "DELETE", &format!("{}/{}", members_uri, carol_id), Some(&carol), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = request(&app, "DELETE", &format!("{}/{}", members_uri, alice_id), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Передача владения: прежний владелец остаётся администратором
+    let transfer_uri = format!("/chats/{}/transfer", chat_id);
+    let (status, _) = request(&app, "POST", &transfer_uri, Some(&bob), Some(json!({ "user_id": bob_id }))).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = request(&app, "POST", &transfer_uri, Some(&alice), Some(json!({ "user_id": carol_id }))).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = request(&app, "POST", &transfer_uri, Some(&alice), Some(json!({ "user_id": bob_id }))).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    assert_eq!(role_of(&app, &bob, &chat_id, &bob_id).await, "owner");
+    assert_eq!(role_of(&app, &bob, &chat_id, &alice_id).await, "admin");
+    let (_, chat) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&alice), None).await;
+    assert_eq!(chat["owner_id"], bob_id.as_str());
+    let (status, _) = update_member(&app, &alice, &chat_id, &bob_id, json!({ "role": "member" })).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = update_member(&app, &bob, &chat_id, &alice_id, json!({ "role": "member" })).await;
+    assert_eq!(status, StatusCode::OK);
+}
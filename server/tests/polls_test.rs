@@ -0,0 +1,182 @@
+// server/tests/polls_test.rs
+//! Опросы и викторины: голоса, отзыв, закрытие и видимость ответа
+//! (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, send, test_app};
+use serde_json::{json, Value};
+
+async fn send_poll(app: &axum::Router, token: &str, chat_id: &str, poll: Value) -> (StatusCode, Value) {
+    send(app, token, chat_id, json!({ "content": "Что выбрать?", "type": "poll", "poll": poll })).await
+}
+
+async fn vote(app: &axum::Router, token: &str, chat_id: &str, message_id: &str, options: Value) -> (StatusCode, Value) {
+    request(
+        app,
+        "POST",
+        &format!("/chats/{}/messages/{}/vote", chat_id, message_id),
+        Some(token),
+        Some(json!({ "options": options })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_public_multiple_choice_poll() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id]).await;
+
+    // Опрос без вариантов или не того типа не отправить
+    let (status, _) = send_poll(&app, &alice, &chat_id, json!({ "options": ["один"] })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send(&app, &alice, &chat_id, json!({ "content": "текст", "poll": { "options": ["a", "b"] } })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, message) = send_poll(
+        &app,
+        &alice,
+        &chat_id,
+        json!({ "options": ["чай", "кофе", "сок"], "multiple": true, "anonymous": false }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(message["type"], "poll");
+    assert_eq!(message["poll"]["options"][0]["text"], "чай");
+    assert_eq!(message["poll"]["total_voters"], 0);
+    let message_id = message["id"].as_str().unwrap();
+
+    let (status, poll) = vote(&app, &bob, &chat_id, message_id, json!([2, 0])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(poll["chosen"], json!([0, 2]));
+    assert_eq!(poll["options"][2]["voters"], json!([bob_id]));
+    assert_eq!(vote(&app, &bob, &chat_id, message_id, json!([1])).await.0, StatusCode::CONFLICT);
+    assert_eq!(vote(&app, &alice, &chat_id, message_id, json!([3])).await.0, StatusCode::BAD_REQUEST);
+    assert_eq!(vote(&app, &carol, &chat_id, message_id, json!([1])).await.0, StatusCode::FORBIDDEN);
+    vote(&app, &alice, &chat_id, message_id, json!([0])).await;
+
+    // В ленте — результаты и свой выбор
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&bob), None).await;
+    let poll = &page["messages"][0]["poll"];
+    assert_eq!(poll["total_voters"], 2);
+    assert_eq!(poll["options"][0]["votes"], 2);
+    let mut voters: Vec<&str> = poll["options"][0]["voters"].as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect();
+    voters.sort_unstable();
+    let mut expected = vec![alice_id.as_str(), bob_id.as_str()];
+    expected.sort_unstable();
+    assert_eq!(voters, expected);
+    assert_eq!(poll["chosen"], json!([0, 2]));
+
+    // Отозвать и переголосовать
+    let vote_uri = format!("/chats/{}/messages/{}/vote", chat_id, message_id);
+    let (status, poll) = request(&app, "DELETE", &vote_uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(poll["total_voters"], 1);
+    assert_eq!(poll["chosen"], json!([]));
+    assert_eq!(request(&app, "DELETE", &vote_uri, Some(&bob), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(vote(&app, &bob, &chat_id, message_id, json!([1])).await.0, StatusCode::OK);
+
+    // Закрыть может только автор; в закрытом не голосуют
+    let close_uri = format!("/chats/{}/messages/{}/close", chat_id, message_id);
+    assert_eq!(request(&app, "POST", &close_uri, Some(&bob), None).await.0, StatusCode::FORBIDDEN);
+    let (status, poll) = request(&app, "POST", &close_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(poll["closed"], true);
+    assert_eq!(request(&app, "DELETE", &vote_uri, Some(&bob), None).await.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_anonymous_quiz() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id, &carol_id]).await;
+
+    let (status, _) = send_poll(
+        &app,
+        &alice,
+        &chat_id,
+        json!({ "options": ["2", "4"], "correct_option": 1, "multiple": true }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = send_poll(
+        &app,
+        &alice,
+        &chat_id,
+        json!({ "options": ["2", "4"], "correct_option": 1, "closes_at": "2000-01-01T00:00:00Z" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (_, message) = send_poll(
+        &app,
+        &alice,
+        &chat_id,
+        json!({ "options": ["2", "4"], "correct_option": 1, "explanation": "2 + 2 = 4" }),
+    )
+    .await;
+    let message_id = message["id"].as_str().unwrap();
+    // Автор видит ответ, остальные — только после своего
+    assert_eq!(message["poll"]["correct_option"], 1);
+    assert_eq!(message["poll"]["quiz"], true);
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&bob), None).await;
+    assert!(page["messages"][0]["poll"]["correct_option"].is_null());
+
+    // Одно значение в викторине
+    assert_eq!(vote(&app, &bob, &chat_id, message_id, json!([0, 1])).await.0, StatusCode::BAD_REQUEST);
+    let (status, poll) = vote(&app, &bob, &chat_id, message_id, json!([0])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(poll["correct_option"], 1);
+    assert_eq!(poll["explanation"], "2 + 2 = 4");
+    assert!(poll["options"][0].get("voters").is_none());
+
+    // Ответ викторины окончательный
+    let vote_uri = format!("/chats/{}/messages/{}/vote", chat_id, message_id);
+    assert_eq!(request(&app, "DELETE", &vote_uri, Some(&bob), None).await.0, StatusCode::FORBIDDEN);
+
+    // После закрытия ответ виден всем
+    let close_uri = format!("/chats/{}/messages/{}/close", chat_id, message_id);
+    request(&app, "POST", &close_uri, Some(&alice), None).await;
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&carol), None).await;
+    assert_eq!(page["messages"][0]["poll"]["correct_option"], 1);
+    assert_eq!(vote(&app, &carol, &chat_id, message_id, json!([1])).await.0, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_concurrent_votes_count_once() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id]).await;
+    let (_, message) = send_poll(&app, &alice, &chat_id, json!({ "options": ["да", "нет"], "anonymous": false })).await;
+    let message_id = message["id"].as_str().unwrap();
+
+    // Одновременно за разные варианты и дважды за один: принят один голос
+    for options in [[json!([0]), json!([1])], [json!([1]), json!([1])]] {
+        let [first, second] = options;
+        let (a, b) = tokio::join!(
+            vote(&app, &bob, &chat_id, message_id, first),
+            vote(&app, &bob, &chat_id, message_id, second),
+        );
+        let mut statuses = [a.0, b.0];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::CONFLICT]);
+
+        let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&alice), None).await;
+        let voters: usize = page["messages"][0]["poll"]["options"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|option| option["voters"].as_array().unwrap().len())
+            .sum();
+        assert_eq!(voters, 1);
+        let vote_uri = format!("/chats/{}/messages/{}/vote", chat_id, message_id);
+        assert_eq!(request(&app, "DELETE", &vote_uri, Some(&bob), None).await.0, StatusCode::OK);
+    }
+}
@@ -0,0 +1,195 @@
+// server/tests/presence_test.rs
+//! Присутствие, «печатает» и приватность через настоящий WebSocket
+
+mod common;
+
+use axum::{extract::WebSocketUpgrade, http::StatusCode, routing::get, Router};
+use common::{register, request};
+use futures::{SinkExt, StreamExt};
+use liberty_reach_server::{
+    api::{self, AppState},
+    db::{Database, DbPool},
+    websocket,
+};
+use serde_json::json;
+use std::time::Duration;
+use tokio_tungstenite::{tungstenite::Message, MaybeTlsStream, WebSocketStream};
+
+type Socket = WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// API и `/ws` на свободном порту
+async fn serve() -> (Router, DbPool, String) {
+    let db = common::test_db().await;
+    let state = AppState::new(db.clone(), "test-secret".to_string(), "./uploads".to_string());
+    let ws_state = state.clone();
+    let app = api::create_router(state).route(
+        "/ws",
+        get(move |ws: WebSocketUpgrade| async move {
+            ws.on_upgrade(move |socket| websocket::handle_socket(socket, ws_state))
+        }),
+    );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+    let server = app.clone();
+    tokio::spawn(async move { axum::serve(listener, server).await.unwrap() });
+    (app, db, url)
+}
+
+async fn send(socket: &mut Socket, message: serde_json::Value) {
+    socket.send(Message::Text(message.to_string())).await.unwrap();
+}
+
+/// Подключиться и авторизоваться
+async fn connect(url: &str, token: &str) -> Socket {
+    let (mut socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+    send(&mut socket, json!({ "type": "auth", "token": token })).await;
+    next_event(&mut socket, "success").await;
+    socket
+}
+
+/// Следующее событие типа `kind`; остальные пропускаются
+async fn next_event(socket: &mut Socket, kind: &str) -> serde_json::Value {
+    let wait = async {
+        loop {
+            if let Message::Text(text) = socket.next().await.unwrap().unwrap() {
+                let event: serde_json::Value = serde_json::from_str(&text).unwrap();
+                if event["type"] == kind {
+                    return event;
+                }
+            }
+        }
+    };
+    tokio::time::timeout(Duration::from_secs(5), wait)
+        .await
+        .unwrap_or_else(|_| panic!("нет события {}", kind))
+}
+
+async fn execute(db: &Database, sql: &str, binds: &[&str]) {
+    match db {
+        Database::Sqlite(pool) => {
+            let mut query = sqlx::query(sql);
+            for bind in binds {
+                query = query.bind(*bind);
+            }
+            query.execute(pool).await.unwrap();
+        }
+        Database::Postgres(pool) => {
+            let mut query = sqlx::query(sql);
+            for bind in binds {
+                query = query.bind(*bind);
+            }
+            query.execute(pool).await.unwrap();
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_presence_and_typing_events() {
+    let (app, _db, url) = serve().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, chat) = request(
+        &app,
+        "POST",
+        "/chats",
+        Some(&alice),
+        Some(json!({ "type": "private", "name": "Личка", "member_ids": [alice_id, bob_id] })),
+    )
+    .await;
+    let chat_id = chat["id"].as_str().unwrap();
+
+    let mut bob_socket = connect(&url, &bob).await;
+    send(&mut bob_socket, json!({ "type": "subscribe", "chat_id": chat_id })).await;
+
+    let mut alice_socket = connect(&url, &alice).await;
+    let presence = next_event(&mut bob_socket, "presence").await;
+    assert_eq!(presence["user_id"], alice_id.as_str());
+    assert_eq!(presence["online"], true);
+    let (_, user) = request(&app, "GET", &format!("/users/{}", alice_id), Some(&bob), None).await;
+    assert_eq!(user["status"], "online");
+
+    // Подписка Боба обрабатывается параллельно — повторяем, пока не дойдёт
+    let typing = loop {
+        send(&mut alice_socket, json!({ "type": "typing", "chat_id": chat_id })).await;
+        let wait = tokio::time::timeout(Duration::from_millis(300), next_event(&mut bob_socket, "user_typing"));
+        if let Ok(event) = wait.await {
+            break event;
+        }
+    };
+    assert_eq!(typing["user_id"], alice_id.as_str());
+    assert_eq!(typing["expires_in_ms"], websocket::TYPING_TTL.as_millis() as u64);
+
+    let (status, _) = request(
+        &app,
+        "POST",
+        &format!("/chats/{}/messages", chat_id),
+        Some(&alice),
+        Some(json!({ "content": "привет" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let stopped = next_event(&mut bob_socket, "user_stopped_typing").await;
+    assert_eq!(stopped["chat_id"], chat_id);
+
+    alice_socket.close(None).await.unwrap();
+    let presence = next_event(&mut bob_socket, "presence").await;
+    assert_eq!(presence["online"], false);
+    let last_seen = presence["last_seen_at"].as_str().unwrap().to_string();
+
+    let (_, user) = request(&app, "GET", &format!("/users/{}", alice_id), Some(&bob), None).await;
+    assert_eq!(user["status"], "offline");
+    assert_eq!(user["last_seen_at"], last_seen.as_str());
+}
+
+#[tokio::test]
+async fn test_privacy_settings_hide_presence() {
+    let (app, db, url) = serve().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, carol) = register(&app, "carol").await;
+    execute(&db, "INSERT INTO contacts (user_id, contact_id) VALUES ($1, $2)", &[&alice_id, &bob_id]).await;
+    execute(&db, "UPDATE users SET last_seen_at = $1 WHERE id = $2", &["2026-01-02 03:04:05", &alice_id]).await;
+
+    let (status, privacy) = request(&app, "GET", "/users/me/privacy", Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(privacy, json!({ "last_seen": "everyone", "online": "everyone", "forwards": "everyone", "read_receipts": true }));
+
+    let (status, privacy) = request(
+        &app,
+        "PATCH",
+        "/users/me/privacy",
+        Some(&alice),
+        Some(json!({ "last_seen": "contacts", "online": "nobody" })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(privacy, json!({ "last_seen": "contacts", "online": "nobody", "forwards": "everyone", "read_receipts": true }));
+    let (status, _) =
+        request(&app, "PATCH", "/users/me/privacy", Some(&alice), Some(json!({ "online": "sometimes" }))).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+    let _alice_socket = connect(&url, &alice).await;
+    let profile = |token: String| {
+        let app = app.clone();
+        let alice_id = alice_id.clone();
+        async move { request(&app, "GET", &format!("/users/{}", alice_id), Some(&token), None).await.1 }
+    };
+
+    // Контакт видит время захода, но не online
+    let seen_by_bob = profile(bob.clone()).await;
+    assert_eq!(seen_by_bob["status"], "offline");
+    assert_eq!(seen_by_bob["last_seen_at"], "2026-01-02 03:04:05");
+
+    let seen_by_carol = profile(carol).await;
+    assert_eq!(seen_by_carol["status"], "offline");
+    assert!(seen_by_carol["last_seen_at"].is_null());
+
+    // Себе видно всё
+    let own = profile(alice.clone()).await;
+    assert_eq!(own["status"], "online");
+    assert_eq!(own["last_seen_at"], "2026-01-02 03:04:05");
+
+    let (status, _) = request(&app, "GET", &format!("/users/{}", alice_id), None, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
@@ -0,0 +1,213 @@
+// server/tests/push_test.rs
+//! Push-уведомления: регистрация устройств, зашифрованные подсказки,
+//! чаты без звука и пропуск подписчиков чата (провайдер — `MockPushProvider`,
+//! см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, register, request, send};
+use liberty_reach_server::{
+    api::{self, AppState},
+    push::{open_hint, HintKind, MockPushProvider, PushGateway, SentPush},
+    websocket::WsMessage,
+};
+use serde_json::{json, Value};
+use std::{sync::Arc, time::Duration};
+
+const HINT_KEY: &str = "AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8=";
+
+async fn push_app() -> (axum::Router, AppState, Arc<MockPushProvider>) {
+    let db = common::test_db().await;
+    let mock = Arc::new(MockPushProvider::new("unifiedpush"));
+    let state = AppState::new(db, "test-secret".to_string(), "./uploads".to_string())
+        .with_push(PushGateway::default().with(mock.clone()));
+    (api::create_router(state.clone()), state, mock)
+}
+
+async fn register_device(app: &axum::Router, token: &str, body: Value) -> (StatusCode, Value) {
+    request(app, "POST", "/push/devices", Some(token), Some(body)).await
+}
+
+/// Дождаться `count` отправленных уведомлений. Очередь обрабатывает задачи
+/// по порядку, так что пропущенные раньше уже не появятся.
+async fn wait_sent(mock: &MockPushProvider, count: usize) -> Vec<SentPush> {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let sent = mock.sent();
+            if sent.len() >= count {
+                return sent;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("уведомление не отправлено")
+}
+
+#[tokio::test]
+async fn test_devices_and_encrypted_hints() {
+    let (app, _state, mock) = push_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+
+    let (_, config) = request(&app, "GET", "/push/config", Some(&bob), None).await;
+    assert_eq!(config, json!({ "providers": ["unifiedpush"], "vapid_public_key": null }));
+
+    // Неизвестный провайдер, не URL, короткий ключ подсказки, неполные ключи подписки
+    let endpoint = "https://push.example.com/up/bob";
+    for body in [
+        json!({ "provider": "webpush", "endpoint": endpoint, "hint_key": HINT_KEY }),
+        json!({ "provider": "unifiedpush", "endpoint": "bob", "hint_key": HINT_KEY }),
+        json!({ "provider": "unifiedpush", "endpoint": endpoint, "hint_key": "c2hvcnQ=" }),
+        json!({ "provider": "unifiedpush", "endpoint": endpoint, "hint_key": HINT_KEY, "auth": "AAECAwQFBgcICQoLDA0ODw" }),
+    ] {
+        assert_eq!(register_device(&app, &bob, body).await.0, StatusCode::BAD_REQUEST);
+    }
+    let (status, device) = register_device(
+        &app,
+        &bob,
+        json!({ "provider": "unifiedpush", "endpoint": endpoint, "hint_key": HINT_KEY }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    // Ключ подсказки наружу не отдаётся
+    assert!(device.get("hint_key").is_none());
+    let (_, devices) = request(&app, "GET", "/push/devices", Some(&bob), None).await;
+    assert_eq!(devices.as_array().unwrap().len(), 1);
+
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+    let (status, message) = send(&app, &alice, &chat_id, json!({ "content": "совершенно секретно" })).await;
+    assert_eq!(status, StatusCode::OK);
+    let message_id = message["id"].as_str().unwrap();
+
+    // Автору уведомление не приходит; в подсказке нет текста
+    let sent = wait_sent(&mock, 1).await;
+    assert_eq!(sent[0].user_id, bob_id);
+    assert_eq!(sent[0].endpoint, endpoint);
+    assert!(!sent[0].urgent);
+    assert!(!sent[0].hint.contains("секретно") && !sent[0].hint.contains(&chat_id));
+    let hint = open_hint(HINT_KEY, &sent[0].hint).expect("подсказка не расшифровывается");
+    assert_eq!(hint.kind, HintKind::Message);
+    assert_eq!(hint.chat_id, chat_id);
+    assert_eq!(hint.message_id, message_id);
+
+    let uri = format!("/push/devices/{}", device["id"].as_str().unwrap());
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&bob), None).await.0, StatusCode::NO_CONTENT);
+    let (_, devices) = request(&app, "GET", "/push/devices", Some(&bob), None).await;
+    assert!(devices.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_mute_presence_and_expired_subscriptions() {
+    let (app, state, mock) = push_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let bob_endpoint = "https://push.example.com/up/bob";
+    for (token, endpoint) in [(&bob, bob_endpoint), (&carol, "https://push.example.com/up/carol")] {
+        let body = json!({ "provider": "unifiedpush", "endpoint": endpoint, "hint_key": HINT_KEY });
+        assert_eq!(register_device(&app, token, body).await.0, StatusCode::OK);
+    }
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id, &carol_id]).await;
+    let settings_uri = format!("/chats/{}/settings", chat_id);
+    // Кэрол уведомляется всегда: её уведомление значит, что получатели
+    // сообщения уже выбраны и настройки Боба можно менять дальше
+    let post_and_wait = |content: &'static str| {
+        let (app, alice, chat_id, carol_id, mock) = (&app, &alice, &chat_id, &carol_id, &mock);
+        async move {
+            let (status, message) = send(app, alice, chat_id, json!({ "content": content })).await;
+            assert_eq!(status, StatusCode::OK);
+            let message_id = message["id"].as_str().unwrap().to_string();
+            wait_for(mock, carol_id, &message_id).await;
+            message_id
+        }
+    };
+
+    // Без звука — только упоминания
+    let (_, settings) = request(&app, "PATCH", &settings_uri, Some(&bob), Some(json!({ "muted": true }))).await;
+    assert_eq!(settings["muted"], true);
+    let quiet_id = post_and_wait("тихо").await;
+    let mention_id = post_and_wait("@bob глянь").await;
+
+    // Упоминания тоже выключены, звук — до даты; `muted: false` её снимает
+    let (_, settings) =
+        request(&app, "PATCH", &settings_uri, Some(&bob), Some(json!({ "muted": false, "mute_mentions": true }))).await;
+    assert_eq!(settings["mute_mentions"], true);
+    let (_, settings) =
+        request(&app, "PATCH", &settings_uri, Some(&bob), Some(json!({ "muted_until": "2999-01-01T00:00:00Z" }))).await;
+    assert_eq!(settings["muted_until"], "2999-01-01 00:00:00");
+    let muted_mention_id = post_and_wait("@bob ещё раз").await;
+
+    // Истёкший `muted_until` снова включает звук
+    request(&app, "PATCH", &settings_uri, Some(&bob), Some(json!({ "muted_until": "2000-01-01T00:00:00Z" }))).await;
+    let loud_id = post_and_wait("а теперь со звуком").await;
+
+    let (_, settings) = request(&app, "PATCH", &settings_uri, Some(&bob), Some(json!({ "muted": false }))).await;
+    assert!(settings["muted_until"].is_null());
+
+    // Подключённый, но не открывший чат, сообщения по WebSocket не получит
+    let mut bob_rx = state.ws.write().await.connect(&bob_id);
+    let unsubscribed_id = post_and_wait("ты где?").await;
+    // Подписчику чата уведомление не нужно: сообщение уже пришло
+    state.ws.write().await.subscribe_chat(chat_id.clone(), bob_id.clone());
+    let online_id = post_and_wait("ты же тут").await;
+    let live: Vec<String> = std::iter::from_fn(|| bob_rx.try_recv().ok())
+        .filter_map(|event| match event {
+            WsMessage::NewMessage { message } => Some(message.id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(live, vec![online_id.clone()]);
+    drop(bob_rx);
+    state.ws.write().await.disconnect(&bob_id);
+
+    // Подписка истекла — устройство удаляется
+    mock.expire(bob_endpoint);
+    post_and_wait("кто-нибудь?").await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !state.db.push().devices(&bob_id).await.unwrap().is_empty() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("устройство не удалено");
+
+    let sent = mock.sent();
+    let recipients = |message_id: &str| -> Vec<(String, bool)> {
+        let mut users: Vec<(String, bool)> = sent
+            .iter()
+            .filter(|push| open_hint(HINT_KEY, &push.hint).unwrap().message_id == message_id)
+            .map(|push| (push.user_id.clone(), push.urgent))
+            .collect();
+        users.sort();
+        users
+    };
+    let bob_too = |urgent: bool| {
+        let mut users = vec![(bob_id.clone(), urgent), (carol_id.clone(), false)];
+        users.sort();
+        users
+    };
+    let carol_only = vec![(carol_id.clone(), false)];
+    assert_eq!(recipients(&quiet_id), carol_only);
+    // Упоминание — срочное
+    assert_eq!(recipients(&mention_id), bob_too(true));
+    assert_eq!(recipients(&muted_mention_id), carol_only);
+    assert_eq!(recipients(&loud_id), bob_too(false));
+    assert_eq!(recipients(&unsubscribed_id), bob_too(false));
+    assert_eq!(recipients(&online_id), carol_only);
+}
+
+/// Дождаться уведомления `user_id` о сообщении
+async fn wait_for(mock: &MockPushProvider, user_id: &str, message_id: &str) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !mock.sent().iter().any(|push| {
+            push.user_id == user_id && open_hint(HINT_KEY, &push.hint).is_some_and(|hint| hint.message_id == message_id)
+        }) {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("уведомление не отправлено")
+}
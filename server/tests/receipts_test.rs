@@ -0,0 +1,157 @@
+// server/tests/receipts_test.rs
+//! Доставка, прочтение и счётчики непрочитанного (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, next_second, register, request, send, test_app};
+use serde_json::{json, Value};
+
+/// Лента от новых к старым
+async fn feed(app: &axum::Router, token: &str, chat_id: &str) -> Vec<Value> {
+    let (status, page) = request(app, "GET", &format!("/chats/{}/messages", chat_id), Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    page["messages"].as_array().unwrap().clone()
+}
+
+/// `(unread_count, unread_mentions)` чата из списка чатов
+async fn counters(app: &axum::Router, token: &str, chat_id: &str) -> (i64, i64) {
+    let (status, chats) = request(app, "GET", "/chats", Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let chat = chats
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|chat| chat["id"] == chat_id)
+        .expect("чат в списке");
+    (chat["unread_count"].as_i64().unwrap(), chat["unread_mentions"].as_i64().unwrap())
+}
+
+async fn mark_read(app: &axum::Router, token: &str, chat_id: &str, message_id: &str) -> (StatusCode, Value) {
+    request(
+        app,
+        "POST",
+        &format!("/chats/{}/read", chat_id),
+        Some(token),
+        Some(json!({ "message_id": message_id })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_unread_counters_and_read_up_to() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let (_, dave) = register(&app, "dave").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id, &carol_id]).await;
+
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "всем привет" })).await.0, StatusCode::OK);
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "@Bob посмотри" })).await.0, StatusCode::OK);
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "и ты, @carol" })).await.0, StatusCode::OK);
+
+    assert_eq!(counters(&app, &bob, &chat_id).await, (3, 1));
+    assert_eq!(counters(&app, &carol, &chat_id).await, (3, 1));
+    assert_eq!(counters(&app, &alice, &chat_id).await, (0, 0));
+    let (_, chats) = request(&app, "GET", "/chats", Some(&dave), None).await;
+    assert!(chats.as_array().unwrap().is_empty());
+
+    // Сообщения одной секунды упорядочены по id — позиции берём из ленты
+    let messages = feed(&app, &bob, &chat_id).await;
+    let newest_mentions_bob = messages[0]["content"].as_str().unwrap().contains("@Bob");
+    let (status, state) = mark_read(&app, &bob, &chat_id, messages[1]["id"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state["unread_count"], 1);
+    assert_eq!(state["unread_mentions"], newest_mentions_bob as i64);
+
+    let (_, state) = mark_read(&app, &bob, &chat_id, messages[0]["id"].as_str().unwrap()).await;
+    assert_eq!((state["unread_count"].as_i64(), state["unread_mentions"].as_i64()), (Some(0), Some(0)));
+
+    // Назад указатель не двигается
+    let (_, state) = mark_read(&app, &bob, &chat_id, messages[2]["id"].as_str().unwrap()).await;
+    assert_eq!(state["unread_count"], 0);
+    assert_eq!(counters(&app, &bob, &chat_id).await, (0, 0));
+
+    // Отправитель видит статусы своих сообщений, остальные — нет
+    for message in feed(&app, &alice, &chat_id).await {
+        assert_eq!(message["status"], "read");
+    }
+    assert!(feed(&app, &bob, &chat_id).await.iter().all(|m| m.get("status").is_none()));
+
+    let reads_uri = format!("/chats/{}/messages/{}/reads", chat_id, messages[2]["id"].as_str().unwrap());
+    let (status, readers) = request(&app, "GET", &reads_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let readers = readers.as_array().unwrap();
+    assert_eq!(readers.len(), 1);
+    assert_eq!(readers[0]["user_id"], bob_id.as_str());
+    assert_eq!(readers[0]["username"], "bob");
+
+    let (status, _) = request(&app, "GET", &reads_uri, Some(&bob), None).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = mark_read(&app, &dave, &chat_id, messages[0]["id"].as_str().unwrap()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, _) = mark_read(&app, &carol, &chat_id, "нет-такого").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_disabled_read_receipts_and_delivery() {
+    let (app, _db) = test_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+
+    let (status, privacy) = request(
+        &app,
+        "PATCH",
+        "/users/me/privacy",
+        Some(&bob),
+        Some(json!({ "read_receipts": false })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(privacy["read_receipts"], false);
+    assert_eq!(privacy["last_seen"], "everyone");
+
+    let (status, message) = send(&app, &alice, &chat_id, json!({ "content": "привет" })).await;
+
+    assert_eq!(status, StatusCode::OK);
+    let message_id = message["id"].as_str().unwrap();
+    assert_eq!(feed(&app, &alice, &chat_id).await[0]["status"], "sent");
+
+    let (status, _) = request(
+        &app,
+        "POST",
+        &format!("/chats/{}/delivered", chat_id),
+        Some(&bob),
+        Some(json!({ "message_id": message_id })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(feed(&app, &alice, &chat_id).await[0]["status"], "delivered");
+
+    // Свои счётчики сбрасываются, но отчёт автору не уходит
+    let (status, state) = mark_read(&app, &bob, &chat_id, message_id).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(state["unread_count"], 0);
+    assert_eq!(feed(&app, &alice, &chat_id).await[0]["status"], "delivered");
+    let (_, readers) = request(
+        &app,
+        "GET",
+        &format!("/chats/{}/messages/{}/reads", chat_id, message_id),
+        Some(&alice),
+        None,
+    )
+    .await;
+    assert!(readers.as_array().unwrap().is_empty());
+
+    // Ответ считается упоминанием, а отправка отмечает чат прочитанным
+    next_second().await;
+    assert_eq!(send(&app, &alice, &chat_id, json!({ "content": "ещё" })).await.0, StatusCode::OK);
+    assert_eq!(counters(&app, &bob, &chat_id).await, (1, 0));
+    next_second().await;
+    assert_eq!(send(&app, &bob, &chat_id, json!({ "content": "да", "reply_to_id": message_id })).await.0, StatusCode::OK);
+    assert_eq!(counters(&app, &bob, &chat_id).await, (0, 0));
+    assert_eq!(counters(&app, &alice, &chat_id).await, (1, 1));
+}
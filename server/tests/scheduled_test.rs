@@ -0,0 +1,268 @@
+// server/tests/scheduled_test.rs
+//! Отложенные сообщения: отправка планировщиком, повторы, правка,
+//! отказ после потери прав и «когда будет в сети»
+
+mod common;
+
+use axum::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{create_chat, register, request};
+use liberty_reach_server::{
+    api::{self, scheduled, AppState},
+    db::Database,
+    websocket::{Rx, WsMessage},
+};
+use serde_json::{json, Value};
+
+async fn scheduled_app() -> (axum::Router, AppState) {
+    let db = common::test_db().await;
+    let state = AppState::new(db, "test-secret".to_string(), "./uploads".to_string());
+    (api::create_router(state.clone()), state)
+}
+
+async fn schedule(app: &axum::Router, token: &str, chat_id: &str, body: Value) -> (StatusCode, Value) {
+    request(app, "POST", &format!("/chats/{}/schedule", chat_id), Some(token), Some(body)).await
+}
+
+async fn pending(app: &axum::Router, token: &str, chat_id: &str) -> Vec<Value> {
+    let (status, list) = request(app, "GET", &format!("/chats/{}/scheduled", chat_id), Some(token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    list.as_array().unwrap().clone()
+}
+
+fn events(rx: &mut Rx) -> Vec<WsMessage> {
+    std::iter::from_fn(|| rx.try_recv().ok()).collect()
+}
+
+fn at(offset: Duration) -> String {
+    (Utc::now() + offset).to_rfc3339()
+}
+
+#[tokio::test]
+async fn test_due_messages_are_posted_and_broadcast() {
+    let (app, state) = scheduled_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, _bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+
+    // Без даты — только «когда будет в сети»; опрос не откладывается
+    assert_eq!(schedule(&app, &alice, &chat_id, json!({ "content": "когда?" })).await.0, StatusCode::BAD_REQUEST);
+    let poll = json!({ "content": "?", "message_type": "poll", "send_at": at(Duration::hours(1)) });
+    assert_eq!(schedule(&app, &alice, &chat_id, poll).await.0, StatusCode::BAD_REQUEST);
+
+    let (status, once) =
+        schedule(&app, &alice, &chat_id, json!({ "content": "доброе утро", "send_at": at(-Duration::minutes(1)) })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(once["status"], "pending");
+    let (_, daily) = schedule(
+        &app,
+        &alice,
+        &chat_id,
+        json!({ "content": "планёрка", "send_at": at(-Duration::hours(1)), "recurrence": "daily" }),
+    )
+    .await;
+    let (_, later) = schedule(&app, &alice, &chat_id, json!({ "content": "потом", "send_at": at(Duration::hours(1)) })).await;
+
+    let mut alice_rx = state.ws.write().await.connect(&alice_id);
+    let mut bob_rx = state.ws.write().await.connect(&bob_id);
+    state.ws.write().await.subscribe_chat(chat_id.clone(), bob_id.clone());
+
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 2);
+    // Повторный проход ничего не дублирует
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 0);
+
+    // Сообщения в чате, подписчики получили `new_message`, автор — `scheduled_sent`
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&alice), None).await;
+    let mut contents: Vec<&str> = page["messages"].as_array().unwrap().iter().map(|m| m["content"].as_str().unwrap()).collect();
+    contents.sort();
+    assert_eq!(contents, ["доброе утро", "планёрка"]);
+    let new_messages = events(&mut bob_rx)
+        .into_iter()
+        .filter(|event| matches!(event, WsMessage::NewMessage { .. }))
+        .count();
+    assert_eq!(new_messages, 2);
+    let sent: Vec<String> = events(&mut alice_rx)
+        .into_iter()
+        .filter_map(|event| match event {
+            WsMessage::ScheduledSent { scheduled_id, message, .. } => {
+                assert_eq!(message.sender_id, alice_id);
+                Some(scheduled_id)
+            }
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sent.len(), 2);
+    assert!(sent.contains(&once["id"].as_str().unwrap().to_string()));
+
+    // Разовое ушло из списка, ежедневное ждёт следующего дня
+    let list = pending(&app, &alice, &chat_id).await;
+    assert_eq!(list.len(), 2);
+    let daily_now = list.iter().find(|s| s["id"] == daily["id"]).unwrap();
+    assert_eq!(daily_now["status"], "pending");
+    assert!(daily_now["message_id"].is_string());
+    let expected = daily["send_at"].as_str().unwrap().to_string();
+    let expected = chrono::NaiveDateTime::parse_from_str(&expected, "%Y-%m-%d %H:%M:%S").unwrap() + Duration::days(1);
+    assert_eq!(daily_now["send_at"], expected.format("%Y-%m-%d %H:%M:%S").to_string());
+    assert!(list.iter().any(|s| s["id"] == later["id"]));
+}
+
+#[tokio::test]
+async fn test_edit_and_cancel() {
+    let (app, state) = scheduled_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+
+    let (_, message) = schedule(&app, &alice, &chat_id, json!({ "content": "черновик", "send_at": at(Duration::hours(1)) })).await;
+    let uri = format!("/chats/{}/scheduled/{}", chat_id, message["id"].as_str().unwrap());
+
+    // Чужое не видно и не меняется
+    assert!(pending(&app, &bob, &chat_id).await.is_empty());
+    let edit = json!({ "content": "чистовик" });
+    assert_eq!(request(&app, "PATCH", &uri, Some(&bob), Some(edit.clone())).await.0, StatusCode::NOT_FOUND);
+
+    let (status, edited) = request(&app, "PATCH", &uri, Some(&alice), Some(edit)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(edited["content"], "чистовик");
+    assert_eq!(edited["send_at"], message["send_at"]);
+
+    // Перенос в прошлое — уходит с ближайшим проходом, дальше не правится
+    let (_, edited) =
+        request(&app, "PATCH", &uri, Some(&alice), Some(json!({ "send_at": at(-Duration::minutes(1)) }))).await;
+    assert_eq!(edited["content"], "чистовик");
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 1);
+    assert_eq!(
+        request(&app, "PATCH", &uri, Some(&alice), Some(json!({ "content": "поздно" }))).await.0,
+        StatusCode::CONFLICT
+    );
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NOT_FOUND);
+
+    let (_, message) = schedule(&app, &alice, &chat_id, json!({ "content": "отменю", "send_at": at(Duration::hours(1)) })).await;
+    let uri = format!("/chats/{}/scheduled/{}", chat_id, message["id"].as_str().unwrap());
+    assert_eq!(request(&app, "DELETE", &uri, Some(&bob), None).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(request(&app, "POST", &uri, Some(&alice), None).await.0, StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NO_CONTENT);
+    assert_eq!(request(&app, "DELETE", &uri, Some(&alice), None).await.0, StatusCode::NOT_FOUND);
+    assert!(pending(&app, &alice, &chat_id).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_sender_without_rights_fails_permanently() {
+    let (app, state) = scheduled_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+
+    let (_, message) = schedule(&app, &bob, &chat_id, json!({ "content": "я ещё тут", "send_at": at(Duration::hours(1)) })).await;
+    let uri = format!("/chats/{}/scheduled/{}", chat_id, message["id"].as_str().unwrap());
+    request(&app, "PATCH", &uri, Some(&bob), Some(json!({ "send_at": at(-Duration::minutes(1)) }))).await;
+    state.db.chats().remove_member(&chat_id, &bob_id).await.unwrap();
+
+    let mut bob_rx = state.ws.write().await.connect(&bob_id);
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 0);
+    let failed = events(&mut bob_rx).into_iter().any(|event| {
+        matches!(event, WsMessage::ScheduledFailed { scheduled_id, .. } if scheduled_id == message["id"].as_str().unwrap())
+    });
+    assert!(failed, "автор не узнал об отказе");
+
+    // Повторов больше нет; вернувшись, автор видит ошибку и может исправить
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 0);
+    request(&app, "POST", &format!("/chats/{}/members", chat_id), Some(&alice), Some(json!({ "user_id": bob_id }))).await;
+    let list = pending(&app, &bob, &chat_id).await;
+    assert_eq!(list[0]["status"], "failed");
+    assert_eq!(list[0]["attempts"], 1);
+    assert!(list[0]["last_error"].is_string());
+
+    let (status, retried) = request(&app, "PATCH", &uri, Some(&bob), Some(json!({}))).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((&retried["status"], &retried["attempts"]), (&json!("pending"), &json!(0)));
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_claim_takes_latest_edit() {
+    let (app, state) = scheduled_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id]).await;
+    let (_, message) = schedule(&app, &alice, &chat_id, json!({ "content": "старое", "send_at": at(-Duration::minutes(1)) })).await;
+    let id = message["id"].as_str().unwrap();
+    let uri = format!("/chats/{}/scheduled/{}", chat_id, id);
+
+    // Изменено после выборки наступивших: отправляется новое содержимое,
+    // а перенесённое на потом не захватывается
+    let repo = state.db.scheduled();
+    assert_eq!(repo.due("", 10).await.unwrap().len(), 1);
+    request(&app, "PATCH", &uri, Some(&alice), Some(json!({ "send_at": at(Duration::hours(1)) }))).await;
+    assert!(repo.claim(id, "c1").await.unwrap().is_none());
+    request(&app, "PATCH", &uri, Some(&alice), Some(json!({ "content": "новое", "send_at": at(-Duration::minutes(1)) }))).await;
+    assert_eq!(repo.claim(id, "c2").await.unwrap().unwrap().content, "новое");
+}
+
+#[tokio::test]
+async fn test_error_after_commit_counts_as_sent() {
+    let (app, state) = scheduled_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, _bob) = register(&app, "bob").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id]).await;
+    let (_, message) = schedule(&app, &alice, &chat_id, json!({ "content": "@bob привет", "send_at": at(-Duration::minutes(1)) })).await;
+
+    // Сообщение сохраняется, а упоминания — уже нет
+    let drop_mentions = "DROP TABLE message_mentions";
+    match &*state.db {
+        Database::Sqlite(pool) => sqlx::query(drop_mentions).execute(pool).await.map(|_| ()),
+        Database::Postgres(pool) => sqlx::query(drop_mentions).execute(pool).await.map(|_| ()),
+    }
+    .unwrap();
+
+    let mut alice_rx = state.ws.write().await.connect(&alice_id);
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 1);
+    assert!(!events(&mut alice_rx).iter().any(|event| matches!(event, WsMessage::ScheduledFailed { .. })));
+
+    // В чате ровно одно сообщение, повтора не будет
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 0);
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&alice), None).await;
+    assert_eq!(page["messages"].as_array().unwrap().len(), 1);
+    assert!(pending(&app, &alice, &chat_id).await.iter().all(|s| s["id"] != message["id"]));
+}
+
+#[tokio::test]
+async fn test_send_when_recipient_comes_online() {
+    let (app, state) = scheduled_app().await;
+    let (alice_id, alice) = register(&app, "alice").await;
+    let (bob_id, _bob) = register(&app, "bob").await;
+    let (carol_id, _carol) = register(&app, "carol").await;
+    let private_id = create_chat(&app, &alice, "private", &[&alice_id, &bob_id]).await;
+    let group_id = create_chat(&app, &alice, "group", &[&alice_id, &bob_id, &carol_id]).await;
+
+    // Только личные чаты и без повтора
+    let body = json!({ "content": "ты тут?", "when_online": true });
+    assert_eq!(schedule(&app, &alice, &group_id, body.clone()).await.0, StatusCode::BAD_REQUEST);
+    let daily = json!({ "content": "ты тут?", "when_online": true, "recurrence": "daily" });
+    assert_eq!(schedule(&app, &alice, &private_id, daily).await.0, StatusCode::BAD_REQUEST);
+
+    let (status, message) = schedule(&app, &alice, &private_id, body).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(message["when_online"], true);
+
+    // Собеседник не в сети — ждёт
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 0);
+    assert_eq!(scheduled::send_when_online(&state, &carol_id).await.unwrap(), 0);
+
+    let _bob_rx = state.ws.write().await.connect(&bob_id);
+    assert_eq!(scheduled::send_when_online(&state, &bob_id).await.unwrap(), 1);
+    assert_eq!(scheduled::send_due(&state).await.unwrap(), 0);
+    assert!(pending(&app, &alice, &private_id).await.is_empty());
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", private_id), Some(&alice), None).await;
+    assert_eq!(page["messages"][0]["content"], "ты тут?");
+
+    // Уже в сети — уходит сразу
+    let (status, _) = schedule(&app, &alice, &private_id, json!({ "content": "и ещё", "when_online": true })).await;
+    assert_eq!(status, StatusCode::OK);
+    tokio::time::timeout(std::time::Duration::from_secs(5), async {
+        while !pending(&app, &alice, &private_id).await.is_empty() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("сообщение не отправлено");
+}
@@ -0,0 +1,580 @@
+// server/tests/schema_test.rs
+//! Сверка запросов с мигрированной схемой
+//!
+//! Каждый метод репозиториев (а через них — каждый запрос `api/*`)
+//! вызывается на чистой базе: опечатка в имени колонки или таблицы
+//! падает здесь, а не в рантайме. Новый метод репозитория — новая строка тут.
+
+mod common;
+
+use liberty_reach_server::encryption::WrappedKey;
+use liberty_reach_server::entities::Entities;
+use liberty_reach_server::permissions::{Membership, Rights, Role};
+use liberty_reach_server::db::{
+    chats::{ChatSettings, NewChat},
+    extra::{NewSavedMessage, NewSelfDestructMessage},
+    features::NewAutoDeleteMessage,
+    files::{LegacyFile, MediaMetadata, NewFile, NewThumbnail},
+    invites::{Approval, NewInvite},
+    messages::{Direction, Feed, ForwardFrom, NewMessage},
+    nodes::NewPeerNode,
+    push::NewPushDevice,
+    scheduled::{NewScheduledMessage, Recurrence, ScheduledSend, ScheduledUpdate},
+    polls::NewPoll,
+    search::{Expression, MessageFilters, SearchOrder},
+    uploads::NewUpload,
+    users::NewUser,
+    Database,
+};
+
+const FUTURE: &str = "2999-01-01 00:00:00";
+
+async fn create_user(db: &Database, id: &str) {
+    db.users()
+        .create(&NewUser {
+            id,
+            username: id,
+            email: None,
+            email_hash: None,
+            password_hash: "hash",
+            public_key: "key",
+        })
+        .await
+        .expect("users.create");
+}
+
+#[tokio::test]
+async fn test_migrations_are_idempotent() {
+    let db = common::test_db().await;
+
+    db.migrate().await.expect("повторный прогон миграций");
+    let status = db.schema_status().await.expect("schema_status");
+    assert!(status.is_current(), "{}", status);
+}
+
+#[tokio::test]
+async fn test_repository_queries_match_schema() {
+    let db = common::test_db().await;
+    create_user(&db, "alice").await;
+    create_user(&db, "bob").await;
+    create_user(&db, "carol").await;
+
+    // Пользователи
+    let users = db.users();
+    assert!(users.credentials("alice").await.expect("users.credentials").is_some());
+    assert!(users.find("alice").await.expect("users.find").is_some());
+    let privacy = users.privacy("alice").await.expect("users.privacy").unwrap();
+    users.set_privacy("alice", &privacy).await.expect("users.set_privacy");
+    users.set_online("alice").await.expect("users.set_online");
+    assert_eq!(users.reset_presence().await.expect("users.reset_presence"), 1);
+    users.set_offline("alice", FUTURE).await.expect("users.set_offline");
+    assert!(!users.is_contact("alice", "bob").await.expect("users.is_contact"));
+    users.set_email_hash("alice", "hash").await.expect("users.set_email_hash");
+    assert!(users.missing_email_hashes("", 10).await.expect("users.missing_email_hashes").is_empty());
+
+    // Контакты и чёрный список
+    let contacts = db.contacts();
+    contacts.add("alice", "bob").await.expect("contacts.add");
+    assert!(contacts.find("alice", "bob").await.expect("contacts.find").is_some());
+    assert_eq!(
+        contacts
+            .import("bob", &["ALICE".to_string()], &["hash".to_string()])
+            .await
+            .expect("contacts.import"),
+        ["alice"]
+    );
+    assert!(contacts.list("alice").await.expect("contacts.list")[0].mutual);
+    contacts.block("carol", "alice").await.expect("contacts.block");
+    assert!(contacts.is_blocked("carol", "alice").await.expect("contacts.is_blocked"));
+    assert_eq!(contacts.blocked("carol").await.expect("contacts.blocked").len(), 1);
+    assert!(contacts.unblock("carol", "alice").await.expect("contacts.unblock"));
+    assert!(contacts.remove("alice", "bob").await.expect("contacts.remove"));
+    assert!(contacts.remove("bob", "alice").await.expect("contacts.remove"));
+
+    // Чаты
+    let chats = db.chats();
+    chats
+        .create(
+            &NewChat {
+                id: "chat",
+                chat_type: "group",
+                name: Some("Чат"),
+                description: None,
+                owner_id: "alice",
+            },
+            &["alice".to_string(), "bob".to_string()],
+        )
+        .await
+        .expect("chats.create");
+    assert_eq!(chats.find("chat").await.expect("chats.find").unwrap().chat_type, "group");
+    assert_eq!(chats.members("chat", "", 10).await.expect("chats.members").len(), 2);
+    assert!(chats.membership("chat", "bob").await.expect("chats.membership").is_some());
+    assert!(chats.is_member("chat", "bob").await.expect("chats.is_member"));
+    let admin = Membership {
+        role: Role::Admin,
+        rights: Rights::ALL,
+        restricted_until: None,
+        promoted_by: Some("alice".to_string()),
+        channel: false,
+        blocked: false,
+    };
+    chats.set_role("chat", "bob", &admin).await.expect("chats.set_role");
+    assert_eq!(chats.membership("chat", "bob").await.expect("chats.membership"), Some(admin));
+    chats.update_info("chat", Some("новое имя"), None, None, Some(false)).await.expect("chats.update_info");
+    chats.ban("chat", "bob", "alice", Some(FUTURE)).await.expect("chats.ban");
+    assert!(chats.is_banned("chat", "bob").await.expect("chats.is_banned"));
+    assert_eq!(chats.bans("chat").await.expect("chats.bans").len(), 1);
+    assert!(chats.unban("chat", "bob").await.expect("chats.unban"));
+    assert!(chats.add_member("chat", "bob").await.expect("chats.add_member"));
+    chats.transfer_ownership("chat", "alice", "bob").await.expect("chats.transfer_ownership");
+    chats.transfer_ownership("chat", "bob", "alice").await.expect("chats.transfer_ownership");
+    chats.remove_member("chat", "bob").await.expect("chats.remove_member");
+    chats.add_member("chat", "bob").await.expect("chats.add_member");
+
+    // Пригласительные ссылки
+    let invites = db.invites();
+    invites
+        .create(&NewInvite {
+            code: "inv",
+            chat_id: "chat",
+            created_by: "alice",
+            name: Some("ссылка"),
+            expires_at: Some(FUTURE),
+            max_uses: Some(5),
+            requires_approval: true,
+        })
+        .await
+        .expect("invites.create");
+    assert!(invites.find("inv").await.expect("invites.find").unwrap().active);
+    assert_eq!(invites.list("chat").await.expect("invites.list").len(), 1);
+    assert_eq!(invites.preview("inv").await.expect("invites.preview").unwrap().member_count, 2);
+    invites.request_join("chat", "carol", "inv").await.expect("invites.request_join");
+    assert_eq!(invites.join_requests("chat").await.expect("invites.join_requests").len(), 1);
+    assert_eq!(invites.approve("chat", "carol").await.expect("invites.approve"), Approval::Approved);
+    assert!(!invites.decline("chat", "carol").await.expect("invites.decline"));
+    assert_eq!(invites.join("inv", "carol").await.expect("invites.join").as_deref(), Some("chat"));
+    assert!(invites.revoke("chat", "inv").await.expect("invites.revoke"));
+    chats.remove_member("chat", "carol").await.expect("chats.remove_member");
+
+    // Сообщения
+    let messages = db.messages();
+    messages
+        .insert(&NewMessage {
+            id: "m1",
+            chat_id: "chat",
+            sender_id: "alice",
+            content: "привет мир",
+            entities: &Entities::default(),
+            message_type: "text",
+            file_url: None,
+            reply_to_id: None,
+            channel_post: false,
+            author_signature: None,
+            thread_id: None,
+            is_comment: false,
+            poll: None,
+            forward_from: None,
+            scheduled: None,
+        })
+        .await
+        .expect("messages.insert");
+    assert!(messages.find("m1").await.expect("messages.find").is_some());
+    let meta = messages.meta("chat", "m1").await.expect("messages.meta").unwrap();
+    messages
+        .page("chat", "bob", Feed::Chat, None, Direction::Older, 10)
+        .await
+        .expect("messages.page");
+    messages
+        .page("chat", "bob", Feed::Thread("m1"), Some(("2000-01-01 00:00:00", "m0")), Direction::Newer, 10)
+        .await
+        .expect("messages.page (курсор)");
+    messages
+        .edit("m1", "alice", &meta.content, "привет, мир", &Entities::default())
+        .await
+        .expect("messages.edit");
+    assert_eq!(messages.edits("m1").await.expect("messages.edits").len(), 1);
+    assert_eq!(
+        db.reads()
+            .add_mentions("m1", "chat", "alice", &["Bob".to_string()], &["alice".to_string()], None)
+            .await
+            .expect("reads.add_mentions"),
+        vec!["bob".to_string()]
+    );
+    messages.hide("m1", "bob").await.expect("messages.hide");
+    // Не пост канала: просмотры не считаются
+    assert!(messages
+        .record_views("chat", "bob", &["m1".to_string()])
+        .await
+        .expect("messages.record_views")
+        .is_empty());
+    assert_eq!(chats.member_ids_after("chat", "", 10).await.expect("chats.member_ids_after").len(), 2);
+
+    // Push-уведомления: чат без звука, но Боба упомянули
+    let push = db.push();
+    let device = push
+        .register(&NewPushDevice {
+            id: "device",
+            user_id: "bob",
+            provider: "unifiedpush",
+            endpoint: "https://push.example.com/bob",
+            p256dh: None,
+            auth: None,
+            hint_key: "key",
+        })
+        .await
+        .expect("push.register");
+    assert_eq!(push.devices("bob").await.expect("push.devices")[0].id, device.id);
+    let muted = ChatSettings { muted_until: Some(FUTURE.to_string()), ..ChatSettings::default() };
+    chats.set_settings("chat", "bob", &muted).await.expect("chats.set_settings");
+    assert_eq!(chats.settings("chat", "bob").await.expect("chats.settings").muted_until, muted.muted_until);
+    let targets = push.targets("chat", "m1", "alice", true, "", 10).await.expect("push.targets");
+    assert!(targets[0].mentioned);
+    assert!(!push.unregister("alice", "device").await.expect("push.unregister"));
+    push.remove("device").await.expect("push.remove");
+
+    // Треды
+    let threads = db.threads();
+    assert_eq!(threads.root_of("chat", "m1").await.expect("threads.root_of").unwrap().id, "m1");
+    threads.set_subscribed("m1", "bob", true).await.expect("threads.set_subscribed");
+    threads.subscribe_if_new("m1", "alice").await.expect("threads.subscribe_if_new");
+    assert!(threads.is_subscribed("m1", "bob").await.expect("threads.is_subscribed"));
+    assert_eq!(threads.subscribers("m1").await.expect("threads.subscribers").len(), 2);
+    assert_eq!(threads.mark_read("m1", "bob", "m1").await.expect("threads.mark_read"), None);
+    assert_eq!(threads.unread("m1", "bob").await.expect("threads.unread"), 0);
+    assert_eq!(threads.subscriptions("bob").await.expect("threads.subscriptions").len(), 1);
+
+    // Опросы и пересылка
+    let options = ["да".to_string(), "нет".to_string()];
+    db.messages()
+        .insert(&NewMessage {
+            id: "poll",
+            chat_id: "chat",
+            sender_id: "alice",
+            content: "вопрос",
+            entities: &Entities::default(),
+            message_type: "poll",
+            file_url: None,
+            reply_to_id: None,
+            channel_post: false,
+            author_signature: None,
+            thread_id: None,
+            is_comment: false,
+            poll: Some(&NewPoll {
+                options: &options,
+                multiple: false,
+                anonymous: false,
+                correct_option: Some(1),
+                explanation: Some("так"),
+                closes_at: Some(FUTURE),
+            }),
+            forward_from: Some(&ForwardFrom {
+                sender_id: Some("bob".to_string()),
+                sender_name: "bob".to_string(),
+                chat_id: None,
+                chat_name: None,
+                message_id: None,
+                date: FUTURE.to_string(),
+            }),
+            scheduled: None,
+        })
+        .await
+        .expect("messages.insert (poll)");
+    let forwarded = db.messages().find("poll").await.expect("messages.find (forward)").unwrap();
+    assert_eq!(forwarded.forward_from.unwrap().sender_id.as_deref(), Some("bob"));
+    let polls = db.polls();
+    assert_eq!(polls.settings("poll").await.expect("polls.settings").unwrap().option_count, 2);
+    assert!(polls.vote("poll", "bob", &[1]).await.expect("polls.vote"));
+    let poll = polls.load("poll", "bob").await.expect("polls.load").unwrap();
+    assert_eq!(poll.options[1].voters, Some(vec!["bob".to_string()]));
+    assert!(polls.retract("poll", "bob").await.expect("polls.retract"));
+    polls.close("poll").await.expect("polls.close");
+
+    // Поиск
+    let expression = Expression::parse("мир").unwrap();
+    let filters = MessageFilters {
+        chat_id: Some("chat"),
+        sender_id: Some("alice"),
+        message_type: Some("text"),
+        from: Some("2000-01-01 00:00:00"),
+        to: Some(FUTURE),
+    };
+    for order in [SearchOrder::Relevance, SearchOrder::Date] {
+        db.search()
+            .messages("alice", &expression, &filters, order, 10, 0)
+            .await
+            .expect("search.messages");
+    }
+    db.search()
+        .saved("alice", &expression, 10, 0)
+        .await
+        .expect("search.saved");
+
+    // Реакции, закрепы, избранное
+    let extra = db.extra();
+    extra.add_reaction("m1", "bob", "👍").await.expect("extra.add_reaction");
+    assert_eq!(extra.reactions("m1").await.expect("extra.reactions").len(), 1);
+    assert!(extra.message_in_chat("chat", "m1").await.expect("extra.message_in_chat").is_some());
+    extra.pin("chat", "m1", "alice").await.expect("extra.pin");
+    assert_eq!(extra.pinned("chat").await.expect("extra.pinned").len(), 1);
+    extra.unpin("chat", "m1").await.expect("extra.unpin");
+    extra
+        .save(&NewSavedMessage {
+            id: "s1",
+            user_id: "alice",
+            content: "заметка",
+            message_type: "text",
+            file_url: None,
+            tags: Some("работа"),
+        })
+        .await
+        .expect("extra.save");
+    extra.saved("alice", None).await.expect("extra.saved");
+    extra.saved("alice", Some("работа")).await.expect("extra.saved (тег)");
+    extra.delete_saved("alice", "s1").await.expect("extra.delete_saved");
+
+    // Отложенные сообщения
+    let scheduled = db.scheduled();
+    scheduled
+        .schedule(&NewScheduledMessage {
+            id: "sch1",
+            chat_id: "chat",
+            sender_id: "alice",
+            content: "позже",
+            message_type: "text",
+            file_url: None,
+            send_at: FUTURE,
+            recurrence: Recurrence::Once,
+            when_online: false,
+        })
+        .await
+        .expect("scheduled.schedule");
+    assert!(scheduled.find("chat", "sch1", "alice").await.expect("scheduled.find").is_some());
+    assert_eq!(scheduled.pending("chat", "alice").await.expect("scheduled.pending").len(), 1);
+    let update = ScheduledUpdate { content: "сейчас", send_at: "2000-01-01 00:00:00", recurrence: Recurrence::Daily };
+    assert!(scheduled.update("chat", "sch1", "alice", &update).await.expect("scheduled.update"));
+    assert_eq!(scheduled.due("", 10).await.expect("scheduled.due").len(), 1);
+    scheduled.due_for_recipient("bob").await.expect("scheduled.due_for_recipient");
+    assert!(scheduled.claim("sch1", "c1").await.expect("scheduled.claim").is_some());
+    assert!(scheduled.fail("sch1", "c1", Some(FUTURE), "ошибка").await.expect("scheduled.fail"));
+    assert!(!scheduled.fail("sch1", "c1", None, "ошибка").await.expect("scheduled.fail"));
+    scheduled.update("chat", "sch1", "alice", &update).await.expect("scheduled.update");
+    assert!(scheduled.claim("sch1", "c2").await.expect("scheduled.claim").is_some());
+    assert_eq!(scheduled.release_stale(FUTURE).await.expect("scheduled.release_stale"), 1);
+    assert!(scheduled.claim("sch1", "c3").await.expect("scheduled.claim").is_some());
+    db.messages()
+        .insert(&NewMessage {
+            id: "from-sch1",
+            chat_id: "chat",
+            sender_id: "alice",
+            content: "сейчас",
+            entities: &Entities::default(),
+            message_type: "text",
+            file_url: None,
+            reply_to_id: None,
+            channel_post: false,
+            author_signature: None,
+            thread_id: None,
+            is_comment: false,
+            poll: None,
+            forward_from: None,
+            scheduled: Some(&ScheduledSend {
+                id: "sch1".to_string(),
+                claim: "c3".to_string(),
+                next_send_at: Some(FUTURE.to_string()),
+            }),
+        })
+        .await
+        .expect("messages.insert (отложенное)");
+    let sch1 = scheduled.find("chat", "sch1", "alice").await.expect("scheduled.find").unwrap();
+    assert_eq!((sch1.status.as_str(), sch1.send_at.as_str()), ("pending", FUTURE));
+    assert_eq!(sch1.message_id.as_deref(), Some("from-sch1"));
+    assert!(scheduled.cancel("chat", "sch1", "alice").await.expect("scheduled.cancel"));
+
+    // Профиль
+    extra.set_bio("alice", "о себе").await.expect("extra.set_bio");
+    assert_eq!(extra.bio("alice").await.expect("extra.bio"), Some(Some("о себе".to_string())));
+    extra.set_theme("alice", "dark").await.expect("extra.set_theme");
+    extra.set_night_mode("alice", true).await.expect("extra.set_night_mode");
+
+    // Демонстрация экрана
+    extra
+        .start_screen_share("ss1", "chat", "alice", "rtmp://stream")
+        .await
+        .expect("extra.start_screen_share");
+    assert_eq!(extra.active_screen_shares("chat").await.expect("extra.active_screen_shares").len(), 1);
+    extra.stop_screen_share("ss1").await.expect("extra.stop_screen_share");
+
+    // Самоуничтожение
+    extra.set_self_destruct_timer("chat", Some(30)).await.expect("extra.set_self_destruct_timer");
+    assert_eq!(extra.self_destruct_timer("chat").await.expect("extra.self_destruct_timer"), Some(30));
+    extra
+        .insert_self_destruct_message(&NewSelfDestructMessage {
+            id: "sd1",
+            chat_id: "chat",
+            sender_id: "alice",
+            content: "исчезнет",
+            message_type: "text",
+            file_url: None,
+            reply_to_id: None,
+            timer_seconds: 30,
+            delete_at: FUTURE,
+        })
+        .await
+        .expect("extra.insert_self_destruct_message");
+
+    // Семейные статусы, обои, автоудаление
+    let features = db.features();
+    features.set_family_status("alice", "married").await.expect("features.set_family_status");
+    features.set_partner("alice", "bob", "married").await.expect("features.set_partner");
+    features
+        .set_wallpaper("chat", "alice", "/wallpapers/roses.jpg", "preset", false)
+        .await
+        .expect("features.set_wallpaper");
+    features.sync_wallpaper("chat", "/wallpapers/ocean.jpg").await.expect("features.sync_wallpaper");
+    let wallpaper = features.wallpaper("chat", "alice").await.expect("features.wallpaper").unwrap();
+    assert_eq!(wallpaper.wallpaper_url, "/wallpapers/ocean.jpg");
+    features.set_auto_delete("chat", 24).await.expect("features.set_auto_delete");
+    features
+        .insert_auto_delete_message(&NewAutoDeleteMessage {
+            id: "ad1",
+            chat_id: "chat",
+            sender_id: "alice",
+            content: "на сутки",
+            message_type: "text",
+            file_url: None,
+            reply_to_id: None,
+            auto_delete_hours: 24,
+            delete_at: FUTURE,
+        })
+        .await
+        .expect("features.insert_auto_delete_message");
+    db.messages().delete_expired().await.expect("messages.delete_expired");
+
+    assert_eq!(
+        db.users().presence_audience("alice").await.expect("users.presence_audience"),
+        [("bob".to_string(), false)]
+    );
+    assert!(db.users().shares_presence("alice", "bob").await.expect("users.shares_presence"));
+
+    // Файлы
+    let files = db.files();
+    files
+        .insert(&NewFile {
+            id: "f1",
+            owner_id: "alice",
+            filename: "f1.png",
+            original_name: "cat.png",
+            mime_type: "image/png",
+            size: 3,
+            url: "/files/f1",
+            content_hash: "hash",
+            media_status: Some("pending"),
+            client_encrypted: false,
+            data_key: None,
+        })
+        .await
+        .expect("files.insert");
+    assert!(files.find("f1").await.expect("files.find").is_some());
+    assert!(files.can_access("f1", "bob").await.is_ok(), "files.can_access");
+    assert_eq!(files.used_bytes("alice").await.expect("files.used_bytes"), 3);
+    assert!(files.legacy("", 10).await.expect("files.legacy").is_empty());
+    assert_eq!(files.pending_media(10).await.expect("files.pending_media"), ["f1"]);
+    files.reserve_blob("thumb", 1, "image/jpeg", None).await.expect("files.reserve_blob");
+    let saved = files
+        .save_media(
+            "f1",
+            &MediaMetadata { width: Some(2), height: Some(1), blurhash: Some("LKO2"), ..Default::default() },
+            &[NewThumbnail {
+                size: "s",
+                width: 2,
+                height: 1,
+                mime_type: "image/jpeg",
+                content_hash: "thumb",
+                byte_size: 1,
+            }],
+        )
+        .await
+        .expect("files.save_media");
+    assert!(saved);
+    assert_eq!(files.thumbnails("f1").await.expect("files.thumbnails").len(), 1);
+    files.fail_media("f1").await.expect("files.fail_media");
+    assert!(files.delete("f1", "alice").await.expect("files.delete"));
+    let mut unreferenced = files.unreferenced_blobs(FUTURE).await.expect("files.unreferenced_blobs");
+    unreferenced.sort();
+    assert_eq!(unreferenced, ["hash", "thumb"]);
+    assert!(files.forget_blob("hash", FUTURE).await.expect("files.forget_blob"));
+
+    // Загрузки с докачкой
+    let uploads = db.uploads();
+    uploads
+        .create(&NewUpload { id: "u1", owner_id: "alice", filename: "a.bin", size: 4, client_encrypted: true })
+        .await
+        .expect("uploads.create");
+    assert!(uploads.find("u1", "alice").await.expect("uploads.find").is_some());
+    assert!(uploads.advance("u1", 0, 4).await.expect("uploads.advance"));
+    assert_eq!(uploads.reserved_bytes("alice").await.expect("uploads.reserved_bytes"), 4);
+    assert!(uploads.stale("2000-01-01 00:00:00").await.expect("uploads.stale").is_empty());
+    let key = WrappedKey { key_id: "k1".to_string(), wrapped_key: "d3JhcHBlZA==".to_string() };
+    files
+        .insert_from_upload(
+            &NewFile {
+                id: "f2",
+                owner_id: "alice",
+                filename: "f2.bin",
+                original_name: "a.bin",
+                mime_type: "application/octet-stream",
+                size: 4,
+                url: "/files/f2",
+                content_hash: "hash2",
+                media_status: None,
+                client_encrypted: true,
+                data_key: Some(&key),
+            },
+            "u1",
+        )
+        .await
+        .expect("files.insert_from_upload");
+    assert_eq!(files.blob_key("hash2").await.expect("files.blob_key"), Some(key.clone()));
+    assert_eq!(files.unencrypted_blobs("", 10).await.expect("files.unencrypted_blobs"), ["thumb"]);
+    let rotated = WrappedKey { key_id: "k2".to_string(), ..key.clone() };
+    assert_eq!(files.keys_to_rewrap("k2", "", 10).await.expect("files.keys_to_rewrap").len(), 1);
+    assert!(files.rewrap_key("hash2", &key, &rotated).await.expect("files.rewrap_key"));
+    assert!(files.set_blob_key("thumb", &rotated).await.expect("files.set_blob_key"));
+    uploads.delete("u1").await.expect("uploads.delete");
+    files
+        .attach_legacy(
+            &LegacyFile { id: "f2".to_string(), url: "/uploads/files/f2.bin".to_string() },
+            "hash2",
+            4,
+            "application/octet-stream",
+            "/files/f2",
+        )
+        .await
+        .expect("files.attach_legacy");
+
+    // P2P ноды
+    let nodes = db.nodes();
+    nodes
+        .insert(&NewPeerNode {
+            id: "n1",
+            user_id: "alice",
+            username: "alice",
+            public_key: "key",
+            peer_id: "peer",
+            multiaddr: "/ip4/127.0.0.1/tcp/4001",
+            version: "1.0.0",
+            capabilities: "relay",
+        })
+        .await
+        .expect("nodes.insert");
+    nodes
+        .refresh("alice", "/ip4/127.0.0.1/tcp/4002", "1.0.1", "relay")
+        .await
+        .expect("nodes.refresh");
+    nodes.heartbeat("alice").await.expect("nodes.heartbeat");
+    assert!(nodes.find_by_user("alice").await.expect("nodes.find_by_user").is_some());
+    assert_eq!(nodes.list().await.expect("nodes.list").len(), 1);
+
+    // Удаление для всех — последним: трогает реакции, закрепы и правки
+    messages.delete_for_everyone("m1").await.expect("messages.delete_for_everyone");
+}
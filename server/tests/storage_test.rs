@@ -0,0 +1,193 @@
+// server/tests/storage_test.rs
+//! S3-бэкенд хранилища против S3-совместимого сервера в процессе
+//!
+//! Сервер проверяет подпись SigV4 тем же `sign_v4` по пришедшим
+//! заголовкам и пути: расхождение канонического запроса с тем, что
+//! реально ушло по сети, даёт 403, как у настоящего S3.
+
+mod common;
+
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::{HeaderMap, Method, StatusCode, Uri},
+    routing::any,
+    Router,
+};
+use common::{register, request};
+use futures::TryStreamExt;
+use liberty_reach_server::{
+    api::{self, AppState},
+    storage::{self, S3Config, S3Storage, StorageBackend},
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use tower::ServiceExt;
+
+type Objects = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+
+fn config(endpoint: String) -> S3Config {
+    S3Config {
+        endpoint,
+        bucket: "liberty".to_string(),
+        region: "us-east-1".to_string(),
+        access_key: "test-access".to_string(),
+        secret_key: "test-secret".to_string(),
+    }
+}
+
+async fn handle_object(
+    State((config, objects)): State<(S3Config, Objects)>,
+    Path((bucket, key)): Path<(String, String)>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    body: Bytes,
+) -> (StatusCode, Vec<u8>) {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).unwrap_or("");
+    let signed = [
+        ("host", header("host")),
+        ("x-amz-content-sha256", header("x-amz-content-sha256")),
+        ("x-amz-date", header("x-amz-date")),
+    ];
+    let expected = storage::sign_v4(
+        &config,
+        method.as_str(),
+        uri.path(),
+        "",
+        &signed,
+        header("x-amz-content-sha256"),
+        header("x-amz-date"),
+    );
+    if bucket != config.bucket || header("authorization") != expected {
+        return (StatusCode::FORBIDDEN, Vec::new());
+    }
+
+    let mut objects = objects.lock().unwrap();
+    match method {
+        Method::PUT => {
+            objects.insert(key, body.to_vec());
+            (StatusCode::OK, Vec::new())
+        }
+        Method::GET | Method::HEAD => match objects.get(&key) {
+            Some(data) => (StatusCode::OK, data.clone()),
+            None => (StatusCode::NOT_FOUND, Vec::new()),
+        },
+        Method::DELETE => {
+            objects.remove(&key);
+            (StatusCode::NO_CONTENT, Vec::new())
+        }
+        _ => (StatusCode::METHOD_NOT_ALLOWED, Vec::new()),
+    }
+}
+
+/// Поднять S3-сервер на свободном порту
+async fn s3_server() -> (S3Config, Objects) {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = config(format!("http://{}", listener.local_addr().unwrap()));
+    let objects = Objects::default();
+
+    let app = Router::new()
+        .route("/:bucket/*key", any(handle_object))
+        .with_state((config.clone(), objects.clone()));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    (config, objects)
+}
+
+async fn read_all(storage: &S3Storage, key: &str) -> Option<Vec<u8>> {
+    let object = storage.get(key).await.unwrap()?;
+    let chunks: Vec<Bytes> = object.body.try_collect().await.unwrap();
+    Some(chunks.concat())
+}
+
+#[tokio::test]
+async fn test_s3_object_lifecycle() {
+    let (config, objects) = s3_server().await;
+    let storage = S3Storage::new(config).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("upload");
+    std::fs::write(&source, b"hello s3").unwrap();
+
+    let key = storage::object_key(&storage::sha256_file(&source).await.unwrap());
+    assert!(!storage.exists(&key).await.unwrap());
+    assert!(storage.get(&key).await.unwrap().is_none());
+
+    storage.put_file(&key, &source).await.unwrap();
+    assert!(!source.exists(), "источник забирается после записи");
+    assert_eq!(objects.lock().unwrap()[&key], b"hello s3");
+    assert!(storage.exists(&key).await.unwrap());
+    assert_eq!(read_all(&storage, &key).await.unwrap(), b"hello s3");
+
+    storage.delete(&key).await.unwrap();
+    assert!(!storage.exists(&key).await.unwrap());
+    storage.delete(&key).await.expect("удаление отсутствующего объекта");
+}
+
+#[tokio::test]
+async fn test_s3_rejects_wrong_credentials() {
+    let (config, _objects) = s3_server().await;
+    let storage = S3Storage::new(S3Config { secret_key: "wrong".to_string(), ..config }).unwrap();
+
+    let error = storage.exists("ab/cd/abcd").await.unwrap_err();
+    assert!(error.to_string().contains("403"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_files_api_over_s3() {
+    let (config, objects) = s3_server().await;
+    let dir = tempfile::tempdir().unwrap();
+    let state = AppState::new(
+        common::test_db().await,
+        "test-secret".to_string(),
+        dir.path().join("uploads").to_string_lossy().into_owned(),
+    )
+    .with_storage(Arc::new(S3Storage::new(config).unwrap()));
+    let app = api::create_router(state);
+    let (_, token) = register(&app, "s3user").await;
+
+    let (status, created) = request(
+        &app,
+        "POST",
+        "/files/uploads",
+        Some(&token),
+        Some(serde_json::json!({ "filename": "notes.txt", "size": 5 })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED);
+    let upload_id = created["upload_id"].as_str().unwrap();
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .method("PATCH")
+                .uri(format!("/files/uploads/{}", upload_id))
+                .header("Authorization", format!("Bearer {}", token))
+                .header("Upload-Offset", "0")
+                .body(axum::body::Body::from("hello"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (status, file) = request(&app, "POST", &format!("/files/uploads/{}/finalize", upload_id), Some(&token), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(objects.lock().unwrap().len(), 1);
+
+    let file_id = file["id"].as_str().unwrap();
+    let (_, signed) = request(&app, "GET", &format!("/files/{}/url", file_id), Some(&token), None).await;
+    let response = app
+        .clone()
+        .oneshot(
+            axum::http::Request::builder()
+                .uri(signed["url"].as_str().unwrap())
+                .body(axum::body::Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"hello");
+}
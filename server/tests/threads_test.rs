@@ -0,0 +1,137 @@
+// server/tests/threads_test.rs
+//! Треды: ответы с пагинацией, непрочитанные, подписки и обсуждения
+//! постов канала (см. `common`)
+
+mod common;
+
+use axum::http::StatusCode;
+use common::{create_chat, next_second, register, request, send, test_app};
+use serde_json::{json, Value};
+
+async fn reply(app: &axum::Router, token: &str, chat_id: &str, thread_id: &str, content: &str) -> (StatusCode, Value) {
+    request(
+        app,
+        "POST",
+        &format!("/chats/{}/threads/{}/messages", chat_id, thread_id),
+        Some(token),
+        Some(json!({ "content": content })),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn test_group_threads_unread_and_subscriptions() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (carol_id, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "group", &[&bob_id, &carol_id]).await;
+
+    let (_, root) = send(&app, &alice, &chat_id, json!({ "content": "обсудим?" })).await;
+    let root_id = root["id"].as_str().unwrap();
+
+    // Ответ из ленты тоже попадает в тред
+    next_second().await;
+    let (status, first) = send(&app, &bob, &chat_id, json!({ "content": "да", "reply_to_id": root_id })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(first["thread_id"], root_id);
+    // Ответ на ответ — в тот же тред
+    next_second().await;
+    let (_, second) = send(&app, &carol, &chat_id, json!({ "content": "и я", "reply_to_id": first["id"] })).await;
+    assert_eq!(second["thread_id"], root_id);
+    next_second().await;
+    let (status, third) = reply(&app, &bob, &chat_id, root_id, "ещё").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(third["reply_to_id"], root_id);
+
+    // Корень показывает число ответов и последний ответ
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&alice), None).await;
+    let root_in_feed = page["messages"].as_array().unwrap().iter().find(|m| m["id"] == root_id).unwrap();
+    assert_eq!(root_in_feed["reply_count"], 3);
+    assert_eq!(root_in_feed["last_reply_id"], third["id"]);
+
+    // Автор корня подписан и видит три непрочитанных ответа
+    let thread_uri = format!("/chats/{}/threads/{}", chat_id, root_id);
+    let (status, thread) = request(&app, "GET", &format!("{}?limit=2", thread_uri), Some(&alice), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(thread["root"]["id"], root_id);
+    assert_eq!(thread["subscribed"], true);
+    assert_eq!(thread["unread_count"], 3);
+    assert_eq!(thread["messages"].as_array().unwrap().len(), 2);
+    assert_eq!(thread["messages"][0]["id"], third["id"]);
+    let cursor = thread["next_cursor"].as_str().unwrap();
+    let (_, older) = request(&app, "GET", &format!("{}?before={}", thread_uri, cursor), Some(&alice), None).await;
+    assert_eq!(older["messages"][0]["id"], first["id"]);
+    assert!(older["next_cursor"].is_null());
+
+    let (status, unread) = request(
+        &app,
+        "POST",
+        &format!("{}/read", thread_uri),
+        Some(&alice),
+        Some(json!({ "message_id": second["id"] })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(unread["unread_count"], 1);
+
+    // Ответ — не тред
+    let reply_uri = format!("/chats/{}/threads/{}", chat_id, first["id"].as_str().unwrap());
+    let (status, _) = request(&app, "GET", &reply_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (_, threads) = request(&app, "GET", "/threads", Some(&alice), None).await;
+    assert_eq!(threads, json!([{
+        "thread_id": root_id,
+        "chat_id": chat_id,
+        "content": "обсудим?",
+        "reply_count": 3,
+        "last_reply_at": third["created_at"],
+        "unread_count": 1,
+    }]));
+
+    // Отписка убирает тред из списка
+    let subscription_uri = format!("{}/subscription", thread_uri);
+    let (status, _) = request(&app, "DELETE", &subscription_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, threads) = request(&app, "GET", "/threads", Some(&alice), None).await;
+    assert!(threads.as_array().unwrap().is_empty());
+    let (status, _) = request(&app, "PUT", &subscription_uri, Some(&alice), None).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (_, threads) = request(&app, "GET", "/threads", Some(&alice), None).await;
+    assert_eq!(threads.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_channel_post_comments() {
+    let (app, _db) = test_app().await;
+    let (_, alice) = register(&app, "alice").await;
+    let (bob_id, bob) = register(&app, "bob").await;
+    let (_, carol) = register(&app, "carol").await;
+    let chat_id = create_chat(&app, &alice, "channel", &[&bob_id]).await;
+
+    let (_, post) = send(&app, &alice, &chat_id, json!({ "content": "пост" })).await;
+    let post_id = post["id"].as_str().unwrap();
+
+    // Подписчик комментирует, хотя писать в канал не может
+    let (status, comment) = reply(&app, &bob, &chat_id, post_id, "комментарий").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(comment["sender_id"], bob_id.as_str());
+    assert_eq!(comment["thread_id"], post_id);
+    assert_eq!(reply(&app, &carol, &chat_id, post_id, "чужой").await.0, StatusCode::FORBIDDEN);
+
+    // В ленте канала только пост, с числом комментариев
+    let (_, page) = request(&app, "GET", &format!("/chats/{}/messages", chat_id), Some(&bob), None).await;
+    let feed = page["messages"].as_array().unwrap();
+    assert_eq!(feed.len(), 1);
+    assert_eq!(feed[0]["reply_count"], 1);
+
+    // Комментарии не считаются непрочитанными в канале, только в треде
+    let (_, chat) = request(&app, "GET", &format!("/chats/{}", chat_id), Some(&alice), None).await;
+    assert_eq!(chat["unread_count"], 0);
+    assert_eq!(chat["last_message"]["id"], post_id);
+    let (_, thread) =
+        request(&app, "GET", &format!("/chats/{}/threads/{}", chat_id, post_id), Some(&alice), None).await;
+    assert_eq!(thread["unread_count"], 1);
+    assert_eq!(thread["messages"][0]["id"], comment["id"]);
+}